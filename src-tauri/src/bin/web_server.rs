@@ -1,7 +1,7 @@
 #[tokio::main]
 async fn main() {
-    if let Err(err) = antigravity_tools_lib::web_server::run().await {
-        eprintln!("Web server failed: {}", err);
+    if let Err(err) = antigravity_tools_lib::cli::main().await {
+        eprintln!("Error: {}", err);
         std::process::exit(1);
     }
 }
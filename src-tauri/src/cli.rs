@@ -0,0 +1,756 @@
+// web_server 命令行 (CLI)
+//
+// `serve` 启动服务 (无子命令时的默认行为)，其余子命令直接操作数据目录中的账号、日志与配置，
+// 便于容器与 systemd 部署时在不打开管理后台的情况下完成运维。
+
+use clap::{Args, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::models::{Account, AppConfig, TokenData};
+use crate::modules;
+
+#[derive(Debug, Parser)]
+#[command(name = "web_server", version, about = "Antigravity Tools web server and management CLI")]
+pub struct Cli {
+    /// 数据目录 (默认 ~/.antigravity_tools)
+    #[arg(long, global = true, env = "ANTIGRAVITY_DATA_DIR", value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// 使用独立的 profile，数据保存在 <data-dir>/profiles/<NAME>
+    #[arg(long, global = true, env = "ANTIGRAVITY_PROFILE", value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// 配置文件路径 (默认 <data-dir>/gui_config.json)
+    #[arg(long, global = true, env = "ANTIGRAVITY_CONFIG", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// TOML / YAML 覆盖文件 (默认查找配置文件同目录下的 config.toml / config.yaml)
+    #[arg(long, global = true, env = "ANTIGRAVITY_CONFIG_OVERLAY", value_name = "FILE")]
+    pub config_overlay: Option<PathBuf>,
+
+    /// 日志级别或过滤表达式 (如 debug、info,hyper=warn)，RUST_LOG 优先
+    #[arg(long, global = true, env = "ANTIGRAVITY_LOG_LEVEL", value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 启动反代与管理后台
+    Serve(ServeArgs),
+    /// 账号管理
+    #[command(subcommand)]
+    Accounts(AccountsCommand),
+    /// 配额
+    #[command(subcommand)]
+    Quota(QuotaCommand),
+    /// 请求日志
+    #[command(subcommand)]
+    Logs(LogsCommand),
+    /// 请求与 token 用量统计
+    Stats(StatsArgs),
+    /// 读取 / 修改 / 校验配置
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// 导出账号 (及可选的配置) 为 JSON
+    Export(ExportArgs),
+    /// 从 export 生成的 JSON 导入账号 (及可选的配置)
+    Import(ImportArgs),
+    /// 列出 profile 及其运行状态
+    #[command(subcommand)]
+    Profiles(ProfilesCommand),
+}
+
+#[derive(Debug, Args, Default)]
+pub struct ServeArgs {
+    /// 监听端口 (覆盖 proxy.port)
+    #[arg(long, env = "ANTIGRAVITY_PORT")]
+    pub port: Option<u16>,
+    /// 监听地址 (覆盖 proxy.allow_lan_access，如 0.0.0.0)
+    #[arg(long, env = "ANTIGRAVITY_BIND")]
+    pub bind: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AccountsCommand {
+    /// 列出账号
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+    /// 添加账号: 默认打印授权地址并等待粘贴回调 URL
+    Add {
+        /// 在本机回环端口上等待授权回调
+        #[arg(long)]
+        loopback: bool,
+        /// 从文件读取 Refresh Token 直接添加 (`-` 表示从 stdin 读取，避免令牌出现在命令行参数中)
+        #[arg(long, value_name = "FILE", conflicts_with = "loopback")]
+        refresh_token_file: Option<PathBuf>,
+    },
+    /// 删除账号 (ID 或邮箱)
+    Remove { account: String },
+    /// 禁用账号，号池不再调度
+    Disable {
+        account: String,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// 重新启用账号
+    Enable { account: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum QuotaCommand {
+    /// 刷新配额 (不指定账号时刷新全部)
+    Refresh { account: Option<String> },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LogsCommand {
+    /// 显示最近的请求日志
+    Tail {
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// 持续输出新日志
+        #[arg(short, long)]
+        follow: bool,
+        /// 只显示错误
+        #[arg(long)]
+        errors: bool,
+        /// 按 URL / 模型 / 账号过滤
+        #[arg(long, default_value = "")]
+        filter: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfilesCommand {
+    /// 列出数据目录下的所有 profile
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// 统计最近多少小时
+    #[arg(long, default_value_t = 24)]
+    pub hours: i64,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// 读取配置项 (点分路径，如 proxy.port；省略时输出全部)
+    Get { key: Option<String> },
+    /// 修改配置项，VALUE 按 JSON 解析，失败时按字符串处理
+    Set { key: String, value: String },
+    /// 校验配置文件 (省略时校验当前配置)
+    Validate { file: Option<PathBuf> },
+    /// 列出配置历史版本
+    History,
+    /// 回滚到历史版本 (运行中的服务会自动加载)
+    Rollback { id: String },
+    /// 输出叠加覆盖文件与环境变量后的生效配置 (密钥已隐去)
+    Effective {
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// 输出文件，省略时输出到 stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// 同时导出配置
+    #[arg(long)]
+    pub include_config: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    pub file: PathBuf,
+    /// 同时导入文件中的配置 (覆盖当前配置)
+    #[arg(long)]
+    pub with_config: bool,
+}
+
+/// export / import 的文件格式
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportBundle {
+    version: u32,
+    exported_at: i64,
+    accounts: Vec<ExportedAccount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    config: Option<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedAccount {
+    email: String,
+    refresh_token: String,
+    #[serde(default)]
+    disabled: bool,
+}
+
+/// 解析命令行并执行
+pub async fn main() -> Result<(), String> {
+    let cli = Cli::parse();
+
+    modules::profile::activate(cli.data_dir.as_deref(), cli.profile.as_deref())?;
+    if let Some(config) = &cli.config {
+        modules::config::set_config_path_override(config.clone());
+    }
+    if let Some(overlay) = &cli.config_overlay {
+        modules::config_overlay::set_overlay_path_override(overlay.clone());
+    }
+
+    match cli.command.unwrap_or(Command::Serve(ServeArgs::default())) {
+        Command::Serve(args) => {
+            crate::web_server::run_with(crate::web_server::ServeOptions {
+                port: args.port,
+                bind: args.bind,
+                log_level: cli.log_level,
+            })
+            .await
+        }
+        Command::Accounts(cmd) => accounts(cmd).await,
+        Command::Quota(QuotaCommand::Refresh { account }) => refresh_quota(account.as_deref()).await,
+        Command::Logs(LogsCommand::Tail { lines, follow, errors, filter }) => {
+            tail_logs(lines, follow, errors, &filter).await
+        }
+        Command::Stats(args) => stats(args),
+        Command::Config(cmd) => config(cmd),
+        Command::Export(args) => export(args),
+        Command::Import(args) => import(args).await,
+        Command::Profiles(ProfilesCommand::List { json }) => list_profiles(cli.data_dir.as_deref(), json),
+    }
+}
+
+fn list_profiles(root: Option<&std::path::Path>, json: bool) -> Result<(), String> {
+    let profiles = modules::profile::list_profiles(root)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&profiles).map_err(|e| e.to_string())?);
+        return Ok(());
+    }
+    for profile in profiles {
+        let status = match &profile.running {
+            Some(lock) => format!("running (pid {}, port {})", lock.pid, lock.port),
+            None => "stopped".to_string(),
+        };
+        println!("{:<20} {:<28} {}", profile.name, status, profile.data_dir.display());
+    }
+    Ok(())
+}
+
+/// 按 ID 或邮箱查找账号
+fn find_account(key: &str) -> Result<Account, String> {
+    modules::list_accounts()?
+        .into_iter()
+        .find(|a| a.id == key || a.email.eq_ignore_ascii_case(key))
+        .ok_or_else(|| format!("Account not found: {}", key))
+}
+
+async fn accounts(cmd: AccountsCommand) -> Result<(), String> {
+    match cmd {
+        AccountsCommand::List { json } => {
+            let accounts = modules::list_accounts()?;
+            if json {
+                let summary: Vec<Value> = accounts
+                    .iter()
+                    .map(|a| {
+                        serde_json::json!({
+                            "id": a.id,
+                            "email": a.email,
+                            "name": a.name,
+                            "disabled": a.disabled,
+                            "proxy_disabled": a.proxy_disabled,
+                            "subscription_tier": a.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
+                            "last_used": a.last_used,
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&summary).unwrap_or_default());
+                return Ok(());
+            }
+            println!("{:<38} {:<40} {:<10} TIER", "ID", "EMAIL", "STATUS");
+            for a in &accounts {
+                let status = if a.disabled {
+                    "disabled"
+                } else if a.proxy_disabled {
+                    "no-proxy"
+                } else {
+                    "active"
+                };
+                let tier = a.quota.as_ref().and_then(|q| q.subscription_tier.clone()).unwrap_or_default();
+                println!("{:<38} {:<40} {:<10} {}", a.id, a.email, status, tier);
+            }
+            println!("{} account(s)", accounts.len());
+            Ok(())
+        }
+        AccountsCommand::Add { loopback, refresh_token_file } => match refresh_token_file {
+            Some(path) => {
+                let token = read_refresh_token(&path)?;
+                let account = add_by_refresh_token(&token).await?;
+                println!("Added account {}", account.email);
+                Ok(())
+            }
+            None => add_account_interactive(loopback).await,
+        },
+        AccountsCommand::Remove { account } => {
+            let account = find_account(&account)?;
+            modules::delete_account(&account.id)?;
+            println!("Removed account {}", account.email);
+            Ok(())
+        }
+        AccountsCommand::Disable { account, reason } => {
+            let mut account = find_account(&account)?;
+            account.disabled = true;
+            account.disabled_reason = Some(reason.unwrap_or_else(|| "Disabled from CLI".to_string()));
+            account.disabled_at = Some(chrono::Utc::now().timestamp());
+            modules::save_account(&account)?;
+            println!("Disabled account {}", account.email);
+            Ok(())
+        }
+        AccountsCommand::Enable { account } => {
+            let mut account = find_account(&account)?;
+            account.disabled = false;
+            account.disabled_reason = None;
+            account.disabled_at = None;
+            modules::save_account(&account)?;
+            println!("Enabled account {}", account.email);
+            Ok(())
+        }
+    }
+}
+
+/// 从文件或 stdin (`-`) 读取 Refresh Token
+fn read_refresh_token(path: &Path) -> Result<String, String> {
+    let content = if path == Path::new("-") {
+        let mut buf = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf).map_err(|e| format!("stdin: {}", e))?;
+        buf
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?
+    };
+    let token = content.trim();
+    if token.is_empty() {
+        return Err("Refresh token is empty".to_string());
+    }
+    Ok(token.to_string())
+}
+
+async fn add_by_refresh_token(refresh_token: &str) -> Result<Account, String> {
+    let token_res = modules::oauth::refresh_access_token(refresh_token).await?;
+    let user_info = modules::oauth::get_user_info(&token_res.access_token).await?;
+    let project_id = crate::proxy::project_resolver::fetch_project_id(&token_res.access_token)
+        .await
+        .ok();
+    let token = TokenData::new(
+        token_res.access_token,
+        refresh_token.to_string(),
+        token_res.expires_in,
+        Some(user_info.email.clone()),
+        project_id,
+        None,
+    );
+    modules::upsert_account(user_info.email.clone(), user_info.get_display_name(), token)
+}
+
+/// 交互式添加账号 (无浏览器的服务器上使用): 打印授权地址，等待粘贴回调 URL 或回环回调
+async fn add_account_interactive(loopback: bool) -> Result<(), String> {
+    use tokio::io::AsyncBufReadExt;
+
+    let _ = modules::account::get_accounts_dir()?;
+    let login = if loopback {
+        modules::oauth_flow::start_loopback().await?
+    } else {
+        modules::oauth_flow::start(modules::oauth_flow::PASTE_REDIRECT_URI)
+    };
+
+    println!("Open this URL in a browser and sign in with the Google account to add:\n\n  {}\n", login.auth_url);
+    if loopback {
+        let port = url::Url::parse(&login.redirect_uri)
+            .ok()
+            .and_then(|u| u.port())
+            .unwrap_or_default();
+        println!("Waiting for the callback on {}.", login.redirect_uri);
+        println!("If the browser runs on another machine, forward the port first: ssh -L {0}:127.0.0.1:{0} <this-host>", port);
+        println!("Alternatively, paste the URL the browser was redirected to:");
+    } else {
+        println!(
+            "After approving, the browser is redirected to {} and the page will fail to load.",
+            login.redirect_uri
+        );
+        println!("Copy the full URL from the address bar (or just the code) and paste it here:");
+    }
+
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = true;
+    let account = loop {
+        tokio::select! {
+            line = lines.next_line(), if stdin_open => match line {
+                Ok(Some(line)) if !line.trim().is_empty() => {
+                    break modules::oauth_flow::complete(&line, Some(&login.state)).await?;
+                }
+                Ok(Some(_)) => continue,
+                _ if loopback => stdin_open = false,
+                _ => return Err("No authorization code provided".to_string()),
+            },
+            _ = tokio::time::sleep(std::time::Duration::from_millis(500)), if loopback => {
+                match modules::oauth_flow::status(&login.state) {
+                    Some(modules::oauth_flow::LoginStatus::Pending) => continue,
+                    Some(modules::oauth_flow::LoginStatus::Completed { account }) => break *account,
+                    Some(modules::oauth_flow::LoginStatus::Failed { error }) => return Err(error),
+                    None => return Err("Login session expired, please try again".to_string()),
+                }
+            }
+        }
+    };
+
+    println!("Added account {}", account.email);
+    println!("A running web_server picks it up on the next account reload (or restart it).");
+    Ok(())
+}
+
+async fn refresh_quota(account: Option<&str>) -> Result<(), String> {
+    let Some(key) = account else {
+        let stats = modules::account::refresh_all_quotas_logic().await?;
+        for line in &stats.details {
+            println!("{}", line);
+        }
+        println!("Refreshed {}/{} account(s), {} failed", stats.success, stats.total, stats.failed);
+        return Ok(());
+    };
+
+    let mut account = find_account(key)?;
+    let quota = modules::account::fetch_quota_with_retry(&mut account)
+        .await
+        .map_err(|e| e.to_string())?;
+    modules::update_account_quota(&account.id, quota.clone())?;
+    for model in &quota.models {
+        println!("{:<40} {:>3}%  reset {}", model.name, model.percentage, model.reset_time);
+    }
+    println!("Refreshed quota for {}", account.email);
+    Ok(())
+}
+
+fn print_log(log: &crate::proxy::monitor::ProxyRequestLog) {
+    let time = chrono::DateTime::from_timestamp_millis(log.timestamp)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    println!(
+        "{} {} {:<6} {:<32} {:<28} {:<32} {:>6}ms {}",
+        time,
+        log.status,
+        log.method,
+        log.url,
+        log.model.as_deref().unwrap_or("-"),
+        log.account_email.as_deref().unwrap_or("-"),
+        log.duration,
+        log.error.as_deref().unwrap_or("")
+    );
+}
+
+async fn tail_logs(lines: usize, follow: bool, errors: bool, filter: &str) -> Result<(), String> {
+    modules::proxy_db::init_db()?;
+    let mut logs = modules::proxy_db::get_logs_filtered(filter, errors, lines, 0)?;
+    logs.reverse();
+    for log in &logs {
+        print_log(log);
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut seen: std::collections::HashSet<String> = logs.iter().map(|l| l.id.clone()).collect();
+    let mut last_ts = logs.last().map(|l| l.timestamp).unwrap_or(0);
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        let mut fresh: Vec<_> = modules::proxy_db::get_logs_filtered(filter, errors, 200, 0)?
+            .into_iter()
+            .filter(|l| l.timestamp >= last_ts && !seen.contains(&l.id))
+            .collect();
+        fresh.reverse();
+        for log in fresh {
+            print_log(&log);
+            last_ts = last_ts.max(log.timestamp);
+            seen.insert(log.id);
+        }
+    }
+}
+
+fn stats(args: StatsArgs) -> Result<(), String> {
+    modules::proxy_db::init_db()?;
+    modules::token_stats::init_db()?;
+    let requests = modules::proxy_db::get_stats()?;
+    let tokens = modules::token_stats::get_summary_stats(args.hours)?;
+    let models = modules::token_stats::get_model_stats(args.hours)?;
+
+    if args.json {
+        let out = serde_json::json!({ "requests": requests, "tokens": tokens, "models": models });
+        println!("{}", serde_json::to_string_pretty(&out).unwrap_or_default());
+        return Ok(());
+    }
+
+    println!(
+        "Requests (all time): {} total, {} ok, {} errors",
+        requests.total_requests, requests.success_count, requests.error_count
+    );
+    println!(
+        "Last {}h: {} requests, {} input + {} output tokens, {} account(s), cost ${:.4}",
+        args.hours,
+        tokens.total_requests,
+        tokens.total_input_tokens,
+        tokens.total_output_tokens,
+        tokens.unique_accounts,
+        tokens.total_cost
+    );
+    for model in models {
+        println!("  {:<40} {:>8} req {:>12} tokens", model.model, model.request_count, model.total_tokens);
+    }
+    Ok(())
+}
+
+/// 按点分路径读取 JSON 节点
+fn json_path<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.').filter(|k| !k.is_empty()).try_fold(value, |v, k| v.get(k))
+}
+
+/// 按点分路径写入 JSON 节点 (中间节点不存在时创建)
+fn set_json_path(value: &mut Value, key: &str, new_value: Value) -> Result<(), String> {
+    let parts: Vec<&str> = key.split('.').filter(|k| !k.is_empty()).collect();
+    let (last, parents) = parts.split_last().ok_or("Empty config key")?;
+    let mut node = value;
+    for part in parents {
+        let obj = node.as_object_mut().ok_or_else(|| format!("'{}' is not an object", part))?;
+        node = obj.entry(part.to_string()).or_insert_with(|| Value::Object(Default::default()));
+    }
+    node.as_object_mut()
+        .ok_or_else(|| format!("Cannot set '{}' on a non-object", key))?
+        .insert(last.to_string(), new_value);
+    Ok(())
+}
+
+fn config(cmd: ConfigCommand) -> Result<(), String> {
+    match cmd {
+        ConfigCommand::Get { key } => {
+            let config = serde_json::to_value(modules::load_app_config()?).map_err(|e| e.to_string())?;
+            let value = match key.as_deref() {
+                Some(key) => json_path(&config, key).ok_or_else(|| format!("Unknown config key: {}", key))?,
+                None => &config,
+            };
+            match value {
+                Value::String(s) => println!("{}", s),
+                other => println!("{}", serde_json::to_string_pretty(other).unwrap_or_default()),
+            }
+            Ok(())
+        }
+        ConfigCommand::Set { key, value } => {
+            let mut config = serde_json::to_value(modules::load_app_config()?).map_err(|e| e.to_string())?;
+            if json_path(&config, &key).is_none() {
+                return Err(format!("Unknown config key: {}", key));
+            }
+            let parsed = serde_json::from_str(&value).unwrap_or(Value::String(value));
+            set_json_path(&mut config, &key, parsed)?;
+            let updated: AppConfig =
+                serde_json::from_value(config).map_err(|e| format!("Invalid value for {}: {}", key, e))?;
+            modules::config_reload::validate(&updated).map_err(|e| e.to_string())?;
+            modules::save_app_config(&updated)?;
+            println!("Updated {} (a running server reloads it automatically)", key);
+            Ok(())
+        }
+        ConfigCommand::Validate { file } => {
+            let path = match file {
+                Some(path) => path,
+                None => modules::config::get_config_path()?,
+            };
+            let content = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let value: Value = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let config = modules::config::config_from_file_value(value, &path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let errors = modules::config_validation::validate(
+                &config,
+                &modules::config_validation::ValidationContext::detect(None),
+            );
+            if !errors.is_empty() {
+                for error in &errors {
+                    eprintln!("  {}: {}", error.field, error.message);
+                }
+                return Err(format!("{}: {} error(s)", path.display(), errors.len()));
+            }
+            println!("{}: OK", path.display());
+            Ok(())
+        }
+        ConfigCommand::History => {
+            for entry in modules::config_history::list()? {
+                let saved_at = chrono::DateTime::from_timestamp_millis(entry.saved_at)
+                    .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default();
+                println!("{}  {}  {} bytes", entry.id, saved_at, entry.size);
+            }
+            Ok(())
+        }
+        ConfigCommand::Rollback { id } => {
+            modules::config_reload::restore_version(&id).map_err(|e| e.to_string())?;
+            println!("Restored config version {}", id);
+            Ok(())
+        }
+        ConfigCommand::Effective { json } => {
+            let (config, overrides) = modules::config::effective_config_masked()?;
+            if json {
+                let output = serde_json::json!({ "config": config, "overrides": overrides });
+                println!("{}", serde_json::to_string_pretty(&output).map_err(|e| e.to_string())?);
+                return Ok(());
+            }
+            println!("{}", serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?);
+            if !overrides.is_empty() {
+                eprintln!("\nOverridden keys:");
+                for item in overrides {
+                    eprintln!("  {:<40} {}", item.path, item.source);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn export(args: ExportArgs) -> Result<(), String> {
+    let accounts = modules::list_accounts()?
+        .into_iter()
+        .map(|a| ExportedAccount {
+            email: a.email,
+            refresh_token: a.token.refresh_token,
+            disabled: a.disabled,
+        })
+        .collect::<Vec<_>>();
+    let config = if args.include_config {
+        Some(serde_json::to_value(modules::load_app_config()?).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    let count = accounts.len();
+    let bundle = ExportBundle {
+        version: 1,
+        exported_at: chrono::Utc::now().timestamp(),
+        accounts,
+        config,
+    };
+    let json = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    match args.output {
+        Some(path) => {
+            // 导出文件包含 Refresh Token，仅允许当前用户读写
+            use std::io::Write;
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+                options.mode(0o600);
+                if path.exists() {
+                    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                }
+            }
+            options
+                .open(&path)
+                .and_then(|mut f| f.write_all(json.as_bytes()))
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            eprintln!("Exported {} account(s) to {}", count, path.display());
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+async fn import(args: ImportArgs) -> Result<(), String> {
+    let content = std::fs::read_to_string(&args.file).map_err(|e| format!("{}: {}", args.file.display(), e))?;
+    let bundle: ExportBundle = serde_json::from_str(&content).map_err(|e| format!("Invalid export file: {}", e))?;
+
+    if args.with_config {
+        if let Some(config) = bundle.config {
+            let config: AppConfig = serde_json::from_value(config).map_err(|e| format!("Invalid config: {}", e))?;
+            modules::config_reload::validate(&config).map_err(|e| format!("Invalid config: {}", e))?;
+            modules::save_app_config(&config)?;
+            println!("Imported config");
+        }
+    }
+
+    let mut imported = 0;
+    for entry in &bundle.accounts {
+        match add_by_refresh_token(&entry.refresh_token).await {
+            Ok(mut account) => {
+                if entry.disabled && !account.disabled {
+                    account.disabled = true;
+                    account.disabled_at = Some(chrono::Utc::now().timestamp());
+                    modules::save_account(&account)?;
+                }
+                imported += 1;
+                println!("Imported {}", account.email);
+            }
+            Err(e) => eprintln!("Failed to import {}: {}", entry.email, e),
+        }
+    }
+    println!("Imported {}/{} account(s)", imported, bundle.accounts.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_parses_serve_flags() {
+        let cli = Cli::try_parse_from(["web_server", "--data-dir", "/tmp/ag", "serve", "--port", "9000", "--bind", "0.0.0.0"]).unwrap();
+        assert_eq!(cli.data_dir, Some(PathBuf::from("/tmp/ag")));
+        match cli.command {
+            Some(Command::Serve(args)) => {
+                assert_eq!(args.port, Some(9000));
+                assert_eq!(args.bind.as_deref(), Some("0.0.0.0"));
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+        assert!(Cli::try_parse_from(["web_server"]).unwrap().command.is_none());
+
+        let cli = Cli::try_parse_from(["web_server", "profiles", "list", "--profile", "team-a"]).unwrap();
+        assert_eq!(cli.profile.as_deref(), Some("team-a"));
+        assert!(matches!(cli.command, Some(Command::Profiles(ProfilesCommand::List { json: false }))));
+
+        // Refresh Token 只能从文件或 stdin 读取
+        assert!(Cli::try_parse_from(["web_server", "accounts", "add", "--refresh-token", "1//abc"]).is_err());
+        let cli = Cli::try_parse_from(["web_server", "accounts", "add", "--refresh-token-file", "-"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Accounts(AccountsCommand::Add { loopback: false, refresh_token_file: Some(ref p) })) if p == Path::new("-")
+        ));
+    }
+
+    #[test]
+    fn test_read_refresh_token_file() {
+        let path = std::env::temp_dir().join(format!("refresh_token_test_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&path, "  1//abc\n").unwrap();
+        assert_eq!(read_refresh_token(&path).unwrap(), "1//abc");
+        std::fs::write(&path, "\n").unwrap();
+        assert!(read_refresh_token(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_config_paths() {
+        let mut config = serde_json::json!({ "proxy": { "port": 8045 } });
+        assert_eq!(json_path(&config, "proxy.port"), Some(&serde_json::json!(8045)));
+        assert!(json_path(&config, "proxy.missing").is_none());
+        set_json_path(&mut config, "proxy.port", serde_json::json!(9000)).unwrap();
+        set_json_path(&mut config, "proxy.zai.enabled", serde_json::json!(true)).unwrap();
+        assert_eq!(config["proxy"]["port"], 9000);
+        assert_eq!(config["proxy"]["zai"]["enabled"], true);
+        assert!(set_json_path(&mut config, "proxy.port.x", serde_json::json!(1)).is_err());
+    }
+}
@@ -4,3 +4,4 @@ mod utils;
 mod proxy;
 pub mod error;
 pub mod web_server;
+pub mod cli;
@@ -23,6 +23,10 @@ pub struct AppConfig {
     pub quota_protection: QuotaProtectionConfig, // [NEW] Quota protection configuration
     #[serde(default)]
     pub pinned_quota_models: PinnedQuotaModelsConfig, // [NEW] Pinned quota models list
+    #[serde(default)]
+    pub token_stats: TokenStatsRetentionConfig, // [NEW] Token stats rollup retention
+    #[serde(default)]
+    pub management: ManagementConfig, // [NEW] Management API access (CORS, sessions)
 }
 
 /// Scheduled warmup configuration
@@ -129,6 +133,95 @@ impl Default for PinnedQuotaModelsConfig {
     }
 }
 
+/// Token stats retention configuration
+/// Hourly rollups older than `hourly_retention_days` are folded into daily rollups,
+/// daily rollups older than `daily_retention_days` into monthly rollups.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenStatsRetentionConfig {
+    /// Days to keep raw per-request usage rows (0 = forever)
+    #[serde(default = "default_raw_retention_days")]
+    pub raw_retention_days: i64,
+
+    /// Days to keep hourly resolution
+    #[serde(default = "default_hourly_retention_days")]
+    pub hourly_retention_days: i64,
+
+    /// Days to keep daily resolution
+    #[serde(default = "default_daily_retention_days")]
+    pub daily_retention_days: i64,
+
+    /// Months to keep monthly resolution (0 = forever)
+    #[serde(default)]
+    pub monthly_retention_months: i64,
+}
+
+fn default_raw_retention_days() -> i64 {
+    30
+}
+
+fn default_hourly_retention_days() -> i64 {
+    14
+}
+
+fn default_daily_retention_days() -> i64 {
+    365
+}
+
+impl Default for TokenStatsRetentionConfig {
+    fn default() -> Self {
+        Self {
+            raw_retention_days: default_raw_retention_days(),
+            hourly_retention_days: default_hourly_retention_days(),
+            daily_retention_days: default_daily_retention_days(),
+            monthly_retention_months: 0,
+        }
+    }
+}
+
+/// Management API (web UI) access configuration
+/// Users and roles are stored separately in `web_users.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManagementConfig {
+    /// Browser origins allowed to call the management API (CORS)
+    #[serde(default = "default_allowed_origins")]
+    pub allowed_origins: Vec<String>,
+
+    /// Login session lifetime in hours
+    #[serde(default = "default_session_ttl_hours")]
+    pub session_ttl_hours: u64,
+
+    /// Days to keep audit log entries (0 = forever)
+    #[serde(default = "default_audit_retention_days")]
+    pub audit_retention_days: i64,
+}
+
+fn default_allowed_origins() -> Vec<String> {
+    vec![
+        "http://localhost:1420".to_string(),
+        "http://127.0.0.1:1420".to_string(),
+        "tauri://localhost".to_string(),
+        "http://tauri.localhost".to_string(),
+    ]
+}
+
+fn default_session_ttl_hours() -> u64 {
+    24
+}
+
+fn default_audit_retention_days() -> i64 {
+    365
+}
+
+impl Default for ManagementConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: default_allowed_origins(),
+            session_ttl_hours: default_session_ttl_hours(),
+            audit_retention_days: default_audit_retention_days(),
+        }
+    }
+}
+
 impl AppConfig {
     pub fn new() -> Self {
         Self {
@@ -146,6 +239,8 @@ impl AppConfig {
             scheduled_warmup: ScheduledWarmupConfig::default(),
             quota_protection: QuotaProtectionConfig::default(),
             pinned_quota_models: PinnedQuotaModelsConfig::default(),
+            token_stats: TokenStatsRetentionConfig::default(),
+            management: ManagementConfig::default(),
         }
     }
 }
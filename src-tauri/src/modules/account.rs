@@ -17,11 +17,26 @@ const DATA_DIR: &str = ".antigravity_tools";
 const ACCOUNTS_INDEX: &str = "accounts.json";
 const ACCOUNTS_DIR: &str = "accounts";
 
+/// Data directory chosen on the command line (`--data-dir` / `--profile`); falls back to ~/.antigravity_tools
+static DATA_DIR_OVERRIDE: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+/// Override the data directory. Must be called before anything reads from it.
+pub fn set_data_dir_override(dir: PathBuf) {
+    let _ = DATA_DIR_OVERRIDE.set(dir);
+}
+
 // ... existing functions get_data_dir, get_accounts_dir, load_account_index, save_account_index ...
+/// Default data directory (~/.antigravity_tools), also the root for named profiles
+pub fn default_data_root() -> Result<PathBuf, String> {
+    Ok(dirs::home_dir().ok_or("failed_to_get_home_dir")?.join(DATA_DIR))
+}
+
 /// Get data directory path
 pub fn get_data_dir() -> Result<PathBuf, String> {
-    let home = dirs::home_dir().ok_or("failed_to_get_home_dir")?;
-    let data_dir = home.join(DATA_DIR);
+    let data_dir = match DATA_DIR_OVERRIDE.get() {
+        Some(dir) => dir.clone(),
+        None => default_data_root()?,
+    };
     
     // Ensure directory exists
     if !data_dir.exists() {
@@ -0,0 +1,367 @@
+//! Append-only audit trail of management actions (`audit_log.db`).
+//!
+//! Every state-changing `invoke` command, denied command and login attempt is recorded
+//! with the acting user, source IP, sanitized arguments and outcome. Rows cannot be
+//! updated; they are only removed by the retention cleanup.
+
+use rusqlite::{params, Connection, ToSql};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Max bytes of sanitized arguments kept per entry
+const ARGS_LIMIT: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(default)]
+    pub id: i64,
+    /// Unix timestamp (milliseconds)
+    pub timestamp: i64,
+    pub username: String,
+    pub role: Option<String>,
+    pub source_ip: Option<String>,
+    pub forwarded_for: Option<String>,
+    pub command: String,
+    pub args: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Filters for `query`; all fields are optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Only entries at or after this timestamp (ms)
+    #[serde(default)]
+    pub since: Option<i64>,
+    /// Only entries before this timestamp (ms)
+    #[serde(default)]
+    pub until: Option<i64>,
+    #[serde(default)]
+    pub success: Option<bool>,
+    /// Substring matched against the sanitized arguments (e.g. an account id)
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditQueryResult {
+    pub total: u64,
+    pub items: Vec<AuditEntry>,
+}
+
+fn get_db_path() -> Result<PathBuf, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    Ok(data_dir.join("audit_log.db"))
+}
+
+fn connect_db() -> Result<Connection, String> {
+    let conn = Connection::open(get_db_path()?).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000)
+        .map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+pub fn init_db() -> Result<(), String> {
+    let conn = connect_db()?;
+    init_schema(&conn)
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            username TEXT NOT NULL,
+            role TEXT,
+            source_ip TEXT,
+            forwarded_for TEXT,
+            command TEXT NOT NULL,
+            args TEXT,
+            success INTEGER NOT NULL,
+            error TEXT,
+            duration_ms INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_timestamp ON audit_log (timestamp DESC);
+        CREATE INDEX IF NOT EXISTS idx_audit_username ON audit_log (username);
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'audit log is append-only');
+        END;",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Whether an argument key holds a credential
+fn is_sensitive_key(key: &str) -> bool {
+    let normalized: String = key
+        .chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect();
+    ["password", "token", "apikey", "secret", "code", "cookie"]
+        .iter()
+        .any(|suffix| normalized.ends_with(suffix))
+}
+
+/// Replace credential values in command arguments with `[REDACTED]`
+pub fn sanitize_args(args: &Value) -> Value {
+    match args {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let value = if is_sensitive_key(k) && !v.is_null() {
+                        Value::String("[REDACTED]".to_string())
+                    } else {
+                        sanitize_args(v)
+                    };
+                    (k.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(sanitize_args).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Serialize sanitized arguments, truncated to `ARGS_LIMIT`
+pub fn format_args(args: &Value) -> Option<String> {
+    if args.is_null() || args.as_object().map(|m| m.is_empty()).unwrap_or(false) {
+        return None;
+    }
+    let mut text = sanitize_args(args).to_string();
+    if text.len() > ARGS_LIMIT {
+        let mut end = ARGS_LIMIT;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("...[truncated]");
+    }
+    Some(text)
+}
+
+fn insert_with_conn(conn: &Connection, entry: &AuditEntry) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO audit_log (timestamp, username, role, source_ip, forwarded_for, command, args, success, error, duration_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            entry.timestamp,
+            entry.username,
+            entry.role,
+            entry.source_ip,
+            entry.forwarded_for,
+            entry.command,
+            entry.args,
+            entry.success,
+            entry.error,
+            entry.duration_ms,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Append an entry; failures are logged rather than surfaced to the caller
+pub fn record(entry: &AuditEntry) {
+    let result = connect_db().and_then(|conn| insert_with_conn(&conn, entry));
+    if let Err(e) = result {
+        tracing::error!("[Audit] Failed to record '{}' by {}: {}", entry.command, entry.username, e);
+    }
+}
+
+fn query_with_conn(conn: &Connection, filter: &AuditQuery, limit: usize, offset: usize) -> Result<AuditQueryResult, String> {
+    let mut conditions: Vec<String> = Vec::new();
+    let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+    let mut push = |condition: &str, value: Box<dyn ToSql>| {
+        values.push(value);
+        conditions.push(condition.replace('?', &format!("?{}", values.len())));
+    };
+
+    if let Some(username) = filter.username.as_ref().filter(|s| !s.is_empty()) {
+        push("username = ?", Box::new(username.clone()));
+    }
+    if let Some(command) = filter.command.as_ref().filter(|s| !s.is_empty()) {
+        push("command = ?", Box::new(command.clone()));
+    }
+    if let Some(since) = filter.since {
+        push("timestamp >= ?", Box::new(since));
+    }
+    if let Some(until) = filter.until {
+        push("timestamp < ?", Box::new(until));
+    }
+    if let Some(success) = filter.success {
+        push("success = ?", Box::new(success));
+    }
+    if let Some(text) = filter.text.as_ref().filter(|s| !s.is_empty()) {
+        push("instr(COALESCE(args, ''), ?) > 0", Box::new(text.clone()));
+    }
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let params: Vec<&dyn ToSql> = values.iter().map(|v| v.as_ref()).collect();
+
+    let total: u64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM audit_log {}", where_clause),
+            params.as_slice(),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let sql = format!(
+        "SELECT id, timestamp, username, role, source_ip, forwarded_for, command, args, success, error, duration_ms
+         FROM audit_log {} ORDER BY timestamp DESC, id DESC LIMIT {} OFFSET {}",
+        where_clause, limit, offset
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params.as_slice(), |row| {
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                username: row.get(2)?,
+                role: row.get(3)?,
+                source_ip: row.get(4)?,
+                forwarded_for: row.get(5)?,
+                command: row.get(6)?,
+                args: row.get(7)?,
+                success: row.get(8)?,
+                error: row.get(9)?,
+                duration_ms: row.get(10)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        items.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(AuditQueryResult { total, items })
+}
+
+/// Query the audit trail, newest first
+pub fn query(filter: &AuditQuery, limit: usize, offset: usize) -> Result<AuditQueryResult, String> {
+    let conn = connect_db()?;
+    query_with_conn(&conn, filter, limit, offset)
+}
+
+/// Delete entries older than `days` (0 = keep forever)
+pub fn cleanup_old_entries(days: i64) -> Result<usize, String> {
+    if days <= 0 {
+        return Ok(0);
+    }
+    let cutoff = chrono::Utc::now().timestamp_millis() - days * 24 * 3600 * 1000;
+    let conn = connect_db()?;
+    conn.execute("DELETE FROM audit_log WHERE timestamp < ?1", [cutoff])
+        .map_err(|e| e.to_string())
+}
+
+/// Apply the configured retention now and then once a day
+pub fn start_retention_task() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(24 * 3600));
+        loop {
+            interval.tick().await;
+            let days = crate::modules::config::load_app_config()
+                .map(|c| c.management.audit_retention_days)
+                .unwrap_or_default();
+            match tokio::task::spawn_blocking(move || cleanup_old_entries(days)).await {
+                Ok(Ok(deleted)) if deleted > 0 => {
+                    tracing::info!("[Audit] Removed {} entries older than {} days", deleted, days);
+                }
+                Ok(Err(e)) => tracing::error!("[Audit] Retention cleanup failed: {}", e),
+                Err(e) => tracing::error!("[Audit] Retention task panicked: {}", e),
+                _ => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(username: &str, command: &str, args: Value, success: bool, timestamp: i64) -> AuditEntry {
+        AuditEntry {
+            id: 0,
+            timestamp,
+            username: username.to_string(),
+            role: Some("admin".to_string()),
+            source_ip: Some("192.168.1.20".to_string()),
+            forwarded_for: None,
+            command: command.to_string(),
+            args: format_args(&args),
+            success,
+            error: None,
+            duration_ms: 3,
+        }
+    }
+
+    #[test]
+    fn test_sanitize_args() {
+        let args = json!({
+            "accountId": "acc-1",
+            "refreshToken": "1//secret",
+            "config": { "proxy": { "api_key": "sk-123", "port": 8045 }, "token_stats": { "raw_retention_days": 30 } },
+            "newPassword": "hunter22",
+        });
+        let sanitized = sanitize_args(&args);
+        assert_eq!(sanitized["accountId"], "acc-1");
+        assert_eq!(sanitized["refreshToken"], "[REDACTED]");
+        assert_eq!(sanitized["newPassword"], "[REDACTED]");
+        assert_eq!(sanitized["config"]["proxy"]["api_key"], "[REDACTED]");
+        assert_eq!(sanitized["config"]["proxy"]["port"], 8045);
+        assert_eq!(sanitized["config"]["token_stats"]["raw_retention_days"], 30);
+        assert!(format_args(&json!({})).is_none());
+    }
+
+    #[test]
+    fn test_append_only_and_query() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+
+        insert_with_conn(&conn, &entry("alice", "toggle_proxy_status", json!({ "accountId": "acc-1", "enable": false }), true, 1_000)).unwrap();
+        insert_with_conn(&conn, &entry("bob", "delete_account", json!({ "accountId": "acc-2" }), false, 2_000)).unwrap();
+        insert_with_conn(&conn, &entry("alice", "save_config", json!({ "config": {} }), true, 3_000)).unwrap();
+
+        assert!(conn.execute("UPDATE audit_log SET username = 'mallory'", []).is_err());
+
+        let all = query_with_conn(&conn, &AuditQuery::default(), 10, 0).unwrap();
+        assert_eq!(all.total, 3);
+        assert_eq!(all.items[0].command, "save_config");
+
+        let filter = AuditQuery {
+            text: Some("acc-1".to_string()),
+            ..Default::default()
+        };
+        let res = query_with_conn(&conn, &filter, 10, 0).unwrap();
+        assert_eq!(res.total, 1);
+        assert_eq!(res.items[0].username, "alice");
+        assert_eq!(res.items[0].source_ip.as_deref(), Some("192.168.1.20"));
+
+        let filter = AuditQuery {
+            username: Some("alice".to_string()),
+            since: Some(2_000),
+            ..Default::default()
+        };
+        assert_eq!(query_with_conn(&conn, &filter, 10, 0).unwrap().total, 1);
+
+        let filter = AuditQuery {
+            success: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(query_with_conn(&conn, &filter, 10, 0).unwrap().items[0].username, "bob");
+    }
+}
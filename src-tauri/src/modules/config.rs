@@ -1,21 +1,49 @@
 use std::fs;
+use std::path::{Path, PathBuf};
 use serde_json;
 
 use crate::models::AppConfig;
 use super::account::get_data_dir;
+use super::{config_history, config_overlay};
 
 const CONFIG_FILE: &str = "gui_config.json";
 
-/// Load application configuration
+/// Config file chosen on the command line (`--config`)
+static CONFIG_PATH_OVERRIDE: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+/// Override the config file path. Must be called before the config is first loaded.
+pub fn set_config_path_override(path: PathBuf) {
+    let _ = CONFIG_PATH_OVERRIDE.set(path);
+}
+
+/// Path of the application config file
+pub fn get_config_path() -> Result<PathBuf, String> {
+    match CONFIG_PATH_OVERRIDE.get() {
+        Some(path) => Ok(path.clone()),
+        None => Ok(get_data_dir()?.join(CONFIG_FILE)),
+    }
+}
+
+/// Load application configuration (file + overlay file + `ANTIGRAVITY_*` env vars)
 pub fn load_app_config() -> Result<AppConfig, String> {
-    let data_dir = get_data_dir()?;
-    let config_path = data_dir.join(CONFIG_FILE);
-    
+    let config_path = get_config_path()?;
+    let v = load_file_config(&config_path)?;
+    config_from_file_value(v, &config_path)
+}
+
+/// Apply the overlay file and env vars on top of a config file value
+pub fn config_from_file_value(mut v: serde_json::Value, config_path: &Path) -> Result<AppConfig, String> {
+    config_overlay::apply(&mut v, config_path)?;
+    serde_json::from_value(v).map_err(|e| format!("failed_to_apply_config_overrides: {}", e))
+}
+
+/// Load the config file alone (defaults when missing), migrating legacy fields
+fn load_file_config(config_path: &Path) -> Result<serde_json::Value, String> {
     if !config_path.exists() {
-        return Ok(AppConfig::new());
+        return serde_json::to_value(AppConfig::new()).map_err(|e| e.to_string());
     }
     
-    let content = fs::read_to_string(&config_path)
+    let content = fs::read_to_string(config_path)
         .map_err(|e| format!("failed_to_read_config_file: {}", e))?;
     
     let mut v: serde_json::Value = serde_json::from_str(&content)
@@ -64,25 +92,47 @@ pub fn load_app_config() -> Result<AppConfig, String> {
         }
     }
 
-    let config: AppConfig = serde_json::from_value(v)
+    let config: AppConfig = serde_json::from_value(v.clone())
         .map_err(|e| format!("failed_to_convert_config_after_migration: {}", e))?;
     
     // If migration occurred, auto-save once to clean up the file
     if modified {
-        let _ = save_app_config(&config);
+        let _ = write_config_file(config_path, &config);
     }
 
-    Ok(config)
+    Ok(v)
+}
+
+/// Effective config with secrets masked, plus the overridden keys and their sources
+pub fn effective_config_masked() -> Result<(serde_json::Value, Vec<config_overlay::Override>), String> {
+    let config = load_app_config()?;
+    let mut value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+    config_overlay::mask_secrets(&mut value);
+    Ok((value, config_overlay::active_overrides()))
 }
 
-/// Save application configuration
+/// Save application configuration (values coming from overrides are not written back)
 pub fn save_app_config(config: &AppConfig) -> Result<(), String> {
-    let data_dir = get_data_dir()?;
-    let config_path = data_dir.join(CONFIG_FILE);
-    
-    let content = serde_json::to_string_pretty(config)
+    let mut value = serde_json::to_value(config)
         .map_err(|e| format!("failed_to_serialize_config: {}", e))?;
-    
+    config_overlay::strip_overrides(&mut value);
+    let content = serde_json::to_string_pretty(&value)
+        .map_err(|e| format!("failed_to_serialize_config: {}", e))?;
+    write_config_content(&content)
+}
+
+/// Replace the config file, keeping the previous version in the config history
+pub fn write_config_content(content: &str) -> Result<(), String> {
+    let config_path = get_config_path()?;
+    if let Err(e) = config_history::record_file(&config_path) {
+        tracing::warn!("Failed to record config history: {}", e);
+    }
     fs::write(&config_path, content)
         .map_err(|e| format!("failed_to_save_config: {}", e))
 }
+
+fn write_config_file(config_path: &Path, config: &AppConfig) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("failed_to_serialize_config: {}", e))?;
+    fs::write(config_path, content).map_err(|e| format!("failed_to_save_config: {}", e))
+}
@@ -0,0 +1,127 @@
+// 配置历史 (Config history)
+//
+// 每次配置文件被覆盖前，将旧内容保存到 `<data_dir>/config_history/<毫秒时间戳>.json`，
+// 最多保留 MAX_ENTRIES 份，可通过管理后台或 `config rollback` 回滚。
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HISTORY_DIR: &str = "config_history";
+const MAX_ENTRIES: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    /// 版本号 (保存时间，毫秒时间戳)
+    pub id: String,
+    pub saved_at: i64,
+    pub size: u64,
+}
+
+fn history_dir() -> Result<PathBuf, String> {
+    let dir = super::account::get_data_dir()?.join(HISTORY_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("failed_to_create_history_dir: {}", e))?;
+    Ok(dir)
+}
+
+fn entry_path(dir: &Path, id: &str) -> Result<PathBuf, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid config version: {}", id));
+    }
+    Ok(dir.join(format!("{}.json", id)))
+}
+
+fn list_in(dir: &Path) -> Vec<HistoryEntry> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut history: Vec<HistoryEntry> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let id = name.strip_suffix(".json")?.to_string();
+            let saved_at = id.parse::<i64>().ok()?;
+            Some(HistoryEntry {
+                id,
+                saved_at,
+                size: e.metadata().map(|m| m.len()).unwrap_or(0),
+            })
+        })
+        .collect();
+    history.sort_by_key(|e| std::cmp::Reverse(e.saved_at));
+    history
+}
+
+fn record_in(dir: &Path, content: &str) -> Result<Option<HistoryEntry>, String> {
+    let history = list_in(dir);
+    // 与最近一份相同时不重复保存
+    if let Some(latest) = history.first() {
+        if fs::read_to_string(dir.join(format!("{}.json", latest.id))).is_ok_and(|c| c == content) {
+            return Ok(None);
+        }
+    }
+
+    let mut saved_at = chrono::Utc::now().timestamp_millis();
+    if let Some(latest) = history.first() {
+        saved_at = saved_at.max(latest.saved_at + 1);
+    }
+    let id = saved_at.to_string();
+    fs::write(entry_path(dir, &id)?, content).map_err(|e| format!("failed_to_save_config_history: {}", e))?;
+
+    for old in history.iter().skip(MAX_ENTRIES - 1) {
+        let _ = fs::remove_file(dir.join(format!("{}.json", old.id)));
+    }
+    Ok(Some(HistoryEntry {
+        id,
+        saved_at,
+        size: content.len() as u64,
+    }))
+}
+
+/// 保存一份历史版本
+pub fn record(content: &str) -> Result<Option<HistoryEntry>, String> {
+    record_in(&history_dir()?, content)
+}
+
+/// 保存配置文件当前内容 (文件不存在时忽略)
+pub fn record_file(config_path: &Path) -> Result<(), String> {
+    match fs::read_to_string(config_path) {
+        Ok(content) => record(&content).map(|_| ()),
+        Err(_) => Ok(()),
+    }
+}
+
+/// 历史版本列表，最新的在前
+pub fn list() -> Result<Vec<HistoryEntry>, String> {
+    Ok(list_in(&history_dir()?))
+}
+
+/// 读取历史版本内容
+pub fn load(id: &str) -> Result<String, String> {
+    let path = entry_path(&history_dir()?, id)?;
+    fs::read_to_string(&path).map_err(|_| format!("Config version not found: {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_rotation() {
+        let dir = std::env::temp_dir().join(format!("ag_history_{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+
+        assert!(record_in(&dir, "v0").unwrap().is_some());
+        assert!(record_in(&dir, "v0").unwrap().is_none());
+        for i in 1..=MAX_ENTRIES + 2 {
+            record_in(&dir, &format!("v{}", i)).unwrap();
+        }
+        let history = list_in(&dir);
+        assert_eq!(history.len(), MAX_ENTRIES);
+        let latest = fs::read_to_string(entry_path(&dir, &history[0].id).unwrap()).unwrap();
+        assert_eq!(latest, format!("v{}", MAX_ENTRIES + 2));
+        assert!(entry_path(&dir, "../gui_config").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
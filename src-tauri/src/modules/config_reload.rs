@@ -0,0 +1,254 @@
+// 配置热加载 (Config hot-reload)
+//
+// 轮询配置文件与覆盖文件，内容变化时重新加载、校验并应用到运行中的反代; 校验失败时保留当前配置。
+// 端口与监听地址的变更需要重启服务才会生效。
+
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use crate::models::AppConfig;
+use crate::modules::config_validation::{self, FieldError, ValidationContext};
+use crate::modules::web_api::WebApiState;
+use crate::modules::{config, config_history};
+use crate::proxy;
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 当前已应用的配置，用于跳过未发生变化的重新加载 (如管理后台保存后文件被写入)
+static APPLIED: Lazy<RwLock<Option<Value>>> = Lazy::new(|| RwLock::new(None));
+static BINDING: OnceLock<ListenBinding> = OnceLock::new();
+
+/// 服务启动时确定的监听参数，重启前不会随配置文件变化
+#[derive(Debug, Clone, Copy)]
+pub struct ListenBinding {
+    pub port: u16,
+    /// 端口由命令行 --port 指定，配置文件中的 proxy.port 暂不生效
+    pub port_override: bool,
+    /// 按实际监听地址得出的局域网访问状态
+    pub allow_lan_access: bool,
+}
+
+impl ListenBinding {
+    /// 校验时需检查可用性的当前端口; 命令行指定端口时配置文件中的端口不会被使用，无需检查
+    fn validation_port(&self) -> Option<u16> {
+        (!self.port_override).then_some(self.port)
+    }
+
+    /// 运行时鉴权配置; 监听地址重启后才会变化，auth=auto 按实际监听地址决定是否鉴权
+    fn security_config(&self, config: &proxy::ProxyConfig) -> proxy::ProxySecurityConfig {
+        let mut security = proxy::ProxySecurityConfig::from_proxy_config(config);
+        security.allow_lan_access = self.allow_lan_access;
+        security
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// 校验失败的字段
+    Invalid(Vec<FieldError>),
+    Failed(String),
+}
+
+impl From<String> for ConfigError {
+    fn from(e: String) -> Self {
+        Self::Failed(e)
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(errors) => write!(f, "Invalid config: {}", config_validation::summarize(errors)),
+            Self::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// 按运行中服务的状态校验配置
+pub fn validate(config: &AppConfig) -> Result<(), ConfigError> {
+    let running_port = BINDING.get().and_then(ListenBinding::validation_port);
+    let errors = config_validation::validate(config, &ValidationContext::detect(running_port));
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(errors))
+    }
+}
+
+fn mark_applied(config: &AppConfig) -> Option<Value> {
+    let value = serde_json::to_value(config).ok();
+    APPLIED.write().ok().and_then(|mut applied| std::mem::replace(&mut *applied, value))
+}
+
+/// 将配置应用到运行中的反代 (映射、上游代理、鉴权、z.ai、实验功能、调度等)
+pub async fn apply_config(state: &WebApiState, app_config: &AppConfig) {
+    let config = &app_config.proxy;
+    {
+        let mut mapping = state.proxy_runtime.custom_mapping.write().await;
+        *mapping = config.custom_mapping.clone();
+    }
+    {
+        let mut proxy_state = state.proxy_runtime.proxy_state.write().await;
+        *proxy_state = config.upstream_proxy.clone();
+    }
+    {
+        let mut security = state.proxy_runtime.security_state.write().await;
+        *security = match BINDING.get() {
+            Some(binding) => binding.security_config(config),
+            None => proxy::ProxySecurityConfig::from_proxy_config(config),
+        };
+    }
+    {
+        let mut zai = state.proxy_runtime.zai_state.write().await;
+        *zai = config.zai.clone();
+    }
+    {
+        let mut experimental = state.proxy_runtime.experimental.write().await;
+        *experimental = config.experimental.clone();
+        proxy::SignatureCache::global().configure_persistence(
+            experimental.persist_signature_cache,
+            experimental.signature_cache_max_entries,
+        );
+    }
+    state.monitor.set_capture_config(config.log_capture.clone());
+    proxy::mappers::tool_result_compressor::configure(config.tool_results.clone());
+    proxy::image_store::configure(config.image_storage.clone());
+    proxy::audio::speech::configure(config.speech.clone());
+    proxy::audio::transcription::configure(config.transcription.clone());
+    state.token_manager.update_sticky_config(config.scheduling.clone()).await;
+
+    let previous = mark_applied(app_config);
+    let restart_needed = previous.is_some_and(|prev| {
+        prev["proxy"]["port"] != config.port
+            || prev["proxy"]["allow_lan_access"] != config.allow_lan_access
+            || serde_json::to_value(&config.tls).is_ok_and(|tls| prev["proxy"]["tls"] != tls)
+    });
+    if restart_needed {
+        tracing::warn!("[Config] proxy.port / proxy.allow_lan_access / proxy.tls changed; restart the server to apply");
+    }
+}
+
+/// 校验并保存配置，然后应用生效配置 (叠加覆盖文件与环境变量后)
+pub async fn save_and_apply(state: &WebApiState, config: &AppConfig) -> Result<AppConfig, ConfigError> {
+    validate(config)?;
+    config::save_app_config(config)?;
+    let effective = config::load_app_config()?;
+    apply_config(state, &effective).await;
+    Ok(effective)
+}
+
+/// 从磁盘重新加载配置，返回是否有变化并已应用
+pub async fn reload(state: &WebApiState) -> Result<bool, ConfigError> {
+    let config = config::load_app_config()?;
+    let value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+    if APPLIED.read().is_ok_and(|applied| applied.as_ref() == Some(&value)) {
+        return Ok(false);
+    }
+    validate(&config)?;
+    apply_config(state, &config).await;
+    Ok(true)
+}
+
+/// 将历史版本写回配置文件 (当前版本同样会进入历史)，返回回滚后的生效配置; 不会应用到运行中的服务
+pub fn restore_version(id: &str) -> Result<AppConfig, ConfigError> {
+    let content = config_history::load(id)?;
+    let value: Value = serde_json::from_str(&content).map_err(|e| format!("Corrupted config version {}: {}", id, e))?;
+    let effective = config::config_from_file_value(value, &config::get_config_path()?)?;
+    validate(&effective)?;
+    config::write_config_content(&content)?;
+    Ok(effective)
+}
+
+/// 回滚到历史版本并立即应用
+pub async fn rollback(state: &WebApiState, id: &str) -> Result<AppConfig, ConfigError> {
+    let effective = restore_version(id)?;
+    apply_config(state, &effective).await;
+    tracing::info!("[Config] Rolled back to version {}", id);
+    Ok(effective)
+}
+
+/// 配置文件与覆盖文件内容的指纹
+fn fingerprint(config_path: &Path) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(std::fs::read(config_path).unwrap_or_default());
+    if let Some(overlay) = crate::modules::config_overlay::overlay_path(config_path) {
+        hasher.update(overlay.to_string_lossy().as_bytes());
+        hasher.update(std::fs::read(overlay).unwrap_or_default());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// 启动配置文件监听
+pub fn spawn_watcher(state: WebApiState, initial: &AppConfig, binding: ListenBinding) {
+    let _ = BINDING.set(binding);
+    mark_applied(initial);
+
+    tokio::spawn(async move {
+        let Ok(config_path) = config::get_config_path() else {
+            return;
+        };
+        let mut seen = fingerprint(&config_path);
+        // 最近一次成功应用时的文件内容，外部修改生效后存入历史
+        let mut last_good = std::fs::read_to_string(&config_path).ok();
+
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let current = fingerprint(&config_path);
+            if current == seen {
+                continue;
+            }
+            seen = current;
+
+            let content = std::fs::read_to_string(&config_path).ok();
+            match reload(&state).await {
+                Ok(true) => {
+                    if let Some(previous) = last_good.as_deref().filter(|p| Some(*p) != content.as_deref()) {
+                        if let Err(e) = config_history::record(previous) {
+                            tracing::warn!("Failed to record config history: {}", e);
+                        }
+                    }
+                    tracing::info!("[Config] Reloaded {}", config_path.display());
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::error!("[Config] Ignoring changes to {}: {}", config_path.display(), e);
+                    continue;
+                }
+            }
+            last_good = content;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_binding_until_restart() {
+        let binding = ListenBinding {
+            port: 9000,
+            port_override: true,
+            allow_lan_access: true,
+        };
+        assert_eq!(binding.validation_port(), None);
+        assert_eq!(ListenBinding { port_override: false, ..binding }.validation_port(), Some(9000));
+
+        // 仍监听 0.0.0.0 时关闭 allow_lan_access 不应关闭鉴权
+        let config = proxy::ProxyConfig {
+            auth_mode: proxy::ProxyAuthMode::Auto,
+            allow_lan_access: false,
+            ..Default::default()
+        };
+        assert!(matches!(
+            binding.security_config(&config).effective_auth_mode(),
+            proxy::ProxyAuthMode::AllExceptHealth
+        ));
+    }
+}
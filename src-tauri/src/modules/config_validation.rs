@@ -0,0 +1,231 @@
+// 配置校验 (Config validation)
+//
+// 保存、热加载与回滚前对完整 AppConfig 做校验，返回字段级错误，校验失败时不会应用新配置。
+
+use serde::Serialize;
+
+use crate::models::AppConfig;
+use crate::proxy::common::model_mapping;
+use crate::proxy::config::ClientAuthMode;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    /// 点分路径，如 `proxy.upstream_proxy.url`
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// 校验时需要参考的运行时状态
+#[derive(Debug, Clone, Default)]
+pub struct ValidationContext {
+    /// 当前服务正在监听的端口; 新端口与之不同时检查是否可用
+    pub running_port: Option<u16>,
+    /// 已被其他服务 (如 HTTP API) 占用的端口
+    pub reserved_ports: Vec<(u16, &'static str)>,
+}
+
+impl ValidationContext {
+    pub fn detect(running_port: Option<u16>) -> Self {
+        let mut reserved_ports = Vec::new();
+        if let Ok(settings) = crate::modules::http_api::load_settings() {
+            if settings.enabled {
+                reserved_ports.push((settings.port, "HTTP API"));
+            }
+        }
+        Self {
+            running_port,
+            reserved_ports,
+        }
+    }
+}
+
+fn check_url(errors: &mut Vec<FieldError>, field: &str, value: &str, schemes: &[&str]) {
+    match url::Url::parse(value) {
+        Ok(url) if schemes.contains(&url.scheme()) => {
+            if url.host_str().is_none_or(str::is_empty) {
+                errors.push(FieldError::new(field, "URL has no host"));
+            }
+        }
+        Ok(url) => errors.push(FieldError::new(
+            field,
+            format!("Unsupported scheme '{}', expected one of: {}", url.scheme(), schemes.join(", ")),
+        )),
+        Err(e) => errors.push(FieldError::new(field, format!("Invalid URL: {}", e))),
+    }
+}
+
+/// 校验配置，返回所有字段错误 (为空表示通过)
+pub fn validate(config: &AppConfig, ctx: &ValidationContext) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let proxy = &config.proxy;
+
+    // 端口
+    if proxy.port == 0 {
+        errors.push(FieldError::new("proxy.port", "Port must be between 1 and 65535"));
+    } else if let Some((_, owner)) = ctx.reserved_ports.iter().find(|(port, _)| *port == proxy.port) {
+        errors.push(FieldError::new(
+            "proxy.port",
+            format!("Port {} conflicts with the {} server", proxy.port, owner),
+        ));
+    } else if ctx.running_port.is_some_and(|running| running != proxy.port)
+        && std::net::TcpListener::bind((proxy.get_bind_address(), proxy.port)).is_err()
+    {
+        errors.push(FieldError::new(
+            "proxy.port",
+            format!("Port {} is already in use", proxy.port),
+        ));
+    }
+    if proxy.request_timeout == 0 {
+        errors.push(FieldError::new("proxy.request_timeout", "Timeout must be greater than 0"));
+    }
+
+    // 上游代理
+    if proxy.upstream_proxy.enabled {
+        if proxy.upstream_proxy.url.trim().is_empty() {
+            errors.push(FieldError::new("proxy.upstream_proxy.url", "Proxy URL is required when the upstream proxy is enabled"));
+        } else {
+            check_url(
+                &mut errors,
+                "proxy.upstream_proxy.url",
+                proxy.upstream_proxy.url.trim(),
+                &["http", "https", "socks5", "socks5h"],
+            );
+        }
+    }
+
+    // z.ai
+    if proxy.zai.enabled {
+        check_url(&mut errors, "proxy.zai.base_url", &proxy.zai.base_url, &["http", "https"]);
+        if proxy.zai.api_key.trim().is_empty() {
+            errors.push(FieldError::new("proxy.zai.api_key", "API key is required when z.ai is enabled"));
+        }
+    }
+    for (from, to) in &proxy.zai.model_mapping {
+        if to.trim().is_empty() {
+            errors.push(FieldError::new(format!("proxy.zai.model_mapping.{}", from), "Target model is empty"));
+        }
+    }
+
+    // 自定义模型映射
+    let mut mapping: Vec<_> = proxy.custom_mapping.iter().collect();
+    mapping.sort();
+    for (from, to) in mapping {
+        let field = format!("proxy.custom_mapping.{}", from);
+        if from.trim().is_empty() {
+            errors.push(FieldError::new("proxy.custom_mapping", "Source model is empty"));
+        } else if from.matches('*').count() > 1 {
+            errors.push(FieldError::new(&field, "Only one '*' wildcard is supported"));
+        }
+        if !model_mapping::is_valid_mapping_target(to) {
+            errors.push(FieldError::new(
+                &field,
+                format!("Unknown target model '{}' (expected a model listed by /v1/models)", to),
+            ));
+        }
+    }
+
+    // TLS
+    let tls = &proxy.tls;
+    let is_set = |p: &Option<String>| p.as_deref().is_some_and(|p| !p.trim().is_empty());
+    if tls.enabled {
+        match (is_set(&tls.cert_path), is_set(&tls.key_path)) {
+            (true, false) => errors.push(FieldError::new("proxy.tls.key_path", "Required when cert_path is set")),
+            (false, true) => errors.push(FieldError::new("proxy.tls.cert_path", "Required when key_path is set")),
+            (false, false) if !tls.self_signed => errors.push(FieldError::new(
+                "proxy.tls.cert_path",
+                "Set cert_path / key_path or enable self_signed",
+            )),
+            _ => {}
+        }
+        for (field, path) in [("proxy.tls.cert_path", &tls.cert_path), ("proxy.tls.key_path", &tls.key_path), ("proxy.tls.client_ca_path", &tls.client_ca_path)] {
+            if let Some(path) = path.as_deref().filter(|p| !p.trim().is_empty()) {
+                if !std::path::Path::new(path.trim()).is_file() {
+                    errors.push(FieldError::new(field, format!("File not found: {}", path)));
+                }
+            }
+        }
+    }
+    if tls.client_auth != ClientAuthMode::Off && !is_set(&tls.client_ca_path) {
+        errors.push(FieldError::new("proxy.tls.client_ca_path", "Required when client_auth is enabled"));
+    }
+    if tls.client_cert_auth && (tls.client_auth == ClientAuthMode::Off || !tls.enabled) {
+        errors.push(FieldError::new(
+            "proxy.tls.client_cert_auth",
+            "Requires tls.enabled and client_auth set to optional or required",
+        ));
+    }
+
+    // 其他数值
+    if proxy.speech.max_chunk_chars == 0 {
+        errors.push(FieldError::new("proxy.speech.max_chunk_chars", "Must be greater than 0"));
+    }
+    if proxy.transcription.segment_seconds <= proxy.transcription.overlap_seconds * 2 {
+        errors.push(FieldError::new(
+            "proxy.transcription.segment_seconds",
+            "Must be more than twice overlap_seconds",
+        ));
+    }
+    if config.refresh_interval <= 0 {
+        errors.push(FieldError::new("refresh_interval", "Must be greater than 0"));
+    }
+    if config.sync_interval <= 0 {
+        errors.push(FieldError::new("sync_interval", "Must be greater than 0"));
+    }
+    for (i, origin) in config.management.allowed_origins.iter().enumerate() {
+        if origin != "*" {
+            check_url(&mut errors, &format!("management.allowed_origins.{}", i), origin, &["http", "https", "tauri"]);
+        }
+    }
+
+    errors
+}
+
+/// 将字段错误拼成一行，用于日志与命令行输出
+pub fn summarize(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("{}: {}", e.field, e.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_config() {
+        let config = AppConfig::new();
+        assert!(validate(&config, &ValidationContext::default()).is_empty());
+
+        let mut config = AppConfig::new();
+        config.proxy.upstream_proxy.enabled = true;
+        config.proxy.upstream_proxy.url = "ftp://proxy:21".to_string();
+        config.proxy.custom_mapping.insert("gpt-4*".to_string(), "gemini-3-flash".to_string());
+        config.proxy.custom_mapping.insert("o1".to_string(), "gpt-5".to_string());
+        config.proxy.zai.enabled = true;
+        let ctx = ValidationContext {
+            running_port: None,
+            reserved_ports: vec![(config.proxy.port, "HTTP API")],
+        };
+        let fields: Vec<String> = validate(&config, &ctx).into_iter().map(|e| e.field).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "proxy.port",
+                "proxy.upstream_proxy.url",
+                "proxy.zai.api_key",
+                "proxy.custom_mapping.o1",
+            ]
+        );
+    }
+}
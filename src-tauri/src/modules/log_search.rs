@@ -0,0 +1,639 @@
+//! Full-text search over proxy request logs.
+//!
+//! Query syntax (terms are AND-ed):
+//! - free words / `"quoted phrases"` / `prefix*`  match bodies, model, URL and error text
+//! - `-word`                                     excludes logs containing the word
+//! - `status:500` `status:5xx` `status:>=400` `status:error` `status:ok`
+//! - `account:alice@`  `protocol:openai`  `model:gemini`  `url:/v1/messages`  `key:***abcd`
+//! - `since:1h` `since:2025-01-01` `until:2025-01-02T12:00:00Z`  (`after:` / `before:` are aliases)
+//! - `duration:>1500` `duration:>2s`  `tokens:>10k`  `input:>5000`  `output:<100`
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::proxy::monitor::ProxyRequestLog;
+
+/// Tokens of context shown in a snippet
+const SNIPPET_TOKENS: usize = 24;
+/// Private-use sentinels marking hits until the snippet is HTML-escaped
+const MARK_OPEN: char = '\u{E000}';
+const MARK_CLOSE: char = '\u{E001}';
+
+/// Lowercased tokens of a free-text term and whether its last token is a prefix
+type HighlightTerm = (Vec<String>, bool);
+/// Byte ranges of the tokens in a text
+type Spans = Vec<(usize, usize)>;
+
+/// A search hit (bodies omitted) with a highlighted snippet of the best matching column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSearchHit {
+    #[serde(flatten)]
+    pub log: ProxyRequestLog,
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSearchResult {
+    pub total: u64,
+    pub items: Vec<LogSearchHit>,
+}
+
+/// Parsed query: an FTS5 MATCH expression plus SQL conditions on `request_logs` (aliased `l`)
+#[derive(Debug, Default)]
+pub struct ParsedLogQuery {
+    pub fts: Option<String>,
+    /// Positive text terms (lowercased tokens, prefix flag) used to highlight snippets
+    pub terms: Vec<HighlightTerm>,
+    pub conditions: Vec<String>,
+    pub params: Vec<SqlValue>,
+}
+
+impl ParsedLogQuery {
+    fn push(&mut self, condition: &str, values: Vec<SqlValue>) {
+        // Placeholders are written as `?` and numbered here so callers can append LIMIT/OFFSET
+        let mut values = values.into_iter();
+        let mut sql = String::new();
+        for ch in condition.chars() {
+            if ch == '?' {
+                self.params.push(values.next().unwrap_or(SqlValue::Null));
+                sql.push_str(&format!("?{}", self.params.len()));
+            } else {
+                sql.push(ch);
+            }
+        }
+        self.conditions.push(sql);
+    }
+}
+
+/// Split on whitespace while keeping double-quoted sections together
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for ch in query.chars() {
+        match ch {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(ch);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn unquote(value: &str) -> String {
+    value.trim_matches('"').to_string()
+}
+
+/// Quote a term for FTS5 so user input never reaches the query grammar
+fn fts_term(raw: &str) -> Option<String> {
+    let (body, prefix) = match raw.strip_suffix('*') {
+        Some(b) => (b, true),
+        None => (raw, false),
+    };
+    let body = unquote(body);
+    if body.trim().is_empty() {
+        return None;
+    }
+    let quoted = format!("\"{}\"", body.replace('"', "\"\""));
+    Some(if prefix { format!("{}*", quoted) } else { quoted })
+}
+
+/// Split text into tokens the way FTS5's `unicode61` tokenizer does: runs of alphanumerics
+fn word_spans(text: &str) -> Spans {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, ch) in text.char_indices() {
+        match (ch.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// Highlight term for a free-text token, mirroring `fts_term`
+fn highlight_term(raw: &str) -> Option<HighlightTerm> {
+    let (body, prefix) = match raw.strip_suffix('*') {
+        Some(b) => (b, true),
+        None => (raw, false),
+    };
+    let body = unquote(body);
+    let words: Vec<String> = word_spans(&body).iter().map(|&(s, e)| body[s..e].to_lowercase()).collect();
+    (!words.is_empty()).then_some((words, prefix))
+}
+
+/// Flag the tokens of `text` matched by any term (phrases match consecutive tokens)
+fn match_terms(text: &str, spans: &[(usize, usize)], terms: &[HighlightTerm]) -> Vec<bool> {
+    let tokens: Vec<String> = spans.iter().map(|&(s, e)| text[s..e].to_lowercase()).collect();
+    let mut hits = vec![false; tokens.len()];
+    for (words, prefix) in terms {
+        if words.len() > tokens.len() {
+            continue;
+        }
+        for i in 0..=tokens.len() - words.len() {
+            let matched = words.iter().enumerate().all(|(j, word)| {
+                let token = &tokens[i + j];
+                token == word || (*prefix && j == words.len() - 1 && token.starts_with(word.as_str()))
+            });
+            if matched {
+                hits[i..i + words.len()].fill(true);
+            }
+        }
+    }
+    hits
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Snippet of the column with the most hits, HTML-escaped with hits wrapped in `<mark>`
+fn build_snippet(columns: &[Option<&str>], terms: &[HighlightTerm]) -> Option<String> {
+    let mut best: Option<(usize, &str, Spans, Vec<bool>)> = None;
+    for text in columns.iter().flatten() {
+        let spans = word_spans(text);
+        let hits = match_terms(text, &spans, terms);
+        let score = hits.iter().filter(|h| **h).count();
+        if score > 0 && best.as_ref().is_none_or(|b| score > b.0) {
+            best = Some((score, text, spans, hits));
+        }
+    }
+    let (_, text, spans, hits) = best?;
+
+    let first = hits.iter().position(|h| *h)?;
+    let start = first.saturating_sub(SNIPPET_TOKENS / 4);
+    let end = (start + SNIPPET_TOKENS).min(spans.len());
+    let mut pos = if start == 0 { 0 } else { spans[start].0 };
+    let to = if end == spans.len() { text.len() } else { spans[end - 1].1 };
+
+    // Body text must never carry our sentinels into the output
+    let plain = |s: &str| s.replace([MARK_OPEN, MARK_CLOSE], "");
+    let mut raw = String::new();
+    if start > 0 {
+        raw.push('…');
+    }
+    for k in start..end {
+        let (s, e) = spans[k];
+        raw.push_str(&plain(&text[pos..s]));
+        if hits[k] {
+            raw.push(MARK_OPEN);
+            raw.push_str(&text[s..e]);
+            raw.push(MARK_CLOSE);
+        } else {
+            raw.push_str(&text[s..e]);
+        }
+        pos = e;
+    }
+    raw.push_str(&plain(&text[pos..to]));
+    if end < spans.len() {
+        raw.push('…');
+    }
+
+    Some(
+        escape_html(&raw)
+            .replace(MARK_OPEN, "<mark>")
+            .replace(MARK_CLOSE, "</mark>"),
+    )
+}
+
+/// Parse `>100`, `>=100`, `<100`, `<=100`, `100..200` or `100` into SQL comparisons
+fn parse_comparison(value: &str, parse_num: fn(&str) -> Option<i64>) -> Option<Vec<(&'static str, i64)>> {
+    if let Some((lo, hi)) = value.split_once("..") {
+        return Some(vec![(">=", parse_num(lo)?), ("<=", parse_num(hi)?)]);
+    }
+    for op in [">=", "<=", ">", "<", "="] {
+        if let Some(rest) = value.strip_prefix(op) {
+            return Some(vec![(op, parse_num(rest)?)]);
+        }
+    }
+    Some(vec![("=", parse_num(value)?)])
+}
+
+/// Plain numbers with optional k/m suffix (10k = 10000)
+fn parse_count(value: &str) -> Option<i64> {
+    let v = value.trim().to_lowercase();
+    let (num, mult) = if let Some(n) = v.strip_suffix('k') {
+        (n, 1_000.0)
+    } else if let Some(n) = v.strip_suffix('m') {
+        (n, 1_000_000.0)
+    } else {
+        (v.as_str(), 1.0)
+    };
+    num.parse::<f64>().ok().map(|n| (n * mult) as i64)
+}
+
+/// Durations in milliseconds: `1500`, `1500ms`, `1.5s`, `2m`
+fn parse_duration_ms(value: &str) -> Option<i64> {
+    let v = value.trim().to_lowercase();
+    let (num, mult) = if let Some(n) = v.strip_suffix("ms") {
+        (n, 1.0)
+    } else if let Some(n) = v.strip_suffix('s') {
+        (n, 1_000.0)
+    } else if let Some(n) = v.strip_suffix('m') {
+        (n, 60_000.0)
+    } else {
+        (v.as_str(), 1.0)
+    };
+    num.parse::<f64>().ok().map(|n| (n * mult) as i64)
+}
+
+/// Absolute (`2025-01-01`, RFC 3339) or relative (`30m`, `6h`, `7d`) time, as epoch millis
+fn parse_time_ms(value: &str, now_ms: i64) -> Option<i64> {
+    let v = unquote(value);
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&v) {
+        return Some(dt.timestamp_millis());
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(&v, "%Y-%m-%d") {
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp_millis());
+    }
+    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(&v, "%Y-%m-%d %H:%M") {
+        return Some(dt.and_utc().timestamp_millis());
+    }
+    let (num, unit_ms) = if let Some(n) = v.strip_suffix('m') {
+        (n, 60_000)
+    } else if let Some(n) = v.strip_suffix('h') {
+        (n, 3_600_000)
+    } else if let Some(n) = v.strip_suffix('d') {
+        (n, 86_400_000)
+    } else if let Some(n) = v.strip_suffix('w') {
+        (n, 7 * 86_400_000)
+    } else {
+        return None;
+    };
+    // Overflowing offsets (e.g. `since:99999999999999d`) are invalid, not a panic
+    num.parse::<i64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit_ms))
+        .and_then(|offset| now_ms.checked_sub(offset))
+}
+
+fn parse_status(query: &mut ParsedLogQuery, value: &str) -> Result<(), String> {
+    let v = value.to_lowercase();
+    match v.as_str() {
+        "error" | "err" | "failed" => query.push("(l.status < 200 OR l.status >= 400)", vec![]),
+        "ok" | "success" => query.push("(l.status >= 200 AND l.status < 400)", vec![]),
+        _ if v.len() == 3 && v.ends_with("xx") => {
+            let class: i64 = v[..1].parse().map_err(|_| format!("Invalid status filter: {}", value))?;
+            query.push(
+                "(l.status >= ? AND l.status < ?)",
+                vec![SqlValue::Integer(class * 100), SqlValue::Integer(class * 100 + 100)],
+            );
+        }
+        _ => {
+            let cmps = parse_comparison(&v, |s| s.parse().ok())
+                .ok_or_else(|| format!("Invalid status filter: {}", value))?;
+            for (op, n) in cmps {
+                query.push(&format!("l.status {} ?", op), vec![SqlValue::Integer(n)]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a search query into FTS and column conditions
+pub fn parse_query(input: &str, now_ms: i64) -> Result<ParsedLogQuery, String> {
+    let mut query = ParsedLogQuery::default();
+    let mut positive = Vec::new();
+    let mut negative = Vec::new();
+
+    for token in tokenize(input) {
+        let field = token
+            .split_once(':')
+            .filter(|(k, v)| !k.starts_with('"') && !v.is_empty());
+
+        let Some((key, raw_value)) = field else {
+            if let Some(neg) = token.strip_prefix('-').filter(|t| !t.is_empty()) {
+                negative.extend(fts_term(neg));
+            } else {
+                positive.extend(fts_term(&token));
+                query.terms.extend(highlight_term(&token));
+            }
+            continue;
+        };
+        let value = unquote(raw_value);
+        let like = |v: &str| SqlValue::Text(format!("%{}%", v));
+
+        match key.to_lowercase().as_str() {
+            "status" => parse_status(&mut query, &value)?,
+            "account" => query.push("l.account_email LIKE ?", vec![like(&value)]),
+            "protocol" => query.push("l.protocol = ?", vec![SqlValue::Text(value.to_lowercase())]),
+            "model" => query.push("(l.model LIKE ? OR l.mapped_model LIKE ?)", vec![like(&value), like(&value)]),
+            "url" | "path" => query.push("l.url LIKE ?", vec![like(&value)]),
+            "key" | "client" => query.push("l.client_key LIKE ?", vec![like(&value)]),
+            "method" => query.push("l.method = ?", vec![SqlValue::Text(value.to_uppercase())]),
+            "id" => query.push("l.id = ?", vec![SqlValue::Text(value)]),
+            "since" | "after" | "from" => {
+                let ts = parse_time_ms(&value, now_ms).ok_or_else(|| format!("Invalid time: {}", value))?;
+                query.push("l.timestamp >= ?", vec![SqlValue::Integer(ts)]);
+            }
+            "until" | "before" | "to" => {
+                let ts = parse_time_ms(&value, now_ms).ok_or_else(|| format!("Invalid time: {}", value))?;
+                query.push("l.timestamp < ?", vec![SqlValue::Integer(ts)]);
+            }
+            "duration" | "latency" => {
+                let cmps = parse_comparison(&value, parse_duration_ms)
+                    .ok_or_else(|| format!("Invalid duration filter: {}", value))?;
+                for (op, n) in cmps {
+                    query.push(&format!("l.duration {} ?", op), vec![SqlValue::Integer(n)]);
+                }
+            }
+            "tokens" | "input" | "output" => {
+                let column = match key.to_lowercase().as_str() {
+                    "input" => "COALESCE(l.input_tokens, 0)",
+                    "output" => "COALESCE(l.output_tokens, 0)",
+                    _ => "(COALESCE(l.input_tokens, 0) + COALESCE(l.output_tokens, 0))",
+                };
+                let cmps = parse_comparison(&value, parse_count)
+                    .ok_or_else(|| format!("Invalid token filter: {}", value))?;
+                for (op, n) in cmps {
+                    query.push(&format!("{} {} ?", column, op), vec![SqlValue::Integer(n)]);
+                }
+            }
+            // Unknown prefixes (e.g. "http://host") are treated as plain text
+            _ => {
+                positive.extend(fts_term(&token));
+                query.terms.extend(highlight_term(&token));
+            }
+        }
+    }
+
+    if !positive.is_empty() {
+        let mut expr = positive.join(" AND ");
+        for n in negative {
+            expr = format!("{} NOT {}", expr, n);
+        }
+        query.fts = Some(expr);
+    } else if !negative.is_empty() {
+        // FTS5 NOT is binary; a pure exclusion becomes a rowid anti-join
+        let expr = negative.join(" OR ");
+        query.push(
+            "l.seq NOT IN (SELECT rowid FROM request_logs_fts WHERE request_logs_fts MATCH ?)",
+            vec![SqlValue::Text(expr)],
+        );
+    }
+
+    Ok(query)
+}
+
+pub(crate) fn search_with_conn(
+    conn: &Connection,
+    input: &str,
+    limit: usize,
+    offset: usize,
+) -> Result<LogSearchResult, String> {
+    let mut query = parse_query(input, chrono::Utc::now().timestamp_millis())?;
+
+    // The FTS table is contentless, so snippets are built from the stored columns
+    let (from, bodies) = if let Some(fts) = query.fts.take() {
+        query.params.push(SqlValue::Text(fts));
+        query
+            .conditions
+            .push(format!("request_logs_fts MATCH ?{}", query.params.len()));
+        (
+            "request_logs l JOIN request_logs_fts ON request_logs_fts.rowid = l.seq",
+            "l.request_body, l.response_body",
+        )
+    } else {
+        ("request_logs l", "NULL, NULL")
+    };
+    let where_clause = if query.conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", query.conditions.join(" AND "))
+    };
+
+    let total: u64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM {} {}", from, where_clause),
+            rusqlite::params_from_iter(query.params.iter()),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let n = query.params.len();
+    let sql = format!(
+        "SELECT l.id, l.timestamp, l.method, l.url, l.status, l.duration, l.model, l.error,
+                l.input_tokens, l.output_tokens, l.account_email, l.mapped_model, l.protocol,
+                l.cached_tokens, l.thinking_tokens, l.client_key, {}
+         FROM {} {}
+         ORDER BY l.timestamp DESC
+         LIMIT ?{} OFFSET ?{}",
+        bodies,
+        from,
+        where_clause,
+        n + 1,
+        n + 2
+    );
+    query.params.push(SqlValue::Integer(limit as i64));
+    query.params.push(SqlValue::Integer(offset as i64));
+
+    let terms = query.terms;
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(query.params.iter()), |row| {
+            let mut hit = LogSearchHit {
+                log: ProxyRequestLog {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    method: row.get(2)?,
+                    url: row.get(3)?,
+                    status: row.get(4)?,
+                    duration: row.get(5)?,
+                    model: row.get(6)?,
+                    mapped_model: row.get(11).unwrap_or(None),
+                    account_email: row.get(10).unwrap_or(None),
+                    error: row.get(7)?,
+                    request_body: None,
+                    response_body: None,
+                    input_tokens: row.get(8).unwrap_or(None),
+                    output_tokens: row.get(9).unwrap_or(None),
+                    protocol: row.get(12).unwrap_or(None),
+                    cached_tokens: row.get(13).unwrap_or(None),
+                    thinking_tokens: row.get(14).unwrap_or(None),
+                    cache_creation_tokens: None,
+                    client_key: row.get(15).unwrap_or(None),
+                },
+                snippet: None,
+            };
+            if !terms.is_empty() {
+                use crate::modules::proxy_db::{read_body, truncate_for_index};
+                let request_body = read_body(row, 16);
+                let response_body = read_body(row, 17);
+                let model_text = format!(
+                    "{} {}",
+                    hit.log.model.as_deref().unwrap_or(""),
+                    hit.log.mapped_model.as_deref().unwrap_or("")
+                );
+                hit.snippet = build_snippet(
+                    &[
+                        Some(model_text.as_str()),
+                        Some(hit.log.url.as_str()),
+                        hit.log.error.as_deref(),
+                        truncate_for_index(request_body.as_deref()),
+                        truncate_for_index(response_body.as_deref()),
+                    ],
+                    &terms,
+                );
+            }
+            Ok(hit)
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        items.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(LogSearchResult { total, items })
+}
+
+/// Search proxy logs with the query syntax described at the top of this module
+pub fn search_logs(query: &str, limit: usize, offset: usize) -> Result<LogSearchResult, String> {
+    let conn = crate::modules::proxy_db::connect_db()?;
+    search_with_conn(&conn, query, limit, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    #[test]
+    fn test_parse_fields_and_terms() {
+        let q = parse_query(
+            r#"status:5xx account:alice protocol:OpenAI "tool_use id" timeout* -retry duration:>2s tokens:1k..5k"#,
+            NOW,
+        )
+        .unwrap();
+
+        assert_eq!(
+            q.fts.as_deref(),
+            Some(r#""tool_use id" AND "timeout"* NOT "retry""#)
+        );
+        assert_eq!(q.conditions[0], "(l.status >= ?1 AND l.status < ?2)");
+        assert_eq!(q.conditions[1], "l.account_email LIKE ?3");
+        assert_eq!(q.conditions[2], "l.protocol = ?4");
+        assert_eq!(q.conditions[3], "l.duration > ?5");
+        assert_eq!(q.params[4], SqlValue::Integer(2000));
+        assert_eq!(q.params[5], SqlValue::Integer(1000));
+        assert_eq!(q.params[6], SqlValue::Integer(5000));
+        assert_eq!(q.params[3], SqlValue::Text("openai".to_string()));
+    }
+
+    #[test]
+    fn test_parse_time_bounds() {
+        let q = parse_query("since:1h until:2023-11-14", NOW).unwrap();
+        assert!(q.fts.is_none());
+        assert_eq!(q.params[0], SqlValue::Integer(NOW - 3_600_000));
+        assert_eq!(q.params[1], SqlValue::Integer(1_699_920_000_000));
+        assert!(parse_query("since:yesterday", NOW).is_err());
+        assert!(parse_query("since:99999999999999999d", NOW).is_err());
+        assert!(parse_query("since:-9223372036854775807m", NOW).is_err());
+    }
+
+    #[test]
+    fn test_snippet_is_escaped() {
+        let terms = parse_query(r#"deadline "tool use" stream*"#, NOW).unwrap().terms;
+        let body = format!("<img src=x onerror=alert(1)> deadline {} tool_use &amp; streaming", MARK_CLOSE);
+        let snippet = build_snippet(&[None, Some("/v1/chat"), Some(body.as_str())], &terms).unwrap();
+        assert_eq!(
+            snippet,
+            "&lt;img src=x onerror=alert(1)&gt; <mark>deadline</mark>  <mark>tool</mark>_<mark>use</mark> &amp;amp; <mark>streaming</mark>"
+        );
+
+        let long = format!("{} needle {}", "word ".repeat(40), "tail ".repeat(40));
+        let snippet = build_snippet(&[Some(long.as_str())], &parse_query("needle", NOW).unwrap().terms).unwrap();
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("<mark>needle</mark>"));
+        assert!(build_snippet(&[Some("nothing here")], &terms).is_none());
+    }
+
+    #[test]
+    fn test_fts_input_is_quoted() {
+        let q = parse_query("OR NEAR(x) AND", NOW).unwrap();
+        assert_eq!(q.fts.as_deref(), Some(r#""OR" AND "NEAR(x)" AND "AND""#));
+        assert_eq!(fts_term(r#"a"b"#).as_deref(), Some(r#""a""b""#));
+    }
+
+    #[test]
+    fn test_search_with_snippets() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::modules::proxy_db::init_schema(&conn).unwrap();
+
+        for (i, (status, body)) in [
+            (200, "please summarize the quarterly report"),
+            (500, "upstream deadline exceeded while streaming"),
+            (200, "hello world"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let log = ProxyRequestLog {
+                id: format!("log-{}", i),
+                timestamp: NOW + i as i64,
+                method: "POST".to_string(),
+                url: "/v1/chat/completions".to_string(),
+                status: *status,
+                duration: 100 * (i as u64 + 1),
+                model: Some("gemini-3-flash".to_string()),
+                mapped_model: None,
+                account_email: Some("alice@example.com".to_string()),
+                error: None,
+                request_body: Some(body.to_string()),
+                response_body: None,
+                input_tokens: Some(10),
+                output_tokens: Some(20),
+                protocol: Some("openai".to_string()),
+                cached_tokens: None,
+                thinking_tokens: None,
+                cache_creation_tokens: None,
+                client_key: None,
+            };
+            crate::modules::proxy_db::save_log_with_conn(&conn, &log, true).unwrap();
+        }
+
+        let res = search_with_conn(&conn, "deadline status:error", 10, 0).unwrap();
+        assert_eq!(res.total, 1);
+        assert_eq!(res.items[0].log.id, "log-1");
+        assert!(res.items[0].snippet.as_deref().unwrap().contains("<mark>deadline</mark>"));
+
+        let res = search_with_conn(&conn, "duration:>=200 -hello", 10, 0).unwrap();
+        assert_eq!(res.total, 1);
+        assert_eq!(res.items[0].log.id, "log-1");
+
+        let res = search_with_conn(&conn, "model:flash account:alice", 2, 0).unwrap();
+        assert_eq!(res.total, 3);
+        assert_eq!(res.items.len(), 2);
+        assert!(res.items[0].snippet.is_none());
+    }
+}
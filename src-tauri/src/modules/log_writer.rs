@@ -0,0 +1,301 @@
+//! Background writer for request logs and token usage.
+//!
+//! Requests only enqueue records on a bounded channel; a dedicated thread drains it and
+//! writes each batch in one transaction per database, keeping SQLite off the request path.
+//! When the queue is full, logs are dropped immediately while usage records wait up to
+//! `USAGE_ENQUEUE_TIMEOUT` (backpressure) before being dropped. Drops are counted.
+
+use rusqlite::Connection;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::modules::token_stats::UsageRecord;
+use crate::proxy::monitor::ProxyRequestLog;
+
+const QUEUE_CAPACITY: usize = 4096;
+const MAX_BATCH_SIZE: usize = 256;
+const USAGE_ENQUEUE_TIMEOUT: Duration = Duration::from_secs(2);
+
+enum WriteJob {
+    Log(Box<ProxyRequestLog>, bool),
+    Usage(UsageRecord),
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Default)]
+struct Counters {
+    written_logs: AtomicU64,
+    written_usage: AtomicU64,
+    dropped_logs: AtomicU64,
+    dropped_usage: AtomicU64,
+    failed_logs: AtomicU64,
+    failed_batches: AtomicU64,
+}
+
+/// Writer counters exposed to the management API
+#[derive(Debug, Clone, Serialize)]
+pub struct LogWriterStats {
+    pub queued: usize,
+    pub capacity: usize,
+    pub written_logs: u64,
+    pub written_usage: u64,
+    pub dropped_logs: u64,
+    pub dropped_usage: u64,
+    /// Rows skipped inside an otherwise committed batch
+    pub failed_logs: u64,
+    pub failed_batches: u64,
+}
+
+type Opener = Box<dyn FnMut() -> Result<Connection, String> + Send>;
+
+pub struct LogWriter {
+    tx: mpsc::Sender<WriteJob>,
+    counters: Arc<Counters>,
+}
+
+impl LogWriter {
+    /// Start the writer thread against `proxy_logs.db` and `token_stats.db`
+    pub fn start() -> Self {
+        Self::spawn(
+            Box::new(crate::modules::proxy_db::connect_db),
+            Box::new(crate::modules::token_stats::connect_db),
+        )
+    }
+
+    fn spawn(mut open_logs: Opener, mut open_stats: Opener) -> Self {
+        let (tx, mut rx) = mpsc::channel::<WriteJob>(QUEUE_CAPACITY);
+        let counters = Arc::new(Counters::default());
+        let thread_counters = counters.clone();
+
+        let spawned = std::thread::Builder::new()
+            .name("log-writer".to_string())
+            .spawn(move || {
+                let mut log_conn: Option<Connection> = None;
+                let mut stats_conn: Option<Connection> = None;
+                let mut prices = crate::modules::pricing::PriceCache::default();
+
+                while let Some(job) = rx.blocking_recv() {
+                    let mut batch = vec![job];
+                    while batch.len() < MAX_BATCH_SIZE {
+                        match rx.try_recv() {
+                            Ok(job) => batch.push(job),
+                            Err(_) => break,
+                        }
+                    }
+                    write_batch(
+                        batch,
+                        &mut log_conn,
+                        &mut open_logs,
+                        &mut stats_conn,
+                        &mut open_stats,
+                        &mut prices,
+                        &thread_counters,
+                    );
+                }
+            });
+        if let Err(e) = spawned {
+            tracing::error!("[LogWriter] Failed to start writer thread: {}", e);
+        }
+
+        Self { tx, counters }
+    }
+
+    /// Queue a request log; dropped (and counted) when the queue is full
+    pub fn enqueue_log(&self, log: ProxyRequestLog, compress: bool) {
+        if self.tx.try_send(WriteJob::Log(Box::new(log), compress)).is_err() {
+            self.counters.dropped_logs.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Queue a usage record, waiting briefly for room before dropping it
+    pub async fn enqueue_usage(&self, record: UsageRecord) {
+        if self
+            .tx
+            .send_timeout(WriteJob::Usage(record), USAGE_ENQUEUE_TIMEOUT)
+            .await
+            .is_err()
+        {
+            self.counters.dropped_usage.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!("[LogWriter] Queue full, dropped a token usage record");
+        }
+    }
+
+    /// Wait until everything queued before this call has been written
+    pub async fn flush(&self) {
+        let (ack_tx, ack_rx) = oneshot::channel();
+        if self.tx.send(WriteJob::Flush(ack_tx)).await.is_ok() {
+            let _ = ack_rx.await;
+        }
+    }
+
+    pub fn stats(&self) -> LogWriterStats {
+        LogWriterStats {
+            queued: QUEUE_CAPACITY - self.tx.capacity(),
+            capacity: QUEUE_CAPACITY,
+            written_logs: self.counters.written_logs.load(Ordering::Relaxed),
+            written_usage: self.counters.written_usage.load(Ordering::Relaxed),
+            dropped_logs: self.counters.dropped_logs.load(Ordering::Relaxed),
+            dropped_usage: self.counters.dropped_usage.load(Ordering::Relaxed),
+            failed_logs: self.counters.failed_logs.load(Ordering::Relaxed),
+            failed_batches: self.counters.failed_batches.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Lazily (re)open a connection; a failed batch drops it so the next batch reconnects
+fn with_conn<F>(conn: &mut Option<Connection>, open: &mut Opener, write: F) -> Result<(), String>
+where
+    F: FnOnce(&mut Connection) -> Result<(), String>,
+{
+    if conn.is_none() {
+        *conn = Some(open()?);
+    }
+    let result = write(conn.as_mut().expect("connection opened above"));
+    if result.is_err() {
+        *conn = None;
+    }
+    result
+}
+
+fn write_batch(
+    batch: Vec<WriteJob>,
+    log_conn: &mut Option<Connection>,
+    open_logs: &mut Opener,
+    stats_conn: &mut Option<Connection>,
+    open_stats: &mut Opener,
+    prices: &mut crate::modules::pricing::PriceCache,
+    counters: &Counters,
+) {
+    let mut logs = Vec::new();
+    let mut usage = Vec::new();
+    let mut flushes = Vec::new();
+    for job in batch {
+        match job {
+            WriteJob::Log(log, compress) => logs.push((*log, compress)),
+            WriteJob::Usage(record) => usage.push(record),
+            WriteJob::Flush(ack) => flushes.push(ack),
+        }
+    }
+
+    if !logs.is_empty() {
+        let mut failed = 0;
+        match with_conn(log_conn, open_logs, |conn| {
+            failed = crate::modules::proxy_db::save_logs_batch_with_conn(conn, &logs)?;
+            Ok(())
+        }) {
+            Ok(()) => {
+                counters.written_logs.fetch_add((logs.len() - failed) as u64, Ordering::Relaxed);
+                counters.failed_logs.fetch_add(failed as u64, Ordering::Relaxed);
+            }
+            Err(e) => {
+                counters.failed_batches.fetch_add(1, Ordering::Relaxed);
+                tracing::error!("[LogWriter] Failed to save {} proxy logs: {}", logs.len(), e);
+            }
+        }
+    }
+
+    if !usage.is_empty() {
+        match with_conn(stats_conn, open_stats, |conn| {
+            crate::modules::token_stats::record_usage_batch_with_conn(conn, prices, &usage)
+        }) {
+            Ok(()) => {
+                counters.written_usage.fetch_add(usage.len() as u64, Ordering::Relaxed);
+            }
+            Err(e) => {
+                counters.failed_batches.fetch_add(1, Ordering::Relaxed);
+                tracing::error!("[LogWriter] Failed to record {} token usage entries: {}", usage.len(), e);
+            }
+        }
+    }
+
+    for ack in flushes {
+        let _ = ack.send(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::pricing::TokenUsage;
+
+    fn shared_memory_db(name: &str) -> String {
+        format!("file:{}?mode=memory&cache=shared", name)
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_flushes() {
+        let logs_uri = shared_memory_db("log_writer_logs");
+        let stats_uri = shared_memory_db("log_writer_stats");
+
+        // Keep one connection open per database so the shared in-memory DBs persist
+        let logs_db = Connection::open(&logs_uri).unwrap();
+        crate::modules::proxy_db::init_schema(&logs_db).unwrap();
+        let mut stats_db = Connection::open(&stats_uri).unwrap();
+        crate::modules::token_stats::init_schema(&mut stats_db).unwrap();
+
+        let writer = LogWriter::spawn(
+            Box::new(move || Connection::open(&logs_uri).map_err(|e| e.to_string())),
+            Box::new(move || Connection::open(&stats_uri).map_err(|e| e.to_string())),
+        );
+
+        for i in 0..300 {
+            writer.enqueue_log(
+                ProxyRequestLog {
+                    id: format!("log-{}", i),
+                    timestamp: i,
+                    method: "POST".to_string(),
+                    url: "/v1/messages".to_string(),
+                    status: 200,
+                    duration: 1,
+                    model: Some("claude-sonnet-4-5".to_string()),
+                    mapped_model: None,
+                    account_email: None,
+                    error: None,
+                    request_body: Some("{}".to_string()),
+                    response_body: None,
+                    input_tokens: None,
+                    output_tokens: None,
+                    protocol: None,
+                    cached_tokens: None,
+                    thinking_tokens: None,
+                    cache_creation_tokens: None,
+                    client_key: None,
+                },
+                i % 2 == 0,
+            );
+            writer
+                .enqueue_usage(UsageRecord {
+                    timestamp: chrono::Utc::now(),
+                    account_email: "a@example.com".to_string(),
+                    model: "claude-sonnet-4-5".to_string(),
+                    protocol: "anthropic".to_string(),
+                    client_key: String::new(),
+                    usage: TokenUsage {
+                        input_tokens: 10,
+                        output_tokens: 5,
+                        ..Default::default()
+                    },
+                })
+                .await;
+        }
+        writer.flush().await;
+
+        let logs: i64 = logs_db
+            .query_row("SELECT COUNT(*) FROM request_logs", [], |row| row.get(0))
+            .unwrap();
+        let usage: i64 = stats_db
+            .query_row("SELECT COALESCE(SUM(total_tokens), 0) FROM token_usage", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(logs, 300);
+        assert_eq!(usage, 300 * 15);
+
+        let stats = writer.stats();
+        assert_eq!(stats.written_logs, 300);
+        assert_eq!(stats.written_usage, 300);
+        assert_eq!(stats.dropped_logs + stats.dropped_usage + stats.failed_batches, 0);
+        assert_eq!(stats.queued, 0);
+    }
+}
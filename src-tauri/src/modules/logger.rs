@@ -25,8 +25,8 @@ pub fn get_log_dir() -> Result<PathBuf, String> {
     Ok(log_dir)
 }
 
-/// Initialize the log system
-pub fn init_logger() {
+/// Initialize the log system with an optional filter (e.g. `debug`, `info,hyper=warn`); `RUST_LOG` wins when set
+pub fn init_logger_with_level(level: Option<&str>) {
     // Capture log macro logs
     let _ = tracing_log::LogTracer::init();
     
@@ -60,7 +60,7 @@ pub fn init_logger() {
 
     // 4. Set filtering layer (default to INFO level to reduce log size)
     let filter_layer = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info"));
+        .unwrap_or_else(|_| EnvFilter::new(level.unwrap_or("info")));
 
     // 5. Initialize global subscriber (use try_init to avoid crash on repeated initialization)
     let _ = tracing_subscriber::registry()
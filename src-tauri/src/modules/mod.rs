@@ -1,17 +1,29 @@
 pub mod account;
+pub mod profile;
 pub mod quota;
 pub mod config;
+pub mod config_overlay;
+pub mod config_history;
+pub mod config_reload;
+pub mod config_validation;
 pub mod logger;
 pub mod db;
 pub mod process;
 pub mod oauth;
+pub mod oauth_flow;
 pub mod migration;
 pub mod proxy_db;
+pub mod log_search;
 pub mod device;
 pub mod update_checker;
 pub mod http_api;
 pub mod token_stats;
+pub mod pricing;
+pub mod log_writer;
 pub mod web_api;
+pub mod web_auth;
+pub mod audit_log;
+pub mod signature_db;
 pub mod scheduler;
 
 use crate::models;
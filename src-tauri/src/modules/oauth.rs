@@ -48,8 +48,8 @@ impl UserInfo {
 }
 
 
-/// Generate OAuth authorization URL
-pub fn get_auth_url(redirect_uri: &str) -> String {
+/// Generate OAuth authorization URL with an optional `state` token and PKCE challenge (S256)
+pub fn get_auth_url(redirect_uri: &str, state: Option<&str>, code_challenge: Option<&str>) -> String {
     let scopes = vec![
         "https://www.googleapis.com/auth/cloud-platform",
         "https://www.googleapis.com/auth/userinfo.email",
@@ -58,7 +58,7 @@ pub fn get_auth_url(redirect_uri: &str) -> String {
         "https://www.googleapis.com/auth/experimentsandconfigs"
     ].join(" ");
 
-    let params = vec![
+    let mut params = vec![
         ("client_id", CLIENT_ID),
         ("redirect_uri", redirect_uri),
         ("response_type", "code"),
@@ -67,22 +67,101 @@ pub fn get_auth_url(redirect_uri: &str) -> String {
         ("prompt", "consent"),
         ("include_granted_scopes", "true"),
     ];
-    
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+    if let Some(challenge) = code_challenge {
+        params.push(("code_challenge", challenge));
+        params.push(("code_challenge_method", "S256"));
+    }
+
     let url = url::Url::parse_with_params(AUTH_URL, &params).expect("Invalid Auth URL");
     url.to_string()
 }
 
-/// Exchange authorization code for token
-pub async fn exchange_code(code: &str, redirect_uri: &str) -> Result<TokenResponse, String> {
+/// PKCE code verifier and its S256 challenge
+#[derive(Debug, Clone)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    pub fn generate() -> Self {
+        use rand::Rng;
+
+        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-._~";
+        let mut rng = rand::thread_rng();
+        let verifier: String = (0..64)
+            .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+            .collect();
+        Self {
+            challenge: Self::challenge_for(&verifier),
+            verifier,
+        }
+    }
+
+    pub fn challenge_for(verifier: &str) -> String {
+        use base64::Engine as _;
+        use sha2::Digest;
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(sha2::Sha256::digest(verifier.as_bytes()))
+    }
+}
+
+/// Parse what the user pasted after consent: either the full redirected URL or the bare code.
+/// Returns `(code, state)`.
+pub fn parse_pasted_code(input: &str) -> Result<(String, Option<String>), String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("Authorization code is empty".to_string());
+    }
+    let query = if let Ok(url) = url::Url::parse(input) {
+        url.query().map(str::to_string)
+    } else if input.contains("code=") {
+        input.rsplit('?').next().map(str::to_string)
+    } else {
+        None
+    };
+    let Some(query) = query else {
+        return Ok((input.to_string(), None));
+    };
+
+    let mut code = None;
+    let mut state = None;
+    let mut error = None;
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "code" => code = Some(value.into_owned()),
+            "state" => state = Some(value.into_owned()),
+            "error" => error = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+    if let Some(error) = error {
+        return Err(format!("Authorization was denied: {}", error));
+    }
+    code.map(|c| (c, state))
+        .ok_or_else(|| "No authorization code found in the pasted URL".to_string())
+}
+
+/// Exchange authorization code for token, sending the PKCE verifier when the flow used one
+pub async fn exchange_code(
+    code: &str,
+    redirect_uri: &str,
+    code_verifier: Option<&str>,
+) -> Result<TokenResponse, String> {
     let client = crate::utils::http::get_client();
     
-    let params = [
+    let mut params = vec![
         ("client_id", CLIENT_ID),
         ("client_secret", CLIENT_SECRET),
         ("code", code),
         ("redirect_uri", redirect_uri),
         ("grant_type", "authorization_code"),
     ];
+    if let Some(verifier) = code_verifier {
+        params.push(("code_verifier", verifier));
+    }
 
     let response = client
         .post(TOKEN_URL)
@@ -201,3 +280,32 @@ pub async fn ensure_fresh_token(
         None,  // session_id will be generated in token_manager
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pkce_challenge_rfc7636() {
+        // RFC 7636 Appendix B
+        assert_eq!(
+            Pkce::challenge_for("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        let pkce = Pkce::generate();
+        assert_eq!(pkce.verifier.len(), 64);
+        assert_eq!(pkce.challenge, Pkce::challenge_for(&pkce.verifier));
+    }
+
+    #[test]
+    fn test_parse_pasted_code() {
+        assert_eq!(
+            parse_pasted_code("http://localhost/?state=abc&code=4%2F0Adeu&scope=email").unwrap(),
+            ("4/0Adeu".to_string(), Some("abc".to_string()))
+        );
+        assert_eq!(parse_pasted_code("  4/0Adeu\n").unwrap(), ("4/0Adeu".to_string(), None));
+        assert_eq!(parse_pasted_code("code=xyz&state=s1").unwrap(), ("xyz".to_string(), Some("s1".to_string())));
+        assert!(parse_pasted_code("http://localhost/?error=access_denied").is_err());
+        assert!(parse_pasted_code("").is_err());
+    }
+}
@@ -0,0 +1,285 @@
+// 无界面 OAuth 登录 (Headless OAuth)
+//
+// web_server 运行在远程主机时，浏览器无法访问 `redirectUri`。这里维护短时有效的登录会话 (state + PKCE)，支持:
+// - 复制粘贴: 授权后浏览器跳转到 http://localhost 打不开，用户把地址栏 URL (或其中的 code) 粘贴回来
+// - 本机回环: 在服务器上临时监听 127.0.0.1 随机端口接收回调 (配合 ssh -L 端口转发，或在本机运行时直接可用)
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::models::{Account, TokenData};
+use crate::modules::oauth;
+
+/// 登录会话 (state) 有效期
+const LOGIN_TTL_SECS: i64 = 600;
+
+/// 复制粘贴模式的回调地址 (已注册的桌面客户端允许任意 localhost 回调)
+pub const PASTE_REDIRECT_URI: &str = "http://localhost";
+
+const LOOPBACK_PATH: &str = "/oauth-callback";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginStatus {
+    Pending,
+    Completed { account: Box<Account> },
+    Failed { error: String },
+}
+
+struct PendingLogin {
+    verifier: String,
+    redirect_uri: String,
+    created_at: i64,
+    status: LoginStatus,
+    /// 已有调用方取走 verifier 正在兑换授权码，其它调用方不能再完成此会话
+    claimed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginStart {
+    pub state: String,
+    pub auth_url: String,
+    pub redirect_uri: String,
+    pub expires_in: i64,
+}
+
+static LOGINS: Lazy<Mutex<HashMap<String, PendingLogin>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn purge_expired(logins: &mut HashMap<String, PendingLogin>) {
+    let cutoff = now() - LOGIN_TTL_SECS;
+    logins.retain(|_, login| login.created_at >= cutoff);
+}
+
+/// 创建登录会话并返回授权地址
+pub fn start(redirect_uri: &str) -> LoginStart {
+    let state = uuid::Uuid::new_v4().simple().to_string();
+    let pkce = oauth::Pkce::generate();
+    let auth_url = oauth::get_auth_url(redirect_uri, Some(&state), Some(&pkce.challenge));
+
+    if let Ok(mut logins) = LOGINS.lock() {
+        purge_expired(&mut logins);
+        logins.insert(
+            state.clone(),
+            PendingLogin {
+                verifier: pkce.verifier,
+                redirect_uri: redirect_uri.to_string(),
+                created_at: now(),
+                status: LoginStatus::Pending,
+                claimed: false,
+            },
+        );
+    }
+
+    LoginStart {
+        state,
+        auth_url,
+        redirect_uri: redirect_uri.to_string(),
+        expires_in: LOGIN_TTL_SECS,
+    }
+}
+
+/// 在本机回环地址上临时监听授权回调，收到一次有效回调或会话过期后关闭
+pub async fn start_loopback() -> Result<LoginStart, String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| format!("Failed to bind loopback listener: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let login = start(&format!("http://127.0.0.1:{}{}", port, LOOPBACK_PATH));
+
+    let state = login.state.clone();
+    tokio::spawn(async move {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(LOGIN_TTL_SECS as u64);
+        while tokio::time::Instant::now() < deadline {
+            if !matches!(status(&state), Some(LoginStatus::Pending)) {
+                break; // 已通过粘贴完成、被取消或已过期
+            }
+            let Ok(Ok((stream, _))) = tokio::time::timeout(Duration::from_secs(1), listener.accept()).await else {
+                continue;
+            };
+            if handle_loopback_connection(stream, &state).await {
+                break;
+            }
+        }
+        tracing::debug!("[OAuth] Loopback listener on port {} closed", port);
+    });
+
+    Ok(login)
+}
+
+/// 处理一次回环连接，返回是否已完成登录
+async fn handle_loopback_connection(mut stream: tokio::net::TcpStream, expected_state: &str) -> bool {
+    let mut buf = vec![0u8; 8192];
+    let mut len = 0;
+    while len < buf.len() {
+        match tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf[len..])).await {
+            Ok(Ok(0)) | Err(_) | Ok(Err(_)) => break,
+            Ok(Ok(n)) => {
+                len += n;
+                if buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+                    break;
+                }
+            }
+        }
+    }
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let target = request.lines().next().and_then(|line| line.split_whitespace().nth(1)).unwrap_or("");
+
+    let (status_line, message, done) = if !target.starts_with(LOOPBACK_PATH) {
+        ("404 Not Found", "Not found".to_string(), false)
+    } else {
+        match oauth::parse_pasted_code(&format!("http://127.0.0.1{}", target)) {
+            Ok((_, state)) if state.as_deref() != Some(expected_state) => {
+                ("400 Bad Request", "State mismatch, please restart the login.".to_string(), false)
+            }
+            Ok((code, _)) => match complete(&code, Some(expected_state)).await {
+                Ok(account) => ("200 OK", format!("Signed in as {}. You can close this window.", account.email), true),
+                Err(e) => ("400 Bad Request", format!("Login failed: {}", e), true),
+            },
+            Err(e) => {
+                record_status(expected_state, LoginStatus::Failed { error: e.clone() });
+                ("400 Bad Request", format!("Login failed: {}", e), true)
+            }
+        }
+    };
+
+    let body = format!("<!DOCTYPE html><html><body><p>{}</p></body></html>", message);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status_line,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+    done
+}
+
+fn record_status(state: &str, status: LoginStatus) {
+    if let Ok(mut logins) = LOGINS.lock() {
+        if let Some(login) = logins.get_mut(state) {
+            login.status = status;
+        }
+    }
+}
+
+/// 查询登录会话状态 (回环模式供前端/CLI 轮询)
+pub fn status(state: &str) -> Option<LoginStatus> {
+    let mut logins = LOGINS.lock().ok()?;
+    purge_expired(&mut logins);
+    logins.get(state).map(|login| login.status.clone())
+}
+
+/// 取消登录会话
+pub fn cancel(state: &str) -> bool {
+    LOGINS
+        .lock()
+        .map(|mut logins| logins.remove(state).is_some())
+        .unwrap_or(false)
+}
+
+/// 按 state 认领待完成的会话，返回 (verifier, redirect_uri); 在同一把锁内标记为已认领，保证只有一个调用方能兑换
+fn claim_pending(state: &str) -> Result<(String, String), String> {
+    let mut logins = LOGINS.lock().map_err(|e| e.to_string())?;
+    purge_expired(&mut logins);
+    match logins.get_mut(state) {
+        Some(login) if !login.claimed && matches!(login.status, LoginStatus::Pending) => {
+            login.claimed = true;
+            Ok((std::mem::take(&mut login.verifier), login.redirect_uri.clone()))
+        }
+        Some(_) => Err("This login session has already been completed".to_string()),
+        None => Err("Login session expired or unknown, please start again".to_string()),
+    }
+}
+
+/// 完成登录: `pasted` 可以是回调 URL 或授权码; state 必须对应一个由 [`start`] 创建的会话
+pub async fn complete(pasted: &str, state: Option<&str>) -> Result<Account, String> {
+    let (code, pasted_state) = oauth::parse_pasted_code(pasted)?;
+    let state = match (state, pasted_state.as_deref()) {
+        (Some(expected), Some(pasted)) if expected != pasted => {
+            return Err("State mismatch, please restart the login".to_string())
+        }
+        (Some(state), _) | (None, Some(state)) => state.to_string(),
+        (None, None) => return Err("Missing OAuth state, please start the login again".to_string()),
+    };
+
+    let (verifier, redirect_uri) = claim_pending(&state)?;
+    let result = finish_login(&code, &redirect_uri, Some(&verifier)).await;
+    record_status(
+        &state,
+        match &result {
+            Ok(account) => LoginStatus::Completed { account: Box::new(account.clone()) },
+            Err(e) => LoginStatus::Failed { error: e.clone() },
+        },
+    );
+    result
+}
+
+/// 兑换授权码并保存账号
+pub async fn finish_login(code: &str, redirect_uri: &str, code_verifier: Option<&str>) -> Result<Account, String> {
+    let token_res = oauth::exchange_code(code, redirect_uri, code_verifier).await?;
+    let refresh_token = token_res.refresh_token.ok_or_else(|| {
+        "未获取到 Refresh Token。请先在 Google 账号授权页撤销访问后重试，或使用 Refresh Token 手动添加账号。".to_string()
+    })?;
+    let user_info = oauth::get_user_info(&token_res.access_token).await?;
+    let project_id = crate::proxy::project_resolver::fetch_project_id(&token_res.access_token)
+        .await
+        .ok();
+    let token = TokenData::new(
+        token_res.access_token,
+        refresh_token,
+        token_res.expires_in,
+        Some(user_info.email.clone()),
+        project_id,
+        None,
+    );
+    crate::modules::upsert_account(user_info.email.clone(), user_info.get_display_name(), token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_sessions() {
+        let login = start(PASTE_REDIRECT_URI);
+        assert!(login.auth_url.contains(&format!("state={}", login.state)));
+        assert!(login.auth_url.contains("code_challenge_method=S256"));
+        assert!(matches!(status(&login.state), Some(LoginStatus::Pending)));
+
+        let (verifier, redirect_uri) = claim_pending(&login.state).unwrap();
+        assert_eq!(redirect_uri, PASTE_REDIRECT_URI);
+        assert!(!verifier.is_empty());
+        assert!(claim_pending("unknown").is_err());
+
+        // 兑换进行中时第二个调用方直接失败，状态仍为 Pending 供轮询
+        assert!(claim_pending(&login.state).unwrap_err().contains("already been completed"));
+        assert!(matches!(status(&login.state), Some(LoginStatus::Pending)));
+
+        record_status(&login.state, LoginStatus::Failed { error: "denied".to_string() });
+        assert!(claim_pending(&login.state).is_err());
+        assert!(cancel(&login.state));
+        assert!(status(&login.state).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_complete_requires_state() {
+        let err = complete("4/abc", None).await.unwrap_err();
+        assert!(err.contains("Missing OAuth state"));
+
+        let login = start(PASTE_REDIRECT_URI);
+        let pasted = "http://localhost/?state=other&code=4/abc";
+        let err = complete(pasted, Some(&login.state)).await.unwrap_err();
+        assert!(err.contains("State mismatch"));
+        assert!(complete("4/abc", Some("unknown")).await.unwrap_err().contains("expired or unknown"));
+        assert!(matches!(status(&login.state), Some(LoginStatus::Pending)));
+        cancel(&login.state);
+    }
+}
@@ -0,0 +1,372 @@
+//! Model price table used to convert token usage into equivalent API cost.
+//!
+//! Prices are stored in `token_stats.db` (table `model_prices`) as USD per 1M tokens.
+//! Each entry applies to a model pattern (exact id, or a prefix ending in `*`) starting
+//! at `effective_from`; the most specific pattern that is already effective wins, and
+//! among those the newest effective date wins.
+//!
+//! The usage writer keeps a [`PriceCache`] so the table is only re-read after an edit.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Bumped on every price table edit so cached copies know to reload
+static PRICES_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// A single price table entry (USD per 1M tokens)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub id: i64,
+    /// Exact model id, or a prefix pattern such as `claude-sonnet-4-5*`
+    pub model_pattern: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    /// Rate for cache hits; falls back to `input_per_million` when unset
+    #[serde(default)]
+    pub cached_input_per_million: Option<f64>,
+    /// Rate for thinking tokens; falls back to `output_per_million` when unset
+    #[serde(default)]
+    pub thinking_per_million: Option<f64>,
+    /// Rate for prompt tokens written to the cache; falls back to `input_per_million` when unset
+    #[serde(default)]
+    pub cache_write_per_million: Option<f64>,
+    /// Unix timestamp (seconds) from which this price applies
+    #[serde(default)]
+    pub effective_from: i64,
+}
+
+/// Token counts for a single request, normalized for pricing
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Prompt tokens, including cache hits
+    pub input_tokens: u32,
+    /// Completion tokens, excluding thinking
+    pub output_tokens: u32,
+    pub cached_tokens: u32,
+    pub thinking_tokens: u32,
+    /// Prompt tokens written to the cache, included in `input_tokens`
+    #[serde(default)]
+    pub cache_creation_tokens: u32,
+}
+
+impl ModelPrice {
+    /// Whether this entry's pattern covers the given model id
+    pub fn matches(&self, model: &str) -> bool {
+        match self.model_pattern.strip_suffix('*') {
+            Some(prefix) => model.starts_with(prefix),
+            None => self.model_pattern == model,
+        }
+    }
+
+    /// Specificity used to rank overlapping patterns (exact match beats any prefix)
+    fn specificity(&self) -> usize {
+        if self.model_pattern.ends_with('*') {
+            self.model_pattern.len() - 1
+        } else {
+            usize::MAX
+        }
+    }
+
+    /// Cost in USD for the given usage
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached = usage.cached_tokens.min(usage.input_tokens);
+        let written = usage.cache_creation_tokens.min(usage.input_tokens - cached);
+        let uncached = usage.input_tokens - cached - written;
+        let cached_rate = self.cached_input_per_million.unwrap_or(self.input_per_million);
+        let write_rate = self.cache_write_per_million.unwrap_or(self.input_per_million);
+        let thinking_rate = self.thinking_per_million.unwrap_or(self.output_per_million);
+
+        (uncached as f64 * self.input_per_million
+            + cached as f64 * cached_rate
+            + written as f64 * write_rate
+            + usage.output_tokens as f64 * self.output_per_million
+            + usage.thinking_tokens as f64 * thinking_rate)
+            / 1_000_000.0
+    }
+}
+
+/// Price table cached by its owner (one per connection), reloaded only after an edit
+#[derive(Debug, Default)]
+pub struct PriceCache {
+    version: u64,
+    prices: Option<Vec<ModelPrice>>,
+}
+
+impl PriceCache {
+    pub fn get(&mut self, conn: &Connection) -> Result<&[ModelPrice], String> {
+        let current = PRICES_VERSION.load(Ordering::Acquire);
+        if self.prices.is_none() || self.version != current {
+            self.prices = Some(load_prices_with_conn(conn)?);
+            self.version = current;
+        }
+        Ok(self.prices.as_deref().unwrap_or_default())
+    }
+}
+
+/// Pick the price entry applying to `model` at `timestamp`
+pub fn select_price<'a>(prices: &'a [ModelPrice], model: &str, timestamp: i64) -> Option<&'a ModelPrice> {
+    prices
+        .iter()
+        .filter(|p| p.effective_from <= timestamp && p.matches(model))
+        .max_by_key(|p| (p.specificity(), p.effective_from))
+}
+
+/// Default prices (public list prices), seeded into an empty table
+fn default_prices() -> Vec<ModelPrice> {
+    let entry = |pattern: &str, input: f64, output: f64, cached: f64, cache_write: Option<f64>| ModelPrice {
+        id: 0,
+        model_pattern: pattern.to_string(),
+        input_per_million: input,
+        output_per_million: output,
+        cached_input_per_million: Some(cached),
+        thinking_per_million: None,
+        cache_write_per_million: cache_write,
+        effective_from: 0,
+    };
+
+    // Claude cache writes (5 minute TTL) cost 1.25x input; Gemini has no per-token write rate
+    vec![
+        entry("claude-opus-4-5*", 5.0, 25.0, 0.5, Some(6.25)),
+        entry("claude-sonnet-4-5*", 3.0, 15.0, 0.3, Some(3.75)),
+        entry("claude-haiku-4-5*", 1.0, 5.0, 0.1, Some(1.25)),
+        entry("gemini-3-pro*", 2.0, 12.0, 0.2, None),
+        entry("gemini-3-flash*", 0.5, 3.0, 0.05, None),
+        entry("gemini-2.5-pro*", 1.25, 10.0, 0.125, None),
+        entry("gemini-2.5-flash*", 0.3, 2.5, 0.03, None),
+    ]
+}
+
+/// Create the price table and seed defaults on first run
+pub(crate) fn init_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS model_prices (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            model_pattern TEXT NOT NULL,
+            input_per_million REAL NOT NULL DEFAULT 0,
+            output_per_million REAL NOT NULL DEFAULT 0,
+            cached_input_per_million REAL,
+            thinking_per_million REAL,
+            cache_write_per_million REAL,
+            effective_from INTEGER NOT NULL DEFAULT 0,
+            UNIQUE (model_pattern, effective_from)
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    let _ = conn.execute("ALTER TABLE model_prices ADD COLUMN cache_write_per_million REAL", []);
+
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM model_prices", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if count == 0 {
+        for price in default_prices() {
+            upsert_price_with_conn(conn, &price)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn load_prices_with_conn(conn: &Connection) -> Result<Vec<ModelPrice>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, model_pattern, input_per_million, output_per_million,
+                cached_input_per_million, thinking_per_million, effective_from, cache_write_per_million
+         FROM model_prices
+         ORDER BY model_pattern ASC, effective_from DESC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ModelPrice {
+                id: row.get(0)?,
+                model_pattern: row.get(1)?,
+                input_per_million: row.get(2)?,
+                output_per_million: row.get(3)?,
+                cached_input_per_million: row.get(4)?,
+                thinking_per_million: row.get(5)?,
+                effective_from: row.get(6)?,
+                cache_write_per_million: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(result)
+}
+
+fn upsert_price_with_conn(conn: &Connection, price: &ModelPrice) -> Result<i64, String> {
+    let pattern = price.model_pattern.trim();
+    if pattern.is_empty() {
+        return Err("model_pattern must not be empty".to_string());
+    }
+    let rates = [
+        Some(price.input_per_million),
+        Some(price.output_per_million),
+        price.cached_input_per_million,
+        price.thinking_per_million,
+        price.cache_write_per_million,
+    ];
+    if rates.iter().flatten().any(|r| !r.is_finite() || *r < 0.0) {
+        return Err("price rates must be non-negative numbers".to_string());
+    }
+
+    conn.execute(
+        "INSERT INTO model_prices (model_pattern, input_per_million, output_per_million,
+            cached_input_per_million, thinking_per_million, effective_from, cache_write_per_million)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(model_pattern, effective_from) DO UPDATE SET
+            input_per_million = ?2,
+            output_per_million = ?3,
+            cached_input_per_million = ?4,
+            thinking_per_million = ?5,
+            cache_write_per_million = ?7",
+        params![
+            pattern,
+            price.input_per_million,
+            price.output_per_million,
+            price.cached_input_per_million,
+            price.thinking_per_million,
+            price.effective_from,
+            price.cache_write_per_million,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    PRICES_VERSION.fetch_add(1, Ordering::AcqRel);
+
+    conn.query_row(
+        "SELECT id FROM model_prices WHERE model_pattern = ?1 AND effective_from = ?2",
+        params![pattern, price.effective_from],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| "failed to read back price entry".to_string())
+}
+
+/// List all price table entries
+pub fn list_prices() -> Result<Vec<ModelPrice>, String> {
+    let conn = crate::modules::token_stats::connect_db()?;
+    load_prices_with_conn(&conn)
+}
+
+/// Insert or update a price entry (keyed by pattern + effective date), returns its id
+pub fn upsert_price(price: &ModelPrice) -> Result<i64, String> {
+    let conn = crate::modules::token_stats::connect_db()?;
+    upsert_price_with_conn(&conn, price)
+}
+
+/// Delete a price entry by id
+pub fn delete_price(id: i64) -> Result<(), String> {
+    let conn = crate::modules::token_stats::connect_db()?;
+    conn.execute("DELETE FROM model_prices WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    PRICES_VERSION.fetch_add(1, Ordering::AcqRel);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(pattern: &str, input: f64, output: f64, effective_from: i64) -> ModelPrice {
+        ModelPrice {
+            id: 0,
+            model_pattern: pattern.to_string(),
+            input_per_million: input,
+            output_per_million: output,
+            cached_input_per_million: None,
+            thinking_per_million: None,
+            cache_write_per_million: None,
+            effective_from,
+        }
+    }
+
+    #[test]
+    fn test_select_price_prefers_specific_and_effective() {
+        let prices = vec![
+            price("claude-*", 1.0, 1.0, 0),
+            price("claude-sonnet-4-5*", 3.0, 15.0, 0),
+            price("claude-sonnet-4-5*", 4.0, 20.0, 1_000),
+            price("claude-sonnet-4-5-thinking", 9.0, 9.0, 0),
+        ];
+
+        let p = select_price(&prices, "claude-sonnet-4-5", 500).unwrap();
+        assert_eq!(p.input_per_million, 3.0);
+        let p = select_price(&prices, "claude-sonnet-4-5", 2_000).unwrap();
+        assert_eq!(p.input_per_million, 4.0);
+        let p = select_price(&prices, "claude-sonnet-4-5-thinking", 2_000).unwrap();
+        assert_eq!(p.input_per_million, 9.0);
+        let p = select_price(&prices, "claude-opus-4-5", 0).unwrap();
+        assert_eq!(p.input_per_million, 1.0);
+        assert!(select_price(&prices, "gemini-3-flash", 0).is_none());
+    }
+
+    #[test]
+    fn test_cost_with_cache_and_thinking() {
+        let mut p = price("m", 2.0, 10.0, 0);
+        p.cached_input_per_million = Some(0.5);
+        p.thinking_per_million = Some(20.0);
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cached_tokens: 400_000,
+            thinking_tokens: 50_000,
+            cache_creation_tokens: 0,
+        };
+        // 0.6M * 2 + 0.4M * 0.5 + 0.1M * 10 + 0.05M * 20
+        let cost = p.cost(&usage);
+        assert!((cost - (1.2 + 0.2 + 1.0 + 1.0)).abs() < 1e-9);
+
+        // Cache writes are billed at the write rate, the rest of the prompt at the input rate
+        p.cache_write_per_million = Some(2.5);
+        let usage = TokenUsage {
+            cache_creation_tokens: 200_000,
+            ..usage
+        };
+        // 0.4M * 2 + 0.4M * 0.5 + 0.2M * 2.5 + 0.1M * 10 + 0.05M * 20
+        let cost = p.cost(&usage);
+        assert!((cost - (0.8 + 0.2 + 0.5 + 1.0 + 1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_price_cache_reloads_after_edit() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        let mut cache = PriceCache::default();
+        let seeded = cache.get(&conn).unwrap().len();
+
+        upsert_price_with_conn(&conn, &price("gemini-3-flash", 1.0, 1.0, 0)).unwrap();
+        assert_eq!(cache.get(&conn).unwrap().len(), seeded + 1);
+    }
+
+    #[test]
+    fn test_seed_and_upsert() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        let seeded = load_prices_with_conn(&conn).unwrap();
+        assert_eq!(seeded.len(), default_prices().len());
+
+        let mut custom = price("gemini-3-flash", 1.0, 1.0, 100);
+        upsert_price_with_conn(&conn, &custom).unwrap();
+        custom.output_per_million = 2.0;
+        upsert_price_with_conn(&conn, &custom).unwrap();
+        assert_eq!(load_prices_with_conn(&conn).unwrap().len(), seeded.len() + 1);
+
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 1_000_000,
+            ..Default::default()
+        };
+        let prices = load_prices_with_conn(&conn).unwrap();
+        let cost = select_price(&prices, "gemini-3-flash", 200).unwrap().cost(&usage);
+        assert!((cost - 3.0).abs() < 1e-9);
+        assert!(upsert_price_with_conn(&conn, &price("gemini", -1.0, 0.0, 0)).is_err());
+    }
+}
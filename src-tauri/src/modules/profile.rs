@@ -0,0 +1,248 @@
+// 多实例 / 多 Profile (Profiles)
+//
+// 同一台主机上可以运行多个互不影响的账号池: 每个 profile 使用独立的数据目录 `<root>/profiles/<name>`，
+// 账号、配置、日志、proxy_logs.db、token_stats.db 与设备指纹基线都随数据目录隔离。
+// 服务启动时对数据目录中的 `instance.lock` 加操作系统文件锁 (进程存活期间一直持有，退出或崩溃时由系统释放)，
+// 防止两个实例误用同一份数据; 持有者信息写在旁边的 `instance.json`。
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const PROFILES_DIR: &str = "profiles";
+const LOCK_FILE: &str = "instance.lock";
+const LOCK_INFO_FILE: &str = "instance.json";
+const MAX_PROFILE_NAME_LEN: usize = 64;
+
+static ACTIVE_PROFILE: OnceLock<String> = OnceLock::new();
+
+/// 校验 profile 名称: 仅允许字母、数字、`-`、`_`，避免路径穿越
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_PROFILE_NAME_LEN {
+        return Err(format!(
+            "Invalid profile name '{}': must be 1-{} characters",
+            name, MAX_PROFILE_NAME_LEN
+        ));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!(
+            "Invalid profile name '{}': only letters, digits, '-' and '_' are allowed",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// 计算数据目录: `root` 缺省为 ~/.antigravity_tools，指定 profile 时为 `<root>/profiles/<name>`
+pub fn resolve_data_dir(root: Option<&Path>, profile: Option<&str>) -> Result<PathBuf, String> {
+    let root = match root {
+        Some(root) => root.to_path_buf(),
+        None => super::account::default_data_root()?,
+    };
+    match profile {
+        Some(name) => {
+            validate_profile_name(name)?;
+            Ok(root.join(PROFILES_DIR).join(name))
+        }
+        None => Ok(root),
+    }
+}
+
+/// 激活数据目录与 profile，须在读取任何数据之前调用
+pub fn activate(root: Option<&Path>, profile: Option<&str>) -> Result<PathBuf, String> {
+    let dir = resolve_data_dir(root, profile)?;
+    if root.is_some() || profile.is_some() {
+        fs::create_dir_all(&dir).map_err(|e| format!("failed_to_create_data_dir: {}", e))?;
+        super::account::set_data_dir_override(dir.clone());
+    }
+    if let Some(name) = profile {
+        let _ = ACTIVE_PROFILE.set(name.to_string());
+    }
+    Ok(dir)
+}
+
+/// 当前 profile 名称 (未指定时为 None，即默认数据目录)
+pub fn active_profile() -> Option<&'static str> {
+    ACTIVE_PROFILE.get().map(String::as_str)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub pid: u32,
+    #[serde(default)]
+    pub port: u16,
+    #[serde(default)]
+    pub started_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileSummary {
+    pub name: String,
+    pub data_dir: PathBuf,
+    /// 正在使用该目录的实例
+    pub running: Option<LockInfo>,
+}
+
+/// 列出 `root` 下的默认目录与所有 profile
+pub fn list_profiles(root: Option<&Path>) -> Result<Vec<ProfileSummary>, String> {
+    let root = resolve_data_dir(root, None)?;
+    let mut profiles = vec![ProfileSummary {
+        name: "default".to_string(),
+        running: read_live_lock(&root),
+        data_dir: root.clone(),
+    }];
+
+    let Ok(entries) = fs::read_dir(root.join(PROFILES_DIR)) else {
+        return Ok(profiles);
+    };
+    let mut named: Vec<ProfileSummary> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            validate_profile_name(&name).ok()?;
+            Some(ProfileSummary {
+                running: read_live_lock(&e.path()),
+                data_dir: e.path(),
+                name,
+            })
+        })
+        .collect();
+    named.sort_by(|a, b| a.name.cmp(&b.name));
+    profiles.extend(named);
+    Ok(profiles)
+}
+
+/// 锁文件被其它进程 (或本进程的另一个句柄) 独占时返回持有者信息
+fn read_live_lock(dir: &Path) -> Option<LockInfo> {
+    let file = fs::File::open(dir.join(LOCK_FILE)).ok()?;
+    match file.try_lock_shared() {
+        Err(fs::TryLockError::WouldBlock) => {
+            let content = fs::read_to_string(dir.join(LOCK_INFO_FILE)).ok()?;
+            serde_json::from_str(&content).ok()
+        }
+        // 能拿到共享锁说明没有实例在运行 (句柄关闭时自动释放)
+        _ => None,
+    }
+}
+
+/// 实例锁: 持有 `instance.lock` 的独占文件锁直到 Drop (或进程退出)
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: fs::File,
+    info_path: PathBuf,
+    info: LockInfo,
+}
+
+impl InstanceLock {
+    /// 占用数据目录；已有实例持有锁时返回错误。端口在读取配置后通过 [`InstanceLock::set_port`] 补充
+    pub fn acquire(dir: &Path) -> Result<Self, String> {
+        let path = dir.join(LOCK_FILE);
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(fs::TryLockError::WouldBlock) => {
+                let owner = fs::read_to_string(dir.join(LOCK_INFO_FILE))
+                    .ok()
+                    .and_then(|c| serde_json::from_str::<LockInfo>(&c).ok())
+                    .map(|o| match o.port {
+                        0 => format!(" (pid {})", o.pid),
+                        port => format!(" (pid {}, port {})", o.pid, port),
+                    })
+                    .unwrap_or_default();
+                return Err(format!(
+                    "Data directory {} is already in use by another instance{}. \
+                     Use --profile or --data-dir to run an isolated instance.",
+                    dir.display(),
+                    owner
+                ));
+            }
+            Err(fs::TryLockError::Error(e)) => {
+                return Err(format!("Failed to lock {}: {}", path.display(), e));
+            }
+        }
+
+        let lock = Self {
+            _file: file,
+            info_path: dir.join(LOCK_INFO_FILE),
+            info: LockInfo {
+                pid: std::process::id(),
+                port: 0,
+                started_at: chrono::Utc::now().timestamp(),
+            },
+        };
+        lock.write_info()?;
+        Ok(lock)
+    }
+
+    /// 记录监听端口 (供 `profile list` 与冲突提示显示)
+    pub fn set_port(&mut self, port: u16) -> Result<(), String> {
+        self.info.port = port;
+        self.write_info()
+    }
+
+    fn write_info(&self) -> Result<(), String> {
+        let content = serde_json::to_string(&self.info).map_err(|e| e.to_string())?;
+        fs::write(&self.info_path, content).map_err(|e| format!("Failed to write {}: {}", self.info_path.display(), e))
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // 锁仍由本进程持有，信息文件一定是自己写的; 锁文件保留，文件锁随句柄关闭释放
+        let _ = fs::remove_file(&self.info_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_dirs() {
+        let root = Path::new("/srv/antigravity");
+        assert_eq!(resolve_data_dir(Some(root), None).unwrap(), root);
+        assert_eq!(
+            resolve_data_dir(Some(root), Some("team-a")).unwrap(),
+            root.join("profiles").join("team-a")
+        );
+        assert!(resolve_data_dir(Some(root), Some("../etc")).is_err());
+        assert!(resolve_data_dir(Some(root), Some("")).is_err());
+        assert!(validate_profile_name("prod_2").is_ok());
+        assert!(validate_profile_name("a/b").is_err());
+    }
+
+    #[test]
+    fn test_instance_lock() {
+        let dir = std::env::temp_dir().join(format!("ag_lock_{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // 残留的锁文件与信息 (没有进程持有文件锁) 可以被接管
+        fs::write(dir.join(LOCK_FILE), r#"{"pid":4294967294,"port":1}"#).unwrap();
+        fs::write(dir.join(LOCK_INFO_FILE), r#"{"pid":4294967294,"port":1}"#).unwrap();
+        assert!(read_live_lock(&dir).is_none());
+        let mut lock = InstanceLock::acquire(&dir).unwrap();
+        assert_eq!(read_live_lock(&dir).unwrap().port, 0);
+        lock.set_port(8045).unwrap();
+        assert_eq!(read_live_lock(&dir).unwrap().port, 8045);
+        assert_eq!(list_profiles(Some(&dir)).unwrap()[0].running.as_ref().unwrap().pid, std::process::id());
+
+        // 持有期间第二个实例无法占用
+        let err = InstanceLock::acquire(&dir).unwrap_err();
+        assert!(err.contains("port 8045"), "{}", err);
+
+        drop(lock);
+        assert!(read_live_lock(&dir).is_none());
+        let relocked = InstanceLock::acquire(&dir).unwrap();
+        assert_eq!(read_live_lock(&dir).unwrap().pid, std::process::id());
+        drop(relocked);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,4 +1,5 @@
-use rusqlite::{params, Connection};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, Row};
 use std::path::PathBuf;
 use crate::proxy::monitor::ProxyRequestLog;
 
@@ -7,7 +8,13 @@ pub fn get_proxy_db_path() -> Result<PathBuf, String> {
     Ok(data_dir.join("proxy_logs.db"))
 }
 
-fn connect_db() -> Result<Connection, String> {
+/// Max bytes of each body indexed for full-text search
+const FTS_BODY_LIMIT: usize = 256 * 1024;
+
+/// zstd level used for stored bodies
+const BODY_COMPRESSION_LEVEL: i32 = 3;
+
+pub(crate) fn connect_db() -> Result<Connection, String> {
     let db_path = get_proxy_db_path()?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
     
@@ -26,10 +33,15 @@ fn connect_db() -> Result<Connection, String> {
 pub fn init_db() -> Result<(), String> {
     // connect_db will initialize WAL mode and other pragmas
     let conn = connect_db()?;
-    
+    init_schema(&conn)
+}
+
+pub(crate) fn init_schema(conn: &Connection) -> Result<(), String> {
+    // `seq` aliases the rowid so it survives VACUUM; the full-text index is keyed on it
     conn.execute(
         "CREATE TABLE IF NOT EXISTS request_logs (
-            id TEXT PRIMARY KEY,
+            seq INTEGER PRIMARY KEY,
+            id TEXT NOT NULL UNIQUE,
             timestamp INTEGER,
             method TEXT,
             url TEXT,
@@ -49,6 +61,11 @@ pub fn init_db() -> Result<(), String> {
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN account_email TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN mapped_model TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN protocol TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN cached_tokens INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN thinking_tokens INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN client_key TEXT", []);
+
+    migrate_stable_rowid(conn)?;
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC)",
@@ -61,15 +78,184 @@ pub fn init_db() -> Result<(), String> {
         [],
     ).map_err(|e| e.to_string())?;
 
+    init_fts(conn)?;
+
     Ok(())
 }
 
-pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
-    let conn = connect_db()?;
+/// Columns of `request_logs` besides `seq`, in table order
+const LOG_COLUMNS: &str = "id, timestamp, method, url, status, duration, model, error, request_body, response_body, \
+    input_tokens, output_tokens, account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key";
+
+/// Rebuild tables created with `id TEXT PRIMARY KEY`, whose implicit rowids VACUUM may renumber.
+/// The full-text index is dropped and backfilled afterwards by `init_fts`.
+fn migrate_stable_rowid(conn: &Connection) -> Result<(), String> {
+    if conn.prepare("SELECT seq FROM request_logs LIMIT 0").is_ok() {
+        return Ok(());
+    }
+
+    conn.execute_batch(&format!(
+        "BEGIN;
+         ALTER TABLE request_logs RENAME TO request_logs_old;
+         CREATE TABLE request_logs (
+            seq INTEGER PRIMARY KEY,
+            id TEXT NOT NULL UNIQUE,
+            timestamp INTEGER,
+            method TEXT,
+            url TEXT,
+            status INTEGER,
+            duration INTEGER,
+            model TEXT,
+            error TEXT,
+            request_body TEXT,
+            response_body TEXT,
+            input_tokens INTEGER,
+            output_tokens INTEGER,
+            account_email TEXT,
+            mapped_model TEXT,
+            protocol TEXT,
+            cached_tokens INTEGER,
+            thinking_tokens INTEGER,
+            client_key TEXT
+         );
+         INSERT INTO request_logs ({cols}) SELECT {cols} FROM request_logs_old ORDER BY timestamp, rowid;
+         DROP TABLE request_logs_old;
+         DROP TABLE IF EXISTS request_logs_fts;
+         COMMIT;",
+        cols = LOG_COLUMNS
+    ))
+    .map_err(|e| {
+        let _ = conn.execute_batch("ROLLBACK");
+        format!("Failed to migrate request_logs: {}", e)
+    })?;
+    tracing::info!("Migrated request_logs to a stable row key");
+    Ok(())
+}
+
+/// Full-text index over bodies, model, URL and error text.
+/// Rows are keyed on `request_logs.seq` (the table's rowid); bodies are indexed up to `FTS_BODY_LIMIT`.
+/// The table is contentless so it never keeps a second plaintext copy of (possibly compressed) bodies;
+/// snippets are built from `request_logs` at query time.
+fn init_fts(conn: &Connection) -> Result<(), String> {
+    // Older indexes stored their own copy of the text; rebuild them as contentless
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'request_logs_fts'",
+            [],
+            |row| row.get(0),
+        )
+        .ok();
+    if existing.is_some_and(|sql| !sql.contains("contentless_delete")) {
+        conn.execute("DROP TABLE request_logs_fts", []).map_err(|e| e.to_string())?;
+        tracing::info!("Rebuilding the proxy log search index as contentless");
+    }
 
     conn.execute(
-        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, protocol)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        "CREATE VIRTUAL TABLE IF NOT EXISTS request_logs_fts USING fts5(
+            model, url, error, request_body, response_body,
+            content = '', contentless_delete = 1,
+            tokenize = 'unicode61'
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    // Backfill logs written before the index existed
+    let indexed: u64 = conn
+        .query_row("SELECT COUNT(*) FROM request_logs_fts", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if indexed == 0 {
+        let backfilled = conn.execute(
+            "INSERT INTO request_logs_fts (rowid, model, url, error, request_body, response_body)
+             SELECT seq, COALESCE(model, '') || ' ' || COALESCE(mapped_model, ''), url, error,
+                    CASE WHEN typeof(request_body) = 'text' THEN substr(request_body, 1, ?1) END,
+                    CASE WHEN typeof(response_body) = 'text' THEN substr(response_body, 1, ?1) END
+             FROM request_logs",
+            [FTS_BODY_LIMIT],
+        ).map_err(|e| e.to_string())?;
+        if backfilled > 0 {
+            tracing::info!("Indexed {} existing proxy logs for full-text search", backfilled);
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn truncate_for_index(text: Option<&str>) -> Option<&str> {
+    text.map(|t| {
+        if t.len() <= FTS_BODY_LIMIT {
+            return t;
+        }
+        let mut end = FTS_BODY_LIMIT;
+        while !t.is_char_boundary(end) {
+            end -= 1;
+        }
+        &t[..end]
+    })
+}
+
+/// Bodies are stored as TEXT, or as a zstd-compressed BLOB when `compress` is set
+enum StoredBody {
+    Text(String),
+    Compressed(Vec<u8>),
+}
+
+impl rusqlite::ToSql for StoredBody {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        match self {
+            StoredBody::Text(s) => s.to_sql(),
+            StoredBody::Compressed(b) => b.to_sql(),
+        }
+    }
+}
+
+fn encode_body(body: Option<&str>, compress: bool) -> Option<StoredBody> {
+    let body = body?;
+    if compress {
+        if let Ok(data) = zstd::encode_all(body.as_bytes(), BODY_COMPRESSION_LEVEL) {
+            if data.len() < body.len() {
+                return Some(StoredBody::Compressed(data));
+            }
+        }
+    }
+    Some(StoredBody::Text(body.to_string()))
+}
+
+/// Read a body column written by `encode_body`
+pub(crate) fn read_body(row: &Row, idx: usize) -> Option<String> {
+    match row.get_ref(idx).ok()? {
+        ValueRef::Text(t) => Some(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => match zstd::decode_all(b) {
+            Ok(data) => Some(String::from_utf8_lossy(&data).into_owned()),
+            Err(e) => Some(format!("[Failed to decompress body: {}]", e)),
+        },
+        _ => None,
+    }
+}
+
+/// Save a batch in one transaction; each row gets its own SAVEPOINT so a bad row is skipped
+/// instead of rolling back the whole batch. Returns the number of skipped rows.
+pub(crate) fn save_logs_batch_with_conn(conn: &mut Connection, logs: &[(ProxyRequestLog, bool)]) -> Result<usize, String> {
+    let mut tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut failed = 0;
+    for (log, compress) in logs {
+        let sp = tx.savepoint().map_err(|e| e.to_string())?;
+        match save_log_with_conn(&sp, log, *compress) {
+            Ok(()) => sp.commit().map_err(|e| e.to_string())?,
+            Err(e) => {
+                // Savepoint 在 drop 时回滚本行
+                tracing::warn!("[ProxyDb] Skipping proxy log {}: {}", log.id, e);
+                failed += 1;
+            }
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(failed)
+}
+
+pub(crate) fn save_log_with_conn(conn: &Connection, log: &ProxyRequestLog, compress: bool) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
         params![
             log.id,
             log.timestamp,
@@ -79,13 +265,34 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
             log.duration,
             log.model,
             log.error,
-            log.request_body,
-            log.response_body,
+            encode_body(log.request_body.as_deref(), compress),
+            encode_body(log.response_body.as_deref(), compress),
             log.input_tokens,
             log.output_tokens,
             log.account_email,
             log.mapped_model,
             log.protocol,
+            log.cached_tokens,
+            log.thinking_tokens,
+            log.client_key,
+        ],
+    ).map_err(|e| e.to_string())?;
+
+    let model_text = format!(
+        "{} {}",
+        log.model.as_deref().unwrap_or(""),
+        log.mapped_model.as_deref().unwrap_or("")
+    );
+    conn.execute(
+        "INSERT INTO request_logs_fts (rowid, model, url, error, request_body, response_body)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            conn.last_insert_rowid(),
+            model_text,
+            log.url,
+            log.error,
+            truncate_for_index(log.request_body.as_deref()),
+            truncate_for_index(log.response_body.as_deref()),
         ],
     ).map_err(|e| e.to_string())?;
 
@@ -99,7 +306,7 @@ pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestL
     let mut stmt = conn.prepare(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol
+                input_tokens, output_tokens, account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key
          FROM request_logs 
          ORDER BY timestamp DESC 
          LIMIT ?1 OFFSET ?2"
@@ -122,6 +329,10 @@ pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestL
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
             protocol: row.get(14).unwrap_or(None),
+            cached_tokens: row.get(15).unwrap_or(None),
+            thinking_tokens: row.get(16).unwrap_or(None),
+            cache_creation_tokens: None,
+            client_key: row.get(17).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -165,7 +376,7 @@ pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
     let mut stmt = conn.prepare(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 request_body, response_body, input_tokens, output_tokens, 
-                account_email, mapped_model, protocol
+                account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key
          FROM request_logs 
          WHERE id = ?1"
     ).map_err(|e| e.to_string())?;
@@ -182,11 +393,15 @@ pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
             mapped_model: row.get(13).unwrap_or(None),
             account_email: row.get(12).unwrap_or(None),
             error: row.get(7)?,
-            request_body: row.get(8).unwrap_or(None),
-            response_body: row.get(9).unwrap_or(None),
+            request_body: read_body(row, 8),
+            response_body: read_body(row, 9),
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
             protocol: row.get(14).unwrap_or(None),
+            cached_tokens: row.get(15).unwrap_or(None),
+            thinking_tokens: row.get(16).unwrap_or(None),
+            cache_creation_tokens: None,
+            client_key: row.get(17).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())
 }
@@ -196,6 +411,11 @@ pub fn cleanup_old_logs(days: i64) -> Result<usize, String> {
     let conn = connect_db()?;
     
     let cutoff_timestamp = chrono::Utc::now().timestamp() - (days * 24 * 3600);
+
+    conn.execute(
+        "DELETE FROM request_logs_fts WHERE rowid IN (SELECT seq FROM request_logs WHERE timestamp < ?1)",
+        [cutoff_timestamp],
+    ).map_err(|e| e.to_string())?;
     
     let deleted = conn.execute(
         "DELETE FROM request_logs WHERE timestamp < ?1",
@@ -213,6 +433,15 @@ pub fn cleanup_old_logs(days: i64) -> Result<usize, String> {
 pub fn limit_max_logs(max_count: usize) -> Result<usize, String> {
     let conn = connect_db()?;
     
+    conn.execute(
+        "DELETE FROM request_logs_fts WHERE rowid IN (
+            SELECT seq FROM request_logs WHERE id NOT IN (
+                SELECT id FROM request_logs ORDER BY timestamp DESC LIMIT ?1
+            )
+        )",
+        [max_count],
+    ).map_err(|e| e.to_string())?;
+
     let deleted = conn.execute(
         "DELETE FROM request_logs WHERE id NOT IN (
             SELECT id FROM request_logs ORDER BY timestamp DESC LIMIT ?1
@@ -228,6 +457,8 @@ pub fn limit_max_logs(max_count: usize) -> Result<usize, String> {
 pub fn clear_logs() -> Result<(), String> {
     let conn = connect_db()?;
     conn.execute("DELETE FROM request_logs", []).map_err(|e| e.to_string())?;
+    conn.execute("INSERT INTO request_logs_fts (request_logs_fts) VALUES ('delete-all')", [])
+        .map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -283,7 +514,7 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
     let sql = if errors_only {
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol
+                input_tokens, output_tokens, account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key
          FROM request_logs 
          WHERE (status < 200 OR status >= 400)
          ORDER BY timestamp DESC 
@@ -291,14 +522,14 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
     } else if filter.is_empty() {
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol
+                input_tokens, output_tokens, account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key
          FROM request_logs 
          ORDER BY timestamp DESC 
          LIMIT ?1 OFFSET ?2"
     } else {
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol
+                input_tokens, output_tokens, account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key
          FROM request_logs 
          WHERE (url LIKE ?3 OR method LIKE ?3 OR model LIKE ?3 OR CAST(status AS TEXT) LIKE ?3)
          ORDER BY timestamp DESC 
@@ -324,6 +555,10 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
                 input_tokens: row.get(10).unwrap_or(None),
                 output_tokens: row.get(11).unwrap_or(None),
                 protocol: row.get(14).unwrap_or(None),
+                cached_tokens: row.get(15).unwrap_or(None),
+                thinking_tokens: row.get(16).unwrap_or(None),
+                cache_creation_tokens: None,
+                client_key: row.get(17).unwrap_or(None),
             })
         }).map_err(|e| e.to_string())?;
         logs_iter.filter_map(|r| r.ok()).collect()
@@ -346,6 +581,10 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
                 input_tokens: row.get(10).unwrap_or(None),
                 output_tokens: row.get(11).unwrap_or(None),
                 protocol: row.get(14).unwrap_or(None),
+                cached_tokens: row.get(15).unwrap_or(None),
+                thinking_tokens: row.get(16).unwrap_or(None),
+                cache_creation_tokens: None,
+                client_key: row.get(17).unwrap_or(None),
             })
         }).map_err(|e| e.to_string())?;
         logs_iter.filter_map(|r| r.ok()).collect()
@@ -368,6 +607,10 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
                 input_tokens: row.get(10).unwrap_or(None),
                 output_tokens: row.get(11).unwrap_or(None),
                 protocol: row.get(14).unwrap_or(None),
+                cached_tokens: row.get(15).unwrap_or(None),
+                thinking_tokens: row.get(16).unwrap_or(None),
+                cache_creation_tokens: None,
+                client_key: row.get(17).unwrap_or(None),
             })
         }).map_err(|e| e.to_string())?;
         logs_iter.filter_map(|r| r.ok()).collect()
@@ -383,7 +626,7 @@ pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
     let mut stmt = conn.prepare(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 request_body, response_body, input_tokens, output_tokens, 
-                account_email, mapped_model, protocol
+                account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key
          FROM request_logs 
          ORDER BY timestamp DESC"
     ).map_err(|e| e.to_string())?;
@@ -400,11 +643,15 @@ pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
             mapped_model: row.get(13).unwrap_or(None),
             account_email: row.get(12).unwrap_or(None),
             error: row.get(7)?,
-            request_body: row.get(8).unwrap_or(None),
-            response_body: row.get(9).unwrap_or(None),
+            request_body: read_body(row, 8),
+            response_body: read_body(row, 9),
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
             protocol: row.get(14).unwrap_or(None),
+            cached_tokens: row.get(15).unwrap_or(None),
+            thinking_tokens: row.get(16).unwrap_or(None),
+            cache_creation_tokens: None,
+            client_key: row.get(17).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -429,7 +676,7 @@ pub fn get_logs_by_ids(ids: &[String]) -> Result<Vec<ProxyRequestLog>, String> {
     let sql = format!(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 request_body, response_body, input_tokens, output_tokens, 
-                account_email, mapped_model, protocol
+                account_email, mapped_model, protocol, cached_tokens, thinking_tokens, client_key
          FROM request_logs 
          WHERE id IN ({})
          ORDER BY timestamp DESC",
@@ -453,11 +700,15 @@ pub fn get_logs_by_ids(ids: &[String]) -> Result<Vec<ProxyRequestLog>, String> {
             mapped_model: row.get(13).unwrap_or(None),
             account_email: row.get(12).unwrap_or(None),
             error: row.get(7)?,
-            request_body: row.get(8).unwrap_or(None),
-            response_body: row.get(9).unwrap_or(None),
+            request_body: read_body(row, 8),
+            response_body: read_body(row, 9),
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
             protocol: row.get(14).unwrap_or(None),
+            cached_tokens: row.get(15).unwrap_or(None),
+            thinking_tokens: row.get(16).unwrap_or(None),
+            cache_creation_tokens: None,
+            client_key: row.get(17).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -467,3 +718,140 @@ pub fn get_logs_by_ids(ids: &[String]) -> Result<Vec<ProxyRequestLog>, String> {
     }
     Ok(logs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_log(id: &str, timestamp: i64, url: &str) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: id.to_string(),
+            timestamp,
+            method: "POST".to_string(),
+            url: url.to_string(),
+            status: 200,
+            duration: 0,
+            model: None,
+            mapped_model: None,
+            account_email: None,
+            error: None,
+            request_body: None,
+            response_body: None,
+            input_tokens: None,
+            output_tokens: None,
+            protocol: None,
+            cached_tokens: None,
+            thinking_tokens: None,
+            cache_creation_tokens: None,
+            client_key: None,
+        }
+    }
+
+    fn fts_hit(conn: &Connection, term: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT l.id FROM request_logs l JOIN request_logs_fts ON request_logs_fts.rowid = l.seq WHERE request_logs_fts MATCH ?1")
+            .unwrap();
+        stmt.query_map([term], |row| row.get(0)).unwrap().map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_fts_survives_migration_and_vacuum() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Schema from before `seq` existed
+        conn.execute_batch(
+            "CREATE TABLE request_logs (id TEXT PRIMARY KEY, timestamp INTEGER, method TEXT, url TEXT, status INTEGER, duration INTEGER, model TEXT, error TEXT);
+             INSERT INTO request_logs (id, timestamp, method, url, status, duration) VALUES ('old', 1, 'GET', '/v1/legacy', 200, 0);",
+        )
+        .unwrap();
+        init_schema(&conn).unwrap();
+        assert_eq!(fts_hit(&conn, "legacy"), vec!["old"]);
+
+        save_log_with_conn(&conn, &test_log("a", 2, "/v1/alpha"), false).unwrap();
+        save_log_with_conn(&conn, &test_log("b", 3, "/v1/beta"), false).unwrap();
+        conn.execute("DELETE FROM request_logs_fts WHERE rowid IN (SELECT seq FROM request_logs WHERE id = 'old')", [])
+            .unwrap();
+        conn.execute("DELETE FROM request_logs WHERE id = 'old'", []).unwrap();
+        conn.execute("VACUUM", []).unwrap();
+
+        assert_eq!(fts_hit(&conn, "alpha"), vec!["a"]);
+        assert_eq!(fts_hit(&conn, "beta"), vec!["b"]);
+        assert!(fts_hit(&conn, "legacy").is_empty());
+    }
+
+    #[test]
+    fn test_batch_skips_failing_rows() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        let logs = vec![
+            (test_log("a", 1, "/v1/alpha"), false),
+            (test_log("a", 2, "/v1/duplicate"), false),
+            (test_log("b", 3, "/v1/beta"), true),
+        ];
+        assert_eq!(save_logs_batch_with_conn(&mut conn, &logs).unwrap(), 1);
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM request_logs", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(fts_hit(&conn, "alpha"), vec!["a"]);
+        assert_eq!(fts_hit(&conn, "beta"), vec!["b"]);
+        assert!(fts_hit(&conn, "duplicate").is_empty());
+    }
+
+    #[test]
+    fn test_compressed_body_roundtrip() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+
+        let body = "{\"messages\":[{\"role\":\"user\",\"content\":\"hello\"}]}".repeat(50);
+        for (id, compress) in [("plain", false), ("zstd", true)] {
+            let log = ProxyRequestLog {
+                id: id.to_string(),
+                timestamp: 0,
+                method: "POST".to_string(),
+                url: "/v1/chat/completions".to_string(),
+                status: 200,
+                duration: 0,
+                model: None,
+                mapped_model: None,
+                account_email: None,
+                error: None,
+                request_body: Some(body.clone()),
+                response_body: None,
+                input_tokens: None,
+                output_tokens: None,
+                protocol: None,
+                cached_tokens: None,
+                thinking_tokens: None,
+                cache_creation_tokens: None,
+                client_key: None,
+            };
+            save_log_with_conn(&conn, &log, compress).unwrap();
+        }
+
+        let mut stmt = conn
+            .prepare("SELECT id, typeof(request_body), request_body, response_body FROM request_logs ORDER BY id")
+            .unwrap();
+        let rows: Vec<(String, String, Option<String>, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, read_body(row, 2), read_body(row, 3))))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+
+        assert_eq!(rows[0].0, "plain");
+        assert_eq!(rows[0].1, "text");
+        assert_eq!(rows[1].1, "blob");
+        for (_, _, request_body, response_body) in &rows {
+            assert_eq!(request_body.as_deref(), Some(body.as_str()));
+            assert!(response_body.is_none());
+        }
+
+        // Compressed bodies are still searchable, but the index keeps no copy of the text
+        let hits: i64 = conn
+            .query_row("SELECT COUNT(*) FROM request_logs_fts WHERE request_logs_fts MATCH 'hello'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(hits, 2);
+        let stored: Option<String> = conn
+            .query_row("SELECT request_body FROM request_logs_fts WHERE request_logs_fts MATCH 'hello' LIMIT 1", [], |row| row.get(0))
+            .unwrap();
+        assert!(stored.is_none());
+    }
+}
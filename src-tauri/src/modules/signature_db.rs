@@ -0,0 +1,292 @@
+//! Persistent backing store for `SignatureCache` (`signature_cache.db`).
+//!
+//! Entries are written by a background thread so request handlers never wait on SQLite;
+//! reads happen lazily, only when the in-memory cache misses, through a small pool of reader
+//! connections and off the async worker threads. Expired rows and rows beyond the configured
+//! bound (oldest first) are evicted after every write batch.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const QUEUE_CAPACITY: usize = 1024;
+const MAX_BATCH_SIZE: usize = 128;
+const READER_POOL_SIZE: usize = 4;
+
+enum StoreJob {
+    Put {
+        layer: &'static str,
+        key: String,
+        value: String,
+        stored_at: i64,
+    },
+    /// Delete every persisted entry (ordered after previously queued writes)
+    Clear,
+    Flush(SyncSender<()>),
+}
+
+fn get_db_path() -> Result<PathBuf, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    Ok(data_dir.join("signature_cache.db"))
+}
+
+fn connect_db() -> Result<Connection, String> {
+    let conn = Connection::open(get_db_path()?).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000)
+        .map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS signature_cache (
+            layer TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            stored_at INTEGER NOT NULL,
+            PRIMARY KEY (layer, key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_signature_cache_stored_at ON signature_cache (stored_at);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+type Opener = Box<dyn Fn() -> Result<Connection, String>>;
+
+pub struct SignatureStore {
+    tx: SyncSender<StoreJob>,
+    readers: Vec<Mutex<Connection>>,
+    next_reader: AtomicUsize,
+    ttl: Duration,
+    max_entries: Arc<AtomicUsize>,
+}
+
+impl SignatureStore {
+    /// Open `signature_cache.db` and start the writer thread
+    pub fn open(ttl: Duration, max_entries: usize) -> Result<Self, String> {
+        Self::open_with(Box::new(connect_db), ttl, max_entries)
+    }
+
+    fn open_with(open: Opener, ttl: Duration, max_entries: usize) -> Result<Self, String> {
+        let reader = open()?;
+        init_schema(&reader)?;
+        let mut readers = vec![Mutex::new(reader)];
+        for _ in 1..READER_POOL_SIZE {
+            readers.push(Mutex::new(open()?));
+        }
+        let writer = open()?;
+
+        let (tx, rx) = sync_channel(QUEUE_CAPACITY);
+        let max_entries = Arc::new(AtomicUsize::new(max_entries));
+        let thread_max = max_entries.clone();
+        std::thread::Builder::new()
+            .name("signature-store".to_string())
+            .spawn(move || run_writer(writer, rx, ttl, thread_max))
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            tx,
+            readers,
+            next_reader: AtomicUsize::new(0),
+            ttl,
+            max_entries,
+        })
+    }
+
+    pub fn set_max_entries(&self, max_entries: usize) {
+        self.max_entries.store(max_entries, Ordering::Relaxed);
+    }
+
+    /// Queue an entry for persistence; dropped when the writer is backlogged
+    pub fn put(&self, layer: &'static str, key: &str, value: &str, stored_at: i64) {
+        let job = StoreJob::Put {
+            layer,
+            key: key.to_string(),
+            value: value.to_string(),
+            stored_at,
+        };
+        if self.tx.try_send(job).is_err() {
+            tracing::debug!("[SignatureStore] Write queue full, skipped {} entry", layer);
+        }
+    }
+
+    /// Run `f` on an idle reader connection, off the async worker thread when called from one
+    fn with_reader<T>(&self, f: impl FnOnce(&Connection) -> Option<T>) -> Option<T> {
+        let read = || {
+            let conn = match self.readers.iter().find_map(|reader| reader.try_lock().ok()) {
+                Some(conn) => conn,
+                None => {
+                    let idx = self.next_reader.fetch_add(1, Ordering::Relaxed) % self.readers.len();
+                    self.readers[idx].lock().ok()?
+                }
+            };
+            f(&conn)
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(read)
+            }
+            _ => read(),
+        }
+    }
+
+    /// Look up a non-expired entry, returning its value and `stored_at` (unix seconds)
+    pub fn get(&self, layer: &str, key: &str) -> Option<(String, i64)> {
+        let cutoff = chrono::Utc::now().timestamp() - self.ttl.as_secs() as i64;
+        self.with_reader(|conn| {
+            conn.query_row(
+                "SELECT value, stored_at FROM signature_cache
+                 WHERE layer = ?1 AND key = ?2 AND stored_at >= ?3",
+                params![layer, key, cutoff],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .unwrap_or_else(|e| {
+                tracing::warn!("[SignatureStore] Lookup failed: {}", e);
+                None
+            })
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.with_reader(|conn| {
+            conn.query_row("SELECT COUNT(*) FROM signature_cache", [], |row| row.get::<_, i64>(0))
+                .ok()
+        })
+        .unwrap_or(0) as usize
+    }
+
+    /// Delete all persisted entries, waiting until the writer has done so
+    pub fn clear(&self) {
+        if self.tx.send(StoreJob::Clear).is_ok() {
+            self.flush();
+        }
+    }
+
+    /// Block until everything queued before this call has been written
+    pub fn flush(&self) {
+        let (ack_tx, ack_rx) = sync_channel(1);
+        if self.tx.send(StoreJob::Flush(ack_tx)).is_ok() {
+            let _ = ack_rx.recv();
+        }
+    }
+}
+
+fn run_writer(mut conn: Connection, rx: Receiver<StoreJob>, ttl: Duration, max_entries: Arc<AtomicUsize>) {
+    while let Ok(job) = rx.recv() {
+        let mut batch = vec![job];
+        while batch.len() < MAX_BATCH_SIZE {
+            match rx.try_recv() {
+                Ok(job) => batch.push(job),
+                Err(_) => break,
+            }
+        }
+
+        let mut flushes = Vec::new();
+        let mut puts = Vec::new();
+        for job in batch {
+            match job {
+                StoreJob::Put { layer, key, value, stored_at } => puts.push((layer, key, value, stored_at)),
+                // 清空前写入排在它之前的条目会一并删除
+                StoreJob::Clear => {
+                    puts.clear();
+                    if let Err(e) = conn.execute("DELETE FROM signature_cache", []) {
+                        tracing::error!("[SignatureStore] Failed to clear persisted entries: {}", e);
+                    }
+                }
+                StoreJob::Flush(ack) => flushes.push(ack),
+            }
+        }
+
+        if !puts.is_empty() {
+            let max = max_entries.load(Ordering::Relaxed);
+            if let Err(e) = write_batch(&mut conn, &puts, ttl, max) {
+                tracing::error!("[SignatureStore] Failed to persist {} entries: {}", puts.len(), e);
+            }
+        }
+        for ack in flushes {
+            let _ = ack.send(());
+        }
+    }
+}
+
+fn write_batch(
+    conn: &mut Connection,
+    puts: &[(&'static str, String, String, i64)],
+    ttl: Duration,
+    max_entries: usize,
+) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx
+            .prepare_cached(
+                "INSERT OR REPLACE INTO signature_cache (layer, key, value, stored_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )
+            .map_err(|e| e.to_string())?;
+        for (layer, key, value, stored_at) in puts {
+            stmt.execute(params![layer, key, value, stored_at])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    // TTL 过期淘汰，再按写入时间保留最新的 max_entries 条
+    let cutoff = chrono::Utc::now().timestamp() - ttl.as_secs() as i64;
+    tx.execute("DELETE FROM signature_cache WHERE stored_at < ?1", [cutoff])
+        .map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM signature_cache WHERE rowid IN (
+            SELECT rowid FROM signature_cache ORDER BY stored_at DESC LIMIT -1 OFFSET ?1
+        )",
+        [max_entries as i64],
+    )
+    .map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+pub(crate) fn open_in_memory(name: &str, ttl: Duration, max_entries: usize) -> (SignatureStore, Connection) {
+    let uri = format!("file:{}?mode=memory&cache=shared", name);
+    // Keep one connection alive so the shared in-memory database persists
+    let keep = Connection::open(&uri).unwrap();
+    let store = SignatureStore::open_with(
+        Box::new(move || Connection::open(&uri).map_err(|e| e.to_string())),
+        ttl,
+        max_entries,
+    )
+    .unwrap();
+    (store, keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_and_size_eviction() {
+        let (store, _keep) = open_in_memory("signature_db_eviction", Duration::from_secs(3600), 3);
+        let now = chrono::Utc::now().timestamp();
+
+        store.put("tool", "expired", "sig-expired", now - 7200);
+        for i in 0..5 {
+            store.put("tool", &format!("call_{}", i), &format!("sig-{}", i), now - 10 + i);
+        }
+        store.flush();
+
+        assert_eq!(store.count(), 3);
+        assert!(store.get("tool", "expired").is_none());
+        assert!(store.get("tool", "call_0").is_none());
+        assert_eq!(store.get("tool", "call_4").map(|(v, _)| v).as_deref(), Some("sig-4"));
+        assert!(store.get("session", "call_4").is_none());
+
+        store.put("tool", "call_5", "sig-5", now);
+        store.clear();
+        assert_eq!(store.count(), 0);
+    }
+}
@@ -2,6 +2,9 @@ use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::models::config::TokenStatsRetentionConfig;
+use crate::modules::pricing::{self, TokenUsage};
+
 /// Token usage statistics record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenUsageRecord {
@@ -12,6 +15,13 @@ pub struct TokenUsageRecord {
     pub input_tokens: u32,
     pub output_tokens: u32,
     pub total_tokens: u32,
+    #[serde(default)]
+    pub cached_tokens: u32,
+    #[serde(default)]
+    pub thinking_tokens: u32,
+    /// Equivalent API cost in USD, computed from the price table at record time
+    #[serde(default)]
+    pub cost: f64,
 }
 
 /// Aggregated token statistics
@@ -22,6 +32,8 @@ pub struct TokenStatsAggregated {
     pub total_output_tokens: u64,
     pub total_tokens: u64,
     pub request_count: u64,
+    #[serde(default)]
+    pub total_cost: f64,
 }
 
 /// Per-account token statistics
@@ -32,6 +44,8 @@ pub struct AccountTokenStats {
     pub total_output_tokens: u64,
     pub total_tokens: u64,
     pub request_count: u64,
+    #[serde(default)]
+    pub total_cost: f64,
 }
 
 /// Summary statistics
@@ -42,6 +56,8 @@ pub struct TokenStatsSummary {
     pub total_tokens: u64,
     pub total_requests: u64,
     pub unique_accounts: u64,
+    #[serde(default)]
+    pub total_cost: f64,
 }
 
 /// Per-model token statistics
@@ -52,6 +68,8 @@ pub struct ModelTokenStats {
     pub total_output_tokens: u64,
     pub total_tokens: u64,
     pub request_count: u64,
+    #[serde(default)]
+    pub total_cost: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,12 +85,41 @@ pub struct AccountTrendPoint {
     pub account_data: std::collections::HashMap<String, u64>,
 }
 
+/// Per-dimension token statistics (protocol, client key, ...)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupedTokenStats {
+    pub group: String,
+    pub total_input_tokens: u64,
+    pub total_output_tokens: u64,
+    pub total_tokens: u64,
+    pub request_count: u64,
+    pub total_cost: f64,
+}
+
+/// Rows removed or folded by a maintenance pass
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RollupMaintenanceReport {
+    pub raw_deleted: usize,
+    pub hourly_compacted: usize,
+    pub daily_compacted: usize,
+    pub monthly_deleted: usize,
+}
+
+const HOUR_FORMAT: &str = "%Y-%m-%d %H:00";
+const DAY_FORMAT: &str = "%Y-%m-%d";
+const MONTH_FORMAT: &str = "%Y-%m";
+
+/// Rows at every resolution that fall inside a range; ?1/?2/?3 are the hour/day/month cutoffs
+const RANGE_FILTER: &str = "((resolution = 'hour' AND bucket >= ?1)
+            OR (resolution = 'day' AND bucket >= ?2)
+            OR (resolution = 'month' AND bucket >= ?3))";
+
 fn get_db_path() -> Result<PathBuf, String> {
     let data_dir = crate::modules::account::get_data_dir()?;
     Ok(data_dir.join("token_stats.db"))
 }
 
-fn connect_db() -> Result<Connection, String> {
+pub(crate) fn connect_db() -> Result<Connection, String> {
     let db_path = get_db_path()?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
@@ -89,8 +136,11 @@ fn connect_db() -> Result<Connection, String> {
 
 /// Initialize the token stats database
 pub fn init_db() -> Result<(), String> {
-    let conn = connect_db()?;
+    let mut conn = connect_db()?;
+    init_schema(&mut conn)
+}
 
+pub(crate) fn init_schema(conn: &mut Connection) -> Result<(), String> {
     // Create main usage table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS token_usage (
@@ -119,85 +169,330 @@ pub fn init_db() -> Result<(), String> {
     )
     .map_err(|e| e.to_string())?;
 
-    // Create hourly aggregation table for fast queries
+    // Cost accounting and dimension columns (ignore errors if they exist)
+    let _ = conn.execute("ALTER TABLE token_usage ADD COLUMN cached_tokens INTEGER NOT NULL DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE token_usage ADD COLUMN thinking_tokens INTEGER NOT NULL DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE token_usage ADD COLUMN cost REAL NOT NULL DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE token_usage ADD COLUMN protocol TEXT NOT NULL DEFAULT ''", []);
+    let _ = conn.execute("ALTER TABLE token_usage ADD COLUMN client_key TEXT NOT NULL DEFAULT ''", []);
+
+    // Multi-resolution rollups: every request lands in an 'hour' row, which is later
+    // folded into 'day' and then 'month' rows. A request is counted at exactly one
+    // resolution at any time, so unions across resolutions never double count.
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS token_stats_hourly (
-            hour_bucket TEXT NOT NULL,
+        "CREATE TABLE IF NOT EXISTS token_rollups (
+            resolution TEXT NOT NULL,
+            bucket TEXT NOT NULL,
             account_email TEXT NOT NULL,
-            total_input_tokens INTEGER NOT NULL DEFAULT 0,
-            total_output_tokens INTEGER NOT NULL DEFAULT 0,
+            model TEXT NOT NULL,
+            protocol TEXT NOT NULL DEFAULT '',
+            client_key TEXT NOT NULL DEFAULT '',
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
             total_tokens INTEGER NOT NULL DEFAULT 0,
+            cached_tokens INTEGER NOT NULL DEFAULT 0,
+            thinking_tokens INTEGER NOT NULL DEFAULT 0,
             request_count INTEGER NOT NULL DEFAULT 0,
-            PRIMARY KEY (hour_bucket, account_email)
+            total_cost REAL NOT NULL DEFAULT 0,
+            PRIMARY KEY (resolution, bucket, account_email, model, protocol, client_key)
         )",
         [],
     )
     .map_err(|e| e.to_string())?;
 
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_rollup_bucket ON token_rollups (resolution, bucket)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    migrate_legacy_hourly(conn)?;
+    pricing::init_table(conn)?;
+
+    Ok(())
+}
+
+/// Move data from the old `token_stats_hourly` table (account-only) into `token_rollups`.
+/// Hours still covered by raw usage are rebuilt with full dimensions; older hours keep
+/// their account totals under model "unknown".
+fn migrate_legacy_hourly(conn: &mut Connection) -> Result<(), String> {
+    let legacy_exists: bool = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'token_stats_hourly'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|e| e.to_string())?
+        > 0;
+    if !legacy_exists {
+        return Ok(());
+    }
+
+    let _ = conn.execute("ALTER TABLE token_stats_hourly ADD COLUMN total_cost REAL NOT NULL DEFAULT 0", []);
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO token_rollups (resolution, bucket, account_email, model, protocol, client_key,
+            input_tokens, output_tokens, total_tokens, cached_tokens, thinking_tokens, request_count, total_cost)
+         SELECT 'hour', strftime('%Y-%m-%d %H:00', datetime(timestamp, 'unixepoch')) as hour_bucket,
+            account_email, model, protocol, client_key,
+            SUM(input_tokens), SUM(output_tokens), SUM(total_tokens), SUM(cached_tokens),
+            SUM(thinking_tokens), COUNT(*), SUM(cost)
+         FROM token_usage
+         WHERE true
+         GROUP BY hour_bucket, account_email, model, protocol, client_key
+         ON CONFLICT DO NOTHING",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO token_rollups (resolution, bucket, account_email, model,
+            input_tokens, output_tokens, total_tokens, request_count, total_cost)
+         SELECT 'hour', hour_bucket, account_email, 'unknown',
+            total_input_tokens, total_output_tokens, total_tokens, request_count, total_cost
+         FROM token_stats_hourly
+         WHERE hour_bucket < COALESCE(
+            (SELECT strftime('%Y-%m-%d %H:00', datetime(MIN(timestamp), 'unixepoch')) FROM token_usage),
+            '9999')
+         ON CONFLICT DO NOTHING",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute("DROP TABLE token_stats_hourly", [])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    tracing::info!("[TokenStats] Migrated legacy hourly stats into multi-resolution rollups");
+    Ok(())
+}
+
+/// Token usage of a single request, queued for persistence
+#[derive(Debug, Clone)]
+pub struct UsageRecord {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub account_email: String,
+    pub model: String,
+    pub protocol: String,
+    pub client_key: String,
+    pub usage: TokenUsage,
+}
+
+/// Record a batch of usage records in a single transaction, pricing each against the (cached) model price table
+pub(crate) fn record_usage_batch_with_conn(
+    conn: &mut Connection,
+    prices: &mut pricing::PriceCache,
+    records: &[UsageRecord],
+) -> Result<(), String> {
+    let prices = prices.get(conn)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for record in records {
+        insert_usage(&tx, prices, record)?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
-/// Record token usage from a request
-pub fn record_usage(
-    account_email: &str,
-    model: &str,
-    input_tokens: u32,
-    output_tokens: u32,
+fn insert_usage(
+    tx: &rusqlite::Transaction,
+    prices: &[pricing::ModelPrice],
+    record: &UsageRecord,
 ) -> Result<(), String> {
-    let conn = connect_db()?;
-    let timestamp = chrono::Utc::now().timestamp();
+    let usage = &record.usage;
+    let (account_email, model, protocol, client_key) =
+        (&record.account_email, &record.model, &record.protocol, &record.client_key);
+    let timestamp = record.timestamp.timestamp();
+    let input_tokens = usage.input_tokens;
+    let output_tokens = usage.output_tokens;
     let total_tokens = input_tokens + output_tokens;
+    let cost = pricing::select_price(prices, model, timestamp)
+        .map(|p| p.cost(usage))
+        .unwrap_or(0.0);
 
     // Insert into raw usage table
-    conn.execute(
-        "INSERT INTO token_usage (timestamp, account_email, model, input_tokens, output_tokens, total_tokens)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![timestamp, account_email, model, input_tokens, output_tokens, total_tokens],
+    tx.execute(
+        "INSERT INTO token_usage (timestamp, account_email, model, input_tokens, output_tokens, total_tokens,
+            cached_tokens, thinking_tokens, cost, protocol, client_key)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            timestamp,
+            account_email,
+            model,
+            input_tokens,
+            output_tokens,
+            total_tokens,
+            usage.cached_tokens,
+            usage.thinking_tokens,
+            cost,
+            protocol,
+            client_key
+        ],
     ).map_err(|e| e.to_string())?;
 
-    let hour_bucket = chrono::Utc::now().format("%Y-%m-%d %H:00").to_string();
-    conn.execute(
-        "INSERT INTO token_stats_hourly (hour_bucket, account_email, total_input_tokens, total_output_tokens, total_tokens, request_count)
-         VALUES (?1, ?2, ?3, ?4, ?5, 1)
-         ON CONFLICT(hour_bucket, account_email) DO UPDATE SET
-            total_input_tokens = total_input_tokens + ?3,
-            total_output_tokens = total_output_tokens + ?4,
-            total_tokens = total_tokens + ?5,
-            request_count = request_count + 1",
-        params![hour_bucket, account_email, input_tokens, output_tokens, total_tokens],
+    let hour_bucket = record.timestamp.format(HOUR_FORMAT).to_string();
+    tx.execute(
+        "INSERT INTO token_rollups (resolution, bucket, account_email, model, protocol, client_key,
+            input_tokens, output_tokens, total_tokens, cached_tokens, thinking_tokens, request_count, total_cost)
+         VALUES ('hour', ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 1, ?11)
+         ON CONFLICT(resolution, bucket, account_email, model, protocol, client_key) DO UPDATE SET
+            input_tokens = input_tokens + excluded.input_tokens,
+            output_tokens = output_tokens + excluded.output_tokens,
+            total_tokens = total_tokens + excluded.total_tokens,
+            cached_tokens = cached_tokens + excluded.cached_tokens,
+            thinking_tokens = thinking_tokens + excluded.thinking_tokens,
+            request_count = request_count + excluded.request_count,
+            total_cost = total_cost + excluded.total_cost",
+        params![
+            hour_bucket,
+            account_email,
+            model,
+            protocol,
+            client_key,
+            input_tokens,
+            output_tokens,
+            total_tokens,
+            usage.cached_tokens,
+            usage.thinking_tokens,
+            cost
+        ],
     ).map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
-/// Get hourly aggregated stats for a time range
-pub fn get_hourly_stats(hours: i64) -> Result<Vec<TokenStatsAggregated>, String> {
-    let conn = connect_db()?;
+/// Fold rows of `from` resolution older than `cutoff` into `to` resolution buckets
+fn compact_resolution(
+    tx: &rusqlite::Transaction,
+    from: &str,
+    to: &str,
+    bucket_len: usize,
+    cutoff: &str,
+) -> Result<usize, String> {
+    tx.execute(
+        "INSERT INTO token_rollups (resolution, bucket, account_email, model, protocol, client_key,
+            input_tokens, output_tokens, total_tokens, cached_tokens, thinking_tokens, request_count, total_cost)
+         SELECT ?2, substr(bucket, 1, ?3) as target_bucket, account_email, model, protocol, client_key,
+            SUM(input_tokens), SUM(output_tokens), SUM(total_tokens), SUM(cached_tokens),
+            SUM(thinking_tokens), SUM(request_count), SUM(total_cost)
+         FROM token_rollups
+         WHERE resolution = ?1 AND bucket < ?4
+         GROUP BY target_bucket, account_email, model, protocol, client_key
+         ON CONFLICT(resolution, bucket, account_email, model, protocol, client_key) DO UPDATE SET
+            input_tokens = input_tokens + excluded.input_tokens,
+            output_tokens = output_tokens + excluded.output_tokens,
+            total_tokens = total_tokens + excluded.total_tokens,
+            cached_tokens = cached_tokens + excluded.cached_tokens,
+            thinking_tokens = thinking_tokens + excluded.thinking_tokens,
+            request_count = request_count + excluded.request_count,
+            total_cost = total_cost + excluded.total_cost",
+        params![from, to, bucket_len, cutoff],
+    )
+    .map_err(|e| e.to_string())?;
+
+    tx.execute(
+        "DELETE FROM token_rollups WHERE resolution = ?1 AND bucket < ?2",
+        params![from, cutoff],
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn run_maintenance_with_conn(
+    conn: &mut Connection,
+    policy: &TokenStatsRetentionConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<RollupMaintenanceReport, String> {
+    let mut report = RollupMaintenanceReport::default();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    if policy.raw_retention_days > 0 {
+        let cutoff = (now - chrono::Duration::days(policy.raw_retention_days)).timestamp();
+        report.raw_deleted = tx
+            .execute("DELETE FROM token_usage WHERE timestamp < ?1", [cutoff])
+            .map_err(|e| e.to_string())?;
+    }
+
+    // Only whole days/months are folded, so cutoffs are the start of the bucket
+    let hour_cutoff = (now - chrono::Duration::days(policy.hourly_retention_days.max(1)))
+        .format(DAY_FORMAT)
+        .to_string();
+    report.hourly_compacted = compact_resolution(&tx, "hour", "day", 10, &hour_cutoff)?;
+
+    let day_cutoff = (now - chrono::Duration::days(policy.daily_retention_days.max(policy.hourly_retention_days).max(1)))
+        .format(MONTH_FORMAT)
+        .to_string();
+    report.daily_compacted = compact_resolution(&tx, "day", "month", 7, &day_cutoff)?;
+
+    if policy.monthly_retention_months > 0 {
+        let month_cutoff = (now - chrono::Duration::days(policy.monthly_retention_months * 31))
+            .format(MONTH_FORMAT)
+            .to_string();
+        report.monthly_deleted = tx
+            .execute(
+                "DELETE FROM token_rollups WHERE resolution = 'month' AND bucket < ?1",
+                [month_cutoff],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+/// Downsample rollups and apply retention limits
+pub fn run_rollup_maintenance(policy: &TokenStatsRetentionConfig) -> Result<RollupMaintenanceReport, String> {
+    let mut conn = connect_db()?;
+    let report = run_maintenance_with_conn(&mut conn, policy, chrono::Utc::now())?;
+
+    if report.raw_deleted > 0 || report.monthly_deleted > 0 {
+        conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
+    }
+    Ok(report)
+}
+
+/// Start the background downsampling task (runs hourly, re-reading the retention policy)
+pub fn start_rollup_maintenance_task() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let policy = crate::modules::config::load_app_config()
+                .map(|c| c.token_stats)
+                .unwrap_or_default();
+            match tokio::task::spawn_blocking(move || run_rollup_maintenance(&policy)).await {
+                Ok(Ok(report)) => {
+                    if report.hourly_compacted + report.daily_compacted + report.raw_deleted + report.monthly_deleted > 0 {
+                        tracing::info!("[TokenStats] Rollup maintenance: {:?}", report);
+                    }
+                }
+                Ok(Err(e)) => tracing::error!("[TokenStats] Rollup maintenance failed: {}", e),
+                Err(e) => tracing::error!("[TokenStats] Rollup maintenance task panicked: {}", e),
+            }
+        }
+    });
+}
+
+/// Hour/day/month cutoff strings for a range ending now
+fn range_cutoffs(hours: i64) -> [String; 3] {
     let cutoff = chrono::Utc::now() - chrono::Duration::hours(hours);
-    let cutoff_bucket = cutoff.format("%Y-%m-%d %H:00").to_string();
+    [
+        cutoff.format(HOUR_FORMAT).to_string(),
+        cutoff.format(DAY_FORMAT).to_string(),
+        cutoff.format(MONTH_FORMAT).to_string(),
+    ]
+}
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT hour_bucket, 
-                SUM(total_input_tokens) as input, 
-                SUM(total_output_tokens) as output,
-                SUM(total_tokens) as total,
-                SUM(request_count) as count
-         FROM token_stats_hourly 
-         WHERE hour_bucket >= ?1
-         GROUP BY hour_bucket
-         ORDER BY hour_bucket ASC",
-        )
-        .map_err(|e| e.to_string())?;
+fn query_aggregated(sql: &str, params: &[&dyn rusqlite::ToSql]) -> Result<Vec<TokenStatsAggregated>, String> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
 
     let rows = stmt
-        .query_map([cutoff_bucket], |row| {
+        .query_map(params, |row| {
             Ok(TokenStatsAggregated {
                 period: row.get(0)?,
                 total_input_tokens: row.get(1)?,
                 total_output_tokens: row.get(2)?,
                 total_tokens: row.get(3)?,
                 request_count: row.get(4)?,
+                total_cost: row.get(5)?,
             })
         })
         .map_err(|e| e.to_string())?;
@@ -209,73 +504,106 @@ pub fn get_hourly_stats(hours: i64) -> Result<Vec<TokenStatsAggregated>, String>
     Ok(result)
 }
 
-/// Get daily aggregated stats for a time range
+/// Get hourly aggregated stats for a time range (hour rollups only)
+pub fn get_hourly_stats(hours: i64) -> Result<Vec<TokenStatsAggregated>, String> {
+    let [cutoff_bucket, _, _] = range_cutoffs(hours);
+    query_aggregated(
+        "SELECT bucket,
+                SUM(input_tokens) as input,
+                SUM(output_tokens) as output,
+                SUM(total_tokens) as total,
+                SUM(request_count) as count,
+                SUM(total_cost) as cost
+         FROM token_rollups
+         WHERE resolution = 'hour' AND bucket >= ?1
+         GROUP BY bucket
+         ORDER BY bucket ASC",
+        &[&cutoff_bucket],
+    )
+}
+
+/// Get daily aggregated stats for a time range (hour + day rollups)
 pub fn get_daily_stats(days: i64) -> Result<Vec<TokenStatsAggregated>, String> {
-    let conn = connect_db()?;
-    let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
-    let cutoff_bucket = cutoff.format("%Y-%m-%d").to_string();
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT substr(hour_bucket, 1, 10) as day_bucket, 
-                SUM(total_input_tokens) as input, 
-                SUM(total_output_tokens) as output,
+    let [_, cutoff_bucket, _] = range_cutoffs(days * 24);
+    query_aggregated(
+        "SELECT substr(bucket, 1, 10) as day_bucket,
+                SUM(input_tokens) as input,
+                SUM(output_tokens) as output,
                 SUM(total_tokens) as total,
-                SUM(request_count) as count
-         FROM token_stats_hourly 
-         WHERE substr(hour_bucket, 1, 10) >= ?1
+                SUM(request_count) as count,
+                SUM(total_cost) as cost
+         FROM token_rollups
+         WHERE resolution IN ('hour', 'day') AND bucket >= ?1
          GROUP BY day_bucket
          ORDER BY day_bucket ASC",
-        )
-        .map_err(|e| e.to_string())?;
+        &[&cutoff_bucket],
+    )
+}
 
-    let rows = stmt
-        .query_map([cutoff_bucket], |row| {
-            Ok(TokenStatsAggregated {
-                period: row.get(0)?,
-                total_input_tokens: row.get(1)?,
-                total_output_tokens: row.get(2)?,
-                total_tokens: row.get(3)?,
-                request_count: row.get(4)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+/// Get weekly aggregated stats (hour + day rollups)
+pub fn get_weekly_stats(weeks: i64) -> Result<Vec<TokenStatsAggregated>, String> {
+    let [_, cutoff_bucket, _] = range_cutoffs(weeks * 7 * 24);
+    query_aggregated(
+        "SELECT strftime('%Y-W%W', substr(bucket, 1, 10)) as week_bucket,
+                SUM(input_tokens) as input,
+                SUM(output_tokens) as output,
+                SUM(total_tokens) as total,
+                SUM(request_count) as count,
+                SUM(total_cost) as cost
+         FROM token_rollups
+         WHERE resolution IN ('hour', 'day') AND bucket >= ?1
+         GROUP BY week_bucket
+         ORDER BY week_bucket ASC",
+        &[&cutoff_bucket],
+    )
+}
 
-    let mut result = Vec::new();
-    for row in rows {
-        result.push(row.map_err(|e| e.to_string())?);
-    }
-    Ok(result)
+/// Get monthly aggregated stats (all resolutions)
+pub fn get_monthly_stats(months: i64) -> Result<Vec<TokenStatsAggregated>, String> {
+    let [_, _, cutoff_bucket] = range_cutoffs(months * 31 * 24);
+    query_aggregated(
+        "SELECT substr(bucket, 1, 7) as month_bucket,
+                SUM(input_tokens) as input,
+                SUM(output_tokens) as output,
+                SUM(total_tokens) as total,
+                SUM(request_count) as count,
+                SUM(total_cost) as cost
+         FROM token_rollups
+         WHERE bucket >= ?1
+         GROUP BY month_bucket
+         ORDER BY month_bucket ASC",
+        &[&cutoff_bucket],
+    )
 }
 
-/// Get weekly aggregated stats
-pub fn get_weekly_stats(weeks: i64) -> Result<Vec<TokenStatsAggregated>, String> {
+/// Aggregate a rollup column over a time range, across all resolutions
+fn get_grouped_stats(column: &str, hours: i64) -> Result<Vec<GroupedTokenStats>, String> {
     let conn = connect_db()?;
-    let cutoff = chrono::Utc::now() - chrono::Duration::weeks(weeks);
-    let cutoff_timestamp = cutoff.timestamp();
+    let [hour_cutoff, day_cutoff, month_cutoff] = range_cutoffs(hours);
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT strftime('%Y-W%W', datetime(timestamp, 'unixepoch')) as week_bucket,
-                SUM(input_tokens) as input, 
+    let sql = format!(
+        "SELECT {column},
+                SUM(input_tokens) as input,
                 SUM(output_tokens) as output,
                 SUM(total_tokens) as total,
-                COUNT(*) as count
-         FROM token_usage 
-         WHERE timestamp >= ?1
-         GROUP BY week_bucket
-         ORDER BY week_bucket ASC",
-        )
-        .map_err(|e| e.to_string())?;
+                SUM(request_count) as count,
+                SUM(total_cost) as cost
+         FROM token_rollups
+         WHERE {RANGE_FILTER}
+         GROUP BY {column}
+         ORDER BY total DESC"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
 
     let rows = stmt
-        .query_map([cutoff_timestamp], |row| {
-            Ok(TokenStatsAggregated {
-                period: row.get(0)?,
+        .query_map(params![hour_cutoff, day_cutoff, month_cutoff], |row| {
+            Ok(GroupedTokenStats {
+                group: row.get(0)?,
                 total_input_tokens: row.get(1)?,
                 total_output_tokens: row.get(2)?,
                 total_tokens: row.get(3)?,
                 request_count: row.get(4)?,
+                total_cost: row.get(5)?,
             })
         })
         .map_err(|e| e.to_string())?;
@@ -289,68 +617,62 @@ pub fn get_weekly_stats(weeks: i64) -> Result<Vec<TokenStatsAggregated>, String>
 
 /// Get per-account statistics for a time range
 pub fn get_account_stats(hours: i64) -> Result<Vec<AccountTokenStats>, String> {
-    let conn = connect_db()?;
-    let cutoff = chrono::Utc::now() - chrono::Duration::hours(hours);
-    let cutoff_bucket = cutoff.format("%Y-%m-%d %H:00").to_string();
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT account_email,
-                SUM(total_input_tokens) as input, 
-                SUM(total_output_tokens) as output,
-                SUM(total_tokens) as total,
-                SUM(request_count) as count
-         FROM token_stats_hourly 
-         WHERE hour_bucket >= ?1
-         GROUP BY account_email
-         ORDER BY total DESC",
-        )
-        .map_err(|e| e.to_string())?;
+    Ok(get_grouped_stats("account_email", hours)?
+        .into_iter()
+        .map(|s| AccountTokenStats {
+            account_email: s.group,
+            total_input_tokens: s.total_input_tokens,
+            total_output_tokens: s.total_output_tokens,
+            total_tokens: s.total_tokens,
+            request_count: s.request_count,
+            total_cost: s.total_cost,
+        })
+        .collect())
+}
 
-    let rows = stmt
-        .query_map([cutoff_bucket], |row| {
-            Ok(AccountTokenStats {
-                account_email: row.get(0)?,
-                total_input_tokens: row.get(1)?,
-                total_output_tokens: row.get(2)?,
-                total_tokens: row.get(3)?,
-                request_count: row.get(4)?,
-            })
+pub fn get_model_stats(hours: i64) -> Result<Vec<ModelTokenStats>, String> {
+    Ok(get_grouped_stats("model", hours)?
+        .into_iter()
+        .map(|s| ModelTokenStats {
+            model: s.group,
+            total_input_tokens: s.total_input_tokens,
+            total_output_tokens: s.total_output_tokens,
+            total_tokens: s.total_tokens,
+            request_count: s.request_count,
+            total_cost: s.total_cost,
         })
-        .map_err(|e| e.to_string())?;
+        .collect())
+}
 
-    let mut result = Vec::new();
-    for row in rows {
-        result.push(row.map_err(|e| e.to_string())?);
-    }
-    Ok(result)
+/// Get per-protocol statistics (openai / anthropic / gemini) for a time range
+pub fn get_protocol_stats(hours: i64) -> Result<Vec<GroupedTokenStats>, String> {
+    get_grouped_stats("protocol", hours)
+}
+
+/// Get per-client-key statistics for a time range (keys are stored masked)
+pub fn get_client_key_stats(hours: i64) -> Result<Vec<GroupedTokenStats>, String> {
+    get_grouped_stats("client_key", hours)
 }
 
 /// Get summary statistics for a time range
 pub fn get_summary_stats(hours: i64) -> Result<TokenStatsSummary, String> {
     let conn = connect_db()?;
-    let cutoff = chrono::Utc::now() - chrono::Duration::hours(hours);
-    let cutoff_bucket = cutoff.format("%Y-%m-%d %H:00").to_string();
+    let [hour_cutoff, day_cutoff, month_cutoff] = range_cutoffs(hours);
 
-    let (total_input, total_output, total, requests): (u64, u64, u64, u64) = conn
-        .query_row(
-            "SELECT COALESCE(SUM(total_input_tokens), 0),
-                COALESCE(SUM(total_output_tokens), 0),
+    let sql = format!(
+        "SELECT COALESCE(SUM(input_tokens), 0),
+                COALESCE(SUM(output_tokens), 0),
                 COALESCE(SUM(total_tokens), 0),
-                COALESCE(SUM(request_count), 0)
-         FROM token_stats_hourly 
-         WHERE hour_bucket >= ?1",
-            [&cutoff_bucket],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
-        )
-        .map_err(|e| e.to_string())?;
-
-    let unique_accounts: u64 = conn
-        .query_row(
-            "SELECT COUNT(DISTINCT account_email) FROM token_stats_hourly WHERE hour_bucket >= ?1",
-            [&cutoff_bucket],
-            |row| row.get(0),
-        )
+                COALESCE(SUM(request_count), 0),
+                COALESCE(SUM(total_cost), 0.0),
+                COUNT(DISTINCT account_email)
+         FROM token_rollups
+         WHERE {RANGE_FILTER}"
+    );
+    let (total_input, total_output, total, requests, total_cost, unique_accounts): (u64, u64, u64, u64, f64, u64) = conn
+        .query_row(&sql, params![hour_cutoff, day_cutoff, month_cutoff], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+        })
         .map_err(|e| e.to_string())?;
 
     Ok(TokenStatsSummary {
@@ -359,10 +681,11 @@ pub fn get_summary_stats(hours: i64) -> Result<TokenStatsSummary, String> {
         total_tokens: total,
         total_requests: requests,
         unique_accounts,
+        total_cost,
     })
 }
 
-/// Clean up old data (keep last N days of raw data)
+/// Clean up old raw data (keep last N days); rollups are governed by the retention policy
 pub fn cleanup_old_data(days: i64) -> Result<usize, String> {
     let conn = connect_db()?;
     let cutoff = chrono::Utc::now().timestamp() - (days * 24 * 3600);
@@ -371,74 +694,36 @@ pub fn cleanup_old_data(days: i64) -> Result<usize, String> {
         .execute("DELETE FROM token_usage WHERE timestamp < ?1", [cutoff])
         .map_err(|e| e.to_string())?;
 
-    // Keep hourly aggregations for longer (90 days)
-    let cutoff_bucket = (chrono::Utc::now() - chrono::Duration::days(90))
-        .format("%Y-%m-%d %H:00")
-        .to_string();
-    conn.execute(
-        "DELETE FROM token_stats_hourly WHERE hour_bucket < ?1",
-        [cutoff_bucket],
-    )
-    .map_err(|e| e.to_string())?;
-
     conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
 
     Ok(deleted)
 }
 
-pub fn get_model_stats(hours: i64) -> Result<Vec<ModelTokenStats>, String> {
+/// Trend of total tokens per (period, column); hourly uses hour rollups, daily adds day rollups
+fn get_trend(column: &str, daily: bool, hours: i64) -> Result<std::collections::BTreeMap<String, std::collections::HashMap<String, u64>>, String> {
     let conn = connect_db()?;
-    let cutoff = chrono::Utc::now().timestamp() - (hours * 3600);
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT model,
-                SUM(input_tokens) as input,
-                SUM(output_tokens) as output,
-                SUM(total_tokens) as total,
-                COUNT(*) as count
-         FROM token_usage
-         WHERE timestamp >= ?1
-         GROUP BY model
-         ORDER BY total DESC",
+    let [hour_cutoff, day_cutoff, _] = range_cutoffs(hours);
+
+    let sql = if daily {
+        format!(
+            "SELECT substr(bucket, 1, 10) as period, {column}, SUM(total_tokens) as total
+             FROM token_rollups
+             WHERE resolution IN ('hour', 'day') AND bucket >= ?1
+             GROUP BY period, {column}
+             ORDER BY period ASC"
         )
-        .map_err(|e| e.to_string())?;
-
-    let rows = stmt
-        .query_map([cutoff], |row| {
-            Ok(ModelTokenStats {
-                model: row.get(0)?,
-                total_input_tokens: row.get(1)?,
-                total_output_tokens: row.get(2)?,
-                total_tokens: row.get(3)?,
-                request_count: row.get(4)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    let mut result = Vec::new();
-    for row in rows {
-        result.push(row.map_err(|e| e.to_string())?);
-    }
-    Ok(result)
-}
-
-pub fn get_model_trend_hourly(hours: i64) -> Result<Vec<ModelTrendPoint>, String> {
-    let conn = connect_db()?;
-    let cutoff = chrono::Utc::now().timestamp() - (hours * 3600);
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT strftime('%Y-%m-%d %H:00', datetime(timestamp, 'unixepoch')) as hour_bucket,
-                model,
-                SUM(total_tokens) as total
-         FROM token_usage
-         WHERE timestamp >= ?1
-         GROUP BY hour_bucket, model
-         ORDER BY hour_bucket ASC",
+    } else {
+        format!(
+            "SELECT bucket as period, {column}, SUM(total_tokens) as total
+             FROM token_rollups
+             WHERE resolution = 'hour' AND bucket >= ?1
+             GROUP BY period, {column}
+             ORDER BY period ASC"
         )
-        .map_err(|e| e.to_string())?;
+    };
+    let cutoff = if daily { day_cutoff } else { hour_cutoff };
 
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
     let mut trend_map: std::collections::BTreeMap<String, std::collections::HashMap<String, u64>> =
         std::collections::BTreeMap::new();
 
@@ -453,134 +738,126 @@ pub fn get_model_trend_hourly(hours: i64) -> Result<Vec<ModelTrendPoint>, String
         .map_err(|e| e.to_string())?;
 
     for row in rows {
-        let (period, model, total) = row.map_err(|e| e.to_string())?;
-        trend_map.entry(period).or_default().insert(model, total);
+        let (period, key, total) = row.map_err(|e| e.to_string())?;
+        trend_map.entry(period).or_default().insert(key, total);
     }
+    Ok(trend_map)
+}
 
-    Ok(trend_map
+pub fn get_model_trend_hourly(hours: i64) -> Result<Vec<ModelTrendPoint>, String> {
+    Ok(get_trend("model", false, hours)?
         .into_iter()
         .map(|(period, model_data)| ModelTrendPoint { period, model_data })
         .collect())
 }
 
 pub fn get_model_trend_daily(days: i64) -> Result<Vec<ModelTrendPoint>, String> {
-    let conn = connect_db()?;
-    let cutoff = chrono::Utc::now().timestamp() - (days * 24 * 3600);
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT strftime('%Y-%m-%d', datetime(timestamp, 'unixepoch')) as day_bucket,
-                model,
-                SUM(total_tokens) as total
-         FROM token_usage
-         WHERE timestamp >= ?1
-         GROUP BY day_bucket, model
-         ORDER BY day_bucket ASC",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let mut trend_map: std::collections::BTreeMap<String, std::collections::HashMap<String, u64>> =
-        std::collections::BTreeMap::new();
-
-    let rows = stmt
-        .query_map([cutoff], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, u64>(2)?,
-            ))
-        })
-        .map_err(|e| e.to_string())?;
-
-    for row in rows {
-        let (period, model, total) = row.map_err(|e| e.to_string())?;
-        trend_map.entry(period).or_default().insert(model, total);
-    }
-
-    Ok(trend_map
+    Ok(get_trend("model", true, days * 24)?
         .into_iter()
         .map(|(period, model_data)| ModelTrendPoint { period, model_data })
         .collect())
 }
 
 pub fn get_account_trend_hourly(hours: i64) -> Result<Vec<AccountTrendPoint>, String> {
-    let conn = connect_db()?;
-    let cutoff = chrono::Utc::now().timestamp() - (hours * 3600);
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT strftime('%Y-%m-%d %H:00', datetime(timestamp, 'unixepoch')) as hour_bucket,
-                account_email,
-                SUM(total_tokens) as total
-         FROM token_usage
-         WHERE timestamp >= ?1
-         GROUP BY hour_bucket, account_email
-         ORDER BY hour_bucket ASC",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let mut trend_map: std::collections::BTreeMap<String, std::collections::HashMap<String, u64>> =
-        std::collections::BTreeMap::new();
-
-    let rows = stmt
-        .query_map([cutoff], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, u64>(2)?,
-            ))
-        })
-        .map_err(|e| e.to_string())?;
-
-    for row in rows {
-        let (period, account, total) = row.map_err(|e| e.to_string())?;
-        trend_map.entry(period).or_default().insert(account, total);
-    }
-
-    Ok(trend_map
+    Ok(get_trend("account_email", false, hours)?
         .into_iter()
         .map(|(period, account_data)| AccountTrendPoint { period, account_data })
         .collect())
 }
 
 pub fn get_account_trend_daily(days: i64) -> Result<Vec<AccountTrendPoint>, String> {
-    let conn = connect_db()?;
-    let cutoff = chrono::Utc::now().timestamp() - (days * 24 * 3600);
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT strftime('%Y-%m-%d', datetime(timestamp, 'unixepoch')) as day_bucket,
-                account_email,
-                SUM(total_tokens) as total
-         FROM token_usage
-         WHERE timestamp >= ?1
-         GROUP BY day_bucket, account_email
-         ORDER BY day_bucket ASC",
-        )
-        .map_err(|e| e.to_string())?;
+    Ok(get_trend("account_email", true, days * 24)?
+        .into_iter()
+        .map(|(period, account_data)| AccountTrendPoint { period, account_data })
+        .collect())
+}
 
-    let mut trend_map: std::collections::BTreeMap<String, std::collections::HashMap<String, u64>> =
-        std::collections::BTreeMap::new();
+/// Export a cost report as CSV
+/// group_by: "account" | "model" | "protocol" | "client_key" | "hourly" | "daily" | "monthly"
+pub fn export_cost_report_csv(hours: i64, group_by: &str) -> Result<String, String> {
+    let header = "input_tokens,output_tokens,total_tokens,request_count,cost_usd";
+    let mut lines = Vec::new();
+
+    match group_by {
+        "account" => {
+            lines.push(format!("account_email,{}", header));
+            for s in get_account_stats(hours)? {
+                lines.push(format!(
+                    "{},{},{},{},{},{:.6}",
+                    csv_escape(&s.account_email),
+                    s.total_input_tokens,
+                    s.total_output_tokens,
+                    s.total_tokens,
+                    s.request_count,
+                    s.total_cost
+                ));
+            }
+        }
+        "model" => {
+            lines.push(format!("model,{}", header));
+            for s in get_model_stats(hours)? {
+                lines.push(format!(
+                    "{},{},{},{},{},{:.6}",
+                    csv_escape(&s.model),
+                    s.total_input_tokens,
+                    s.total_output_tokens,
+                    s.total_tokens,
+                    s.request_count,
+                    s.total_cost
+                ));
+            }
+        }
+        "protocol" | "client_key" => {
+            let stats = if group_by == "protocol" {
+                get_protocol_stats(hours)?
+            } else {
+                get_client_key_stats(hours)?
+            };
+            lines.push(format!("{},{}", group_by, header));
+            for s in stats {
+                lines.push(format!(
+                    "{},{},{},{},{},{:.6}",
+                    csv_escape(&s.group),
+                    s.total_input_tokens,
+                    s.total_output_tokens,
+                    s.total_tokens,
+                    s.request_count,
+                    s.total_cost
+                ));
+            }
+        }
+        "hourly" | "daily" | "monthly" => {
+            let stats = match group_by {
+                "hourly" => get_hourly_stats(hours)?,
+                "daily" => get_daily_stats((hours + 23) / 24)?,
+                _ => get_monthly_stats((hours + 24 * 31 - 1) / (24 * 31))?,
+            };
+            lines.push(format!("period,{}", header));
+            for s in stats {
+                lines.push(format!(
+                    "{},{},{},{},{},{:.6}",
+                    csv_escape(&s.period),
+                    s.total_input_tokens,
+                    s.total_output_tokens,
+                    s.total_tokens,
+                    s.request_count,
+                    s.total_cost
+                ));
+            }
+        }
+        other => return Err(format!("Unsupported group_by: {}", other)),
+    }
 
-    let rows = stmt
-        .query_map([cutoff], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, u64>(2)?,
-            ))
-        })
-        .map_err(|e| e.to_string())?;
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
 
-    for row in rows {
-        let (period, account, total) = row.map_err(|e| e.to_string())?;
-        trend_map.entry(period).or_default().insert(account, total);
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
     }
-
-    Ok(trend_map
-        .into_iter()
-        .map(|(period, account_data)| AccountTrendPoint { period, account_data })
-        .collect())
 }
 
 #[cfg(test)]
@@ -593,4 +870,72 @@ mod tests {
         // For now, just verify the module compiles
         assert!(true);
     }
+
+    fn rollup_totals(conn: &Connection) -> (String, i64, i64) {
+        conn.query_row(
+            "SELECT GROUP_CONCAT(DISTINCT resolution), SUM(total_tokens), SUM(request_count) FROM token_rollups",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rollup_compaction_preserves_totals() {
+        use chrono::TimeZone;
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&mut conn).unwrap();
+
+        let usage = TokenUsage {
+            input_tokens: 100,
+            output_tokens: 50,
+            ..Default::default()
+        };
+        let old = chrono::Utc.with_ymd_and_hms(2025, 1, 10, 8, 30, 0).unwrap();
+        let recent = chrono::Utc.with_ymd_and_hms(2025, 3, 20, 9, 0, 0).unwrap();
+        let records: Vec<UsageRecord> = [(old, "gemini-3-flash"), (old, "gemini-3-flash"), (recent, "claude-sonnet-4-5")]
+            .into_iter()
+            .map(|(timestamp, model)| UsageRecord {
+                timestamp,
+                account_email: "a@example.com".to_string(),
+                model: model.to_string(),
+                protocol: "openai".to_string(),
+                client_key: "***abcd".to_string(),
+                usage,
+            })
+            .collect();
+        record_usage_batch_with_conn(&mut conn, &mut pricing::PriceCache::default(), &records).unwrap();
+        assert_eq!(rollup_totals(&conn), ("hour".to_string(), 450, 3));
+
+        let policy = TokenStatsRetentionConfig {
+            raw_retention_days: 30,
+            hourly_retention_days: 7,
+            daily_retention_days: 30,
+            monthly_retention_months: 0,
+        };
+        let now = chrono::Utc.with_ymd_and_hms(2025, 3, 22, 0, 0, 0).unwrap();
+        let report = run_maintenance_with_conn(&mut conn, &policy, now).unwrap();
+        assert_eq!(report.raw_deleted, 2);
+        assert_eq!(report.hourly_compacted, 1);
+        assert_eq!(report.daily_compacted, 1);
+
+        let (_, total, requests) = rollup_totals(&conn);
+        assert_eq!((total, requests), (450, 3));
+        let month_bucket: String = conn
+            .query_row("SELECT bucket FROM token_rollups WHERE resolution = 'month'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(month_bucket, "2025-01");
+
+        // Running again is a no-op
+        let report = run_maintenance_with_conn(&mut conn, &policy, now).unwrap();
+        assert_eq!(report.hourly_compacted + report.daily_compacted, 0);
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("user@example.com"), "user@example.com");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
 }
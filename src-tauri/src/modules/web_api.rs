@@ -1,11 +1,14 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
-    routing::post,
-    Json, Router,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::{self as axum_middleware, Next},
+    response::Response,
+    routing::{get, post},
+    Extension, Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use crate::models::{Account, AppConfig, DeviceProfile, QuotaData};
@@ -14,13 +17,17 @@ use crate::proxy;
 use crate::proxy::server::ProxyRuntime;
 use reqwest::Client;
 use std::time::Duration;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::models::config::ManagementConfig;
+use crate::modules::web_auth::{AuthSession, Role, WebAuth};
 
 #[derive(Clone)]
 pub struct WebApiState {
     pub token_manager: Arc<proxy::TokenManager>,
     pub proxy_runtime: ProxyRuntime,
     pub monitor: Arc<proxy::monitor::ProxyMonitor>,
+    pub auth: Arc<WebAuth>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,6 +52,21 @@ fn ok(data: Value) -> Json<InvokeResponse> {
     })
 }
 
+/// 配置校验失败时返回 422 及字段级错误
+fn config_err(e: modules::config_reload::ConfigError) -> (StatusCode, Json<InvokeResponse>) {
+    match e {
+        modules::config_reload::ConfigError::Invalid(errors) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(InvokeResponse {
+                ok: false,
+                error: Some(modules::config_validation::summarize(&errors)),
+                data: Some(json!({ "errors": errors })),
+            }),
+        ),
+        modules::config_reload::ConfigError::Failed(e) => err(StatusCode::BAD_REQUEST, e),
+    }
+}
+
 fn err(status: StatusCode, message: String) -> (StatusCode, Json<InvokeResponse>) {
     (
         status,
@@ -56,21 +78,200 @@ fn err(status: StatusCode, message: String) -> (StatusCode, Json<InvokeResponse>
     )
 }
 
-pub fn router(state: WebApiState) -> Router {
+pub fn router(state: WebApiState, management: &ManagementConfig) -> Router {
+    let origins: Vec<header::HeaderValue> = management
+        .allowed_origins
+        .iter()
+        .filter_map(|origin| origin.parse().ok())
+        .collect();
     let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]);
 
     Router::new()
         .route("/invoke", post(invoke_handler))
+        .route("/auth/me", get(me_handler))
+        .route("/auth/logout", post(logout_handler))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), session_middleware))
+        .route("/auth/login", post(login_handler))
         .layer(cors)
         .with_state(state)
 }
 
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// 校验管理后台会话, 并将 `AuthSession` 注入请求扩展
+async fn session_middleware(
+    State(state): State<WebApiState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<InvokeResponse>)> {
+    let session = bearer_token(request.headers())
+        .and_then(|token| state.auth.authenticate(token))
+        .ok_or_else(|| err(StatusCode::UNAUTHORIZED, "unauthorized".to_string()))?;
+    request.extensions_mut().insert(session);
+    Ok(next.run(request).await)
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+/// 请求来源, 用于审计日志
+struct RequestOrigin {
+    source_ip: Option<String>,
+    forwarded_for: Option<String>,
+}
+
+impl RequestOrigin {
+    fn new(connect_info: Option<ConnectInfo<SocketAddr>>, headers: &HeaderMap) -> Self {
+        Self {
+            source_ip: connect_info.map(|ConnectInfo(addr)| addr.ip().to_string()),
+            forwarded_for: headers
+                .get("x-forwarded-for")
+                .and_then(|h| h.to_str().ok())
+                .map(|s| s.to_string()),
+        }
+    }
+}
+
+async fn record_audit(
+    origin: &RequestOrigin,
+    username: &str,
+    role: Option<Role>,
+    command: &str,
+    args: &Value,
+    error: Option<String>,
+    started: std::time::Instant,
+) {
+    let entry = modules::audit_log::AuditEntry {
+        id: 0,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        username: username.to_string(),
+        role: role.map(|r| format!("{:?}", r).to_lowercase()),
+        source_ip: origin.source_ip.clone(),
+        forwarded_for: origin.forwarded_for.clone(),
+        command: command.to_string(),
+        args: modules::audit_log::format_args(args),
+        success: error.is_none(),
+        error,
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    let _ = tokio::task::spawn_blocking(move || modules::audit_log::record(&entry)).await;
+}
+
+async fn login_handler(
+    State(state): State<WebApiState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Json<InvokeResponse>, (StatusCode, Json<InvokeResponse>)> {
+    let started = std::time::Instant::now();
+    let origin = RequestOrigin::new(connect_info, &headers);
+    let username = payload.username.clone();
+    let auth = state.auth.clone();
+    let client_ip = origin.source_ip.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        auth.login(&payload.username, &payload.password, client_ip.as_deref())
+    })
+    .await
+    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let (role, error) = match &result {
+        Ok((_, session)) => (Some(session.role), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    record_audit(&origin, &username, role, "auth.login", &Value::Null, error, started).await;
+
+    match result {
+        Ok((token, session)) => Ok(ok(json!({
+            "token": token,
+            "username": session.username,
+            "role": session.role,
+            "expires_at": session.expires_at,
+        }))),
+        Err(e @ modules::web_auth::LoginError::LockedOut(_)) => {
+            Err(err(StatusCode::TOO_MANY_REQUESTS, e.to_string()))
+        }
+        Err(e) => {
+            // 放慢暴力破解
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            Err(err(StatusCode::UNAUTHORIZED, e.to_string()))
+        }
+    }
+}
+
+async fn logout_handler(
+    State(state): State<WebApiState>,
+    Extension(session): Extension<AuthSession>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Json<InvokeResponse> {
+    let started = std::time::Instant::now();
+    if let Some(token) = bearer_token(&headers) {
+        state.auth.logout(token);
+    }
+    let origin = RequestOrigin::new(connect_info, &headers);
+    record_audit(&origin, &session.username, Some(session.role), "auth.logout", &Value::Null, None, started).await;
+    ok(json!(true))
+}
+
+async fn me_handler(Extension(session): Extension<AuthSession>) -> Json<InvokeResponse> {
+    ok(json!(session))
+}
+
+/// 需要写入审计日志的命令: 所有非只读命令, 以及修改自身密码
+fn should_audit(cmd: &str) -> bool {
+    modules::web_auth::required_role(cmd) > Role::Viewer || cmd == "change_web_password"
+}
+
 async fn invoke_handler(
     State(state): State<WebApiState>,
+    Extension(session): Extension<AuthSession>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(payload): Json<InvokeRequest>,
+) -> Result<Json<InvokeResponse>, (StatusCode, Json<InvokeResponse>)> {
+    let started = std::time::Instant::now();
+    let origin = RequestOrigin::new(connect_info, &headers);
+    let cmd = payload.cmd.clone();
+    let audit_args = should_audit(&cmd).then(|| payload.args.clone());
+
+    let required = modules::web_auth::required_role(&cmd);
+    if session.role < required {
+        let message = format!("forbidden: '{}' requires role {:?}", cmd, required);
+        record_audit(&origin, &session.username, Some(session.role), &cmd, &payload.args, Some(message.clone()), started).await;
+        return Err(err(StatusCode::FORBIDDEN, message));
+    }
+
+    let result = dispatch_command(&state, &session, payload).await;
+    if let Some(args) = audit_args {
+        let error = result.as_ref().err().map(|(_, body)| body.0.error.clone().unwrap_or_default());
+        record_audit(&origin, &session.username, Some(session.role), &cmd, &args, error, started).await;
+    }
+
+    let mut response = result?;
+    if session.role < Role::Admin {
+        if let Some(data) = response.0.data.as_mut() {
+            modules::web_auth::redact_secrets(data);
+        }
+    }
+    Ok(response)
+}
+
+async fn dispatch_command(
+    state: &WebApiState,
+    session: &AuthSession,
+    payload: InvokeRequest,
 ) -> Result<Json<InvokeResponse>, (StatusCode, Json<InvokeResponse>)> {
     let cmd = payload.cmd.as_str();
     let args = payload.args;
@@ -385,15 +586,48 @@ async fn invoke_handler(
             }
             let input: SaveArgs = serde_json::from_value(args)
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
-            modules::config::save_app_config(&input.config)
-                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
-            apply_proxy_config(&state, &input.config.proxy).await;
-            state
-                .token_manager
-                .update_sticky_config(input.config.proxy.scheduling.clone())
-                .await;
+            modules::config_reload::save_and_apply(state, &input.config)
+                .await
+                .map_err(config_err)?;
             Ok(ok(json!(true)))
         }
+        "validate_config" => {
+            #[derive(Deserialize)]
+            struct ValidateArgs {
+                config: AppConfig,
+            }
+            let input: ValidateArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let errors = match modules::config_reload::validate(&input.config) {
+                Ok(()) => Vec::new(),
+                Err(modules::config_reload::ConfigError::Invalid(errors)) => errors,
+                Err(e) => return Err(err(StatusCode::BAD_REQUEST, e.to_string())),
+            };
+            Ok(ok(json!({ "valid": errors.is_empty(), "errors": errors })))
+        }
+        "reload_config" => {
+            let changed = modules::config_reload::reload(state)
+                .await
+                .map_err(config_err)?;
+            Ok(ok(json!({ "changed": changed })))
+        }
+        "get_config_history" => {
+            let history = modules::config_history::list()
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(history)))
+        }
+        "rollback_config" => {
+            #[derive(Deserialize)]
+            struct RollbackArgs {
+                id: String,
+            }
+            let input: RollbackArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let config = modules::config_reload::rollback(state, &input.id)
+                .await
+                .map_err(config_err)?;
+            Ok(ok(json!(config)))
+        }
         "get_proxy_status" => {
             let config = modules::config::load_app_config()
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
@@ -401,7 +635,12 @@ async fn invoke_handler(
             Ok(ok(json!({
                 "running": config.proxy.enabled,
                 "port": config.proxy.port,
-                "base_url": format!("http://{}:{}", config.proxy.get_bind_address(), config.proxy.port),
+                "base_url": format!(
+                    "{}://{}:{}",
+                    if config.proxy.tls.enabled { "https" } else { "http" },
+                    config.proxy.get_bind_address(),
+                    config.proxy.port
+                ),
                 "active_accounts": active_accounts
             })))
         }
@@ -482,6 +721,43 @@ async fn invoke_handler(
             state.token_manager.clear_all_sessions();
             Ok(ok(json!(true)))
         }
+        "list_proxy_sessions" => Ok(ok(json!(state.token_manager.list_sessions()))),
+        "get_proxy_session" => {
+            #[derive(Deserialize)]
+            struct SessionArgs {
+                sessionId: String,
+            }
+            let input: SessionArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let session = state
+                .token_manager
+                .get_session(&input.sessionId)
+                .ok_or_else(|| err(StatusCode::NOT_FOUND, format!("Session not found: {}", input.sessionId)))?;
+            Ok(ok(json!(session)))
+        }
+        "rebind_proxy_session" => {
+            #[derive(Deserialize)]
+            struct RebindArgs {
+                sessionId: String,
+                accountId: String,
+            }
+            let input: RebindArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let session = state
+                .token_manager
+                .rebind_session(&input.sessionId, &input.accountId)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(session)))
+        }
+        "evict_proxy_session" => {
+            #[derive(Deserialize)]
+            struct SessionArgs {
+                sessionId: String,
+            }
+            let input: SessionArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            Ok(ok(json!(state.token_manager.clear_session_binding(&input.sessionId))))
+        }
         "set_preferred_account" => {
             #[derive(Deserialize)]
             struct PreferredArgs {
@@ -502,6 +778,71 @@ async fn invoke_handler(
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
             Ok(ok(json!(stats)))
         }
+        "get_proxy_log_writer_stats" => Ok(ok(json!(state.monitor.writer_stats()))),
+        "get_signature_cache_stats" => Ok(ok(json!(proxy::SignatureCache::global().stats()))),
+        "get_model_catalog" => Ok(ok(json!(proxy::model_catalog::ModelCatalog::global().models().await))),
+        "list_images" => {
+            #[derive(Deserialize, Default)]
+            #[serde(default)]
+            struct ListImagesArgs {
+                limit: usize,
+                offset: usize,
+                model: Option<String>,
+                account: Option<String>,
+                since: Option<i64>,
+            }
+            let input: ListImagesArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let store = proxy::image_store::global()
+                .ok_or_else(|| err(StatusCode::NOT_FOUND, "Image storage is disabled".to_string()))?;
+            let images = store
+                .list(&proxy::image_store::ImageQuery {
+                    limit: input.limit,
+                    offset: input.offset,
+                    model: input.model,
+                    account: input.account,
+                    since: input.since,
+                })
+                .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            Ok(ok(json!(images)))
+        }
+        "get_image_store_stats" => {
+            let store = proxy::image_store::global()
+                .ok_or_else(|| err(StatusCode::NOT_FOUND, "Image storage is disabled".to_string()))?;
+            let stats = store.stats().map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            Ok(ok(json!(stats)))
+        }
+        "get_image_url" => {
+            #[derive(Deserialize)]
+            struct ImageUrlArgs {
+                fileName: String,
+                baseUrl: Option<String>,
+            }
+            let input: ImageUrlArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let store = proxy::image_store::global()
+                .ok_or_else(|| err(StatusCode::NOT_FOUND, "Image storage is disabled".to_string()))?;
+            let base = input
+                .baseUrl
+                .map(|b| b.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| store.base_url(&axum::http::Uri::default(), &HeaderMap::new()));
+            Ok(ok(json!({
+                "url": store.signed_url(&base, &input.fileName, chrono::Utc::now().timestamp()),
+                "expires_in": store.url_ttl_seconds(),
+            })))
+        }
+        "delete_image" => {
+            #[derive(Deserialize)]
+            struct DeleteImageArgs {
+                id: String,
+            }
+            let input: DeleteImageArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let store = proxy::image_store::global()
+                .ok_or_else(|| err(StatusCode::NOT_FOUND, "Image storage is disabled".to_string()))?;
+            let deleted = store.delete(&input.id).map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            Ok(ok(json!(deleted)))
+        }
         "get_proxy_logs_count_filtered" => {
             #[derive(Deserialize)]
             struct LogsArgs {
@@ -528,6 +869,32 @@ async fn invoke_handler(
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
             Ok(ok(json!(logs)))
         }
+        "search_proxy_logs" => {
+            #[derive(Deserialize)]
+            struct SearchArgs {
+                query: String,
+                #[serde(default = "default_search_limit")]
+                limit: usize,
+                #[serde(default)]
+                offset: usize,
+            }
+            let input: SearchArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let result = modules::log_search::search_logs(&input.query, input.limit.min(500), input.offset)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(result)))
+        }
+        "get_proxy_log_detail" => {
+            #[derive(Deserialize)]
+            struct DetailArgs {
+                logId: String,
+            }
+            let input: DetailArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let log = modules::proxy_db::get_log_detail(&input.logId)
+                .map_err(|e| err(StatusCode::NOT_FOUND, e))?;
+            Ok(ok(json!(log)))
+        }
         "set_proxy_monitor_enabled" => {
             #[derive(Deserialize)]
             struct MonitorArgs {
@@ -612,6 +979,31 @@ async fn invoke_handler(
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
             Ok(ok(json!(data)))
         }
+        "get_token_stats_monthly" => {
+            let months = args.get("months").and_then(|v| v.as_i64()).unwrap_or(12);
+            let data = modules::token_stats::get_monthly_stats(months)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(data)))
+        }
+        "get_token_stats_by_protocol" => {
+            let hours = args.get("hours").and_then(|v| v.as_i64()).unwrap_or(24);
+            let data = modules::token_stats::get_protocol_stats(hours)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(data)))
+        }
+        "get_token_stats_by_client_key" => {
+            let hours = args.get("hours").and_then(|v| v.as_i64()).unwrap_or(24);
+            let data = modules::token_stats::get_client_key_stats(hours)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(data)))
+        }
+        "run_token_stats_maintenance" => {
+            let config = modules::config::load_app_config()
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            let report = modules::token_stats::run_rollup_maintenance(&config.token_stats)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(report)))
+        }
         "get_token_stats_by_account" => {
             let hours = args.get("hours").and_then(|v| v.as_i64()).unwrap_or(24);
             let data = modules::token_stats::get_account_stats(hours)
@@ -654,6 +1046,40 @@ async fn invoke_handler(
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
             Ok(ok(json!(data)))
         }
+        "export_token_cost_csv" => {
+            let hours = args.get("hours").and_then(|v| v.as_i64()).unwrap_or(24 * 30);
+            let group_by = args.get("groupBy").and_then(|v| v.as_str()).unwrap_or("account");
+            let csv = modules::token_stats::export_cost_report_csv(hours, group_by)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(csv)))
+        }
+        "get_model_prices" => {
+            let prices = modules::pricing::list_prices()
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(prices)))
+        }
+        "save_model_price" => {
+            #[derive(Deserialize)]
+            struct PriceArgs {
+                price: modules::pricing::ModelPrice,
+            }
+            let input: PriceArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let id = modules::pricing::upsert_price(&input.price)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(id)))
+        }
+        "delete_model_price" => {
+            #[derive(Deserialize)]
+            struct PriceArgs {
+                id: i64,
+            }
+            let input: PriceArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            modules::pricing::delete_price(input.id)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(true)))
+        }
         "check_for_updates" => {
             let info = modules::update_checker::check_for_updates()
                 .await
@@ -722,6 +1148,11 @@ async fn invoke_handler(
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
             Ok(ok(json!(dir.to_string_lossy().to_string())))
         }
+        "get_effective_config" => {
+            let (config, overrides) = modules::config::effective_config_masked()
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!({ "config": config, "overrides": overrides })))
+        }
         "get_data_dir_path" => {
             let dir = modules::account::get_data_dir()
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
@@ -751,59 +1182,136 @@ async fn invoke_handler(
             }
             let input: OAuthArgs = serde_json::from_value(args)
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
-            let url = modules::oauth::get_auth_url(&input.redirectUri);
-            Ok(ok(json!({ "auth_url": url })))
+            let login = modules::oauth_flow::start(&input.redirectUri);
+            Ok(ok(json!({ "auth_url": login.auth_url, "state": login.state })))
+        }
+        "start_headless_oauth_login" => {
+            #[derive(Deserialize, Default)]
+            #[serde(default)]
+            struct HeadlessArgs {
+                loopback: bool,
+            }
+            let input: HeadlessArgs = serde_json::from_value(args).unwrap_or_default();
+            let login = if input.loopback {
+                modules::oauth_flow::start_loopback()
+                    .await
+                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?
+            } else {
+                modules::oauth_flow::start(modules::oauth_flow::PASTE_REDIRECT_URI)
+            };
+            Ok(ok(json!(login)))
+        }
+        "get_oauth_login_status" => {
+            #[derive(Deserialize)]
+            struct StatusArgs {
+                state: String,
+            }
+            let input: StatusArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let status = modules::oauth_flow::status(&input.state)
+                .ok_or_else(|| err(StatusCode::NOT_FOUND, "Login session expired or unknown".to_string()))?;
+            if let modules::oauth_flow::LoginStatus::Completed { .. } = &status {
+                // 回环模式在后台完成登录，这里确保号池已加载新账号
+                let _ = state.token_manager.reload_all_accounts().await;
+            }
+            Ok(ok(json!(status)))
         }
         "complete_oauth_login" => {
             #[derive(Deserialize)]
             struct OAuthArgs {
+                /// 授权码，或授权后跳转的完整 URL
                 code: String,
-                redirectUri: String,
+                /// start_oauth_login 返回的 state (code 为完整 URL 时可从中读取)
+                #[serde(default)]
+                state: Option<String>,
             }
             let input: OAuthArgs = serde_json::from_value(args)
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
-            let token_res = modules::oauth::exchange_code(&input.code, &input.redirectUri)
+            let mut account = modules::oauth_flow::complete(&input.code, input.state.as_deref())
                 .await
-                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
-            let refresh_token = match token_res.refresh_token {
-                Some(token) => token,
-                None => {
-                    return Err(err(
-                        StatusCode::BAD_REQUEST,
-                        "未获取到 Refresh Token。请先在 Google 账号授权页撤销访问后重试，或使用 Refresh Token 手动添加账号。".to_string(),
-                    ));
-                }
-            };
-            let user_info = modules::oauth::get_user_info(&token_res.access_token)
-                .await
-                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
-            let project_id = crate::proxy::project_resolver::fetch_project_id(&token_res.access_token)
-                .await
-                .ok();
-            let token = crate::models::TokenData::new(
-                token_res.access_token,
-                refresh_token,
-                token_res.expires_in,
-                Some(user_info.email.clone()),
-                project_id,
-                None,
-            );
-            let mut account = modules::upsert_account(
-                user_info.email.clone(),
-                user_info.get_display_name(),
-                token,
-            )
             .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
             let _ = internal_refresh_account_quota(&mut account).await;
             let _ = state.token_manager.reload_all_accounts().await;
             Ok(ok(json!(account)))
         }
-        "cancel_oauth_login" => Ok(ok(json!(true))),
+        "cancel_oauth_login" => {
+            #[derive(Deserialize, Default)]
+            #[serde(default)]
+            struct CancelArgs {
+                state: Option<String>,
+            }
+            let input: CancelArgs = serde_json::from_value(args).unwrap_or_default();
+            let cancelled = input
+                .state
+                .map(|s| modules::oauth_flow::cancel(&s))
+                .unwrap_or(true);
+            Ok(ok(json!(cancelled)))
+        }
         "clear_log_cache" => {
             modules::logger::clear_logs()
                 .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
             Ok(ok(json!(true)))
         }
+        "list_web_users" => Ok(ok(json!(state.auth.list_users()))),
+        "query_audit_log" => {
+            #[derive(Deserialize)]
+            struct AuditArgs {
+                #[serde(flatten)]
+                filter: modules::audit_log::AuditQuery,
+                #[serde(default = "default_search_limit")]
+                limit: usize,
+                #[serde(default)]
+                offset: usize,
+            }
+            let input: AuditArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            let result = modules::audit_log::query(&input.filter, input.limit.min(500), input.offset)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(result)))
+        }
+        "save_web_user" => {
+            #[derive(Deserialize)]
+            struct UserArgs {
+                username: String,
+                #[serde(default)]
+                password: Option<String>,
+                role: Role,
+            }
+            let input: UserArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            state
+                .auth
+                .upsert_user(&input.username, input.password.as_deref().filter(|p| !p.is_empty()), input.role)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(true)))
+        }
+        "delete_web_user" => {
+            #[derive(Deserialize)]
+            struct UserArgs {
+                username: String,
+            }
+            let input: UserArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            state
+                .auth
+                .delete_user(&input.username)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(true)))
+        }
+        "change_web_password" => {
+            #[derive(Deserialize)]
+            struct PasswordArgs {
+                currentPassword: String,
+                newPassword: String,
+            }
+            let input: PasswordArgs = serde_json::from_value(args)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e.to_string()))?;
+            state
+                .auth
+                .change_password(&session.username, &input.currentPassword, &input.newPassword)
+                .map_err(|e| err(StatusCode::BAD_REQUEST, e))?;
+            Ok(ok(json!(true)))
+        }
         "save_text_file" | "read_text_file" => {
             Err(err(StatusCode::NOT_IMPLEMENTED, "File operations are handled in the browser in web mode.".to_string()))
         }
@@ -814,6 +1322,10 @@ async fn invoke_handler(
     }
 }
 
+fn default_search_limit() -> usize {
+    50
+}
+
 async fn internal_refresh_account_quota(account: &mut Account) -> Result<QuotaData, String> {
     match modules::account::fetch_quota_with_retry(account).await {
         Ok(quota) => {
@@ -824,29 +1336,6 @@ async fn internal_refresh_account_quota(account: &mut Account) -> Result<QuotaDa
     }
 }
 
-async fn apply_proxy_config(state: &WebApiState, config: &proxy::ProxyConfig) {
-    {
-        let mut mapping = state.proxy_runtime.custom_mapping.write().await;
-        *mapping = config.custom_mapping.clone();
-    }
-    {
-        let mut proxy_state = state.proxy_runtime.proxy_state.write().await;
-        *proxy_state = config.upstream_proxy.clone();
-    }
-    {
-        let mut security = state.proxy_runtime.security_state.write().await;
-        *security = proxy::ProxySecurityConfig::from_proxy_config(config);
-    }
-    {
-        let mut zai = state.proxy_runtime.zai_state.write().await;
-        *zai = config.zai.clone();
-    }
-    {
-        let mut experimental = state.proxy_runtime.experimental.write().await;
-        *experimental = config.experimental.clone();
-    }
-}
-
 async fn toggle_proxy_status(account_id: &str, enable: bool, reason: Option<String>) -> Result<(), String> {
     let data_dir = modules::account::get_data_dir()?;
     let account_path = data_dir.join("accounts").join(format!("{}.json", account_id));
@@ -0,0 +1,565 @@
+//! Management API authentication.
+//!
+//! Users (stored in `web_users.json`, separate from the proxy API key) log in with a
+//! password and receive a bearer session token. Each user has a role; every `invoke`
+//! command requires a minimum role (see `required_role`), and unknown commands require
+//! admin. On first start an `admin` user is created with the password from
+//! `ANTIGRAVITY_ADMIN_PASSWORD`, or a random one written to `initial_admin_password.txt`.
+//! Repeated failed logins lock out the username and the client IP for a while.
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+const USERS_FILE: &str = "web_users.json";
+const INITIAL_PASSWORD_FILE: &str = "initial_admin_password.txt";
+const ADMIN_PASSWORD_ENV: &str = "ANTIGRAVITY_ADMIN_PASSWORD";
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// Failed logins allowed per username / client IP within `FAILURE_WINDOW_SECS`
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const FAILURE_WINDOW_SECS: i64 = 15 * 60;
+const LOCKOUT_SECS: i64 = 15 * 60;
+
+/// Verified for unknown usernames so they take as long as a wrong password
+static DUMMY_HASH: Lazy<String> = Lazy::new(|| hash_password(&random_string(20)).unwrap_or_default());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Read-only access to accounts, stats and logs
+    Viewer,
+    /// Viewer plus day-to-day operations (switch accounts, refresh quotas, start/stop proxy)
+    Operator,
+    /// Full access, including credentials, configuration and user management
+    Admin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WebUser {
+    username: String,
+    password_hash: String,
+    role: Role,
+    #[serde(default)]
+    created_at: i64,
+}
+
+/// User entry returned by the API (without password hash)
+#[derive(Debug, Clone, Serialize)]
+pub struct WebUserInfo {
+    pub username: String,
+    pub role: Role,
+    pub created_at: i64,
+}
+
+/// Authenticated session attached to management API requests
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthSession {
+    pub username: String,
+    pub role: Role,
+    pub expires_at: i64,
+}
+
+#[derive(Debug)]
+pub enum LoginError {
+    InvalidCredentials,
+    /// Too many failed attempts; seconds until the next attempt is accepted
+    LockedOut(i64),
+    Internal(String),
+}
+
+impl std::fmt::Display for LoginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidCredentials => write!(f, "invalid username or password"),
+            Self::LockedOut(secs) => write!(f, "too many failed login attempts, try again in {} seconds", secs),
+            Self::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Failed login counter for one username or client IP
+#[derive(Debug, Clone, Copy)]
+struct FailedAttempts {
+    count: u32,
+    first_at: i64,
+    locked_until: i64,
+}
+
+pub struct WebAuth {
+    path: Option<PathBuf>,
+    users: RwLock<Vec<WebUser>>,
+    sessions: DashMap<String, AuthSession>,
+    session_ttl_secs: i64,
+    /// Keyed by `ip:<addr>` (never by username alone)
+    failures: DashMap<String, FailedAttempts>,
+}
+
+fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| format!("failed to hash password: {}", e))
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false)
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!("password must be at least {} characters", MIN_PASSWORD_LEN));
+    }
+    Ok(())
+}
+
+impl WebAuth {
+    /// Load users from the data directory, creating the initial admin if there are none
+    pub fn load(session_ttl_hours: u64) -> Result<Self, String> {
+        let data_dir = crate::modules::account::get_data_dir()?;
+        let path = data_dir.join(USERS_FILE);
+
+        let users: Vec<WebUser> = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {}", USERS_FILE, e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("failed to parse {}: {}", USERS_FILE, e))?
+        } else {
+            Vec::new()
+        };
+
+        let auth = Self::with_users(Some(path), users, session_ttl_hours);
+        if auth.users.read().map(|u| u.is_empty()).unwrap_or(false) {
+            auth.bootstrap_admin(&data_dir)?;
+        }
+        Ok(auth)
+    }
+
+    fn with_users(path: Option<PathBuf>, users: Vec<WebUser>, session_ttl_hours: u64) -> Self {
+        Self {
+            path,
+            users: RwLock::new(users),
+            sessions: DashMap::new(),
+            session_ttl_secs: (session_ttl_hours.max(1) * 3600) as i64,
+            failures: DashMap::new(),
+        }
+    }
+
+    fn bootstrap_admin(&self, data_dir: &std::path::Path) -> Result<(), String> {
+        let from_env = std::env::var(ADMIN_PASSWORD_ENV).ok().filter(|p| !p.is_empty());
+        let password = from_env.clone().unwrap_or_else(|| random_string(20));
+        self.upsert_user("admin", Some(&password), Role::Admin)?;
+
+        if from_env.is_some() {
+            tracing::info!("Created management user 'admin' from {}", ADMIN_PASSWORD_ENV);
+        } else {
+            let file = data_dir.join(INITIAL_PASSWORD_FILE);
+            std::fs::write(&file, format!("{}\n", password))
+                .map_err(|e| format!("failed to write initial admin password: {}", e))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ = std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o600));
+            }
+            tracing::warn!(
+                "Created management user 'admin'; initial password saved to {} (change it after login)",
+                file.display()
+            );
+        }
+        Ok(())
+    }
+
+    fn save(&self, users: &[WebUser]) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let content = serde_json::to_string_pretty(users).map_err(|e| e.to_string())?;
+        std::fs::write(path, content).map_err(|e| format!("failed to save {}: {}", USERS_FILE, e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+        }
+        Ok(())
+    }
+
+    /// Verify credentials and open a session, returning its bearer token
+    pub fn login(&self, username: &str, password: &str, client_ip: Option<&str>) -> Result<(String, AuthSession), LoginError> {
+        let now = chrono::Utc::now().timestamp();
+        // Lockout is keyed on the client IP only: keying on the username would let anyone lock a user out
+        let keys = vec![format!("ip:{}", client_ip.unwrap_or("unknown"))];
+        if let Some(remaining) = self.lockout_remaining(&keys, now) {
+            return Err(LoginError::LockedOut(remaining));
+        }
+
+        let user = {
+            let users = self.users.read().map_err(|_| LoginError::Internal("user store poisoned".to_string()))?;
+            users.iter().find(|u| u.username == username).cloned()
+        };
+        // Unknown users still pay for a hash verification
+        let verified = match &user {
+            Some(user) => verify_password(password, &user.password_hash),
+            None => {
+                verify_password(password, &DUMMY_HASH);
+                false
+            }
+        };
+        let user = match user {
+            Some(user) if verified => user,
+            _ => {
+                self.record_failure(&keys, now);
+                return Err(LoginError::InvalidCredentials);
+            }
+        };
+        for key in &keys {
+            self.failures.remove(key);
+        }
+
+        self.purge_expired();
+        let token = random_string(48);
+        let session = AuthSession {
+            username: user.username,
+            role: user.role,
+            expires_at: now + self.session_ttl_secs,
+        };
+        self.sessions.insert(token.clone(), session.clone());
+        Ok((token, session))
+    }
+
+    fn lockout_remaining(&self, keys: &[String], now: i64) -> Option<i64> {
+        keys.iter()
+            .filter_map(|key| self.failures.get(key).map(|f| f.locked_until - now))
+            .filter(|remaining| *remaining > 0)
+            .max()
+    }
+
+    fn record_failure(&self, keys: &[String], now: i64) {
+        self.failures
+            .retain(|_, f| f.locked_until > now || now - f.first_at <= FAILURE_WINDOW_SECS);
+        for key in keys {
+            let mut entry = self.failures.entry(key.clone()).or_insert(FailedAttempts {
+                count: 0,
+                first_at: now,
+                locked_until: 0,
+            });
+            if now - entry.first_at > FAILURE_WINDOW_SECS {
+                *entry = FailedAttempts { count: 0, first_at: now, locked_until: 0 };
+            }
+            entry.count += 1;
+            if entry.count >= MAX_FAILED_ATTEMPTS {
+                tracing::warn!("Locking out management login for {} after {} failed attempts", key, entry.count);
+                *entry = FailedAttempts { count: 0, first_at: now, locked_until: now + LOCKOUT_SECS };
+            }
+        }
+    }
+
+    /// Resolve a bearer token to its session, if still valid
+    pub fn authenticate(&self, token: &str) -> Option<AuthSession> {
+        let session = self.sessions.get(token)?.clone();
+        if session.expires_at <= chrono::Utc::now().timestamp() {
+            self.sessions.remove(token);
+            return None;
+        }
+        Some(session)
+    }
+
+    pub fn logout(&self, token: &str) {
+        self.sessions.remove(token);
+    }
+
+    fn purge_expired(&self) {
+        let now = chrono::Utc::now().timestamp();
+        self.sessions.retain(|_, s| s.expires_at > now);
+    }
+
+    fn revoke_sessions_for(&self, username: &str) {
+        self.sessions.retain(|_, s| s.username != username);
+    }
+
+    pub fn list_users(&self) -> Vec<WebUserInfo> {
+        self.users
+            .read()
+            .map(|users| {
+                users
+                    .iter()
+                    .map(|u| WebUserInfo {
+                        username: u.username.clone(),
+                        role: u.role,
+                        created_at: u.created_at,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Create a user or update its role/password; existing sessions of the user are revoked
+    pub fn upsert_user(&self, username: &str, password: Option<&str>, role: Role) -> Result<(), String> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err("username must not be empty".to_string());
+        }
+        if let Some(password) = password {
+            validate_password(password)?;
+        }
+
+        let mut users = self.users.write().map_err(|_| "user store poisoned".to_string())?;
+        let mut updated = users.clone();
+        match updated.iter_mut().find(|u| u.username == username) {
+            Some(user) => {
+                user.role = role;
+                if let Some(password) = password {
+                    user.password_hash = hash_password(password)?;
+                }
+            }
+            None => {
+                let password = password.ok_or_else(|| "password is required for new users".to_string())?;
+                updated.push(WebUser {
+                    username: username.to_string(),
+                    password_hash: hash_password(password)?,
+                    role,
+                    created_at: chrono::Utc::now().timestamp(),
+                });
+            }
+        }
+        if !updated.iter().any(|u| u.role == Role::Admin) {
+            return Err("at least one admin user is required".to_string());
+        }
+
+        self.save(&updated)?;
+        *users = updated;
+        drop(users);
+        self.revoke_sessions_for(username);
+        Ok(())
+    }
+
+    pub fn delete_user(&self, username: &str) -> Result<(), String> {
+        let mut users = self.users.write().map_err(|_| "user store poisoned".to_string())?;
+        let updated: Vec<WebUser> = users.iter().filter(|u| u.username != username).cloned().collect();
+        if updated.len() == users.len() {
+            return Err(format!("user not found: {}", username));
+        }
+        if !updated.iter().any(|u| u.role == Role::Admin) {
+            return Err("at least one admin user is required".to_string());
+        }
+
+        self.save(&updated)?;
+        *users = updated;
+        drop(users);
+        self.revoke_sessions_for(username);
+        Ok(())
+    }
+
+    /// Change the caller's own password after verifying the current one
+    pub fn change_password(&self, username: &str, current: &str, new_password: &str) -> Result<(), String> {
+        validate_password(new_password)?;
+        let role = {
+            let users = self.users.read().map_err(|_| "user store poisoned".to_string())?;
+            let user = users
+                .iter()
+                .find(|u| u.username == username)
+                .ok_or_else(|| format!("user not found: {}", username))?;
+            if !verify_password(current, &user.password_hash) {
+                return Err("current password is incorrect".to_string());
+            }
+            user.role
+        };
+        self.upsert_user(username, Some(new_password), role)?;
+
+        // The generated bootstrap password is no longer valid once changed
+        if let Some(dir) = self.path.as_ref().and_then(|p| p.parent()) {
+            let _ = std::fs::remove_file(dir.join(INITIAL_PASSWORD_FILE));
+        }
+        Ok(())
+    }
+}
+
+/// Read-only commands
+const VIEWER_COMMANDS: &[&str] = &[
+    "list_accounts",
+    "get_current_account",
+    "get_device_profiles",
+    "list_device_versions",
+    "load_config",
+    "get_proxy_status",
+    "get_preferred_account",
+    "get_proxy_stats",
+    "get_proxy_log_writer_stats",
+    "get_signature_cache_stats",
+    "get_proxy_logs_count_filtered",
+    "get_proxy_logs_filtered",
+    "search_proxy_logs",
+    "get_proxy_log_detail",
+    "get_cli_sync_status",
+    "get_token_stats_hourly",
+    "get_token_stats_daily",
+    "get_token_stats_weekly",
+    "get_token_stats_monthly",
+    "get_token_stats_by_protocol",
+    "get_token_stats_by_client_key",
+    "get_token_stats_by_account",
+    "get_token_stats_by_model",
+    "get_token_stats_summary",
+    "get_token_stats_model_trend_hourly",
+    "get_token_stats_model_trend_daily",
+    "get_token_stats_account_trend_hourly",
+    "get_token_stats_account_trend_daily",
+    "export_token_cost_csv",
+    "get_model_prices",
+    "check_for_updates",
+    "should_check_updates",
+    "get_update_settings",
+    "get_http_api_settings",
+    "get_data_dir_path",
+    "get_effective_config",
+    "get_config_history",
+    "validate_config",
+    "get_antigravity_path",
+    "get_antigravity_args",
+    "change_web_password",
+];
+
+/// Day-to-day operations that don't touch credentials or configuration
+const OPERATOR_COMMANDS: &[&str] = &[
+    "switch_account",
+    "fetch_account_quota",
+    "refresh_all_quotas",
+    "reorder_accounts",
+    "toggle_proxy_status",
+    "warm_up_all_accounts",
+    "warm_up_account",
+    "start_proxy_service",
+    "stop_proxy_service",
+    "clear_proxy_session_bindings",
+    "set_preferred_account",
+    "set_proxy_monitor_enabled",
+    "clear_proxy_logs",
+    "run_token_stats_maintenance",
+    "clear_log_cache",
+    "update_last_check_time",
+];
+
+/// Minimum role required to run an `invoke` command (unknown commands require admin)
+pub fn required_role(cmd: &str) -> Role {
+    if VIEWER_COMMANDS.contains(&cmd) {
+        Role::Viewer
+    } else if OPERATOR_COMMANDS.contains(&cmd) {
+        Role::Operator
+    } else {
+        Role::Admin
+    }
+}
+
+/// Fields holding credentials, hidden from non-admin sessions
+const SECRET_FIELDS: &[&str] = &["token", "access_token", "refresh_token", "api_key", "password_hash"];
+
+/// Remove credential fields from a response before returning it to a non-admin session
+pub fn redact_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|k, _| !SECRET_FIELDS.contains(&k.as_str()));
+            map.values_mut().for_each(redact_secrets);
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_login_roles_and_sessions() {
+        let auth = WebAuth::with_users(None, Vec::new(), 24);
+        auth.upsert_user("admin", Some("admin-password"), Role::Admin).unwrap();
+        auth.upsert_user("viewer", Some("viewer-password"), Role::Viewer).unwrap();
+
+        assert!(auth.login("viewer", "wrong-password", None).is_err());
+        assert!(auth.login("nobody", "viewer-password", None).is_err());
+        let (token, session) = auth.login("viewer", "viewer-password", None).unwrap();
+        assert_eq!(session.role, Role::Viewer);
+        assert_eq!(auth.authenticate(&token).unwrap().username, "viewer");
+
+        // Role changes revoke existing sessions
+        auth.upsert_user("viewer", None, Role::Operator).unwrap();
+        assert!(auth.authenticate(&token).is_none());
+
+        // The last admin cannot be removed or demoted
+        assert!(auth.delete_user("admin").is_err());
+        assert!(auth.upsert_user("admin", None, Role::Viewer).is_err());
+        assert!(auth.upsert_user("new", None, Role::Viewer).is_err());
+        assert!(auth.upsert_user("new", Some("short"), Role::Viewer).is_err());
+
+        let (token, _) = auth.login("admin", "admin-password", None).unwrap();
+        auth.logout(&token);
+        assert!(auth.authenticate(&token).is_none());
+    }
+
+    #[test]
+    fn test_failed_login_lockout() {
+        let auth = WebAuth::with_users(None, Vec::new(), 24);
+        auth.upsert_user("admin", Some("admin-password"), Role::Admin).unwrap();
+        auth.upsert_user("viewer", Some("viewer-password"), Role::Viewer).unwrap();
+
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            assert!(matches!(
+                auth.login("admin", "wrong-password", Some("10.0.0.1")),
+                Err(LoginError::InvalidCredentials)
+            ));
+        }
+        // Locked per IP (for any username); other IPs can still log in as the same user
+        assert!(matches!(
+            auth.login("admin", "admin-password", Some("10.0.0.1")),
+            Err(LoginError::LockedOut(_))
+        ));
+        assert!(auth.login("admin", "admin-password", Some("10.0.0.2")).is_ok());
+        assert!(matches!(
+            auth.login("viewer", "viewer-password", Some("10.0.0.1")),
+            Err(LoginError::LockedOut(_))
+        ));
+        assert!(auth.login("viewer", "viewer-password", Some("10.0.0.2")).is_ok());
+
+        // Unknown usernames count against the IP as well
+        for i in 0..MAX_FAILED_ATTEMPTS {
+            let _ = auth.login(&format!("ghost{}", i), "whatever-password", Some("10.0.0.3"));
+        }
+        assert!(matches!(
+            auth.login("viewer", "viewer-password", Some("10.0.0.3")),
+            Err(LoginError::LockedOut(_))
+        ));
+    }
+
+    #[test]
+    fn test_required_role_and_redaction() {
+        assert_eq!(required_role("get_token_stats_summary"), Role::Viewer);
+        assert_eq!(required_role("switch_account"), Role::Operator);
+        assert_eq!(required_role("update_last_check_time"), Role::Operator);
+        assert_eq!(required_role("save_config"), Role::Admin);
+        assert_eq!(required_role("some_future_command"), Role::Admin);
+        assert!(Role::Admin > Role::Operator && Role::Operator > Role::Viewer);
+
+        let mut value = json!([{ "email": "a@example.com", "token": { "refresh_token": "x" } },
+                               { "proxy": { "api_key": "sk-1", "port": 8045 } }]);
+        redact_secrets(&mut value);
+        assert_eq!(value, json!([{ "email": "a@example.com" }, { "proxy": { "port": 8045 } }]));
+    }
+}
@@ -1,5 +1,39 @@
+pub mod speech; // 语音合成 (TTS)
+pub mod transcription; // 长音频分段转录与字幕输出
+
 use base64::{engine::general_purpose, Engine as _};
 use std::path::Path;
+use tokio::io::AsyncWriteExt;
+
+/// 通过 stdin/stdout 调用 ffmpeg (`path` 为空时从 PATH 查找)
+pub async fn run_ffmpeg(path: Option<&str>, args: &[String], input: Vec<u8>) -> Result<Vec<u8>, String> {
+    let program = path.filter(|p| !p.trim().is_empty()).unwrap_or("ffmpeg");
+    let mut child = tokio::process::Command::new(program)
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("无法启动 ffmpeg ({}): {}", program, e))?;
+
+    let mut stdin = child.stdin.take().ok_or("ffmpeg stdin unavailable")?;
+    let writer = tokio::spawn(async move {
+        let _ = stdin.write_all(&input).await;
+    });
+    let output = child.wait_with_output().await.map_err(|e| format!("ffmpeg failed: {}", e))?;
+    let _ = writer.await;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffmpeg exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
 
 pub struct AudioProcessor;
 
@@ -27,7 +61,7 @@ impl AudioProcessor {
         general_purpose::STANDARD.encode(audio_data)
     }
 
-    /// 判断文件是否超过大小限制
+    /// 判断文件是否超过单次内联上传的大小限制 (超过时需要分段转录)
     pub fn exceeds_size_limit(size_bytes: usize) -> bool {
         const MAX_SIZE: usize = 15 * 1024 * 1024; // 15MB
         size_bytes > MAX_SIZE
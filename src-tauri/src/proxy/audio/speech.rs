@@ -0,0 +1,301 @@
+// 语音合成 (Text-to-Speech)
+//
+// 将 OpenAI `/v1/audio/speech` 请求转换为 Gemini TTS 请求。Gemini 只返回 24kHz/16bit/单声道 PCM，
+// wav/pcm 直接封装返回，mp3/opus/aac/flac 通过 ffmpeg 转码。
+
+use base64::Engine as _;
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::sync::RwLock;
+
+use crate::proxy::config::SpeechConfig;
+
+static CONFIG: Lazy<RwLock<SpeechConfig>> = Lazy::new(|| RwLock::new(SpeechConfig::default()));
+
+/// 应用语音合成配置 (启动及配置热更新时调用)
+pub fn configure(config: SpeechConfig) {
+    if let Ok(mut current) = CONFIG.write() {
+        *current = config;
+    }
+}
+
+pub fn current_config() -> SpeechConfig {
+    CONFIG.read().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// 默认输出采样率 (Gemini TTS 固定输出)
+pub const DEFAULT_SAMPLE_RATE: u32 = 24_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeechFormat {
+    Wav,
+    Pcm,
+    Mp3,
+    Opus,
+    Aac,
+    Flac,
+}
+
+impl SpeechFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format.to_lowercase().as_str() {
+            "wav" => Ok(Self::Wav),
+            "pcm" => Ok(Self::Pcm),
+            "mp3" => Ok(Self::Mp3),
+            "opus" => Ok(Self::Opus),
+            "aac" => Ok(Self::Aac),
+            "flac" => Ok(Self::Flac),
+            other => Err(format!("Unsupported response_format: {}", other)),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Wav => "audio/wav",
+            Self::Pcm => "audio/pcm",
+            Self::Mp3 => "audio/mpeg",
+            Self::Opus => "audio/ogg",
+            Self::Aac => "audio/aac",
+            Self::Flac => "audio/flac",
+        }
+    }
+
+    /// wav/pcm 可以逐段流式输出，其余格式需要整体转码
+    pub fn is_streamable(self) -> bool {
+        matches!(self, Self::Wav | Self::Pcm)
+    }
+
+    fn ffmpeg_args(self) -> &'static [&'static str] {
+        match self {
+            Self::Mp3 => &["-f", "mp3", "-c:a", "libmp3lame", "-b:a", "128k"],
+            Self::Opus => &["-f", "ogg", "-c:a", "libopus", "-b:a", "64k"],
+            Self::Aac => &["-f", "adts", "-c:a", "aac", "-b:a", "128k"],
+            Self::Flac => &["-f", "flac", "-c:a", "flac"],
+            Self::Wav | Self::Pcm => &[],
+        }
+    }
+}
+
+/// OpenAI 模型名映射到 Gemini TTS 模型 (gemini-* 原样透传)
+pub fn resolve_model(config: &SpeechConfig, model: Option<&str>) -> String {
+    match model {
+        Some(m) if m.starts_with("gemini-") => m.to_string(),
+        _ => config.default_model.clone(),
+    }
+}
+
+/// OpenAI 音色映射到 Gemini 预置音色; 已是 Gemini 音色名 (首字母大写) 时原样使用
+pub fn resolve_voice(config: &SpeechConfig, voice: Option<&str>) -> String {
+    let Some(voice) = voice.map(str::trim).filter(|v| !v.is_empty()) else {
+        return config.default_voice.clone();
+    };
+    if let Some(mapped) = config.voice_map.get(&voice.to_lowercase()) {
+        return mapped.clone();
+    }
+    if voice.chars().next().is_some_and(|c| c.is_ascii_uppercase()) {
+        return voice.to_string();
+    }
+    config.default_voice.clone()
+}
+
+/// 按句子边界切分长文本，每段不超过 `max_chars` 个字符
+pub fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    let mut sentences = Vec::new();
+    let mut sentence = String::new();
+    for c in text.chars() {
+        sentence.push(c);
+        if matches!(c, '.' | '!' | '?' | '\n' | '。' | '！' | '？' | '；' | ';') {
+            sentences.push(std::mem::take(&mut sentence));
+        }
+    }
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+
+    for sentence in sentences {
+        let len = sentence.chars().count();
+        if current_len + len > max_chars && !current.trim().is_empty() {
+            chunks.push(std::mem::take(&mut current).trim().to_string());
+            current_len = 0;
+        }
+        if len > max_chars {
+            // 超长句子按字符硬切
+            let chars: Vec<char> = sentence.chars().collect();
+            for piece in chars.chunks(max_chars) {
+                let piece: String = piece.iter().collect();
+                if !piece.trim().is_empty() {
+                    chunks.push(piece.trim().to_string());
+                }
+            }
+            continue;
+        }
+        current.push_str(&sentence);
+        current_len += len;
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current.trim().to_string());
+    }
+    chunks
+}
+
+/// 构建 Gemini TTS 请求体
+pub fn build_request(text: &str, voice: &str, instructions: Option<&str>) -> Value {
+    let prompt = match instructions.map(str::trim).filter(|i| !i.is_empty()) {
+        Some(instructions) => format!("{}\n\n{}", instructions, text),
+        None => text.to_string(),
+    };
+    json!({
+        "contents": [{
+            "role": "user",
+            "parts": [{ "text": prompt }]
+        }],
+        "generationConfig": {
+            "responseModalities": ["AUDIO"],
+            "speechConfig": {
+                "voiceConfig": {
+                    "prebuiltVoiceConfig": { "voiceName": voice }
+                }
+            }
+        }
+    })
+}
+
+/// 从 mimeType (如 `audio/L16;codec=pcm;rate=24000`) 中解析采样率
+fn parse_sample_rate(mime_type: &str) -> u32 {
+    mime_type
+        .split(';')
+        .filter_map(|p| p.trim().strip_prefix("rate="))
+        .find_map(|r| r.parse().ok())
+        .unwrap_or(DEFAULT_SAMPLE_RATE)
+}
+
+/// 从 Gemini 响应 (兼容 v1internal 包装) 中提取 PCM 数据与采样率
+pub fn extract_pcm(response: &Value) -> Result<(Vec<u8>, u32), String> {
+    let inner = response.get("response").unwrap_or(response);
+    let parts = inner
+        .get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .ok_or("No audio in response")?;
+
+    let mut pcm = Vec::new();
+    let mut sample_rate = DEFAULT_SAMPLE_RATE;
+    for part in parts {
+        let Some(inline) = part.get("inlineData") else {
+            continue;
+        };
+        if let Some(mime) = inline.get("mimeType").and_then(|m| m.as_str()) {
+            sample_rate = parse_sample_rate(mime);
+        }
+        let data = inline.get("data").and_then(|d| d.as_str()).unwrap_or("");
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| format!("Invalid audio data: {}", e))?;
+        pcm.extend_from_slice(&bytes);
+    }
+    if pcm.is_empty() {
+        return Err("No audio in response".to_string());
+    }
+    Ok((pcm, sample_rate))
+}
+
+/// 16bit 单声道 WAV 头; `data_len` 为 None 时写入最大长度，用于流式输出
+pub fn wav_header(sample_rate: u32, data_len: Option<u32>) -> Vec<u8> {
+    let data_len = data_len.unwrap_or(u32::MAX - 36);
+    let byte_rate = sample_rate * 2;
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&data_len.saturating_add(36).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&1u16.to_le_bytes()); // mono
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&byte_rate.to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes()); // block align
+    header.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+/// 使用 ffmpeg 将 PCM 转码为目标格式
+pub async fn transcode(
+    ffmpeg_path: Option<&str>,
+    pcm: Vec<u8>,
+    sample_rate: u32,
+    format: SpeechFormat,
+) -> Result<Vec<u8>, String> {
+    let mut args: Vec<String> = ["-f", "s16le", "-ar", &sample_rate.to_string(), "-ac", "1", "-i", "pipe:0"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    args.extend(format.ffmpeg_args().iter().map(|s| s.to_string()));
+    args.push("pipe:1".to_string());
+    super::run_ffmpeg(ffmpeg_path, &args, pcm).await.map_err(|e| {
+        format!(
+            "{:?} output requires ffmpeg: {}. Use response_format=wav or pcm, or set speech.ffmpeg_path",
+            format, e
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_voice_and_model() {
+        let config = SpeechConfig::default();
+        assert_eq!(resolve_voice(&config, Some("alloy")), "Kore");
+        assert_eq!(resolve_voice(&config, Some("Shimmer")), "Zephyr");
+        assert_eq!(resolve_voice(&config, Some("Puck")), "Puck");
+        assert_eq!(resolve_voice(&config, Some("unknown")), "Kore");
+        assert_eq!(resolve_voice(&config, None), "Kore");
+
+        assert_eq!(resolve_model(&config, Some("tts-1-hd")), config.default_model);
+        assert_eq!(resolve_model(&config, Some("gemini-2.5-pro-preview-tts")), "gemini-2.5-pro-preview-tts");
+    }
+
+    #[test]
+    fn test_split_text() {
+        assert_eq!(split_text("Hello. World!", 100), vec!["Hello. World!"]);
+        assert_eq!(split_text("Hello. World!", 8), vec!["Hello.", "World!"]);
+        assert_eq!(split_text("你好。世界！", 3), vec!["你好。", "世界！"]);
+        assert_eq!(split_text("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert!(split_text("   ", 10).is_empty());
+    }
+
+    #[test]
+    fn test_extract_pcm_and_wav_header() {
+        let response = json!({
+            "response": {
+                "candidates": [{
+                    "content": {
+                        "parts": [{
+                            "inlineData": { "mimeType": "audio/L16;codec=pcm;rate=16000", "data": "AAEC" }
+                        }]
+                    }
+                }]
+            }
+        });
+        let (pcm, rate) = extract_pcm(&response).unwrap();
+        assert_eq!(pcm, vec![0, 1, 2]);
+        assert_eq!(rate, 16_000);
+        assert!(extract_pcm(&json!({"candidates": []})).is_err());
+
+        let header = wav_header(24_000, Some(100));
+        assert_eq!(header.len(), 44);
+        assert_eq!(&header[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(header[4..8].try_into().unwrap()), 136);
+        assert_eq!(u32::from_le_bytes(header[24..28].try_into().unwrap()), 24_000);
+        assert_eq!(u32::from_le_bytes(header[40..44].try_into().unwrap()), 100);
+    }
+}
@@ -0,0 +1,449 @@
+// 长音频转录 (Transcription)
+//
+// 超过单次内联上限的音频先解码为 16bit 单声道 PCM (WAV 直接解析，其余格式经 ffmpeg)，按
+// `segment_seconds` 切成带重叠的分段并发转录，再按重叠区中点拼接成完整的时间轴。
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::RwLock;
+
+use crate::proxy::config::TranscriptionConfig;
+
+static CONFIG: Lazy<RwLock<TranscriptionConfig>> = Lazy::new(|| RwLock::new(TranscriptionConfig::default()));
+
+/// 应用转录配置 (启动及配置热更新时调用)
+pub fn configure(config: TranscriptionConfig) {
+    if let Ok(mut current) = CONFIG.write() {
+        *current = config;
+    }
+}
+
+pub fn current_config() -> TranscriptionConfig {
+    CONFIG.read().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// ffmpeg 解码后的采样率 (语音识别足够)
+const DECODE_SAMPLE_RATE: u32 = 16_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptionTask {
+    Transcribe,
+    /// 翻译为英文 (/v1/audio/translations)
+    Translate,
+}
+
+impl TranscriptionTask {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Transcribe => "transcribe",
+            Self::Translate => "translate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    Json,
+    Text,
+    VerboseJson,
+    Srt,
+    Vtt,
+}
+
+impl TranscriptFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            "verbose_json" => Ok(Self::VerboseJson),
+            "srt" => Ok(Self::Srt),
+            "vtt" => Ok(Self::Vtt),
+            other => Err(format!("Unsupported response_format: {}", other)),
+        }
+    }
+}
+
+/// 时间轴片段 (秒)
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// 16bit 单声道 PCM
+#[derive(Debug, Clone)]
+pub struct PcmAudio {
+    pub data: Vec<u8>,
+    pub sample_rate: u32,
+}
+
+impl PcmAudio {
+    pub fn duration(&self) -> f64 {
+        self.data.len() as f64 / (self.sample_rate as f64 * 2.0)
+    }
+
+    /// 截取 [start, end) 秒并封装为 WAV
+    pub fn slice_wav(&self, start: f64, end: f64) -> Vec<u8> {
+        let to_offset = |secs: f64| {
+            let sample = (secs.max(0.0) * self.sample_rate as f64) as usize;
+            (sample * 2).min(self.data.len())
+        };
+        let pcm = &self.data[to_offset(start)..to_offset(end)];
+        let mut wav = super::speech::wav_header(self.sample_rate, Some(pcm.len() as u32));
+        wav.extend_from_slice(pcm);
+        wav
+    }
+}
+
+/// 解析 16bit PCM WAV，多声道时混合为单声道; 其他编码返回 None
+pub fn parse_wav(bytes: &[u8]) -> Option<PcmAudio> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+    let read_u16 = |at: usize| bytes.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let read_u32 = |at: usize| bytes.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+    let mut pos = 12;
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = read_u32(pos + 4)? as usize;
+        let body = pos + 8;
+        if id == b"fmt " {
+            format = Some((read_u16(body)?, read_u16(body + 2)?, read_u32(body + 4)?, read_u16(body + 14)?));
+        } else if id == b"data" {
+            let (codec, channels, sample_rate, bits) = format?;
+            if codec != 1 || bits != 16 || channels == 0 {
+                return None;
+            }
+            let data = &bytes[body..(body + size).min(bytes.len())];
+            let frame = channels as usize * 2;
+            let mono = if channels == 1 {
+                data.to_vec()
+            } else {
+                data.chunks_exact(frame)
+                    .flat_map(|f| {
+                        let sum: i32 = f.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as i32).sum();
+                        ((sum / channels as i32) as i16).to_le_bytes()
+                    })
+                    .collect()
+            };
+            return Some(PcmAudio { data: mono, sample_rate });
+        }
+        pos = body + size + (size & 1);
+    }
+    None
+}
+
+/// 解码音频为 PCM: WAV 直接解析，其余格式交给 ffmpeg
+pub async fn decode(ffmpeg_path: Option<&str>, bytes: &[u8]) -> Result<PcmAudio, String> {
+    if let Some(audio) = parse_wav(bytes) {
+        return Ok(audio);
+    }
+    let rate = DECODE_SAMPLE_RATE.to_string();
+    let args: Vec<String> = ["-i", "pipe:0", "-f", "s16le", "-ac", "1", "-ar", &rate, "pipe:1"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let data = super::run_ffmpeg(ffmpeg_path, &args, bytes.to_vec()).await?;
+    Ok(PcmAudio { data, sample_rate: DECODE_SAMPLE_RATE })
+}
+
+/// 规划带重叠的分段窗口 [(start, end)]
+pub fn plan_windows(duration: f64, segment_seconds: f64, overlap_seconds: f64) -> Vec<(f64, f64)> {
+    let segment = segment_seconds.max(1.0);
+    let step = (segment - overlap_seconds.clamp(0.0, segment / 2.0)).max(1.0);
+    let mut windows = Vec::new();
+    let mut start = 0.0;
+    loop {
+        let end = (start + segment).min(duration);
+        windows.push((start, end));
+        if end >= duration {
+            break;
+        }
+        start += step;
+    }
+    windows
+}
+
+/// 构建单段转录请求 (要求返回带时间戳的 JSON)
+pub fn build_request(
+    task: TranscriptionTask,
+    prompt: Option<&str>,
+    language: Option<&str>,
+    mime_type: &str,
+    audio: &[u8],
+) -> Value {
+    let mut instruction = match task {
+        TranscriptionTask::Transcribe => {
+            "Generate a verbatim transcript of the speech in its original language.".to_string()
+        }
+        TranscriptionTask::Translate => "Translate the speech into English.".to_string(),
+    };
+    if let Some(language) = language.filter(|l| !l.trim().is_empty()) {
+        instruction.push_str(&format!(" The spoken language is '{}'.", language.trim()));
+    }
+    if let Some(prompt) = prompt.filter(|p| !p.trim().is_empty()) {
+        instruction.push_str(&format!(" Context and vocabulary hints: {}", prompt.trim()));
+    }
+    instruction.push_str(
+        " Split the result into short segments and give each segment's start and end time in seconds, measured from the beginning of this audio clip.",
+    );
+
+    json!({
+        "contents": [{
+            "role": "user",
+            "parts": [
+                { "text": instruction },
+                {
+                    "inlineData": {
+                        "mimeType": mime_type,
+                        "data": super::AudioProcessor::encode_to_base64(audio)
+                    }
+                }
+            ]
+        }],
+        "generationConfig": {
+            "responseMimeType": "application/json",
+            "responseSchema": {
+                "type": "OBJECT",
+                "properties": {
+                    "segments": {
+                        "type": "ARRAY",
+                        "items": {
+                            "type": "OBJECT",
+                            "properties": {
+                                "start": { "type": "NUMBER" },
+                                "end": { "type": "NUMBER" },
+                                "text": { "type": "STRING" }
+                            },
+                            "required": ["start", "end", "text"]
+                        }
+                    }
+                },
+                "required": ["segments"]
+            }
+        }
+    })
+}
+
+/// 解析分段转录结果; 模型未按 JSON 返回时整体作为一个片段
+pub fn parse_segments(response: &Value, clip_duration: Option<f64>) -> Result<Vec<Segment>, String> {
+    let inner = response.get("response").unwrap_or(response);
+    let text: String = inner
+        .get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .ok_or("No transcript in response")?
+        .iter()
+        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+        .collect();
+
+    let trimmed = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+    let parsed: Option<Value> = serde_json::from_str(trimmed).ok();
+    let items = parsed.as_ref().and_then(|v| v.get("segments").or(Some(v))).and_then(|v| v.as_array());
+
+    let Some(items) = items else {
+        if trimmed.is_empty() {
+            return Ok(Vec::new());
+        }
+        return Ok(vec![Segment { start: 0.0, end: clip_duration.unwrap_or(0.0), text: trimmed.to_string() }]);
+    };
+
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let text = item.get("text")?.as_str()?.trim().to_string();
+            if text.is_empty() {
+                return None;
+            }
+            let start = item.get("start").and_then(|v| v.as_f64()).unwrap_or(0.0).max(0.0);
+            let end = item.get("end").and_then(|v| v.as_f64()).unwrap_or(start).max(start);
+            Some(Segment { start, end, text })
+        })
+        .collect())
+}
+
+/// 拼接各分段结果: 片段时间转换为绝对时间，重叠区以中点为界去重
+pub fn stitch(windows: &[(f64, f64)], parts: Vec<Vec<Segment>>) -> Vec<Segment> {
+    let boundary = |i: usize| (windows[i].0 + windows[i - 1].1) / 2.0;
+    let mut stitched = Vec::new();
+    for (i, segments) in parts.into_iter().enumerate() {
+        let (offset, _) = windows[i];
+        let lower = if i == 0 { f64::MIN } else { boundary(i) };
+        let upper = if i + 1 < windows.len() { boundary(i + 1) } else { f64::MAX };
+        for segment in segments {
+            let start = segment.start + offset;
+            if start >= lower && start < upper {
+                stitched.push(Segment { start, end: segment.end + offset, text: segment.text });
+            }
+        }
+    }
+    stitched
+}
+
+/// 拼接片段文本 (ASCII 单词之间补空格)
+pub fn join_text(segments: &[Segment]) -> String {
+    let mut text = String::new();
+    for segment in segments {
+        let needs_space = text.chars().last().is_some_and(|c| c.is_ascii() && !c.is_whitespace())
+            && segment.text.chars().next().is_some_and(|c| c.is_ascii());
+        if needs_space {
+            text.push(' ');
+        }
+        text.push_str(&segment.text);
+    }
+    text
+}
+
+fn format_timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/// 按 response_format 渲染结果，返回 (Content-Type, 正文)
+pub fn render(
+    format: TranscriptFormat,
+    task: TranscriptionTask,
+    language: Option<&str>,
+    duration: f64,
+    segments: &[Segment],
+) -> (&'static str, String) {
+    let text = join_text(segments);
+    match format {
+        TranscriptFormat::Json => ("application/json", json!({ "text": text }).to_string()),
+        TranscriptFormat::Text => ("text/plain; charset=utf-8", format!("{}\n", text)),
+        TranscriptFormat::VerboseJson => {
+            let segments: Vec<Value> = segments
+                .iter()
+                .enumerate()
+                .map(|(id, s)| json!({ "id": id, "start": s.start, "end": s.end, "text": s.text }))
+                .collect();
+            let body = json!({
+                "task": task.as_str(),
+                "language": match task {
+                    TranscriptionTask::Translate => "english",
+                    TranscriptionTask::Transcribe => language.unwrap_or("unknown"),
+                },
+                "duration": duration,
+                "text": text,
+                "segments": segments
+            });
+            ("application/json", body.to_string())
+        }
+        TranscriptFormat::Srt => {
+            let body: String = segments
+                .iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    format!("{}\n{} --> {}\n{}\n\n", i + 1, format_timestamp(s.start, ','), format_timestamp(s.end, ','), s.text)
+                })
+                .collect();
+            ("text/plain; charset=utf-8", body)
+        }
+        TranscriptFormat::Vtt => {
+            let mut body = String::from("WEBVTT\n\n");
+            for s in segments {
+                body.push_str(&format!("{} --> {}\n{}\n\n", format_timestamp(s.start, '.'), format_timestamp(s.end, '.'), s.text));
+            }
+            ("text/vtt; charset=utf-8", body)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(start: f64, end: f64, text: &str) -> Segment {
+        Segment { start, end, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_plan_windows_and_stitch() {
+        let windows = plan_windows(25.0, 10.0, 2.0);
+        assert_eq!(windows, vec![(0.0, 10.0), (8.0, 18.0), (16.0, 25.0)]);
+        assert_eq!(plan_windows(5.0, 10.0, 2.0), vec![(0.0, 5.0)]);
+
+        // 重叠区 [8, 10) 的中点为 9: 窗口 0 保留 9 之前的片段，窗口 1 保留 9 之后的片段
+        let stitched = stitch(
+            &windows,
+            vec![
+                vec![seg(0.0, 4.0, "one"), seg(8.5, 9.8, "two")],
+                vec![seg(0.5, 1.8, "two"), seg(1.5, 5.0, "three")],
+                vec![seg(3.0, 6.0, "four")],
+            ],
+        );
+        let texts: Vec<&str> = stitched.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["one", "two", "three", "four"]);
+        assert_eq!(stitched[2].start, 9.5);
+        assert_eq!(stitched[3].start, 19.0);
+    }
+
+    #[test]
+    fn test_parse_segments_and_render() {
+        let response = json!({
+            "response": { "candidates": [{ "content": { "parts": [{
+                "text": "{\"segments\":[{\"start\":0,\"end\":1.5,\"text\":\"Hello\"},{\"start\":1.5,\"end\":3.25,\"text\":\"world.\"}]}"
+            }]}}]}
+        });
+        let segments = parse_segments(&response, None).unwrap();
+        assert_eq!(segments, vec![seg(0.0, 1.5, "Hello"), seg(1.5, 3.25, "world.")]);
+
+        let plain = json!({ "candidates": [{ "content": { "parts": [{ "text": "just text" }]}}]});
+        assert_eq!(parse_segments(&plain, Some(4.0)).unwrap(), vec![seg(0.0, 4.0, "just text")]);
+
+        let (_, srt) = render(TranscriptFormat::Srt, TranscriptionTask::Transcribe, None, 3.25, &segments);
+        assert_eq!(srt, "1\n00:00:00,000 --> 00:00:01,500\nHello\n\n2\n00:00:01,500 --> 00:00:03,250\nworld.\n\n");
+        let (content_type, vtt) = render(TranscriptFormat::Vtt, TranscriptionTask::Transcribe, None, 3.25, &segments);
+        assert!(content_type.starts_with("text/vtt"));
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello"));
+        let (_, verbose) = render(TranscriptFormat::VerboseJson, TranscriptionTask::Translate, None, 3.25, &segments);
+        let verbose: Value = serde_json::from_str(&verbose).unwrap();
+        assert_eq!(verbose["text"], "Hello world.");
+        assert_eq!(verbose["task"], "translate");
+        assert_eq!(verbose["segments"][1]["end"], 3.25);
+    }
+
+    #[test]
+    fn test_parse_wav_downmixes_stereo() {
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&44u32.to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(&32000u32.to_le_bytes());
+        wav.extend_from_slice(&4u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&8u32.to_le_bytes());
+        for sample in [100i16, 300, -200, -400] {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let audio = parse_wav(&wav).unwrap();
+        assert_eq!(audio.sample_rate, 8000);
+        assert_eq!(audio.data, [200i16.to_le_bytes(), (-300i16).to_le_bytes()].concat());
+        assert_eq!(audio.slice_wav(0.0, 1.0).len(), 44 + 4);
+        assert!(parse_wav(b"not a wav").is_none());
+    }
+}
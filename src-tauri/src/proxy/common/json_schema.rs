@@ -394,6 +394,195 @@ fn extract_best_schema_from_union(union_array: &Vec<Value>) -> Option<Value> {
 }
 
 
+/// 按 JSON Schema 校验实例 (用于结构化输出的服务端校验)
+///
+/// 只覆盖结构化输出常用的子集: type / properties / required / additionalProperties /
+/// items / enum / const / anyOf / oneOf / allOf / 长度与数值范围，以及指向
+/// `#/$defs` 或 `#/definitions` 的 $ref。不认识的关键字会被忽略。
+/// 返回所有不匹配项，格式为 `路径: 原因`。
+pub fn validate_json_schema(instance: &Value, schema: &Value) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    validate_node(instance, schema, schema, "$", &mut errors, 0);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+const MAX_VALIDATION_DEPTH: usize = 64;
+
+fn resolve_schema_ref<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let path = reference.strip_prefix("#/")?;
+    path.split('/')
+        .try_fold(root, |node, seg| node.get(seg.replace("~1", "/").replace("~0", "~")))
+}
+
+fn instance_matches_type(instance: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        "number" => instance.is_number(),
+        "integer" => {
+            instance.is_i64()
+                || instance.is_u64()
+                || instance.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn validate_node(
+    instance: &Value,
+    schema: &Value,
+    root: &Value,
+    path: &str,
+    errors: &mut Vec<String>,
+    depth: usize,
+) {
+    if depth > MAX_VALIDATION_DEPTH {
+        return;
+    }
+    let map = match schema {
+        Value::Bool(false) => {
+            errors.push(format!("{}: value is not allowed", path));
+            return;
+        }
+        Value::Object(map) => map,
+        _ => return,
+    };
+
+    if let Some(reference) = map.get("$ref").and_then(|r| r.as_str()) {
+        match resolve_schema_ref(root, reference) {
+            Some(target) => validate_node(instance, target, root, path, errors, depth + 1),
+            None => errors.push(format!("{}: unresolved $ref {}", path, reference)),
+        }
+    }
+
+    if let Some(ty) = map.get("type") {
+        let allowed: Vec<String> = match ty {
+            Value::String(t) => vec![t.to_lowercase()],
+            Value::Array(list) => list.iter().filter_map(|t| t.as_str()).map(|t| t.to_lowercase()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| instance_matches_type(instance, t)) {
+            errors.push(format!("{}: expected type {}", path, allowed.join(" | ")));
+            return;
+        }
+    }
+
+    if let Some(options) = map.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(instance) {
+            errors.push(format!("{}: value is not one of the allowed enum values", path));
+        }
+    }
+    if let Some(expected) = map.get("const") {
+        if expected != instance {
+            errors.push(format!("{}: value does not match const", path));
+        }
+    }
+
+    if let Some(all) = map.get("allOf").and_then(|v| v.as_array()) {
+        for sub in all {
+            validate_node(instance, sub, root, path, errors, depth + 1);
+        }
+    }
+    let branch_matches = |sub: &Value| {
+        let mut sub_errors = Vec::new();
+        validate_node(instance, sub, root, path, &mut sub_errors, depth + 1);
+        sub_errors.is_empty()
+    };
+    if let Some(options) = map.get("anyOf").and_then(|v| v.as_array()) {
+        if !options.iter().any(branch_matches) {
+            errors.push(format!("{}: value does not match any schema in anyOf", path));
+        }
+    }
+    // oneOf: 恰好一个分支匹配
+    if let Some(options) = map.get("oneOf").and_then(|v| v.as_array()) {
+        match options.iter().filter(|sub| branch_matches(sub)).take(2).count() {
+            0 => errors.push(format!("{}: value does not match any schema in oneOf", path)),
+            1 => {}
+            _ => errors.push(format!("{}: value matches more than one schema in oneOf", path)),
+        }
+    }
+
+    match instance {
+        Value::Object(obj) => {
+            if let Some(required) = map.get("required").and_then(|r| r.as_array()) {
+                for name in required.iter().filter_map(|n| n.as_str()) {
+                    if !obj.contains_key(name) {
+                        errors.push(format!("{}: missing required property '{}'", path, name));
+                    }
+                }
+            }
+            let properties = map.get("properties").and_then(|p| p.as_object());
+            for (key, value) in obj {
+                let child_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(sub) => validate_node(value, sub, root, &child_path, errors, depth + 1),
+                    None => match map.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{}: unexpected property '{}'", path, key));
+                        }
+                        Some(sub @ Value::Object(_)) => {
+                            validate_node(value, sub, root, &child_path, errors, depth + 1)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = map.get("minItems").and_then(|v| v.as_u64()) {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{}: expected at least {} items", path, min));
+                }
+            }
+            if let Some(max) = map.get("maxItems").and_then(|v| v.as_u64()) {
+                if items.len() as u64 > max {
+                    errors.push(format!("{}: expected at most {} items", path, max));
+                }
+            }
+            if let Some(item_schema) = map.get("items") {
+                for (idx, item) in items.iter().enumerate() {
+                    validate_node(item, item_schema, root, &format!("{}[{}]", path, idx), errors, depth + 1);
+                }
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count() as u64;
+            if let Some(min) = map.get("minLength").and_then(|v| v.as_u64()) {
+                if len < min {
+                    errors.push(format!("{}: shorter than {} characters", path, min));
+                }
+            }
+            if let Some(max) = map.get("maxLength").and_then(|v| v.as_u64()) {
+                if len > max {
+                    errors.push(format!("{}: longer than {} characters", path, max));
+                }
+            }
+        }
+        Value::Number(num) => {
+            if let Some(n) = num.as_f64() {
+                if let Some(min) = map.get("minimum").and_then(|v| v.as_f64()) {
+                    if n < min {
+                        errors.push(format!("{}: less than minimum {}", path, min));
+                    }
+                }
+                if let Some(max) = map.get("maximum").and_then(|v| v.as_f64()) {
+                    if n > max {
+                        errors.push(format!("{}: greater than maximum {}", path, max));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -796,4 +985,48 @@ mod tests {
             assert!(items.get("const").is_none());
         }
     }
+
+    #[test]
+    fn test_validate_json_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "age": { "type": "integer", "minimum": 0 },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" } },
+                "status": { "enum": ["active", "inactive"] }
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false,
+            "$defs": { "tag": { "type": "string" } }
+        });
+
+        let valid = json!({ "name": "Ada", "age": 36, "tags": ["math"], "status": "active" });
+        assert!(validate_json_schema(&valid, &schema).is_ok());
+
+        let invalid = json!({ "name": "", "age": 1.5, "tags": [1], "status": "gone", "extra": true });
+        let errors = validate_json_schema(&invalid, &schema).unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("$.name") && e.contains("shorter")));
+        assert!(errors.iter().any(|e| e.contains("$.age") && e.contains("integer")));
+        assert!(errors.iter().any(|e| e.contains("$.tags[0]")));
+        assert!(errors.iter().any(|e| e.contains("$.status")));
+        assert!(errors.iter().any(|e| e.contains("unexpected property 'extra'")));
+
+        let missing = json!({ "name": "Ada" });
+        let errors = validate_json_schema(&missing, &schema).unwrap_err();
+        assert_eq!(errors, vec!["$: missing required property 'age'".to_string()]);
+    }
+
+    #[test]
+    fn test_validate_one_of_requires_exactly_one() {
+        let schema = json!({ "oneOf": [{ "type": "integer" }, { "type": "number", "minimum": 10 }] });
+        assert!(validate_json_schema(&json!(3), &schema).is_ok());
+        assert!(validate_json_schema(&json!(10.5), &schema).is_ok());
+        let errors = validate_json_schema(&json!(12), &schema).unwrap_err();
+        assert!(errors[0].contains("more than one schema in oneOf"));
+        assert!(validate_json_schema(&json!("x"), &schema).unwrap_err()[0].contains("any schema in oneOf"));
+
+        let any_of = json!({ "anyOf": [{ "type": "integer" }, { "type": "number", "minimum": 10 }] });
+        assert!(validate_json_schema(&json!(12), &any_of).is_ok());
+    }
 }
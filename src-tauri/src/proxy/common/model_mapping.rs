@@ -1,5 +1,5 @@
 // 模型名称映射
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use once_cell::sync::Lazy;
 
 static CLAUDE_TO_GEMINI: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
@@ -81,6 +81,26 @@ pub fn get_supported_models() -> Vec<String> {
     CLAUDE_TO_GEMINI.keys().map(|s| s.to_string()).collect()
 }
 
+/// 自定义映射的目标是否为上游可识别的模型: 内置映射目标，或实时模型目录中的模型;
+/// 目录尚未加载 (如 CLI 校验配置) 时退回 gemini-* / claude-* / gpt-oss-* 前缀检查
+pub fn is_valid_mapping_target(model: &str) -> bool {
+    let known = crate::proxy::model_catalog::ModelCatalog::global().known_model_ids();
+    is_valid_target_in(model, known.as_deref())
+}
+
+fn is_valid_target_in(model: &str, known: Option<&HashSet<String>>) -> bool {
+    if model.is_empty() || model.contains('*') || model.chars().any(char::is_whitespace) {
+        return false;
+    }
+    if CLAUDE_TO_GEMINI.values().any(|v| *v == model) {
+        return true;
+    }
+    match known {
+        Some(known) => crate::proxy::model_catalog::catalog_lists(known, model),
+        None => model.starts_with("gemini-") || model.starts_with("claude-") || model.starts_with("gpt-oss-"),
+    }
+}
+
 /// 动态获取所有可用模型列表 (包含内置与用户自定义)
 pub async fn get_all_dynamic_models(
     custom_mapping: &tokio::sync::RwLock<std::collections::HashMap<String, String>>,
@@ -233,4 +253,23 @@ mod tests {
             "claude-sonnet-4-5"
         );
     }
+
+    #[test]
+    fn test_mapping_target_uses_catalog() {
+        // 目录未加载: 仅按前缀判断
+        assert!(is_valid_target_in("gemini-typo", None));
+        assert!(!is_valid_target_in("gpt-4o", None));
+        assert!(!is_valid_target_in("gemini-*", None));
+
+        let known: HashSet<String> = ["gemini-3-flash", "gemini-3-pro-image", "gpt-oss-120b-medium"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(!is_valid_target_in("gemini-typo", Some(&known)));
+        assert!(is_valid_target_in("gemini-3-flash", Some(&known)));
+        assert!(is_valid_target_in("gemini-3-pro-image-4k-16x9", Some(&known)));
+        assert!(is_valid_target_in("gpt-oss-120b-medium", Some(&known)));
+        // 内置映射目标始终有效
+        assert!(is_valid_target_in("claude-sonnet-4-5", Some(&known)));
+    }
 }
@@ -0,0 +1,298 @@
+// SSE 增量解码器 - 所有上游流的消费方共用
+//
+// 按字节缓冲并按行切分 (支持 \n、\r\n、\r 以及跨 chunk 的 \r\n)，
+// 只在完整的行上做 UTF-8 解码，因此多字节字符被拆到两个 chunk 时也不会损坏。
+// 字段语义遵循 WHATWG EventSource 规范: event / data (多行拼接) / id / retry，
+// 以冒号开头的行是注释 (心跳)，空行分发事件。
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+
+/// 一个完整的 SSE 事件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// `event:` 字段，未指定时为 None (规范中默认为 "message")
+    pub event: Option<String>,
+    /// 所有 `data:` 行以 `\n` 拼接后的内容
+    pub data: String,
+    /// 最近一次收到的 `id:` (规范中的 last event id)
+    pub id: Option<String>,
+    pub retry: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    /// buffer 中已扫描过、确认不含换行的前缀长度; 新 chunk 从这里继续扫描，长行不会被反复扫描
+    scanned: usize,
+    /// 上一个 chunk 以 \r 结尾，下一个 chunk 开头的 \n 属于同一个换行
+    pending_cr: bool,
+    event: Option<String>,
+    data: String,
+    has_data: bool,
+    retry: Option<u64>,
+    last_id: Option<String>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 输入一个网络 chunk，返回其中已完整的事件
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut chunk = chunk;
+        if self.pending_cr {
+            self.pending_cr = false;
+            if chunk.first() == Some(&b'\n') {
+                chunk = &chunk[1..];
+            }
+        }
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        let mut i = self.scanned;
+        while i < self.buffer.len() {
+            let b = self.buffer[i];
+            if b != b'\n' && b != b'\r' {
+                i += 1;
+                continue;
+            }
+            let line_end = i;
+            if b == b'\r' {
+                match self.buffer.get(i + 1) {
+                    Some(b'\n') => i += 1,
+                    Some(_) => {}
+                    // 行尾的 \r 可能与下个 chunk 的 \n 组成 CRLF
+                    None => self.pending_cr = true,
+                }
+            }
+            i += 1;
+
+            let line = String::from_utf8_lossy(&self.buffer[start..line_end]).into_owned();
+            start = i;
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+        }
+        self.buffer.drain(..start);
+        self.scanned = self.buffer.len();
+        events
+    }
+
+    /// 流结束时调用: 处理缺少结尾换行的最后一行，并分发未以空行结束的事件
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let mut event = None;
+        if !self.buffer.is_empty() {
+            let line = String::from_utf8_lossy(&self.buffer).into_owned();
+            self.buffer.clear();
+            self.scanned = 0;
+            event = self.process_line(&line);
+        }
+        self.pending_cr = false;
+        event.or_else(|| self.dispatch())
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            // 注释 / 心跳
+            return None;
+        }
+
+        let (field, value) = match line.find(':') {
+            Some(pos) => {
+                let value = &line[pos + 1..];
+                (&line[..pos], value.strip_prefix(' ').unwrap_or(value))
+            }
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                if self.has_data {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value);
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(ms) = value.parse::<u64>() {
+                    self.retry = Some(ms);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        let retry = self.retry.take();
+        if !self.has_data {
+            // 没有 data 字段的事件按规范丢弃
+            return None;
+        }
+        self.has_data = false;
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data),
+            id: self.last_id.clone(),
+            retry,
+        })
+    }
+}
+
+/// 将字节流包装为 SSE 事件流；流结束时会刷新最后一个未完整结束的事件
+pub fn decode_stream<S, E>(mut stream: S) -> Pin<Box<dyn Stream<Item = Result<SseEvent, E>> + Send>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + Unpin + 'static,
+    E: Send + 'static,
+{
+    Box::pin(async_stream::stream! {
+        let mut decoder = SseDecoder::new();
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(bytes) => {
+                    for event in decoder.feed(&bytes) {
+                        yield Ok(event);
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+        if let Some(event) = decoder.finish() {
+            yield Ok(event);
+        }
+    })
+}
+
+/// 解码一段完整的 SSE 文本 (例如已缓冲的整个响应)
+pub fn decode_all(bytes: &[u8]) -> Vec<SseEvent> {
+    let mut decoder = SseDecoder::new();
+    let mut events = decoder.feed(bytes);
+    events.extend(decoder.finish());
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn data_event(event: Option<&str>, data: &str, id: Option<&str>) -> SseEvent {
+        SseEvent {
+            event: event.map(|s| s.to_string()),
+            data: data.to_string(),
+            id: id.map(|s| s.to_string()),
+            retry: None,
+        }
+    }
+
+    const SAMPLE: &str = ": ping\r\n\
+        event: message_start\r\n\
+        data: {\"type\":\"message_start\",\"text\":\"你好，世界 🌍\"}\r\n\
+        \r\n\
+        id: 42\n\
+        data: {\"a\":\n\
+        data: 1}\n\
+        \n\
+        data:no-space\r\r\
+        event: ignored-without-data\n\
+        \n\
+        retry: 1500\n\
+        data: [DONE]\n\n";
+
+    fn expected() -> Vec<SseEvent> {
+        vec![
+            data_event(
+                Some("message_start"),
+                "{\"type\":\"message_start\",\"text\":\"你好，世界 🌍\"}",
+                None,
+            ),
+            data_event(None, "{\"a\":\n1}", Some("42")),
+            data_event(None, "no-space", Some("42")),
+            SseEvent {
+                retry: Some(1500),
+                ..data_event(None, "[DONE]", Some("42"))
+            },
+        ]
+    }
+
+    #[test]
+    fn test_decode_whole_input() {
+        assert_eq!(decode_all(SAMPLE.as_bytes()), expected());
+    }
+
+    #[test]
+    fn test_decode_every_two_way_split() {
+        let bytes = SAMPLE.as_bytes();
+        for split in 0..=bytes.len() {
+            let mut decoder = SseDecoder::new();
+            let mut events = decoder.feed(&bytes[..split]);
+            events.extend(decoder.feed(&bytes[split..]));
+            events.extend(decoder.finish());
+            assert_eq!(events, expected(), "split at byte {}", split);
+        }
+    }
+
+    #[test]
+    fn test_decode_random_chunking() {
+        let bytes = SAMPLE.as_bytes();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x55E);
+        for _ in 0..500 {
+            let mut decoder = SseDecoder::new();
+            let mut events = Vec::new();
+            let mut pos = 0;
+            while pos < bytes.len() {
+                let len = rng.gen_range(1..=8).min(bytes.len() - pos);
+                events.extend(decoder.feed(&bytes[pos..pos + len]));
+                pos += len;
+            }
+            events.extend(decoder.finish());
+            assert_eq!(events, expected());
+        }
+    }
+
+    #[test]
+    fn test_long_line_resumes_scan() {
+        let mut decoder = SseDecoder::new();
+        let payload = "x".repeat(64 * 1024);
+        let line = format!("data: {}\r", payload);
+        for piece in line.as_bytes().chunks(1024) {
+            assert!(decoder.feed(piece).is_empty());
+            assert_eq!(decoder.scanned, decoder.buffer.len());
+        }
+        // 上个 chunk 结尾的 \r 与本 chunk 的 \n 组成一个换行
+        assert_eq!(decoder.feed(b"\n\n"), vec![data_event(None, &payload, None)]);
+        assert_eq!(decoder.scanned, 0);
+    }
+
+    #[test]
+    fn test_finish_flushes_unterminated_event() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.feed(b"data: {\"done\":true}").is_empty());
+        assert_eq!(decoder.finish(), Some(data_event(None, "{\"done\":true}", None)));
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[tokio::test]
+    async fn test_decode_stream_adapter() {
+        let chunks: Vec<Result<Bytes, std::io::Error>> = SAMPLE
+            .as_bytes()
+            .chunks(3)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect();
+        let events: Vec<SseEvent> = decode_stream(futures::stream::iter(chunks))
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+        assert_eq!(events, expected());
+    }
+}
@@ -0,0 +1,383 @@
+// 服务端会话压缩 (Conversation Compaction)
+//
+// 当请求的估算 token 超过模型窗口的阈值时，用较便宜的模型把较早的轮次总结为一段摘要，
+// 最近的轮次与 tool_use/tool_result 配对原样保留 (切分点由 ContextManager::plan_compaction 决定)。
+// 摘要按会话缓存: 同一会话的后续请求直接复用，会话继续增长时只对新增部分做增量总结。
+
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::proxy::mappers::claude::models::{ClaudeRequest, Message};
+use crate::proxy::mappers::context_manager::ContextManager;
+use crate::proxy::upstream::client::UpstreamClient;
+
+/// 摘要缓存的过期时间
+const SUMMARY_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+/// 摘要缓存的最大会话数
+const SUMMARY_CACHE_MAX_SESSIONS: usize = 1_000;
+/// 单次总结的最大输出 token
+const SUMMARY_MAX_OUTPUT_TOKENS: u32 = 4_096;
+/// 单次总结请求的 transcript 最多占总结模型窗口的比例 (其余留给提示词、上一段摘要与输出)
+const SUMMARY_INPUT_RATIO: f32 = 0.6;
+/// 估算 transcript 长度时每 token 的字符数 (比 ContextManager 的估算更保守)
+const SUMMARY_CHARS_PER_TOKEN: usize = 3;
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You compact long conversations between a user and an AI assistant. \
+Write a dense summary that lets the assistant continue the work without the original messages. \
+Keep: the user's goals and constraints, decisions made, file paths, identifiers, commands and their important \
+results, errors encountered, and any open tasks or pending questions. Omit pleasantries and redundant tool output. \
+Respond with the summary only.";
+
+/// 压缩参数 (来自 ExperimentalConfig)
+#[derive(Debug, Clone)]
+pub struct CompactionSettings {
+    /// 估算用量超过模型窗口的该比例时触发
+    pub threshold: f32,
+    /// 至少原样保留的最近消息数
+    pub keep_recent: usize,
+    /// 用于生成摘要的模型
+    pub model: String,
+}
+
+/// 一次压缩的结果，用于日志与 X-Context-Compacted 响应头
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionOutcome {
+    pub compacted_messages: usize,
+    pub tokens_before: u32,
+    pub tokens_after: u32,
+    pub from_cache: bool,
+}
+
+impl CompactionOutcome {
+    pub fn header_value(&self) -> String {
+        format!(
+            "messages={}; tokens={}->{}; cached={}",
+            self.compacted_messages, self.tokens_before, self.tokens_after, self.from_cache
+        )
+    }
+}
+
+struct CachedSummary {
+    prefix_len: usize,
+    prefix_hash: String,
+    summary: String,
+    updated_at: Instant,
+}
+
+/// 按会话缓存的压缩摘要
+pub struct SummaryCache {
+    entries: DashMap<String, CachedSummary>,
+}
+
+/// 缓存查询结果
+#[derive(Debug, PartialEq, Eq)]
+enum CacheHit {
+    /// 同一前缀已有摘要
+    Exact(String),
+    /// 已总结过前 `prefix_len` 条，只需增量总结其后的消息
+    Partial { prefix_len: usize, summary: String },
+    Miss,
+}
+
+fn prefix_hash(messages: &[Message]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(messages).unwrap_or_default());
+    format!("{:x}", hasher.finalize())
+}
+
+impl SummaryCache {
+    fn new() -> Self {
+        Self { entries: DashMap::new() }
+    }
+
+    pub fn global() -> &'static SummaryCache {
+        static INSTANCE: OnceLock<SummaryCache> = OnceLock::new();
+        INSTANCE.get_or_init(SummaryCache::new)
+    }
+
+    fn lookup(&self, session_id: &str, messages: &[Message], split: usize) -> CacheHit {
+        let Some(entry) = self.entries.get(session_id) else {
+            return CacheHit::Miss;
+        };
+        if entry.updated_at.elapsed() > SUMMARY_TTL || entry.prefix_len > split {
+            return CacheHit::Miss;
+        }
+        if prefix_hash(&messages[..entry.prefix_len]) != entry.prefix_hash {
+            return CacheHit::Miss;
+        }
+        if entry.prefix_len == split {
+            CacheHit::Exact(entry.summary.clone())
+        } else {
+            CacheHit::Partial { prefix_len: entry.prefix_len, summary: entry.summary.clone() }
+        }
+    }
+
+    fn store(&self, session_id: &str, messages: &[Message], split: usize, summary: &str) {
+        if self.entries.len() >= SUMMARY_CACHE_MAX_SESSIONS && !self.entries.contains_key(session_id) {
+            self.entries.retain(|_, v| v.updated_at.elapsed() <= SUMMARY_TTL);
+            // 仍然已满时淘汰最旧的一条
+            if self.entries.len() >= SUMMARY_CACHE_MAX_SESSIONS {
+                let oldest = self
+                    .entries
+                    .iter()
+                    .min_by_key(|e| e.updated_at)
+                    .map(|e| e.key().clone());
+                if let Some(key) = oldest {
+                    self.entries.remove(&key);
+                }
+            }
+        }
+        self.entries.insert(
+            session_id.to_string(),
+            CachedSummary {
+                prefix_len: split,
+                prefix_hash: prefix_hash(&messages[..split]),
+                summary: summary.to_string(),
+                updated_at: Instant::now(),
+            },
+        );
+    }
+}
+
+/// 若请求超过阈值则压缩其历史；未触发压缩时返回 Ok(None)
+///
+/// `target_model` 为实际发送的上游模型 (决定窗口大小)，总结调用使用当前账号的凭据。
+#[allow(clippy::too_many_arguments)]
+pub async fn compact_request(
+    upstream: &UpstreamClient,
+    access_token: &str,
+    project_id: &str,
+    session_id: &str,
+    target_model: &str,
+    request: &mut ClaudeRequest,
+    settings: &CompactionSettings,
+    trace_id: &str,
+) -> Result<Option<CompactionOutcome>, String> {
+    let tokens_before = ContextManager::estimate_token_usage(request);
+    let window = ContextManager::model_context_window(target_model);
+    let limit = (window as f32 * settings.threshold) as u32;
+    if tokens_before <= limit {
+        return Ok(None);
+    }
+
+    let Some(split) = ContextManager::plan_compaction(&request.messages, settings.keep_recent) else {
+        tracing::warn!(
+            "[{}] [Compaction] {} tokens exceeds limit {} but no safe split point was found",
+            trace_id, tokens_before, limit
+        );
+        return Ok(None);
+    };
+
+    let cache = SummaryCache::global();
+    let (summary, from_cache) = match cache.lookup(session_id, &request.messages, split) {
+        CacheHit::Exact(summary) => (summary, true),
+        CacheHit::Partial { prefix_len, summary } => {
+            let transcript = ContextManager::render_transcript(&request.messages[prefix_len..split]);
+            let summary = summarize_transcript(upstream, access_token, project_id, &settings.model, Some(summary), &transcript).await?;
+            cache.store(session_id, &request.messages, split, &summary);
+            (summary, false)
+        }
+        CacheHit::Miss => {
+            let transcript = ContextManager::render_transcript(&request.messages[..split]);
+            let summary = summarize_transcript(upstream, access_token, project_id, &settings.model, None, &transcript).await?;
+            cache.store(session_id, &request.messages, split, &summary);
+            (summary, false)
+        }
+    };
+
+    ContextManager::apply_compaction(&mut request.messages, split, &summary);
+    let outcome = CompactionOutcome {
+        compacted_messages: split,
+        tokens_before,
+        tokens_after: ContextManager::estimate_token_usage(request),
+        from_cache,
+    };
+    tracing::info!(
+        "[{}] [Compaction] Summarized {} messages with {} ({} -> {} tokens, cached: {})",
+        trace_id, split, settings.model, outcome.tokens_before, outcome.tokens_after, from_cache
+    );
+    Ok(Some(outcome))
+}
+
+/// 单次总结请求可容纳的 transcript 字符数 (按总结模型的窗口计算)
+fn transcript_budget_chars(model: &str) -> usize {
+    let window = ContextManager::model_context_window(model);
+    let tokens = ((window as f32 * SUMMARY_INPUT_RATIO) as usize).saturating_sub(2 * SUMMARY_MAX_OUTPUT_TOKENS as usize);
+    (tokens * SUMMARY_CHARS_PER_TOKEN).max(SUMMARY_CHARS_PER_TOKEN * 1_000)
+}
+
+/// 把 transcript 按消息边界 (空行) 切成不超过 `max_chars` 的分块，单条超长消息按字符硬切
+fn chunk_transcript(transcript: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for message in transcript.split_inclusive("\n\n") {
+        let chars: Vec<char> = message.chars().collect();
+        if current_chars + chars.len() > max_chars && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        for piece in chars.chunks(max_chars.max(1)) {
+            if current_chars + piece.len() > max_chars && !current.is_empty() {
+                chunks.push(std::mem::take(&mut current));
+                current_chars = 0;
+            }
+            current.extend(piece);
+            current_chars += piece.len();
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// 总结 transcript; 超出总结模型窗口时分块依次总结，每块在上一块的摘要基础上合并
+async fn summarize_transcript(
+    upstream: &UpstreamClient,
+    access_token: &str,
+    project_id: &str,
+    model: &str,
+    previous_summary: Option<String>,
+    transcript: &str,
+) -> Result<String, String> {
+    let chunks = chunk_transcript(transcript, transcript_budget_chars(model));
+    if chunks.len() > 1 {
+        tracing::info!("[Compaction] Transcript exceeds the {} window, summarizing in {} chunks", model, chunks.len());
+    }
+    let mut summary = previous_summary;
+    for chunk in &chunks {
+        summary = Some(summarize(upstream, access_token, project_id, model, summary.as_deref(), chunk).await?);
+    }
+    summary.ok_or_else(|| "Nothing to summarize".to_string())
+}
+
+fn build_summary_prompt(previous_summary: Option<&str>, transcript: &str) -> String {
+    match previous_summary {
+        Some(previous) => format!(
+            "Here is the summary of the conversation so far:\n\n{}\n\nUpdate it to also cover these later messages:\n\n{}",
+            previous, transcript
+        ),
+        None => format!("Summarize this conversation:\n\n{}", transcript),
+    }
+}
+
+async fn summarize(
+    upstream: &UpstreamClient,
+    access_token: &str,
+    project_id: &str,
+    model: &str,
+    previous_summary: Option<&str>,
+    transcript: &str,
+) -> Result<String, String> {
+    let body = json!({
+        "contents": [{
+            "role": "user",
+            "parts": [{ "text": build_summary_prompt(previous_summary, transcript) }]
+        }],
+        "systemInstruction": { "parts": [{ "text": SUMMARY_SYSTEM_PROMPT }] },
+        "generationConfig": {
+            "temperature": 0.2,
+            "maxOutputTokens": SUMMARY_MAX_OUTPUT_TOKENS
+        }
+    });
+    let wrapped = crate::proxy::mappers::gemini::wrapper::wrap_request(&body, project_id, model, None);
+
+    let response = upstream
+        .call_v1_internal("generateContent", access_token, wrapped, None)
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Summary request failed: HTTP {}: {}", status.as_u16(), text));
+    }
+    let value: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Summary response parse error: {}", e))?;
+    let inner = crate::proxy::mappers::gemini::wrapper::unwrap_response(&value);
+
+    let summary: String = inner["candidates"][0]["content"]["parts"]
+        .as_array()
+        .map(|parts| {
+            parts
+                .iter()
+                .filter(|p| !p.get("thought").and_then(|t| t.as_bool()).unwrap_or(false))
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+    if summary.trim().is_empty() {
+        return Err("Summary model returned no text".to_string());
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::mappers::claude::models::MessageContent;
+
+    fn conversation(turns: usize) -> Vec<Message> {
+        (0..turns * 2)
+            .map(|i| Message {
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: MessageContent::String(format!("message {}", i)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_summary_cache_exact_partial_and_miss() {
+        let cache = SummaryCache::new();
+        let messages = conversation(6);
+        assert_eq!(cache.lookup("s1", &messages, 4), CacheHit::Miss);
+
+        cache.store("s1", &messages, 4, "first four");
+        assert_eq!(cache.lookup("s1", &messages, 4), CacheHit::Exact("first four".to_string()));
+        assert_eq!(
+            cache.lookup("s1", &messages, 8),
+            CacheHit::Partial { prefix_len: 4, summary: "first four".to_string() }
+        );
+        assert_eq!(cache.lookup("s2", &messages, 4), CacheHit::Miss);
+
+        // 被编辑过的历史不能复用旧摘要
+        let mut edited = messages.clone();
+        edited[1].content = MessageContent::String("edited".to_string());
+        assert_eq!(cache.lookup("s1", &edited, 4), CacheHit::Miss);
+    }
+
+    #[test]
+    fn test_chunk_transcript_fits_budget() {
+        let transcript = ContextManager::render_transcript(&conversation(50));
+        let chunks = chunk_transcript(&transcript, 100);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 100));
+        assert_eq!(chunks.concat(), transcript);
+        // 每块都在消息边界结束
+        assert!(chunks.iter().all(|c| c.ends_with("\n\n")));
+
+        // 单条超长消息按字符切开
+        let long = format!("User: {}\n\n", "长".repeat(250));
+        let chunks = chunk_transcript(&long, 100);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.concat(), long);
+
+        assert_eq!(chunk_transcript("short", 100), vec!["short".to_string()]);
+        assert!(transcript_budget_chars("gemini-2.5-flash") > 1_000_000);
+    }
+
+    #[test]
+    fn test_header_value() {
+        let outcome = CompactionOutcome {
+            compacted_messages: 12,
+            tokens_before: 180_000,
+            tokens_after: 40_000,
+            from_cache: true,
+        };
+        assert_eq!(outcome.header_value(), "messages=12; tokens=180000->40000; cached=true");
+    }
+}
@@ -123,6 +123,14 @@ pub struct ExperimentalConfig {
     #[serde(default = "default_true")]
     pub enable_signature_cache: bool,
 
+    /// 将签名缓存持久化到 signature_cache.db，重启后仍可恢复长会话的签名
+    #[serde(default = "default_true")]
+    pub persist_signature_cache: bool,
+
+    /// 持久化签名缓存的最大条目数 (超出时淘汰最旧的条目)
+    #[serde(default = "default_signature_cache_max_entries")]
+    pub signature_cache_max_entries: usize,
+
     /// 启用工具循环自动恢复 (Tool Loop Recovery)
     #[serde(default = "default_true")]
     pub enable_tool_loop_recovery: bool,
@@ -135,19 +143,72 @@ pub struct ExperimentalConfig {
     /// 用于解决客户端因 Gemini 上下文过大而错误触发压缩的问题
     #[serde(default = "default_true")]
     pub enable_usage_scaling: bool,
+
+    /// 按请求中声明的 input_schema 修复工具调用参数 (类型转换、字符串化 JSON 等，默认关闭)
+    #[serde(default)]
+    pub repair_tool_call_args: bool,
+
+    /// 修复后仍不符合 schema 的工具调用，把校验错误回传给模型要求重新生成 (仅 Claude 非流式响应)
+    #[serde(default)]
+    pub reemit_invalid_tool_calls: bool,
+
+    /// 上下文超出模型窗口阈值时，用便宜模型总结较早的轮次 (默认关闭)
+    #[serde(default)]
+    pub enable_context_compaction: bool,
+
+    /// 触发压缩的用量比例 (估算 token / 模型窗口)
+    #[serde(default = "default_context_compaction_threshold")]
+    pub context_compaction_threshold: f32,
+
+    /// 压缩时至少原样保留的最近消息数
+    #[serde(default = "default_context_compaction_keep_recent")]
+    pub context_compaction_keep_recent: usize,
+
+    /// 生成摘要所用的模型
+    #[serde(default = "default_context_compaction_model")]
+    pub context_compaction_model: String,
+
+    /// 对 json_schema 结构化输出做服务端校验，不符合 schema 时换号重试
+    #[serde(default)]
+    pub validate_structured_output: bool,
 }
 
 impl Default for ExperimentalConfig {
     fn default() -> Self {
         Self {
             enable_signature_cache: true,
+            persist_signature_cache: true,
+            signature_cache_max_entries: default_signature_cache_max_entries(),
             enable_tool_loop_recovery: true,
             enable_cross_model_checks: true,
             enable_usage_scaling: true,
+            repair_tool_call_args: false,
+            reemit_invalid_tool_calls: false,
+            enable_context_compaction: false,
+            context_compaction_threshold: default_context_compaction_threshold(),
+            context_compaction_keep_recent: default_context_compaction_keep_recent(),
+            context_compaction_model: default_context_compaction_model(),
+            validate_structured_output: false,
         }
     }
 }
 
+fn default_signature_cache_max_entries() -> usize {
+    10_000
+}
+
+fn default_context_compaction_threshold() -> f32 {
+    0.8
+}
+
+fn default_context_compaction_keep_recent() -> usize {
+    8
+}
+
+fn default_context_compaction_model() -> String {
+    "gemini-2.5-flash".to_string()
+}
+
 fn default_true() -> bool {
     true
 }
@@ -208,6 +269,30 @@ pub struct ProxyConfig {
     /// 实验性功能配置
     #[serde(default)]
     pub experimental: ExperimentalConfig,
+
+    /// 请求日志正文采集与脱敏策略
+    #[serde(default)]
+    pub log_capture: LogCaptureConfig,
+
+    /// 工具结果压缩 / 卸载策略
+    #[serde(default)]
+    pub tool_results: ToolResultConfig,
+
+    /// 生成/编辑图片的本地存储与签名 URL
+    #[serde(default)]
+    pub image_storage: ImageStorageConfig,
+
+    /// 语音合成 (/v1/audio/speech)
+    #[serde(default)]
+    pub speech: SpeechConfig,
+
+    /// 音频转录 / 翻译 (/v1/audio/transcriptions, /v1/audio/translations)
+    #[serde(default)]
+    pub transcription: TranscriptionConfig,
+
+    /// 监听端口的 TLS / 客户端证书 (mTLS) 配置
+    #[serde(default)]
+    pub tls: TlsConfig,
 }
 
 /// 上游代理配置
@@ -235,10 +320,414 @@ impl Default for ProxyConfig {
             zai: ZaiConfig::default(),
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
             experimental: ExperimentalConfig::default(),
+            log_capture: LogCaptureConfig::default(),
+            tool_results: ToolResultConfig::default(),
+            image_storage: ImageStorageConfig::default(),
+            speech: SpeechConfig::default(),
+            transcription: TranscriptionConfig::default(),
+            tls: TlsConfig::default(),
+        }
+    }
+}
+
+/// 请求/响应正文的采集模式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    /// 仅记录元数据 (URL、状态码、模型、token 等), 不保存正文
+    Metadata,
+    /// 正文截断到 `max_body_bytes`
+    Truncated,
+    /// 保存完整正文
+    #[default]
+    Full,
+}
+
+/// 按路由/模型/客户端 Key 匹配的采集规则, 所有非空条件均满足时生效
+/// 匹配值支持精确匹配或以 `*` 结尾的前缀匹配
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureRule {
+    /// 请求路径, 如 `/v1/images/*`
+    #[serde(default)]
+    pub route: Option<String>,
+    /// 客户端请求的模型名, 如 `gemini-3-pro-image*`
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 脱敏后的客户端 Key (与统计页面展示一致), 如 `***cdef`
+    #[serde(default)]
+    pub client_key: Option<String>,
+    pub mode: CaptureMode,
+}
+
+/// 请求日志正文采集与脱敏配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogCaptureConfig {
+    /// 未命中任何规则时的采集模式
+    #[serde(default)]
+    pub default_mode: CaptureMode,
+
+    /// 按顺序匹配的规则, 第一条命中的规则生效
+    #[serde(default)]
+    pub rules: Vec<CaptureRule>,
+
+    /// truncated 模式下每个正文保留的最大字节数
+    #[serde(default = "default_capture_max_body_bytes")]
+    pub max_body_bytes: usize,
+
+    /// 脱敏常见密钥 (API Key、JWT、私钥等)
+    #[serde(default = "default_true")]
+    pub redact_secrets: bool,
+
+    /// 自定义脱敏正则, 命中内容替换为 `[REDACTED]`
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+
+    /// 移除正文中的 base64 图片数据
+    #[serde(default = "default_true")]
+    pub strip_base64_images: bool,
+
+    /// 使用 zstd 压缩落库的正文
+    #[serde(default = "default_true")]
+    pub compress_bodies: bool,
+}
+
+impl Default for LogCaptureConfig {
+    fn default() -> Self {
+        Self {
+            default_mode: CaptureMode::default(),
+            rules: Vec::new(),
+            max_body_bytes: default_capture_max_body_bytes(),
+            redact_secrets: true,
+            redact_patterns: Vec::new(),
+            strip_base64_images: true,
+            compress_bodies: true,
+        }
+    }
+}
+
+fn default_capture_max_body_bytes() -> usize {
+    64 * 1024
+}
+
+/// 工具结果的处理动作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolResultAction {
+    /// 原样保留 (不截断)
+    Keep,
+    /// 保留头部和尾部，省略中间
+    Truncate,
+    /// 智能压缩 (大文件提示、浏览器快照等启发式规则)
+    Summarize,
+    /// 移除 HTML 中的 style / script / base64 后截断
+    StripHtml,
+    /// 超长结果保存到本地，替换为可通过检索工具读取的引用
+    Offload,
+}
+
+/// 按工具名匹配的工具结果策略
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolResultPolicy {
+    /// 工具名, 支持精确匹配或以 `*` 结尾的前缀匹配 (忽略大小写)
+    pub tool: String,
+    pub action: ToolResultAction,
+    /// 超过该字符数才处理, 也是截断 / 压缩的目标长度 (未设置时使用 default_max_chars)
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    /// truncate 时头部所占比例, 其余保留尾部
+    #[serde(default = "default_tool_result_head_ratio")]
+    pub head_ratio: f64,
+    /// 移除结果中的 base64 图片
+    #[serde(default = "default_true")]
+    pub drop_images: bool,
+}
+
+/// 工具结果压缩配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolResultConfig {
+    /// 按顺序匹配的策略, 第一条命中的生效; 未命中时使用内置的智能压缩
+    #[serde(default)]
+    pub policies: Vec<ToolResultPolicy>,
+
+    /// 策略未指定 max_chars 时的阈值
+    #[serde(default = "default_tool_result_max_chars")]
+    pub default_max_chars: usize,
+
+    /// 卸载到本地的工具结果保留时长 (小时)
+    #[serde(default = "default_tool_result_offload_ttl_hours")]
+    pub offload_ttl_hours: u64,
+}
+
+impl Default for ToolResultConfig {
+    fn default() -> Self {
+        Self {
+            policies: Vec::new(),
+            default_max_chars: default_tool_result_max_chars(),
+            offload_ttl_hours: default_tool_result_offload_ttl_hours(),
         }
     }
 }
 
+/// 图片存储配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageStorageConfig {
+    /// 是否把生成/编辑的图片保存到本地 (默认关闭; 仅 url 格式的响应会保存，关闭时退回 data: URI)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// 签名 URL 的有效期 (秒)
+    #[serde(default = "default_image_url_ttl_seconds")]
+    pub url_ttl_seconds: u64,
+
+    /// 图片保留天数, 0 表示不按时间清理
+    #[serde(default = "default_image_retention_days")]
+    pub retention_days: u64,
+
+    /// 最多保留的图片数量, 0 表示不限制
+    #[serde(default = "default_image_max_count")]
+    pub max_images: usize,
+
+    /// 图片总大小上限 (MB), 0 表示不限制
+    #[serde(default = "default_image_max_total_mb")]
+    pub max_total_mb: u64,
+
+    /// 对外访问的基础地址 (如 https://proxy.example.com)，为空时按请求的 Host 生成
+    #[serde(default)]
+    pub public_base_url: Option<String>,
+}
+
+impl Default for ImageStorageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url_ttl_seconds: default_image_url_ttl_seconds(),
+            retention_days: default_image_retention_days(),
+            max_images: default_image_max_count(),
+            max_total_mb: default_image_max_total_mb(),
+            public_base_url: None,
+        }
+    }
+}
+
+/// 语音合成配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechConfig {
+    /// OpenAI 模型名 (tts-1 等) 对应的 Gemini TTS 模型
+    #[serde(default = "default_speech_model")]
+    pub default_model: String,
+
+    /// 未指定或无法识别的音色时使用的 Gemini 预置音色
+    #[serde(default = "default_speech_voice")]
+    pub default_voice: String,
+
+    /// OpenAI 音色 -> Gemini 预置音色
+    #[serde(default = "default_speech_voice_map")]
+    pub voice_map: std::collections::HashMap<String, String>,
+
+    /// 未指定 response_format 时的输出格式
+    #[serde(default = "default_speech_format")]
+    pub default_format: String,
+
+    /// 长文本按句切分，每段最多字符数 (分段合成并流式返回)
+    #[serde(default = "default_speech_chunk_chars")]
+    pub max_chunk_chars: usize,
+
+    /// ffmpeg 路径，为空时从 PATH 查找 (语音合成转码与长音频转录解码共用)
+    #[serde(default)]
+    pub ffmpeg_path: Option<String>,
+}
+
+impl Default for SpeechConfig {
+    fn default() -> Self {
+        Self {
+            default_model: default_speech_model(),
+            default_voice: default_speech_voice(),
+            voice_map: default_speech_voice_map(),
+            default_format: default_speech_format(),
+            max_chunk_chars: default_speech_chunk_chars(),
+            ffmpeg_path: None,
+        }
+    }
+}
+
+/// 音频转录配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionConfig {
+    /// whisper-1 等非 Gemini 模型名对应的转录模型
+    #[serde(default = "default_transcription_model")]
+    pub default_model: String,
+
+    /// 长音频分段时长 (秒)
+    #[serde(default = "default_transcription_segment_seconds")]
+    pub segment_seconds: u64,
+
+    /// 相邻分段的重叠时长 (秒)，用于避免在分段边界丢字
+    #[serde(default = "default_transcription_overlap_seconds")]
+    pub overlap_seconds: u64,
+
+    /// 分段并发转录数 (按账号池调度)
+    #[serde(default = "default_transcription_concurrency")]
+    pub max_concurrency: usize,
+}
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            default_model: default_transcription_model(),
+            segment_seconds: default_transcription_segment_seconds(),
+            overlap_seconds: default_transcription_overlap_seconds(),
+            max_concurrency: default_transcription_concurrency(),
+        }
+    }
+}
+
+fn default_transcription_model() -> String {
+    "gemini-2.0-flash-exp".to_string()
+}
+
+fn default_transcription_segment_seconds() -> u64 {
+    300
+}
+
+fn default_transcription_overlap_seconds() -> u64 {
+    5
+}
+
+fn default_transcription_concurrency() -> usize {
+    4
+}
+
+/// 客户端证书校验模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuthMode {
+    /// 不请求客户端证书
+    #[default]
+    Off,
+    /// 请求但不强制; 提供的证书必须由 CA 签发
+    Optional,
+    /// 必须提供由 CA 签发的客户端证书
+    Required,
+}
+
+/// TLS 配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// 是否启用 HTTPS (反代与管理后台共用同一端口)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// 证书链 (PEM)，与 key_path 同时为空时使用自签名证书
+    #[serde(default)]
+    pub cert_path: Option<String>,
+
+    /// 私钥 (PEM, PKCS#8 / PKCS#1 / SEC1)
+    #[serde(default)]
+    pub key_path: Option<String>,
+
+    /// 未配置证书时自动生成自签名证书 (保存在 <data_dir>/tls/)
+    #[serde(default = "default_true")]
+    pub self_signed: bool,
+
+    /// 自签名证书包含的主机名 / IP
+    #[serde(default = "default_tls_self_signed_hosts")]
+    pub self_signed_hosts: Vec<String>,
+
+    /// 客户端证书校验模式
+    #[serde(default)]
+    pub client_auth: ClientAuthMode,
+
+    /// 用于校验客户端证书的 CA 证书 (PEM，可包含多个)
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+
+    /// 持有有效客户端证书的请求无需 API Key
+    #[serde(default)]
+    pub client_cert_auth: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: None,
+            key_path: None,
+            self_signed: true,
+            self_signed_hosts: default_tls_self_signed_hosts(),
+            client_auth: ClientAuthMode::Off,
+            client_ca_path: None,
+            client_cert_auth: false,
+        }
+    }
+}
+
+fn default_tls_self_signed_hosts() -> Vec<String> {
+    vec!["localhost".to_string(), "127.0.0.1".to_string()]
+}
+
+fn default_speech_model() -> String {
+    "gemini-2.5-flash-preview-tts".to_string()
+}
+
+fn default_speech_voice() -> String {
+    "Kore".to_string()
+}
+
+fn default_speech_voice_map() -> std::collections::HashMap<String, String> {
+    [
+        ("alloy", "Kore"),
+        ("ash", "Orus"),
+        ("ballad", "Algieba"),
+        ("coral", "Aoede"),
+        ("echo", "Charon"),
+        ("fable", "Puck"),
+        ("nova", "Leda"),
+        ("onyx", "Fenrir"),
+        ("sage", "Sulafat"),
+        ("shimmer", "Zephyr"),
+        ("verse", "Enceladus"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect()
+}
+
+fn default_speech_format() -> String {
+    "wav".to_string()
+}
+
+fn default_speech_chunk_chars() -> usize {
+    1500
+}
+
+fn default_image_url_ttl_seconds() -> u64 {
+    24 * 3600
+}
+
+fn default_image_retention_days() -> u64 {
+    7
+}
+
+fn default_image_max_count() -> usize {
+    1000
+}
+
+fn default_image_max_total_mb() -> u64 {
+    2048
+}
+
+fn default_tool_result_head_ratio() -> f64 {
+    0.7
+}
+
+fn default_tool_result_max_chars() -> usize {
+    200_000
+}
+
+fn default_tool_result_offload_ttl_hours() -> u64 {
+    24
+}
+
 fn default_request_timeout() -> u64 {
     120 // 默认 120 秒,原来 60 秒太短
 }
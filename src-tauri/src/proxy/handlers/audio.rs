@@ -1,27 +1,131 @@
 use axum::{
+    body::Body,
     extract::{Multipart, State},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use bytes::Bytes;
+use futures::StreamExt;
 use serde_json::{json, Value};
 use tracing::{debug, info};
 use uuid::Uuid;
 
 use crate::proxy::{
-    audio::AudioProcessor,
+    audio::{
+        speech,
+        transcription::{self, TranscriptFormat, TranscriptionTask},
+        AudioProcessor,
+    },
     server::AppState,
 };
 
+/// 单次音频请求的最大尝试次数 (失败时轮换账号)
+const AUDIO_MAX_ATTEMPTS: usize = 3;
+
+/// 单次内联上传的音频上限 (与 AudioProcessor::exceeds_size_limit 一致)
+const INLINE_AUDIO_LIMIT: usize = 15 * 1024 * 1024;
+
+/// 调用 generateContent，遇到 401/403/429/5xx 时换号重试，返回 (响应, 账号)
+async fn generate_with_retry(
+    state: &AppState,
+    model: &str,
+    request: &Value,
+    request_prefix: &str,
+) -> Result<(Value, String), (StatusCode, String)> {
+    let mut last_error = (StatusCode::SERVICE_UNAVAILABLE, "No available accounts".to_string());
+
+    for attempt in 0..AUDIO_MAX_ATTEMPTS {
+        let (access_token, project_id, email) = state
+            .token_manager
+            .get_token("text", attempt > 0, None, model)
+            .await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+
+        let wrapped_body = json!({
+            "project": project_id,
+            "requestId": format!("{}-{}", request_prefix, Uuid::new_v4()),
+            "request": request,
+            "model": model,
+            "userAgent": "antigravity",
+            "requestType": "text"
+        });
+
+        let response = match state
+            .upstream
+            .call_v1_internal("generateContent", &access_token, wrapped_body, None)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                last_error = (StatusCode::BAD_GATEWAY, format!("上游请求失败: {}", e));
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            tracing::warn!(
+                "[Audio] Attempt {}/{} failed with {} on {}: {}",
+                attempt + 1,
+                AUDIO_MAX_ATTEMPTS,
+                status,
+                email,
+                error_text
+            );
+            let code = StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+            last_error = (code, format!("Gemini API 错误: {}", error_text));
+            if matches!(status.as_u16(), 401 | 403 | 429) || status.is_server_error() {
+                continue;
+            }
+            return Err(last_error);
+        }
+
+        let result: Value = response
+            .json()
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, format!("解析响应失败: {}", e)))?;
+        return Ok((result, email));
+    }
+
+    Err(last_error)
+}
+
 /// 处理音频转录请求 (OpenAI Whisper API 兼容)
 pub async fn handle_audio_transcription(
     State(state): State<AppState>,
+    multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    handle_transcription_task(state, multipart, TranscriptionTask::Transcribe).await
+}
+
+/// 处理音频翻译请求 (翻译为英文，OpenAI 兼容)
+pub async fn handle_audio_translation(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    handle_transcription_task(state, multipart, TranscriptionTask::Translate).await
+}
+
+/// 待转录的音频: 解码后的 PCM (按窗口切片) 或无法解码时的原始文件
+enum ClipSource {
+    Pcm(transcription::PcmAudio),
+    Raw(String, Vec<u8>),
+}
+
+async fn handle_transcription_task(
+    state: AppState,
     mut multipart: Multipart,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    task: TranscriptionTask,
+) -> Result<Response, (StatusCode, String)> {
+    let config = transcription::current_config();
     let mut audio_data: Option<Vec<u8>> = None;
     let mut filename: Option<String> = None;
-    let mut model = "gemini-2.0-flash-exp".to_string();
-    let mut prompt = "Generate a transcript of the speech.".to_string();
+    let mut model: Option<String> = None;
+    let mut prompt: Option<String> = None;
+    let mut language: Option<String> = None;
+    let mut response_format = "json".to_string();
 
     // 1. 解析 multipart/form-data
     while let Some(field) = multipart.next_field().await.map_err(|e| {
@@ -36,11 +140,13 @@ pub async fn handle_audio_transcription(
                     (StatusCode::BAD_REQUEST, format!("读取文件失败: {}", e))
                 })?.to_vec());
             }
-            "model" => {
-                model = field.text().await.unwrap_or(model);
-            }
-            "prompt" => {
-                prompt = field.text().await.unwrap_or(prompt);
+            "model" => model = field.text().await.ok(),
+            "prompt" => prompt = field.text().await.ok(),
+            "language" => language = field.text().await.ok().filter(|l| !l.trim().is_empty()),
+            "response_format" => {
+                if let Ok(value) = field.text().await {
+                    response_format = value;
+                }
             }
             _ => {}
         }
@@ -56,107 +162,235 @@ pub async fn handle_audio_transcription(
         "无法获取文件名".to_string(),
     ))?;
 
+    let format = TranscriptFormat::parse(&response_format).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let model = match model {
+        Some(m) if m.starts_with("gemini-") => m,
+        _ => config.default_model.clone(),
+    };
+
     info!(
-        "收到音频转录请求: 文件={}, 大小={} bytes, 模型={}",
+        "收到音频{}请求: 文件={}, 大小={} bytes, 模型={}, 格式={:?}",
+        if task == TranscriptionTask::Translate { "翻译" } else { "转录" },
         file_name,
         audio_bytes.len(),
-        model
+        model,
+        format
     );
 
-    // 2. 检测 MIME 类型
-    let mime_type = AudioProcessor::detect_mime_type(&file_name)
-        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
-
-    // 3. 验证文件大小
-    if AudioProcessor::exceeds_size_limit(audio_bytes.len()) {
-        let size_mb = audio_bytes.len() as f64 / (1024.0 * 1024.0);
-        return Err((
-            StatusCode::PAYLOAD_TOO_LARGE,
-            format!(
-                "音频文件过大 ({:.1} MB)。最大支持 15 MB (约 16 分钟 MP3)。建议: 1) 压缩音频质量 2) 分段上传",
-                size_mb
-            ),
-        ));
+    // 2. 解码并切分; 无法解码时 (缺少 ffmpeg) 小文件按原格式整体上传
+    let ffmpeg_path = speech::current_config().ffmpeg_path;
+    let (source, windows, duration) = match transcription::decode(ffmpeg_path.as_deref(), &audio_bytes).await {
+        Ok(pcm) => {
+            drop(audio_bytes);
+            // 分段 WAV 也必须低于内联上限
+            let max_seconds = INLINE_AUDIO_LIMIT as f64 * 0.9 / (pcm.sample_rate as f64 * 2.0);
+            let segment = (config.segment_seconds as f64).min(max_seconds);
+            let windows = transcription::plan_windows(pcm.duration(), segment, config.overlap_seconds as f64);
+            let duration = pcm.duration();
+            (ClipSource::Pcm(pcm), windows, Some(duration))
+        }
+        Err(e) => {
+            if AudioProcessor::exceeds_size_limit(audio_bytes.len()) {
+                let size_mb = audio_bytes.len() as f64 / (1024.0 * 1024.0);
+                return Err((
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!(
+                        "音频文件过大 ({:.1} MB)，超过 15 MB 需要服务端分段，但解码失败: {}。请安装 ffmpeg 或上传 16bit PCM WAV",
+                        size_mb, e
+                    ),
+                ));
+            }
+            debug!("音频解码失败，使用 Inline Data 整体上传: {}", e);
+            let mime_type = AudioProcessor::detect_mime_type(&file_name)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            (ClipSource::Raw(mime_type, audio_bytes), vec![(0.0, 0.0)], None)
+        }
+    };
+
+    info!("音频分为 {} 段，并发 {}", windows.len(), config.max_concurrency.max(1));
+
+    // 3. 分段并发转录 (按账号池调度); 每段的 WAV 与 base64 请求体在轮到该段时才生成，避免同时驻留内存
+    let results: Vec<Result<(Value, String), (StatusCode, String)>> = futures::stream::iter(windows.clone())
+        .map(|(start, end)| {
+            let request = match &source {
+                ClipSource::Pcm(pcm) => transcription::build_request(
+                    task,
+                    prompt.as_deref(),
+                    language.as_deref(),
+                    "audio/wav",
+                    &pcm.slice_wav(start, end),
+                ),
+                ClipSource::Raw(mime_type, data) => {
+                    transcription::build_request(task, prompt.as_deref(), language.as_deref(), mime_type, data)
+                }
+            };
+            let state = state.clone();
+            let model = model.clone();
+            async move { generate_with_retry(&state, &model, &request, "audio").await }
+        })
+        .buffered(config.max_concurrency.max(1))
+        .collect()
+        .await;
+    drop(source);
+
+    let mut parts = Vec::with_capacity(results.len());
+    let mut emails: Vec<String> = Vec::new();
+    for (idx, result) in results.into_iter().enumerate() {
+        let (response, email) = result?;
+        let (start, end) = windows[idx];
+        let clip_duration = duration.map(|_| end - start);
+        parts.push(transcription::parse_segments(&response, clip_duration).map_err(|e| (StatusCode::BAD_GATEWAY, e))?);
+        if !emails.contains(&email) {
+            emails.push(email);
+        }
+    }
+
+    // 4. 拼接并按格式输出
+    let segments = transcription::stitch(&windows, parts);
+    let duration = duration.unwrap_or_else(|| segments.last().map(|s| s.end).unwrap_or(0.0));
+    let (content_type, body) = transcription::render(format, task, language.as_deref(), duration, &segments);
+
+    info!("音频转录完成: {} 个片段, {} 字符", segments.len(), body.len());
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (HeaderName::from_static("x-account-email"), emails.join(",")),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// 合成单段语音，返回 (PCM, 采样率, 账号)
+async fn synthesize_speech_chunk(
+    state: &AppState,
+    model: &str,
+    request: &Value,
+) -> Result<(Vec<u8>, u32, String), (StatusCode, String)> {
+    let (response, email) = generate_with_retry(state, model, request, "speech").await?;
+    let (pcm, sample_rate) = speech::extract_pcm(&response).map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    Ok((pcm, sample_rate, email))
+}
+
+/// 处理语音合成请求 (OpenAI /v1/audio/speech 兼容)
+pub async fn handle_audio_speech(
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Result<Response, (StatusCode, String)> {
+    let config = speech::current_config();
+
+    let input = body
+        .get("input")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .ok_or((StatusCode::BAD_REQUEST, "Missing 'input' field".to_string()))?;
+    let model = speech::resolve_model(&config, body.get("model").and_then(|v| v.as_str()));
+    let voice = speech::resolve_voice(&config, body.get("voice").and_then(|v| v.as_str()));
+    let format = speech::SpeechFormat::parse(
+        body.get("response_format")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&config.default_format),
+    )
+    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    // Gemini TTS 没有语速参数，通过风格指令表达
+    let mut instructions = body
+        .get("instructions")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    if let Some(speed) = body.get("speed").and_then(|v| v.as_f64()) {
+        if (speed - 1.0).abs() > f64::EPSILON {
+            if !instructions.is_empty() {
+                instructions.push(' ');
+            }
+            instructions.push_str(&format!("Speak at {:.2}x the normal speaking rate.", speed.clamp(0.25, 4.0)));
+        }
     }
+    let instructions = (!instructions.is_empty()).then_some(instructions);
+
+    let chunks = speech::split_text(input, config.max_chunk_chars);
+    info!(
+        "收到语音合成请求: 模型={}, 音色={}, 格式={:?}, 字符={}, 分段={}",
+        model,
+        voice,
+        format,
+        input.chars().count(),
+        chunks.len()
+    );
+
+    let requests: Vec<Value> = chunks
+        .iter()
+        .map(|chunk| speech::build_request(chunk, &voice, instructions.as_deref()))
+        .collect();
+
+    // 首段同步合成，失败时可直接返回错误状态码
+    let (first_pcm, sample_rate, email) = synthesize_speech_chunk(&state, &model, &requests[0]).await?;
+
+    // 多段 wav/pcm: 边合成边输出
+    if format.is_streamable() && requests.len() > 1 {
+        let mut prefix = if format == speech::SpeechFormat::Wav {
+            speech::wav_header(sample_rate, None)
+        } else {
+            Vec::new()
+        };
+        prefix.extend_from_slice(&first_pcm);
 
-    // 4. 使用 Inline Data 方式
-    debug!("使用 Inline Data 方式处理");
-    let base64_audio = AudioProcessor::encode_to_base64(&audio_bytes);
-
-    // 5. 构建 Gemini 请求
-    let gemini_request = json!({
-        "contents": [{
-            "parts": [
-                {"text": prompt},
-                {
-                    "inlineData": {
-                        "mimeType": mime_type,
-                        "data": base64_audio
+        let stream_model = model.clone();
+        let stream = async_stream::stream! {
+            yield Ok::<Bytes, std::io::Error>(Bytes::from(prefix));
+            for (idx, request) in requests.iter().enumerate().skip(1) {
+                match synthesize_speech_chunk(&state, &stream_model, request).await {
+                    Ok((pcm, _, _)) => yield Ok(Bytes::from(pcm)),
+                    Err((_, e)) => {
+                        tracing::error!("[Speech] Chunk {} failed, aborting stream: {}", idx, e);
+                        yield Err(std::io::Error::other(e));
+                        break;
                     }
                 }
-            ]
-        }]
-    });
-
-    // 6. 获取 Token 和上游客户端
-    let token_manager = state.token_manager;
-    let (access_token, project_id, email) = token_manager
-        .get_token("text", false, None, &model)
-        .await
-        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
-
-    info!("使用账号: {}", email);
-
-    // 7. 包装请求为 v1internal 格式
-    let wrapped_body = json!({
-        "project": project_id,
-        "requestId": format!("audio-{}", Uuid::new_v4()),
-        "request": gemini_request,
-        "model": model,
-        "userAgent": "antigravity",
-        "requestType": "text"
-    });
-
-    // 8. 发送请求到 Gemini
-    let upstream = state.upstream.clone();
-    let response = upstream
-        .call_v1_internal("generateContent", &access_token, wrapped_body, None)
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("上游请求失败: {}", e)))?;
-
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("Gemini API 错误: {}", error_text),
-        ));
+            }
+        };
+
+        return Ok((
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, format.content_type().to_string()),
+                (HeaderName::from_static("x-account-email"), email),
+            ],
+            Body::from_stream(stream),
+        )
+            .into_response());
+    }
+
+    let mut pcm = first_pcm;
+    for request in requests.iter().skip(1) {
+        let (chunk_pcm, _, _) = synthesize_speech_chunk(&state, &model, request).await?;
+        pcm.extend_from_slice(&chunk_pcm);
     }
 
-    let result: Value = response
-        .json()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("解析响应失败: {}", e)))?;
-
-    // 9. 提取文本响应（解包 v1internal 响应）
-    let inner_response = result.get("response").unwrap_or(&result);
-    let text = inner_response
-        .get("candidates")
-        .and_then(|c| c.get(0))
-        .and_then(|c| c.get("content"))
-        .and_then(|c| c.get("parts"))
-        .and_then(|p| p.get(0))
-        .and_then(|p| p.get("text"))
-        .and_then(|t| t.as_str())
-        .unwrap_or("");
-
-    info!("音频转录完成，返回 {} 字符", text.len());
-
-    // 10. 返回标准格式响应
+    let audio = match format {
+        speech::SpeechFormat::Pcm => pcm,
+        speech::SpeechFormat::Wav => {
+            let mut wav = speech::wav_header(sample_rate, Some(pcm.len() as u32));
+            wav.extend_from_slice(&pcm);
+            wav
+        }
+        _ => speech::transcode(config.ffmpeg_path.as_deref(), pcm, sample_rate, format)
+            .await
+            .map_err(|e| (StatusCode::NOT_IMPLEMENTED, e))?,
+    };
+
+    info!("语音合成完成，返回 {} bytes", audio.len());
+
     Ok((
         StatusCode::OK,
-        [("X-Account-Email", email.as_str())],
-        Json(json!({
-            "text": text
-        }))
-    ).into_response())
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (HeaderName::from_static("x-account-email"), email),
+        ],
+        audio,
+    )
+        .into_response())
 }
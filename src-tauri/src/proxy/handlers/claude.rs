@@ -15,14 +15,20 @@ use tracing::{debug, error, info};
 use crate::proxy::mappers::claude::{
     transform_claude_request_in, transform_response, create_claude_sse_stream, ClaudeRequest,
     filter_invalid_thinking_blocks_with_family, close_tool_loop_for_thinking,
-    clean_cache_control_from_messages, merge_consecutive_messages,
+    clean_cache_control_from_messages, merge_consecutive_messages, GeminiByteStream, RetrievalFollowUp,
 };
 use crate::proxy::server::AppState;
+use crate::proxy::session_manager::SESSION_HEADER;
 use crate::proxy::mappers::context_manager::{ContextManager, PurificationStrategy};
+use crate::proxy::mappers::tool_call_repair::ToolSchemas;
+use crate::proxy::mappers::tool_result_compressor;
 use axum::http::HeaderMap;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
+/// 工具调用不符合 schema 时最多把错误回传给模型的次数
+const MAX_TOOL_CALL_REPROMPTS: usize = 2;
 const MIN_SIGNATURE_LENGTH: usize = 10;  // 最小有效签名长度
 
 // ===== Model Constants for Background Tasks =====
@@ -162,6 +168,112 @@ async fn apply_retry_strategy(
     }
 }
 
+/// 修复后仍不符合 input_schema 的工具调用 (用于决定是否让模型重新生成)
+fn invalid_tool_call_error(
+    response: &crate::proxy::mappers::claude::ClaudeResponse,
+    schemas: Option<&ToolSchemas>,
+) -> Option<String> {
+    let schemas = schemas?;
+    let errors: Vec<String> = response
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            crate::proxy::mappers::claude::ContentBlock::ToolUse { name, input, .. } => schemas
+                .validate(name, input)
+                .err()
+                .map(|errs| format!("{}: {}", name, errs.join("; "))),
+            _ => None,
+        })
+        .collect();
+    if errors.is_empty() {
+        None
+    } else {
+        Some(format!("Invalid tool call arguments ({})", errors.join(" | ")))
+    }
+}
+
+/// 构建检索工具的续写回调: 仅当请求注入了检索工具时启用
+fn retrieval_follow_up(
+    gemini_body: &Value,
+    upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
+    access_token: String,
+    extra_headers: std::collections::HashMap<String, String>,
+) -> Option<RetrievalFollowUp> {
+    let has_retrieval_tool = gemini_body["request"]["tools"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|t| t["functionDeclarations"].as_array())
+        .flatten()
+        .any(|d| d["name"] == tool_result_compressor::RETRIEVAL_TOOL_NAME);
+    if !has_retrieval_tool {
+        return None;
+    }
+
+    let mut body = gemini_body.clone();
+    Some(Box::new(move |turns: Vec<Value>| {
+        if let Some(contents) = body["request"]["contents"].as_array_mut() {
+            contents.extend(turns);
+        }
+        let body = body.clone();
+        let upstream = upstream.clone();
+        let access_token = access_token.clone();
+        let extra_headers = extra_headers.clone();
+        Box::pin(async move {
+            let response = upstream
+                .call_v1_internal_with_headers("streamGenerateContent", &access_token, body, Some("alt=sse"), extra_headers)
+                .await?;
+            if !response.status().is_success() {
+                let status = response.status().as_u16();
+                let text = response.text().await.unwrap_or_default();
+                return Err(format!("HTTP {}: {}", status, text));
+            }
+            Ok(Box::pin(response.bytes_stream()) as GeminiByteStream)
+        })
+    }))
+}
+
+/// 把本轮回复与每个工具调用的校验结果 (is_error 的 tool_result) 追加到对话，让模型重新生成工具调用
+fn append_tool_call_feedback(
+    request: &mut ClaudeRequest,
+    response: &crate::proxy::mappers::claude::ClaudeResponse,
+    schemas: &ToolSchemas,
+) {
+    use crate::proxy::mappers::claude::models::{ContentBlock, Message, MessageContent};
+
+    let results: Vec<ContentBlock> = response
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, input, .. } => {
+                let content = match schemas.validate(name, input) {
+                    Err(errors) => format!(
+                        "Invalid arguments for tool '{}': {}. Call the tool again with arguments that match its input_schema.",
+                        name,
+                        errors.join("; ")
+                    ),
+                    Ok(()) => "Not executed because another tool call in this turn had invalid arguments. Call it again if still needed.".to_string(),
+                };
+                Some(ContentBlock::ToolResult {
+                    tool_use_id: id.clone(),
+                    content: json!(content),
+                    is_error: Some(true),
+                })
+            }
+            _ => None,
+        })
+        .collect();
+
+    request.messages.push(Message {
+        role: "assistant".to_string(),
+        content: MessageContent::Array(response.content.clone()),
+    });
+    request.messages.push(Message {
+        role: "user".to_string(),
+        content: MessageContent::Array(results),
+    });
+}
+
 /// 判断是否应该轮换账号
 fn should_rotate_account(status_code: u16) -> bool {
     match status_code {
@@ -318,6 +430,17 @@ pub async fn handle_messages(
     // [NEW] 获取上下文缩放配置
     let scaling_enabled = state.experimental.read().await.enable_usage_scaling;
 
+    // [NEW] 工具调用参数的 schema 修复 / 重新生成配置
+    let (repair_tool_args, reemit_invalid_tool_calls, compaction_settings) = {
+        let exp = state.experimental.read().await;
+        let compaction = exp.enable_context_compaction.then(|| crate::proxy::compaction::CompactionSettings {
+            threshold: exp.context_compaction_threshold,
+            keep_recent: exp.context_compaction_keep_recent,
+            model: exp.context_compaction_model.clone(),
+        });
+        (exp.repair_tool_call_args, exp.reemit_invalid_tool_calls, compaction)
+    };
+
     // 获取最新一条“有意义”的消息内容（用于日志记录和后台任务检测）
     // 策略：反向遍历，首先筛选出所有角色为 "user" 的消息，然后从中找到第一条非 "Warmup" 且非空的文本消息
     // 获取最新一条“有意义”的消息内容（用于日志记录和后台任务检测）
@@ -444,8 +567,9 @@ pub async fn handle_messages(
         let config = crate::proxy::mappers::common_utils::resolve_request_config(&request_for_body.model, &mapped_model, &tools_val);
 
         // 0. 尝试提取 session_id 用于粘性调度 (Phase 2/3)
-        // 使用 SessionManager 生成稳定的会话指纹
-        let session_id_str = crate::proxy::session_manager::SessionManager::extract_session_id(&request_for_body);
+        // 使用 SessionManager 生成稳定的会话指纹 (客户端可通过 X-Session-Id 显式指定)
+        let session_id_str = crate::proxy::session_manager::SessionManager::client_session_id(&headers)
+            .unwrap_or_else(|| crate::proxy::session_manager::SessionManager::extract_session_id(&request_for_body));
         let session_id = Some(session_id_str.as_str());
 
         let force_rotate_token = attempt > 0;
@@ -560,8 +684,41 @@ pub async fn handle_messages(
             }
         }
 
+        // ===== [Context Compaction] 清洗后仍超出模型窗口时，总结较早的轮次 =====
+        let mut compaction_header = "false".to_string();
+        if let Some(settings) = &compaction_settings {
+            match crate::proxy::compaction::compact_request(
+                &upstream,
+                &access_token,
+                &project_id,
+                &session_id_str,
+                &mapped_model,
+                &mut request_with_mapped,
+                settings,
+                &trace_id,
+            )
+            .await
+            {
+                Ok(Some(outcome)) => compaction_header = outcome.header_value(),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("[{}] [Compaction] Failed, sending full history: {}", trace_id, e),
+            }
+        }
+
         request_with_mapped.model = mapped_model;
 
+        // 按最终发送给上游的 tools 构建 schema 表 (上面可能已经移除了工具)
+        let tool_schemas = if repair_tool_args {
+            request_with_mapped
+                .tools
+                .as_deref()
+                .map(ToolSchemas::from_claude_tools)
+                .filter(|s| !s.is_empty())
+                .map(Arc::new)
+        } else {
+            None
+        };
+
         // 生成 Trace ID (简单用时间戳后缀)
         // let _trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
 
@@ -603,6 +760,12 @@ pub async fn handle_messages(
             tracing::debug!("[{}] Added Beta Header: interleaved-thinking-2025-05-14", trace_id);
         }
 
+        let follow_up = if actual_stream {
+            retrieval_follow_up(&gemini_body, upstream.clone(), access_token.clone(), extra_headers.clone())
+        } else {
+            None
+        };
+
         // 5. 上游调用
         let response = match upstream
             .call_v1_internal_with_headers(method, &access_token, gemini_body, query, extra_headers.clone())
@@ -640,7 +803,9 @@ pub async fn handle_messages(
                     email.clone(),
                     Some(session_id_str.clone()),
                     scaling_enabled,
-                    context_limit
+                    context_limit,
+                    tool_schemas.clone(),
+                    follow_up,
                 );
 
                 let mut first_data_chunk = None;
@@ -711,8 +876,10 @@ pub async fn handle_messages(
                                 .header(header::CACHE_CONTROL, "no-cache")
                                 .header(header::CONNECTION, "keep-alive")
                                 .header("X-Account-Email", &email)
+                                .header(SESSION_HEADER, &session_id_str)
                                 .header("X-Mapped-Model", &request_with_mapped.model)
                                 .header("X-Context-Purified", if is_purified { "true" } else { "false" })
+                                .header("X-Context-Compacted", &compaction_header)
                                 .body(Body::from_stream(combined_stream))
                                 .unwrap();
                         } else {
@@ -720,14 +887,63 @@ pub async fn handle_messages(
                             use crate::proxy::mappers::claude::collect_stream_to_json;
                             
                             match collect_stream_to_json(combined_stream).await {
-                                Ok(full_response) => {
+                                Ok(mut full_response) => {
+                                    // 在同一账号上把校验错误回传给模型，让其重新生成工具调用
+                                    let mut reprompts = 0;
+                                    while reemit_invalid_tool_calls && reprompts < MAX_TOOL_CALL_REPROMPTS {
+                                        let Some(schemas) = tool_schemas.as_deref() else {
+                                            break;
+                                        };
+                                        let Some(err) = invalid_tool_call_error(&full_response, Some(schemas)) else {
+                                            break;
+                                        };
+                                        reprompts += 1;
+                                        tracing::warn!(
+                                            "[{}] {}, re-prompting the model ({}/{})",
+                                            trace_id, err, reprompts, MAX_TOOL_CALL_REPROMPTS
+                                        );
+                                        append_tool_call_feedback(&mut request_with_mapped, &full_response, schemas);
+
+                                        let reemitted: Result<_, String> = async {
+                                            let body = transform_claude_request_in(&request_with_mapped, &project_id, retried_without_thinking)?;
+                                            let response = upstream
+                                                .call_v1_internal_with_headers("streamGenerateContent", &access_token, body, Some("alt=sse"), extra_headers.clone())
+                                                .await?;
+                                            if !response.status().is_success() {
+                                                let status = response.status().as_u16();
+                                                return Err(format!("HTTP {}: {}", status, response.text().await.unwrap_or_default()));
+                                            }
+                                            let stream = create_claude_sse_stream(
+                                                Box::pin(response.bytes_stream()),
+                                                trace_id.clone(),
+                                                email.clone(),
+                                                Some(session_id_str.clone()),
+                                                scaling_enabled,
+                                                context_limit,
+                                                tool_schemas.clone(),
+                                                None,
+                                            )
+                                            .map(|r| r.map_err(std::io::Error::other));
+                                            collect_stream_to_json(stream).await
+                                        }
+                                        .await;
+                                        match reemitted {
+                                            Ok(response) => full_response = response,
+                                            Err(e) => {
+                                                tracing::warn!("[{}] Re-prompt for invalid tool calls failed: {}", trace_id, e);
+                                                break;
+                                            }
+                                        }
+                                    }
                                     info!("[{}] ✓ Stream collected and converted to JSON", trace_id);
                                     return Response::builder()
                                         .status(StatusCode::OK)
                                         .header(header::CONTENT_TYPE, "application/json")
                                         .header("X-Account-Email", &email)
+                                        .header(SESSION_HEADER, &session_id_str)
                                         .header("X-Mapped-Model", &request_with_mapped.model)
                                         .header("X-Context-Purified", if is_purified { "true" } else { "false" })
+                                        .header("X-Context-Compacted", &compaction_header)
                                         .body(Body::from(serde_json::to_string(&full_response).unwrap()))
                                         .unwrap();
                                 }
@@ -776,7 +992,7 @@ pub async fn handle_messages(
                 // 转换
                 // [FIX #765] Pass session_id and model_name for signature caching
                 let s_id_owned = session_id.map(|s| s.to_string());
-                let claude_response = match transform_response(&gemini_response, scaling_enabled, context_limit, s_id_owned, request_with_mapped.model.clone()) {
+                let claude_response = match transform_response(&gemini_response, scaling_enabled, context_limit, s_id_owned, request_with_mapped.model.clone(), tool_schemas.clone()) {
                     Ok(r) => r,
                     Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Transform error: {}", e)).into_response(),
                 };
@@ -797,7 +1013,7 @@ pub async fn handle_messages(
                     cache_info
                 );
 
-                return (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", request_with_mapped.model.as_str())], Json(claude_response)).into_response();
+                return (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", request_with_mapped.model.as_str()), ("X-Context-Compacted", compaction_header.as_str()), (SESSION_HEADER, session_id_str.as_str())], Json(claude_response)).into_response();
             }
         }
         
@@ -963,25 +1179,12 @@ pub async fn handle_messages(
 
 /// 列出可用模型
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
-    use crate::proxy::common::model_mapping::get_all_dynamic_models;
-
-    let model_ids = get_all_dynamic_models(
-        &state.custom_mapping,
-    ).await;
-
-    let data: Vec<_> = model_ids.into_iter().map(|id| {
-        json!({
-            "id": id,
-            "object": "model",
-            "created": 1706745600,
-            "owned_by": "antigravity"
-        })
-    }).collect();
+    use crate::proxy::model_catalog::{anthropic_list, ModelCatalog};
 
-    Json(json!({
-        "object": "list",
-        "data": data
-    }))
+    let models = ModelCatalog::global()
+        .list(&state.token_manager, &state.upstream, &state.custom_mapping)
+        .await;
+    Json(anthropic_list(&models))
 }
 
 /// 计算 tokens (占位符)
@@ -1,11 +1,11 @@
 // Gemini Handler
-use axum::{extract::State, extract::{Json, Path}, http::StatusCode, response::IntoResponse};
+use axum::{extract::State, extract::{Json, Path}, http::HeaderMap, http::StatusCode, response::IntoResponse};
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
 use crate::proxy::mappers::gemini::{wrap_request, unwrap_response};
 use crate::proxy::server::AppState;
-use crate::proxy::session_manager::SessionManager;
+use crate::proxy::session_manager::{SessionManager, SESSION_HEADER};
  
 const MAX_RETRY_ATTEMPTS: usize = 3;
  
@@ -14,6 +14,7 @@ const MAX_RETRY_ATTEMPTS: usize = 3;
 pub async fn handle_generate(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
+    headers: HeaderMap,
     Json(body): Json<Value>
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // 解析 model:method
@@ -63,7 +64,8 @@ pub async fn handle_generate(
 
         // 4. 获取 Token (使用准确的 request_type)
         // 提取 SessionId (粘性指纹)
-        let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
+        let session_id = SessionManager::client_session_id(&headers)
+            .unwrap_or_else(|| SessionManager::extract_gemini_session_id(&body, &model_name));
 
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
         let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, attempt > 0, Some(&session_id), &config.final_model).await {
@@ -101,11 +103,10 @@ pub async fn handle_generate(
             if is_stream {
                 use axum::body::Body;
                 use axum::response::Response;
-                use bytes::{Bytes, BytesMut};
+                use bytes::Bytes;
                 use futures::StreamExt;
                 
                 let mut response_stream = response.bytes_stream();
-                let mut buffer = BytesMut::new();
                 let s_id = session_id.clone(); // Clone for stream closure
 
                 // [FIX #859] Implement peek logic for Gemini stream to prevent 0-token 200 OK
@@ -143,16 +144,12 @@ pub async fn handle_generate(
                 }
 
                 let stream = async_stream::stream! {
-                    let mut first_data = first_chunk;
+                    // 预读的首个 chunk 与剩余字节流拼接后统一交给 SSE 解码器
+                    let upstream = futures::stream::iter(first_chunk.map(Ok)).chain(response_stream);
+                    let mut events = crate::proxy::common::sse::decode_stream(Box::pin(upstream));
                     loop {
-                        let item = if let Some(fd) = first_data.take() {
-                            Some(Ok(fd))
-                        } else {
-                            response_stream.next().await
-                        };
-
-                        let bytes = match item {
-                            Some(Ok(b)) => b,
+                        let event = match events.next().await {
+                            Some(Ok(event)) => event,
                             Some(Err(e)) => {
                                 error!("[Gemini-SSE] Connection error: {}", e);
                                 yield Err(format!("Stream error: {}", e));
@@ -161,66 +158,49 @@ pub async fn handle_generate(
                             None => break,
                         };
 
-                        debug!("[Gemini-SSE] Received chunk: {} bytes", bytes.len());
-                        buffer.extend_from_slice(&bytes);
-                        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                            let line_raw = buffer.split_to(pos + 1);
-                            if let Ok(line_str) = std::str::from_utf8(&line_raw) {
-                                let line = line_str.trim();
-                                if line.is_empty() { continue; }
-                                
-                                if line.starts_with("data: ") {
-                                    let json_part = line.trim_start_matches("data: ").trim();
-                                    if json_part == "[DONE]" {
-                                        yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
-                                        continue;
-                                    }
-                                    
-                                    match serde_json::from_str::<Value>(json_part) {
-                                        Ok(mut json) => {
-                                            // [FIX #765] Extract thoughtSignature from stream
-                                            let inner_val = if json.get("response").is_some() {
-                                                json.get("response")
-                                            } else {
-                                                Some(&json)
-                                            };
-
-                                            if let Some(resp) = inner_val {
-                                                if let Some(candidates) = resp.get("candidates").and_then(|c| c.as_array()) {
-                                                    for cand in candidates {
-                                                        if let Some(parts) = cand.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
-                                                            for part in parts {
-                                                                if let Some(sig) = part.get("thoughtSignature").and_then(|s| s.as_str()) {
-                                                                    crate::proxy::SignatureCache::global().cache_session_signature(&s_id, sig.to_string());
-                                                                    debug!("[Gemini-SSE] Cached signature (len: {}) for session: {}", sig.len(), s_id);
-                                                                }
-                                                            }
-                                                        }
+                        let json_part = event.data.trim();
+                        if json_part.is_empty() { continue; }
+                        if json_part == "[DONE]" {
+                            yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
+                            continue;
+                        }
+
+                        match serde_json::from_str::<Value>(json_part) {
+                            Ok(mut json) => {
+                                // [FIX #765] Extract thoughtSignature from stream
+                                let inner_val = if json.get("response").is_some() {
+                                    json.get("response")
+                                } else {
+                                    Some(&json)
+                                };
+
+                                if let Some(resp) = inner_val {
+                                    if let Some(candidates) = resp.get("candidates").and_then(|c| c.as_array()) {
+                                        for cand in candidates {
+                                            if let Some(parts) = cand.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
+                                                for part in parts {
+                                                    if let Some(sig) = part.get("thoughtSignature").and_then(|s| s.as_str()) {
+                                                        crate::proxy::SignatureCache::global().cache_session_signature(&s_id, sig.to_string());
+                                                        debug!("[Gemini-SSE] Cached signature (len: {}) for session: {}", sig.len(), s_id);
                                                     }
                                                 }
                                             }
-
-                                            // Unwrap v1internal response wrapper
-                                            if let Some(inner) = json.get_mut("response").map(|v| v.take()) {
-                                                let new_line = format!("data: {}\n\n", serde_json::to_string(&inner).unwrap_or_default());
-                                                yield Ok::<Bytes, String>(Bytes::from(new_line));
-                                            } else {
-                                                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&json).unwrap_or_default())));
-                                            }
-                                        }
-                                        Err(e) => {
-                                            debug!("[Gemini-SSE] JSON parse error: {}, passing raw line", e);
-                                            yield Ok::<Bytes, String>(Bytes::from(format!("{}\n\n", line)));
                                         }
                                     }
+                                }
+
+                                // Unwrap v1internal response wrapper
+                                if let Some(inner) = json.get_mut("response").map(|v| v.take()) {
+                                    let new_line = format!("data: {}\n\n", serde_json::to_string(&inner).unwrap_or_default());
+                                    yield Ok::<Bytes, String>(Bytes::from(new_line));
                                 } else {
-                                    // Non-data lines (comments, etc.)
-                                    yield Ok::<Bytes, String>(Bytes::from(format!("{}\n\n", line)));
+                                    yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&json).unwrap_or_default())));
                                 }
-                            } else {
-                                // Non-UTF8 data? Just pass it through or skip
-                                debug!("[Gemini-SSE] Non-UTF8 line encountered");
-                                yield Ok::<Bytes, String>(line_raw.freeze());
+                            }
+                            Err(e) => {
+                                debug!("[Gemini-SSE] JSON parse error: {}, passing raw event", e);
+                                let raw: String = event.data.split('\n').map(|l| format!("data: {}\n", l)).collect();
+                                yield Ok::<Bytes, String>(Bytes::from(format!("{}\n", raw)));
                             }
                         }
                     }
@@ -232,6 +212,7 @@ pub async fn handle_generate(
                     .header("Cache-Control", "no-cache")
                     .header("Connection", "keep-alive")
                     .header("X-Account-Email", &email)
+                    .header(SESSION_HEADER, &session_id)
                     .header("X-Mapped-Model", &mapped_model)
                     .body(body)
                     .unwrap()
@@ -266,7 +247,7 @@ pub async fn handle_generate(
             }
 
             let unwrapped = unwrap_response(&gemini_resp);
-            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(unwrapped)).into_response());
+            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str()), (SESSION_HEADER, session_id.as_str())], Json(unwrapped)).into_response());
         }
 
         // 处理错误并重试
@@ -303,37 +284,23 @@ pub async fn handle_generate(
 }
 
 pub async fn handle_list_models(State(state): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
-    use crate::proxy::common::model_mapping::get_all_dynamic_models;
-
-    // 获取所有动态模型列表（与 /v1/models 一致）
-    let model_ids = get_all_dynamic_models(
-        &state.custom_mapping,
-    ).await;
-
-    // 转换为 Gemini API 格式
-    let models: Vec<_> = model_ids.into_iter().map(|id| {
-        json!({
-            "name": format!("models/{}", id),
-            "version": "001",
-            "displayName": id.clone(),
-            "description": "",
-            "inputTokenLimit": 128000,
-            "outputTokenLimit": 8192,
-            "supportedGenerationMethods": ["generateContent", "countTokens"],
-            "temperature": 1.0,
-            "topP": 0.95,
-            "topK": 64
-        })
-    }).collect();
-
-    Ok(Json(json!({ "models": models })))
+    use crate::proxy::model_catalog::{gemini_list, ModelCatalog};
+
+    // 与 /v1/models 使用同一份模型目录
+    let models = ModelCatalog::global()
+        .list(&state.token_manager, &state.upstream, &state.custom_mapping)
+        .await;
+    Ok(Json(gemini_list(&models)))
 }
 
-pub async fn handle_get_model(Path(model_name): Path<String>) -> impl IntoResponse {
-    Json(json!({
-        "name": format!("models/{}", model_name),
-        "displayName": model_name
-    }))
+pub async fn handle_get_model(State(state): State<AppState>, Path(model_name): Path<String>) -> impl IntoResponse {
+    use crate::proxy::model_catalog::{gemini_model, ModelCatalog};
+
+    let models = ModelCatalog::global()
+        .list(&state.token_manager, &state.upstream, &state.custom_mapping)
+        .await;
+    let info = models.into_iter().find(|m| m.id == model_name).and_then(|m| m.info);
+    Json(gemini_model(&model_name, info.as_ref()))
 }
 
 pub async fn handle_count_tokens(State(state): State<AppState>, Path(_model_name): Path<String>, Json(_body): Json<Value>) -> Result<impl IntoResponse, (StatusCode, String)> {
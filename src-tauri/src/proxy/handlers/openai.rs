@@ -1,5 +1,5 @@
 // OpenAI Handler
-use axum::{extract::Json, extract::State, http::StatusCode, response::IntoResponse, response::Response};
+use axum::{extract::Json, extract::State, http::HeaderMap, http::StatusCode, response::IntoResponse, response::Response};
 use base64::Engine as _; 
 use bytes::Bytes;
 use serde_json::{json, Value};
@@ -12,7 +12,10 @@ use crate::proxy::mappers::openai::{
 use crate::proxy::server::AppState;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
-use crate::proxy::session_manager::SessionManager;
+use crate::proxy::image_store::{self, NewImage};
+use crate::proxy::session_manager::{SessionManager, SESSION_HEADER};
+use crate::proxy::mappers::tool_call_repair::ToolSchemas;
+use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 
 /// 重试策略枚举
@@ -69,6 +72,7 @@ async fn apply_retry_strategy(strategy: RetryStrategy, attempt: usize, status_co
 
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(mut body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // [NEW] 自动检测并转换 Responses 格式
@@ -157,6 +161,29 @@ pub async fn handle_chat_completions(
         &*state.custom_mapping.read().await,
     );
 
+    // 结构化输出校验 (仅非流式且开启了 validate_structured_output 时生效)
+    let output_schema = if state.experimental.read().await.validate_structured_output {
+        openai_req
+            .response_format
+            .as_ref()
+            .and_then(|f| f.schema())
+            .cloned()
+    } else {
+        None
+    };
+
+    // 按请求声明的 tools 修复工具调用参数
+    let tool_schemas = if state.experimental.read().await.repair_tool_call_args {
+        openai_req
+            .tools
+            .as_deref()
+            .map(ToolSchemas::from_openai_tools)
+            .filter(|s| !s.is_empty())
+            .map(Arc::new)
+    } else {
+        None
+    };
+
     for attempt in 0..max_attempts {
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
@@ -170,7 +197,8 @@ pub async fn handle_chat_completions(
         );
 
         // 3. 提取 SessionId (粘性指纹)
-        let session_id = SessionManager::extract_openai_session_id(&openai_req);
+        let session_id = SessionManager::client_session_id(&headers)
+            .unwrap_or_else(|| SessionManager::extract_openai_session_id(&openai_req));
 
         // 4. 获取 Token (使用准确的 request_type)
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
@@ -191,7 +219,7 @@ pub async fn handle_chat_completions(
         info!("✓ Using account: {} (type: {})", email, config.request_type);
 
         // 4. 转换请求
-        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
+        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model, &session_id);
 
         // [New] 打印转换后的报文 (Gemini Body) 供调试
         if let Ok(body_json) = serde_json::to_string_pretty(&gemini_body) {
@@ -239,7 +267,7 @@ pub async fn handle_chat_completions(
                 // [P1 FIX] Enhanced Peek logic to handle heartbeats and slow start
                 // Pre-read until we find meaningful content, skip heartbeats
                 let mut openai_stream =
-                    create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                    create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone(), session_id.clone(), tool_schemas.clone());
                 
                 let mut first_data_chunk = None;
                 let mut retry_this_account = false;
@@ -310,6 +338,7 @@ pub async fn handle_chat_completions(
                         .header("Cache-Control", "no-cache")
                         .header("Connection", "keep-alive")
                         .header("X-Account-Email", &email)
+                        .header(SESSION_HEADER, &session_id)
                         .header("X-Mapped-Model", &mapped_model)
                         .body(body)
                         .unwrap()
@@ -321,13 +350,28 @@ pub async fn handle_chat_completions(
                 }
             }
 
-            let gemini_resp: Value = response
+            let mut gemini_resp: Value = response
                 .json()
                 .await
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            if let Some(schemas) = &tool_schemas {
+                schemas.repair_gemini_response(&mut gemini_resp);
+            }
 
-            let openai_response = transform_openai_response(&gemini_resp);
-            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(openai_response)).into_response());
+            let openai_response = transform_openai_response(&gemini_resp, &session_id);
+            if let Some(schema) = &output_schema {
+                if let Err(errors) = validate_structured_content(&openai_response, schema) {
+                    tracing::warn!(
+                        "[OpenAI] Structured output failed schema validation on attempt {}/{}: {}",
+                        attempt + 1,
+                        max_attempts,
+                        errors.join("; ")
+                    );
+                    last_error = format!("Structured output validation failed: {}", errors.join("; "));
+                    continue;
+                }
+            }
+            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str()), (SESSION_HEADER, session_id.as_str())], Json(openai_response)).into_response());
         }
 
         // 处理特定错误并重试
@@ -421,10 +465,37 @@ pub async fn handle_chat_completions(
     }
 }
 
+/// 校验非流式响应中 choices[0].message.content 是否符合 json_schema
+/// (模型选择调用工具而非输出文本时不做校验)
+fn validate_structured_content(
+    response: &crate::proxy::mappers::openai::OpenAIResponse,
+    schema: &Value,
+) -> Result<(), Vec<String>> {
+    let Some(message) = response.choices.first().map(|c| &c.message) else {
+        return Err(vec!["$: response has no choices".to_string()]);
+    };
+    let text = match &message.content {
+        Some(crate::proxy::mappers::openai::OpenAIContent::String(text)) => text.clone(),
+        Some(crate::proxy::mappers::openai::OpenAIContent::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|b| match b {
+                crate::proxy::mappers::openai::OpenAIContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect(),
+        None if message.tool_calls.is_some() => return Ok(()),
+        None => String::new(),
+    };
+    let instance: Value = serde_json::from_str(text.trim())
+        .map_err(|e| vec![format!("$: content is not valid JSON ({})", e)])?;
+    crate::proxy::common::json_schema::validate_json_schema(&instance, schema)
+}
+
 /// 处理 Legacy Completions API (/v1/completions)
 /// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
 pub async fn handle_completions(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(mut body): Json<Value>,
 ) -> Response {
     info!(
@@ -799,6 +870,18 @@ pub async fn handle_completions(
     );
     let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
 
+    // 按请求声明的 tools 修复工具调用参数
+    let tool_schemas = if state.experimental.read().await.repair_tool_call_args {
+        openai_req
+            .tools
+            .as_deref()
+            .map(ToolSchemas::from_openai_tools)
+            .filter(|s| !s.is_empty())
+            .map(Arc::new)
+    } else {
+        None
+    };
+
     for attempt in 0..max_attempts {
         // 3. 模型配置解析
         // 将 OpenAI 工具转为 Value 数组以便探测联网
@@ -814,7 +897,8 @@ pub async fn handle_completions(
 
         // 3. 提取 SessionId (复用)
         // [New] 使用 TokenManager 内部逻辑提取 session_id，支持粘性调度
-        let session_id_str = SessionManager::extract_openai_session_id(&openai_req);
+        let session_id_str = SessionManager::client_session_id(&headers)
+            .unwrap_or_else(|| SessionManager::extract_openai_session_id(&openai_req));
         let session_id = Some(session_id_str.as_str());
         
         // 重试时强制轮换，除非只是简单的网络抖动但 Claude 逻辑里 attempt > 0 总是 force_rotate
@@ -836,7 +920,7 @@ pub async fn handle_completions(
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
 
-        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
+        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model, &session_id_str);
 
         // [New] 打印转换后的报文 (Gemini Body) 供调试 (Codex 路径) ———— 缩减为 simple debug
         debug!("[Codex-Request] Transformed Gemini Body ({} parts)", 
@@ -875,10 +959,10 @@ pub async fn handle_completions(
                 let gemini_stream = response.bytes_stream();
                 let mut openai_stream = if is_codex_style {
                     use crate::proxy::mappers::openai::streaming::create_codex_sse_stream;
-                    create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone())
+                    create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone(), session_id_str.clone(), tool_schemas.clone())
                 } else {
                     use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
-                    create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone())
+                    create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone(), session_id_str.clone())
                 };
 
                 // [P1 FIX] Enhanced Peek logic to handle heartbeats and slow start
@@ -948,13 +1032,14 @@ pub async fn handle_completions(
                     .header("Cache-Control", "no-cache")
                     .header("Connection", "keep-alive")
                     .header("X-Account-Email", &email)
+                    .header(SESSION_HEADER, &session_id_str)
                     .header("X-Mapped-Model", &mapped_model)
                     .body(Body::from_stream(combined_stream))
                     .unwrap()
                     .into_response();
             }
 
-            let gemini_resp: Value = match response.json().await {
+            let mut gemini_resp: Value = match response.json().await {
                 Ok(json) => json,
                 Err(e) => {
                     return (
@@ -964,8 +1049,11 @@ pub async fn handle_completions(
                     ).into_response();
                 }
             };
+            if let Some(schemas) = &tool_schemas {
+                schemas.repair_gemini_response(&mut gemini_resp);
+            }
 
-            let chat_resp = transform_openai_response(&gemini_resp);
+            let chat_resp = transform_openai_response(&gemini_resp, &session_id_str);
 
             // Map Chat Response -> Legacy Completions Response
             let choices = chat_resp.choices.iter().map(|c| {
@@ -989,7 +1077,7 @@ pub async fn handle_completions(
                 "usage": chat_resp.usage
             });
 
-            return (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(legacy_resp)).into_response();
+            return (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str()), (SESSION_HEADER, session_id_str.as_str())], Json(legacy_resp)).into_response();
         }
 
         // Handle errors and retry
@@ -1037,32 +1125,95 @@ pub async fn handle_completions(
     }
 }
 
-pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
-    use crate::proxy::common::model_mapping::get_all_dynamic_models;
-
-    let model_ids = get_all_dynamic_models(
-        &state.custom_mapping,
-    ).await;
-
-    let data: Vec<_> = model_ids.into_iter().map(|id| {
-        json!({
-            "id": id,
-            "object": "model",
-            "created": 1706745600,
-            "owned_by": "antigravity"
-        })
-    }).collect();
-
-    Json(json!({
-        "object": "list",
-        "data": data
+pub async fn handle_list_models(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    use crate::proxy::model_catalog::{anthropic_list, openai_list, ModelCatalog};
+
+    let models = ModelCatalog::global()
+        .list(&state.token_manager, &state.upstream, &state.custom_mapping)
+        .await;
+
+    // Anthropic SDK 同样请求 /v1/models，按 anthropic-version 头返回其列表格式
+    if headers.contains_key("anthropic-version") {
+        return Json(anthropic_list(&models));
+    }
+    Json(openai_list(&models))
+}
+
+/// 构造单张图片的响应条目: `url` 格式时保存到本地图片存储并返回签名 URL (存储不可用时回退为 data URI)
+async fn build_image_entry(
+    img: &Value,
+    response_format: &str,
+    uri: &axum::http::Uri,
+    headers: &HeaderMap,
+    meta: NewImage,
+) -> Option<Value> {
+    let data = img.get("data").and_then(|v| v.as_str()).filter(|d| !d.is_empty())?;
+    if response_format != "url" {
+        return Some(json!({ "b64_json": data }));
+    }
+    let mime_type = img
+        .get("mimeType")
+        .and_then(|v| v.as_str())
+        .unwrap_or("image/png");
+
+    if let Some(store) = image_store::global() {
+        let (owned_data, owned_mime) = (data.to_string(), mime_type.to_string());
+        let saver = store.clone();
+        let saved = tokio::task::spawn_blocking(move || saver.save_base64(&owned_data, &owned_mime, meta))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r);
+        match saved {
+            Ok(saved) => {
+                return Some(json!({
+                    "url": store.signed_url(&store.base_url(uri, headers), &saved.file_name, chrono::Utc::now().timestamp())
+                }))
+            }
+            Err(e) => tracing::warn!("[Images] Failed to store image: {}", e),
+        }
+    }
+    Some(json!({
+        "url": format!("data:{};base64,{}", mime_type, data)
     }))
 }
 
+#[derive(serde::Deserialize)]
+pub struct ImageFileQuery {
+    expires: i64,
+    sig: String,
+}
+
+/// 下载已保存的图片 (凭签名 URL 访问，无需 API Key)
+pub async fn handle_image_file(
+    axum::extract::Path(file): axum::extract::Path<String>,
+    axum::extract::Query(query): axum::extract::Query<ImageFileQuery>,
+) -> Response {
+    let Some(store) = image_store::global() else {
+        return (StatusCode::NOT_FOUND, "Image storage is disabled").into_response();
+    };
+    if !store.verify(&file, query.expires, &query.sig, chrono::Utc::now().timestamp()) {
+        return (StatusCode::FORBIDDEN, "Invalid or expired signature").into_response();
+    }
+    match store.read(&file) {
+        Ok((mime_type, bytes)) => (
+            StatusCode::OK,
+            [
+                (axum::http::header::CONTENT_TYPE, mime_type),
+                (axum::http::header::CACHE_CONTROL, "private, max-age=3600"),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e).into_response(),
+    }
+}
+
 /// OpenAI Images API: POST /v1/images/generations
 /// 处理图像生成请求，转换为 Gemini API 格式
 pub async fn handle_images_generations(
     State(state): State<AppState>,
+    uri: axum::http::Uri,
+    headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // 1. 解析请求参数
@@ -1222,21 +1373,14 @@ pub async fn handle_images_generations(
                     {
                         for part in parts {
                             if let Some(img) = part.get("inlineData") {
-                                let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
-                                if !data.is_empty() {
-                                    if response_format == "url" {
-                                        let mime_type = img
-                                            .get("mimeType")
-                                            .and_then(|v| v.as_str())
-                                            .unwrap_or("image/png");
-                                        images.push(json!({
-                                            "url": format!("data:{};base64,{}", mime_type, data)
-                                        }));
-                                    } else {
-                                        images.push(json!({
-                                            "b64_json": data
-                                        }));
-                                    }
+                                if let Some(entry) = build_image_entry(img, response_format, &uri, &headers, NewImage {
+                                    kind: "generation",
+                                    prompt: Some(prompt.to_string()),
+                                    model: Some(model.to_string()),
+                                    account: Some(email.clone()),
+                                    size: Some(size.to_string()),
+                                }).await {
+                                    images.push(entry);
                                     tracing::debug!("[Images] Task {} succeeded", idx);
                                 }
                             }
@@ -1297,6 +1441,8 @@ pub async fn handle_images_generations(
 
 pub async fn handle_images_edits(
     State(state): State<AppState>,
+    uri: axum::http::Uri,
+    headers: HeaderMap,
     mut multipart: axum::extract::Multipart,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     tracing::info!("[Images] Received edit request");
@@ -1496,21 +1642,14 @@ pub async fn handle_images_edits(
                     {
                         for part in parts {
                             if let Some(img) = part.get("inlineData") {
-                                let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
-                                if !data.is_empty() {
-                                    if response_format == "url" {
-                                        let mime_type = img
-                                            .get("mimeType")
-                                            .and_then(|v| v.as_str())
-                                            .unwrap_or("image/png");
-                                        images.push(json!({
-                                            "url": format!("data:{};base64,{}", mime_type, data)
-                                        }));
-                                    } else {
-                                        images.push(json!({
-                                            "b64_json": data
-                                        }));
-                                    }
+                                if let Some(entry) = build_image_entry(img, &response_format, &uri, &headers, NewImage {
+                                    kind: "edit",
+                                    prompt: Some(prompt.clone()),
+                                    model: Some(model.clone()),
+                                    account: Some(email.clone()),
+                                    size: Some(size.clone()),
+                                }).await {
+                                    images.push(entry);
                                     tracing::debug!("[Images] Task {} succeeded", idx);
                                 }
                             }
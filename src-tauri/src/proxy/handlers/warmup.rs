@@ -98,6 +98,7 @@ pub async fn handle_warmup(
             top_p: None,
             top_k: None,
             tools: None,
+            tool_choice: None,
             metadata: Some(crate::proxy::mappers::claude::models::Metadata {
                 user_id: Some(session_id),
             }),
@@ -0,0 +1,501 @@
+// 图片存储 (Image Store)
+//
+// 生成/编辑的图片按内容哈希保存在数据目录的 `images/` 下，元数据 (prompt、模型、账号、尺寸) 记录在
+// `images/images.db`。对外通过 `/v1/images/files/<file>?expires=..&sig=..` 提供带过期时间的签名 URL，
+// 该路由不需要 API Key，凭 HMAC 签名访问。
+
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::proxy::config::ImageStorageConfig;
+
+/// 图片文件路由前缀 (鉴权中间件对此前缀放行，由签名校验访问)
+pub const FILE_ROUTE_PREFIX: &str = "/v1/images/files/";
+
+const SIGNING_KEY_FILE: &str = "signing.key";
+
+/// 图片元数据
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ImageMeta {
+    /// 内容 SHA-256
+    pub id: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub bytes: u64,
+    pub created_at: i64,
+    pub kind: String,
+    pub prompt: Option<String>,
+    pub model: Option<String>,
+    pub account: Option<String>,
+    pub size: Option<String>,
+}
+
+/// 保存图片时附带的元数据
+#[derive(Debug, Clone, Default)]
+pub struct NewImage {
+    /// "generation" | "edit"
+    pub kind: &'static str,
+    pub prompt: Option<String>,
+    pub model: Option<String>,
+    pub account: Option<String>,
+    pub size: Option<String>,
+}
+
+/// 列表过滤条件
+#[derive(Debug, Clone, Default)]
+pub struct ImageQuery {
+    pub limit: usize,
+    pub offset: usize,
+    pub model: Option<String>,
+    pub account: Option<String>,
+    pub since: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ImageStoreStats {
+    pub count: u64,
+    pub total_bytes: u64,
+}
+
+pub struct ImageStore {
+    dir: PathBuf,
+    key: Vec<u8>,
+    config: ImageStorageConfig,
+}
+
+static STORE: Lazy<RwLock<Option<Arc<ImageStore>>>> = Lazy::new(|| RwLock::new(None));
+/// 监听端口是否启用 TLS (决定未配置 public_base_url 时返回 https 还是 http 链接)
+static LISTENER_TLS: AtomicBool = AtomicBool::new(false);
+
+/// 记录监听端口的协议 (启动监听时调用)
+pub fn set_listener_tls(enabled: bool) {
+    LISTENER_TLS.store(enabled, Ordering::Relaxed);
+}
+
+/// 应用图片存储配置 (启动及配置热更新时调用)
+pub fn configure(config: ImageStorageConfig) {
+    let store = if config.enabled {
+        crate::modules::account::get_data_dir()
+            .and_then(|d| ImageStore::open(d.join("images"), config))
+            .map_err(|e| tracing::warn!("[ImageStore] Disabled: {}", e))
+            .ok()
+            .map(Arc::new)
+    } else {
+        None
+    };
+    if let Some(store) = store.clone() {
+        let cleanup = move || {
+            if let Err(e) = store.enforce_retention() {
+                tracing::warn!("[ImageStore] Retention cleanup failed: {}", e);
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(cleanup)),
+            Err(_) => cleanup(),
+        }
+    }
+    if let Ok(mut current) = STORE.write() {
+        *current = store;
+    }
+}
+
+/// 当前启用的图片存储
+pub fn global() -> Option<Arc<ImageStore>> {
+    STORE.read().ok().and_then(|s| s.clone())
+}
+
+fn extension_for(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        _ => "png",
+    }
+}
+
+fn mime_for(file_name: &str) -> &'static str {
+    match file_name.rsplit('.').next() {
+        Some("jpg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "image/png",
+    }
+}
+
+/// 合法文件名: 64 位十六进制哈希 + 已知扩展名 (防止路径穿越)
+fn is_valid_file_name(file_name: &str) -> bool {
+    let Some((hash, ext)) = file_name.split_once('.') else {
+        return false;
+    };
+    hash.len() == 64
+        && hash.chars().all(|c| c.is_ascii_hexdigit())
+        && matches!(ext, "png" | "jpg" | "webp" | "gif")
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn signing_mac(key: &[u8], file_name: &str, expires: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}:{}", file_name, expires).as_bytes());
+    mac
+}
+
+fn load_or_create_key(dir: &Path) -> Result<Vec<u8>, String> {
+    let path = dir.join(SIGNING_KEY_FILE);
+    if let Ok(hex) = std::fs::read_to_string(&path) {
+        let key: Vec<u8> = (0..hex.trim().len() / 2)
+            .filter_map(|i| u8::from_str_radix(&hex.trim()[i * 2..i * 2 + 2], 16).ok())
+            .collect();
+        if key.len() == 32 {
+            return Ok(key);
+        }
+    }
+    let key: [u8; 32] = rand::random();
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    write_private(&path, hex.as_bytes())?;
+    Ok(key.to_vec())
+}
+
+/// 写入仅当前用户可读写 (0600) 的文件
+fn write_private(path: &Path, content: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut f| f.write_all(content))
+        .map_err(|e| format!("write {}: {}", path.display(), e))
+}
+
+impl ImageStore {
+    /// 打开 (必要时创建) 存储目录、签名密钥与元数据库
+    pub fn open(dir: PathBuf, config: ImageStorageConfig) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("create {}: {}", dir.display(), e))?;
+        let key = load_or_create_key(&dir)?;
+        let store = Self { dir, key, config };
+        store.connect()?.execute(
+            "CREATE TABLE IF NOT EXISTS images (
+                id TEXT PRIMARY KEY,
+                file_name TEXT NOT NULL,
+                mime_type TEXT NOT NULL,
+                bytes INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                prompt TEXT,
+                model TEXT,
+                account TEXT,
+                size TEXT
+            )",
+            [],
+        ).map_err(|e| e.to_string())?;
+        store.connect()?
+            .execute("CREATE INDEX IF NOT EXISTS idx_images_created_at ON images (created_at)", [])
+            .map_err(|e| e.to_string())?;
+        Ok(store)
+    }
+
+    fn connect(&self) -> Result<Connection, String> {
+        let conn = Connection::open(self.dir.join("images.db")).map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "busy_timeout", 5000).map_err(|e| e.to_string())?;
+        Ok(conn)
+    }
+
+    pub fn url_ttl_seconds(&self) -> u64 {
+        self.config.url_ttl_seconds
+    }
+
+    /// 对外基础地址: 配置优先，否则使用请求的地址 (协议取自监听端口)
+    pub fn base_url(&self, uri: &axum::http::Uri, headers: &axum::http::HeaderMap) -> String {
+        self.base_url_with(LISTENER_TLS.load(Ordering::Relaxed), uri, headers)
+    }
+
+    fn base_url_with(&self, tls: bool, uri: &axum::http::Uri, headers: &axum::http::HeaderMap) -> String {
+        if let Some(base) = self.config.public_base_url.as_deref().filter(|b| !b.trim().is_empty()) {
+            return base.trim_end_matches('/').to_string();
+        }
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        // HTTP/2 请求没有 Host 头，地址在 URI 的 authority (:authority) 中
+        let host = header("x-forwarded-host")
+            .or_else(|| uri.authority().map(|a| a.as_str()))
+            .or_else(|| header("host"))
+            .unwrap_or("127.0.0.1:8045");
+        let scheme = header("x-forwarded-proto").unwrap_or(if tls { "https" } else { "http" });
+        format!("{}://{}", scheme, host)
+    }
+
+    /// 保存 base64 图片并记录元数据 (相同内容只存一份)
+    pub fn save_base64(&self, data: &str, mime_type: &str, meta: NewImage) -> Result<ImageMeta, String> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| format!("invalid image data: {}", e))?;
+        let id = format!("{:x}", Sha256::digest(&bytes));
+        let file_name = format!("{}.{}", id, extension_for(mime_type));
+        let path = self.dir.join(&file_name);
+        if !path.exists() {
+            std::fs::write(&path, &bytes).map_err(|e| format!("write {}: {}", path.display(), e))?;
+        }
+
+        let record = ImageMeta {
+            mime_type: mime_for(&file_name).to_string(),
+            id,
+            file_name,
+            bytes: bytes.len() as u64,
+            created_at: chrono::Utc::now().timestamp(),
+            kind: meta.kind.to_string(),
+            prompt: meta.prompt,
+            model: meta.model,
+            account: meta.account,
+            size: meta.size,
+        };
+        self.connect()?
+            .execute(
+                "INSERT OR REPLACE INTO images (id, file_name, mime_type, bytes, created_at, kind, prompt, model, account, size)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    record.id, record.file_name, record.mime_type, record.bytes as i64, record.created_at,
+                    record.kind, record.prompt, record.model, record.account, record.size
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+
+        if let Err(e) = self.enforce_retention() {
+            tracing::warn!("[ImageStore] Retention cleanup failed: {}", e);
+        }
+        Ok(record)
+    }
+
+    fn signature(&self, file_name: &str, expires: i64) -> String {
+        let mac = signing_mac(&self.key, file_name, expires).finalize().into_bytes();
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(mac)
+    }
+
+    /// 生成带过期时间的签名 URL
+    pub fn signed_url(&self, base_url: &str, file_name: &str, now: i64) -> String {
+        let expires = now + self.config.url_ttl_seconds as i64;
+        format!(
+            "{}{}{}?expires={}&sig={}",
+            base_url,
+            FILE_ROUTE_PREFIX,
+            file_name,
+            expires,
+            self.signature(file_name, expires)
+        )
+    }
+
+    /// 校验签名与有效期
+    pub fn verify(&self, file_name: &str, expires: i64, sig: &str, now: i64) -> bool {
+        if expires < now || !is_valid_file_name(file_name) {
+            return false;
+        }
+        let Ok(sig) = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(sig) else {
+            return false;
+        };
+        // verify_slice 为常量时间比较
+        signing_mac(&self.key, file_name, expires).verify_slice(&sig).is_ok()
+    }
+
+    /// 读取图片文件，返回 (mime, bytes)
+    pub fn read(&self, file_name: &str) -> Result<(&'static str, Vec<u8>), String> {
+        if !is_valid_file_name(file_name) {
+            return Err("invalid file name".to_string());
+        }
+        let bytes = std::fs::read(self.dir.join(file_name)).map_err(|_| "image not found".to_string())?;
+        Ok((mime_for(file_name), bytes))
+    }
+
+    pub fn list(&self, query: &ImageQuery) -> Result<Vec<ImageMeta>, String> {
+        let conn = self.connect()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, file_name, mime_type, bytes, created_at, kind, prompt, model, account, size
+                 FROM images
+                 WHERE (?1 IS NULL OR model = ?1) AND (?2 IS NULL OR account = ?2) AND (?3 IS NULL OR created_at >= ?3)
+                 ORDER BY created_at DESC
+                 LIMIT ?4 OFFSET ?5",
+            )
+            .map_err(|e| e.to_string())?;
+        let limit = if query.limit == 0 { 50 } else { query.limit };
+        let rows = stmt
+            .query_map(
+                params![query.model, query.account, query.since, limit as i64, query.offset as i64],
+                |row| {
+                    Ok(ImageMeta {
+                        id: row.get(0)?,
+                        file_name: row.get(1)?,
+                        mime_type: row.get(2)?,
+                        bytes: row.get::<_, i64>(3)? as u64,
+                        created_at: row.get(4)?,
+                        kind: row.get(5)?,
+                        prompt: row.get(6)?,
+                        model: row.get(7)?,
+                        account: row.get(8)?,
+                        size: row.get(9)?,
+                    })
+                },
+            )
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    pub fn stats(&self) -> Result<ImageStoreStats, String> {
+        self.connect()?
+            .query_row("SELECT COUNT(*), COALESCE(SUM(bytes), 0) FROM images", [], |row| {
+                Ok(ImageStoreStats {
+                    count: row.get::<_, i64>(0)? as u64,
+                    total_bytes: row.get::<_, i64>(1)? as u64,
+                })
+            })
+            .map_err(|e| e.to_string())
+    }
+
+    /// 删除图片及其元数据，返回是否存在
+    pub fn delete(&self, id: &str) -> Result<bool, String> {
+        let conn = self.connect()?;
+        let file_name: Option<String> = conn
+            .query_row("SELECT file_name FROM images WHERE id = ?1", [id], |row| row.get(0))
+            .ok();
+        let Some(file_name) = file_name else {
+            return Ok(false);
+        };
+        conn.execute("DELETE FROM images WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
+        let _ = std::fs::remove_file(self.dir.join(file_name));
+        Ok(true)
+    }
+
+    /// 按保留天数、数量与总大小清理最旧的图片 (在一个事务内批量删除)
+    pub fn enforce_retention(&self) -> Result<usize, String> {
+        let cutoff = (self.config.retention_days > 0)
+            .then(|| chrono::Utc::now().timestamp() - self.config.retention_days as i64 * 86400);
+        let max_bytes = (self.config.max_total_mb * 1024 * 1024) as i64;
+
+        let mut conn = self.connect()?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let expired: Vec<(String, String)> = {
+            // 按时间倒序累计数量与大小，超出任一限制的记录即为过期
+            let mut stmt = tx
+                .prepare(
+                    "SELECT id, file_name FROM (
+                        SELECT id, file_name, created_at,
+                               ROW_NUMBER() OVER (ORDER BY created_at DESC, rowid DESC) AS position,
+                               SUM(bytes) OVER (ORDER BY created_at DESC, rowid DESC ROWS UNBOUNDED PRECEDING) AS kept_bytes
+                        FROM images
+                     )
+                     WHERE (?1 IS NOT NULL AND created_at < ?1)
+                        OR (?2 > 0 AND position > ?2)
+                        OR (?3 > 0 AND kept_bytes > ?3)",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(params![cutoff, self.config.max_images as i64, max_bytes], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+        };
+        if expired.is_empty() {
+            return Ok(0);
+        }
+        {
+            let mut delete = tx.prepare("DELETE FROM images WHERE id = ?1").map_err(|e| e.to_string())?;
+            for (id, _) in &expired {
+                delete.execute([id]).map_err(|e| e.to_string())?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+
+        for (_, file_name) in &expired {
+            let _ = std::fs::remove_file(self.dir.join(file_name));
+        }
+        tracing::info!("[ImageStore] Removed {} image(s) past retention limits", expired.len());
+        Ok(expired.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(config: ImageStorageConfig) -> ImageStore {
+        let dir = std::env::temp_dir().join(format!("image_store_test_{}", uuid::Uuid::new_v4().simple()));
+        ImageStore::open(dir, config).unwrap()
+    }
+
+    #[test]
+    fn test_base_url() {
+        use axum::http::{HeaderMap, HeaderValue, Uri};
+        let store = temp_store(ImageStorageConfig::default());
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("proxy.lan:8045"));
+
+        let path_only: Uri = "/v1/images/generations".parse().unwrap();
+        assert_eq!(store.base_url_with(false, &path_only, &headers), "http://proxy.lan:8045");
+        assert_eq!(store.base_url_with(true, &path_only, &headers), "https://proxy.lan:8045");
+
+        // HTTP/2: authority comes from the URI
+        let h2: Uri = "https://proxy.example:8443/v1/images/generations".parse().unwrap();
+        assert_eq!(store.base_url_with(true, &h2, &HeaderMap::new()), "https://proxy.example:8443");
+
+        headers.insert("x-forwarded-host", HeaderValue::from_static("images.example.com"));
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https"));
+        assert_eq!(store.base_url_with(false, &h2, &headers), "https://images.example.com");
+
+        let fixed = temp_store(ImageStorageConfig {
+            public_base_url: Some("https://cdn.example.com/".to_string()),
+            ..ImageStorageConfig::default()
+        });
+        assert_eq!(fixed.base_url_with(false, &path_only, &headers), "https://cdn.example.com");
+    }
+
+    #[test]
+    fn test_save_sign_and_retention() {
+        let store = temp_store(ImageStorageConfig { max_images: 2, ..ImageStorageConfig::default() });
+        let meta = NewImage {
+            kind: "generation",
+            prompt: Some("a cat".to_string()),
+            model: Some("gemini-3-pro-image".to_string()),
+            ..Default::default()
+        };
+
+        let first = store.save_base64("aGVsbG8=", "image/png", meta.clone()).unwrap();
+        assert_eq!(first.file_name, format!("{}.png", first.id));
+        assert_eq!(store.read(&first.file_name).unwrap().1, b"hello");
+        assert!(store.read("../images.db").is_err());
+
+        let url = store.signed_url("http://localhost:8045", &first.file_name, 1_000);
+        let query = url.split_once('?').unwrap().1;
+        let sig = query.split("sig=").nth(1).unwrap();
+        let expires = 1_000 + store.url_ttl_seconds() as i64;
+        assert!(store.verify(&first.file_name, expires, sig, 1_000));
+        assert!(!store.verify(&first.file_name, expires, sig, expires + 1));
+        assert!(!store.verify(&first.file_name, expires + 1, sig, 1_000));
+
+        store.save_base64("d29ybGQ=", "image/jpeg", meta.clone()).unwrap();
+        store.save_base64("Zm9v", "image/webp", meta).unwrap();
+        assert_eq!(store.stats().unwrap().count, 2);
+        assert!(store.read(&first.file_name).is_err());
+        let listed = store.list(&ImageQuery { model: Some("gemini-3-pro-image".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].prompt.as_deref(), Some("a cat"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(store.dir.join(SIGNING_KEY_FILE)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let _ = std::fs::remove_dir_all(&store.dir);
+    }
+}
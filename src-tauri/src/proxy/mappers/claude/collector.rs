@@ -2,6 +2,7 @@
 // 用于非 Stream 请求的自动转换
 
 use super::models::*;
+use crate::proxy::common::sse::SseDecoder;
 use bytes::Bytes;
 use futures::StreamExt;
 use serde_json::{json, Value};
@@ -14,17 +15,6 @@ struct SseEvent {
     data: Value,
 }
 
-/// 解析 SSE 行
-fn parse_sse_line(line: &str) -> Option<(String, String)> {
-    if let Some(colon_pos) = line.find(':') {
-        let key = &line[..colon_pos];
-        let value = line[colon_pos + 1..].trim_start();
-        Some((key.to_string(), value.to_string()))
-    } else {
-        None
-    }
-}
-
 /// 将 SSE Stream 收集为完整的 Claude Response
 ///
 /// 此函数接收一个 SSE 字节流，解析所有事件，并重建完整的 ClaudeResponse 对象。
@@ -35,37 +25,26 @@ pub async fn collect_stream_to_json<S>(
 where
     S: futures::Stream<Item = Result<Bytes, io::Error>> + Unpin,
 {
-    let mut events = Vec::new();
-    let mut current_event_type = String::new();
-    let mut current_data = String::new();
+    let mut decoder = SseDecoder::new();
+    let mut raw_events = Vec::new();
 
-    // 1. 收集所有 SSE 事件
+    // 1. 收集所有 SSE 事件 (事件可能跨 chunk 边界)
     while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
-        let text = String::from_utf8_lossy(&chunk);
-
-        for line in text.lines() {
-            if line.is_empty() {
-                // 空行表示事件结束
-                if !current_data.is_empty() {
-                    if let Ok(data) = serde_json::from_str::<Value>(&current_data) {
-                        events.push(SseEvent {
-                            event_type: current_event_type.clone(),
-                            data,
-                        });
-                    }
-                    current_event_type.clear();
-                    current_data.clear();
-                }
-            } else if let Some((key, value)) = parse_sse_line(line) {
-                match key.as_str() {
-                    "event" => current_event_type = value,
-                    "data" => current_data = value,
-                    _ => {}
-                }
-            }
-        }
+        raw_events.extend(decoder.feed(&chunk));
     }
+    raw_events.extend(decoder.finish());
+
+    let events: Vec<SseEvent> = raw_events
+        .into_iter()
+        .filter_map(|raw| {
+            let data = serde_json::from_str::<Value>(&raw.data).ok()?;
+            Some(SseEvent {
+                event_type: raw.event.unwrap_or_default(),
+                data,
+            })
+        })
+        .collect();
 
     // 2. 重建 ClaudeResponse
     let mut response = ClaudeResponse {
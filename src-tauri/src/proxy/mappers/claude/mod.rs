@@ -17,20 +17,35 @@ pub use thinking_utils::{close_tool_loop_for_thinking, filter_invalid_thinking_b
 pub use collector::collect_stream_to_json;
 
 use bytes::Bytes;
+use futures::future::BoxFuture;
 use futures::Stream;
 use std::pin::Pin;
 
+/// 上游 Gemini SSE 字节流
+pub type GeminiByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>;
+
+/// 检索工具续写回调: 接收需要追加到 contents 的轮次，返回新的上游流
+pub type RetrievalFollowUp =
+    Box<dyn FnMut(Vec<serde_json::Value>) -> BoxFuture<'static, Result<GeminiByteStream, String>> + Send>;
+
+/// 单个请求内代理执行检索工具的最大轮数
+const MAX_RETRIEVAL_ROUNDS: usize = 3;
+
 /// 创建从 Gemini SSE 流到 Claude SSE 流的转换
+///
+/// `retrieval_follow_up` 存在时，模型对检索工具的调用由代理执行并续写到同一条 Claude 流中。
+#[allow(clippy::too_many_arguments)]
 pub fn create_claude_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    gemini_stream: GeminiByteStream,
     trace_id: String,
     email: String,
     session_id: Option<String>, // [NEW v3.3.17] Session ID for signature caching
     scaling_enabled: bool, // [NEW] Flag for context usage scaling
     context_limit: u32,
+    tool_schemas: Option<std::sync::Arc<crate::proxy::mappers::tool_call_repair::ToolSchemas>>,
+    mut retrieval_follow_up: Option<RetrievalFollowUp>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     use async_stream::stream;
-    use bytes::BytesMut;
     use futures::StreamExt;
 
     Box::pin(stream! {
@@ -38,48 +53,66 @@ pub fn create_claude_sse_stream(
         state.session_id = session_id; // Set session ID for signature caching
         state.scaling_enabled = scaling_enabled; // Set scaling enabled flag
         state.context_limit = context_limit;
-        let mut buffer = BytesMut::new();
-
-        loop {
-            // [NEW] 30秒心跳保活: 延长超时时间以兼容长延迟模型
-            let next_chunk = tokio::time::timeout(
-                std::time::Duration::from_secs(30),
-                gemini_stream.next()
-            ).await;
-
-            match next_chunk {
-                Ok(Some(chunk_result)) => {
-                    match chunk_result {
-                        Ok(chunk) => {
-                            buffer.extend_from_slice(&chunk);
-
-                            // Process complete lines
-                            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                                let line_raw = buffer.split_to(pos + 1);
-                                if let Ok(line_str) = std::str::from_utf8(&line_raw) {
-                                    let line = line_str.trim();
-                                    if line.is_empty() { continue; }
-
-                                    if let Some(sse_chunks) = process_sse_line(line, &mut state, &trace_id, &email) {
-                                        for sse_chunk in sse_chunks {
-                                            yield Ok(sse_chunk);
-                                        }
+        state.tool_schemas = tool_schemas;
+        if retrieval_follow_up.is_some() {
+            state.round_parts = Some(Vec::new());
+        }
+        let mut events = crate::proxy::common::sse::decode_stream(gemini_stream);
+        let mut retrieval_rounds = 0;
+
+        'rounds: loop {
+            loop {
+                // [NEW] 30秒心跳保活: 延长超时时间以兼容长延迟模型
+                let next_chunk = tokio::time::timeout(
+                    std::time::Duration::from_secs(30),
+                    events.next()
+                ).await;
+
+                match next_chunk {
+                    Ok(Some(event_result)) => {
+                        match event_result {
+                            Ok(event) => {
+                                if let Some(sse_chunks) = process_sse_data(&event.data, &mut state, &trace_id, &email) {
+                                    for sse_chunk in sse_chunks {
+                                        yield Ok(sse_chunk);
                                     }
                                 }
                             }
+                            Err(e) => {
+                                yield Err(format!("Stream error: {}", e));
+                                break 'rounds;
+                            }
                         }
-                        Err(e) => {
-                            yield Err(format!("Stream error: {}", e));
-                            break;
-                        }
+                    }
+                    Ok(None) => break, // Stream 正常结束
+                    Err(_) => {
+                        // 超时，发送心跳包 (SSE Comment 格式)
+                        yield Ok(Bytes::from(": ping\n\n"));
                     }
                 }
-                Ok(None) => break, // Stream 正常结束
-                Err(_) => {
-                    // 超时，发送心跳包 (SSE Comment 格式)
-                    yield Ok(Bytes::from(": ping\n\n"));
+            }
+
+            // 模型请求读取卸载的工具结果: 执行后带着结果续写
+            if !state.pending_retrievals.is_empty() {
+                let calls = std::mem::take(&mut state.pending_retrievals);
+                let model_parts = state.round_parts.as_mut().map(std::mem::take).unwrap_or_default();
+                match retrieval_follow_up.as_mut() {
+                    Some(follow_up) if retrieval_rounds < MAX_RETRIEVAL_ROUNDS => {
+                        retrieval_rounds += 1;
+                        tracing::info!("[{}] Serving {} offloaded tool result fetch(es), round {}", trace_id, calls.len(), retrieval_rounds);
+                        let turns = crate::proxy::mappers::tool_result_compressor::build_retrieval_turns(&model_parts, &calls);
+                        match follow_up(turns).await {
+                            Ok(next_stream) => {
+                                events = crate::proxy::common::sse::decode_stream(next_stream);
+                                continue 'rounds;
+                            }
+                            Err(e) => tracing::warn!("[{}] Tool result fetch follow-up failed: {}", trace_id, e),
+                        }
+                    }
+                    _ => tracing::warn!("[{}] Dropping {} tool result fetch call(s): follow-up unavailable or round limit reached", trace_id, calls.len()),
                 }
             }
+            break;
         }
 
         // Ensure termination events are sent
@@ -89,18 +122,17 @@ pub fn create_claude_sse_stream(
     })
 }
 
-/// 处理单行 SSE 数据
-fn process_sse_line(line: &str, state: &mut StreamingState, trace_id: &str, email: &str) -> Option<Vec<Bytes>> {
-    if !line.starts_with("data: ") {
-        return None;
-    }
-
-    let data_str = line[6..].trim();
+/// 处理单个 SSE 事件的 data 内容
+fn process_sse_data(data: &str, state: &mut StreamingState, trace_id: &str, email: &str) -> Option<Vec<Bytes>> {
+    let data_str = data.trim();
     if data_str.is_empty() {
         return None;
     }
 
     if data_str == "[DONE]" {
+        if !state.pending_retrievals.is_empty() {
+            return None;
+        }
         let chunks = emit_force_stop(state);
         if chunks.is_empty() {
             return None;
@@ -154,6 +186,9 @@ fn process_sse_line(line: &str, state: &mut StreamingState, trace_id: &str, emai
         .and_then(|p| p.as_array())
     {
         for part_value in parts {
+            if let Some(round_parts) = state.round_parts.as_mut() {
+                round_parts.push(part_value.clone());
+            }
             if let Ok(part) = serde_json::from_value::<GeminiPart>(part_value.clone()) {
                 let mut processor = PartProcessor::new(state);
                 chunks.extend(processor.process(&part));
@@ -207,7 +242,12 @@ fn process_sse_line(line: &str, state: &mut StreamingState, trace_id: &str, emai
              );
         }
 
-        chunks.extend(state.emit_finish(Some(finish_reason), usage.as_ref()));
+        // 有待执行的检索调用时先不结束消息，由外层续写
+        if state.pending_retrievals.is_empty() {
+            chunks.extend(state.emit_finish(Some(finish_reason), usage.as_ref()));
+        } else {
+            chunks.extend(state.end_block());
+        }
     }
 
     if chunks.is_empty() {
@@ -359,9 +399,9 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_process_sse_line_done() {
+    fn test_process_sse_data_done() {
         let mut state = StreamingState::new();
-        let result = process_sse_line("data: [DONE]", &mut state, "test_id", "test@example.com");
+        let result = process_sse_data("[DONE]", &mut state, "test_id", "test@example.com");
         assert!(result.is_some());
         let chunks = result.unwrap();
         assert!(!chunks.is_empty());
@@ -374,12 +414,12 @@ mod tests {
     }
 
     #[test]
-    fn test_process_sse_line_with_text() {
+    fn test_process_sse_data_with_text() {
         let mut state = StreamingState::new();
 
-        let test_data = r#"data: {"candidates":[{"content":{"parts":[{"text":"Hello"}]}}],"usageMetadata":{},"modelVersion":"test","responseId":"123"}"#;
+        let test_data = r#"{"candidates":[{"content":{"parts":[{"text":"Hello"}]}}],"usageMetadata":{},"modelVersion":"test","responseId":"123"}"#;
         
-        let result = process_sse_line(test_data, &mut state, "test_id", "test@example.com");
+        let result = process_sse_data(test_data, &mut state, "test_id", "test@example.com");
         assert!(result.is_some());
 
         let chunks = result.unwrap();
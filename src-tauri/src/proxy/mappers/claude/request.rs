@@ -538,13 +538,27 @@ pub fn transform_claude_request_in(
     }
 
     if let Some(tools_val) = tools {
+        let has_functions = tools_val
+            .as_array()
+            .is_some_and(|arr| arr.iter().any(|t| t.get("functionDeclarations").is_some()));
         inner_request["tools"] = tools_val;
-        // 显式设置工具配置模式为 VALIDATED
+
+        // tool_choice 为 any / tool / none 时按客户端要求设置，否则显式使用 VALIDATED
+        let calling_config = claude_req
+            .tool_choice
+            .as_ref()
+            .filter(|_| has_functions)
+            .and_then(crate::proxy::mappers::common_utils::build_function_calling_config)
+            .filter(|c| c["mode"] != "AUTO")
+            .unwrap_or_else(|| json!({ "mode": "VALIDATED" }));
         inner_request["toolConfig"] = json!({
-            "functionCallingConfig": {
-                "mode": "VALIDATED"
-            }
+            "functionCallingConfig": calling_config
         });
+
+        // 历史中存在被卸载的工具结果时，注入检索工具供模型按需读取 (由代理执行，不会下发给客户端)
+        if has_functions && tool_result_compressor::contains_offload_reference(&inner_request["contents"]) {
+            tool_result_compressor::inject_retrieval_tool(&mut inner_request);
+        }
     }
 
     // Inject googleSearch tool if needed (and not already done by build_tools)
@@ -1115,11 +1129,12 @@ fn build_contents(
                             .unwrap_or_else(|| tool_use_id.clone());
 
                         // [FIX #593] 工具输出压缩: 处理超大工具输出
-                        // 使用智能压缩策略(浏览器快照、大文件提示等)
+                        // 使用智能压缩策略(浏览器快照、大文件提示等)，可按工具名配置策略
                         let mut compacted_content = content.clone();
-                        if let Some(blocks) = compacted_content.as_array_mut() {
-                            tool_result_compressor::sanitize_tool_result_blocks(blocks);
-                        }
+                        let policy_outcome = tool_result_compressor::apply_tool_result_policy(
+                            &func_name,
+                            &mut compacted_content,
+                        );
 
                         // Smart Truncation: strict image removal
                         // Remove all Base64 images from historical tool results to save context.
@@ -1149,7 +1164,7 @@ fn build_contents(
 
                         // Smart Truncation: max chars limit
                         const MAX_TOOL_RESULT_CHARS: usize = 200_000;
-                        if merged_content.len() > MAX_TOOL_RESULT_CHARS {
+                        if !policy_outcome.keep_intact && merged_content.len() > MAX_TOOL_RESULT_CHARS {
                             tracing::warn!("Truncating tool result from {} chars to {}", merged_content.len(), MAX_TOOL_RESULT_CHARS);
                             let mut truncated = merged_content.chars().take(MAX_TOOL_RESULT_CHARS).collect::<String>();
                             truncated.push_str("\n...[truncated output]");
@@ -1740,6 +1755,7 @@ mod tests {
             }],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
             temperature: None,
@@ -1837,6 +1853,7 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
             temperature: None,
@@ -1907,6 +1924,7 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
             temperature: None,
@@ -1979,6 +1997,7 @@ mod tests {
                     // cache_control: None, // removed
                 }
             ]),
+            tool_choice: None,
             stream: false,
             max_tokens: None,
             temperature: None,
@@ -2031,6 +2050,7 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
             temperature: None,
@@ -2081,6 +2101,7 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
             temperature: None,
@@ -2124,6 +2145,7 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
             temperature: None,
@@ -3,29 +3,9 @@
 
 use super::models::*;
 use super::utils::to_claude_usage;
+use crate::proxy::mappers::tool_call_repair::ToolSchemas;
 use serde_json::json;
-
-/// [FIX #547] Helper function to coerce string values to boolean
-/// Gemini sometimes sends boolean parameters as strings (e.g., "true", "-n", "false")
-fn coerce_to_bool(value: &serde_json::Value) -> Option<serde_json::Value> {
-    match value {
-        serde_json::Value::Bool(_) => Some(value.clone()), // Already boolean
-        serde_json::Value::String(s) => {
-            let lower = s.to_lowercase();
-            if lower == "true" || lower == "yes" || lower == "1" || lower == "-n" {
-                Some(serde_json::json!(true))
-            } else if lower == "false" || lower == "no" || lower == "0" {
-                Some(serde_json::json!(false))
-            } else {
-                None // Unknown string, can't coerce
-            }
-        }
-        serde_json::Value::Number(n) => {
-            Some(serde_json::json!(n.as_i64().map(|i| i != 0).unwrap_or(false)))
-        }
-        _ => None,
-    }
-}
+use std::sync::Arc;
 
 /// Known parameter remappings for Gemini → Claude compatibility
 /// [FIX] Gemini sometimes uses different parameter names than specified in tool schema
@@ -171,6 +151,7 @@ pub struct NonStreamingProcessor {
     pub context_limit: u32,
     pub session_id: Option<String>,
     pub model_name: String,
+    pub tool_schemas: Option<Arc<ToolSchemas>>,
 }
 
 impl NonStreamingProcessor {
@@ -186,6 +167,7 @@ impl NonStreamingProcessor {
             context_limit: 1_048_576, // Default to 1M
             session_id,
             model_name,
+            tool_schemas: None,
         }
     }
 
@@ -295,6 +277,15 @@ impl NonStreamingProcessor {
             // [FIX] Remap args for Gemini → Claude compatibility
             let mut args = fc.args.clone().unwrap_or(serde_json::json!({}));
             remap_function_call_args(&tool_name, &mut args);
+            if let Some(schemas) = &self.tool_schemas {
+                let report = schemas.repair(&tool_name, &mut args);
+                if !report.fixes.is_empty() {
+                    tracing::debug!("[Response] Repaired args for '{}': {:?}", tool_name, report.fixes);
+                }
+                if !report.is_valid() {
+                    tracing::warn!("[Response] Tool call '{}' still violates its input_schema: {:?}", tool_name, report.errors);
+                }
+            }
 
             let mut tool_use = ContentBlock::ToolUse {
                 id: tool_id,
@@ -537,8 +528,9 @@ impl NonStreamingProcessor {
 }
 
 /// 转换 Gemini 响应为 Claude 响应 (公共接口)
-pub fn transform_response(gemini_response: &GeminiResponse, scaling_enabled: bool, context_limit: u32, session_id: Option<String>, model_name: String) -> Result<ClaudeResponse, String> {
+pub fn transform_response(gemini_response: &GeminiResponse, scaling_enabled: bool, context_limit: u32, session_id: Option<String>, model_name: String, tool_schemas: Option<Arc<ToolSchemas>>) -> Result<ClaudeResponse, String> {
     let mut processor = NonStreamingProcessor::new(session_id, model_name);
+    processor.tool_schemas = tool_schemas;
     Ok(processor.process(gemini_response, scaling_enabled, context_limit))
 }
 
@@ -575,7 +567,7 @@ mod tests {
             response_id: Some("resp_123".to_string()),
         };
 
-        let result = transform_response(&gemini_resp, false, 1_000_000, None, "gemini-2.5-flash".to_string());
+        let result = transform_response(&gemini_resp, false, 1_000_000, None, "gemini-2.5-flash".to_string(), None);
         assert!(result.is_ok());
 
         let claude_resp = result.unwrap();
@@ -625,7 +617,7 @@ mod tests {
             response_id: Some("resp_456".to_string()),
         };
 
-        let result = transform_response(&gemini_resp, false, 1_000_000, None, "gemini-2.5-flash".to_string());
+        let result = transform_response(&gemini_resp, false, 1_000_000, None, "gemini-2.5-flash".to_string(), None);
         assert!(result.is_ok());
 
         let claude_resp = result.unwrap();
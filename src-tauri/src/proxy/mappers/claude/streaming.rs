@@ -4,31 +4,12 @@
 use super::models::*;
 use super::utils::to_claude_usage;
 // use crate::proxy::mappers::signature_store::store_thought_signature; // Deprecated
+use crate::proxy::mappers::tool_call_repair::ToolSchemas;
+use crate::proxy::mappers::tool_result_compressor;
 use crate::proxy::SignatureCache;
 use bytes::Bytes;
 use serde_json::{json, Value};
-
-/// [FIX #547] Helper function to coerce string values to boolean
-/// Gemini sometimes sends boolean parameters as strings (e.g., "true", "-n", "false")
-fn coerce_to_bool(value: &serde_json::Value) -> Option<serde_json::Value> {
-    match value {
-        serde_json::Value::Bool(_) => Some(value.clone()), // Already boolean
-        serde_json::Value::String(s) => {
-            let lower = s.to_lowercase();
-            if lower == "true" || lower == "yes" || lower == "1" || lower == "-n" {
-                Some(json!(true))
-            } else if lower == "false" || lower == "no" || lower == "0" {
-                Some(json!(false))
-            } else {
-                None // Unknown string, can't coerce
-            }
-        }
-        serde_json::Value::Number(n) => {
-            Some(json!(n.as_i64().map(|i| i != 0).unwrap_or(false)))
-        }
-        _ => None,
-    }
-}
+use std::sync::Arc;
 
 /// Known parameter remappings for Gemini → Claude compatibility
 /// [FIX] Gemini sometimes uses different parameter names than specified in tool schema
@@ -231,6 +212,12 @@ pub struct StreamingState {
     // [NEW] MCP XML Bridge 缓冲区
     pub mcp_xml_buffer: String,
     pub in_mcp_xml: bool,
+    // 请求中声明的工具 schema，用于修复 functionCall 参数 (None 表示不修复)
+    pub tool_schemas: Option<Arc<ToolSchemas>>,
+    // 模型对代理内部检索工具的调用 (原始 Gemini part)，由代理执行后续写，不下发给客户端
+    pub pending_retrievals: Vec<Value>,
+    // 本轮收到的原始 Gemini part，检索续写时作为完整的 model 回合回放 (None 表示不记录)
+    pub round_parts: Option<Vec<Value>>,
 }
 
 impl StreamingState {
@@ -254,6 +241,9 @@ impl StreamingState {
             context_limit: 1_048_576, // Default to 1M
             mcp_xml_buffer: String::new(),
             in_mcp_xml: false,
+            tool_schemas: None,
+            pending_retrievals: Vec::new(),
+            round_parts: None,
         }
     }
 
@@ -625,6 +615,15 @@ impl<'a> PartProcessor<'a> {
 
         // 1. FunctionCall 处理
         if let Some(fc) = &part.function_call {
+            // 检索卸载的工具结果由代理自己执行
+            if fc.name == tool_result_compressor::RETRIEVAL_TOOL_NAME {
+                chunks.extend(self.state.end_block());
+                self.state
+                    .pending_retrievals
+                    .push(serde_json::to_value(part).unwrap_or_default());
+                return chunks;
+            }
+
             // 先处理 trailingSignature (B4/C3 场景)
             if self.state.has_trailing_signature() {
                 chunks.extend(self.state.end_block());
@@ -942,6 +941,16 @@ impl<'a> PartProcessor<'a> {
             }
             remap_function_call_args(&final_tool_name, &mut remapped_args);
 
+            if let Some(schemas) = &self.state.tool_schemas {
+                let report = schemas.repair(&final_tool_name, &mut remapped_args);
+                if !report.fixes.is_empty() {
+                    tracing::debug!("[Streaming] Repaired args for '{}': {:?}", final_tool_name, report.fixes);
+                }
+                if !report.is_valid() {
+                    tracing::warn!("[Streaming] Tool call '{}' still violates its input_schema: {:?}", final_tool_name, report.errors);
+                }
+            }
+
             let json_str =
                 serde_json::to_string(&remapped_args).unwrap_or_else(|_| "{}".to_string());
             chunks.push(
@@ -1027,4 +1036,40 @@ mod tests {
         // 3. content_block_stop
         assert!(output.contains(r#""type":"content_block_stop""#));
     }
+
+    #[test]
+    fn test_function_call_args_repaired_against_schema() {
+        let mut state = StreamingState::new();
+        state.tool_schemas = Some(Arc::new(ToolSchemas::from_claude_tools(&[Tool {
+            type_: None,
+            name: Some("read_lines".to_string()),
+            description: None,
+            input_schema: Some(json!({
+                "type": "object",
+                "properties": {"offset": {"type": "integer"}, "strict": {"type": "boolean"}},
+                "required": ["offset"]
+            })),
+        }])));
+        let mut processor = PartProcessor::new(&mut state);
+
+        let part = GeminiPart {
+            text: None,
+            function_call: Some(FunctionCall {
+                name: "read_lines".to_string(),
+                args: Some(json!({"offset": "10", "strict": "false"})),
+                id: Some("call_repair".to_string()),
+            }),
+            inline_data: None,
+            thought: None,
+            thought_signature: None,
+            function_response: None,
+        };
+
+        let output = processor
+            .process(&part)
+            .iter()
+            .map(|b| String::from_utf8(b.to_vec()).unwrap())
+            .collect::<String>();
+        assert!(output.contains(r#"partial_json":"{\"offset\":10,\"strict\":false}"#));
+    }
 }
@@ -158,6 +158,42 @@ pub fn inject_google_search_tool(body: &mut Value) {
     }
 }
 
+/// 将 OpenAI / Claude 的 tool_choice 转换为 Gemini 的 functionCallingConfig
+///
+/// - OpenAI: "auto" / "none" / "required" / {"type":"function","function":{"name":..}}
+/// - Claude: {"type":"auto"} / {"type":"any"} / {"type":"tool","name":..} / {"type":"none"}
+///
+/// 无法识别的取值返回 None，由调用方保留默认行为
+pub fn build_function_calling_config(tool_choice: &Value) -> Option<Value> {
+    let (mode, name) = match tool_choice {
+        Value::String(mode) => (mode.as_str(), None),
+        Value::Object(obj) => {
+            let mode = obj.get("type").and_then(|v| v.as_str()).unwrap_or("auto");
+            let name = obj
+                .get("function")
+                .and_then(|f| f.get("name"))
+                .or(obj.get("name"))
+                .and_then(|v| v.as_str());
+            (mode, name)
+        }
+        _ => return None,
+    };
+
+    match (mode, name) {
+        ("auto", _) => Some(json!({ "mode": "AUTO" })),
+        ("none", _) => Some(json!({ "mode": "NONE" })),
+        ("required" | "any", _) => Some(json!({ "mode": "ANY" })),
+        ("function" | "tool", Some(name)) => Some(json!({
+            "mode": "ANY",
+            "allowedFunctionNames": [name]
+        })),
+        _ => {
+            tracing::warn!("[ToolChoice] Unsupported tool_choice ignored: {}", tool_choice);
+            None
+        }
+    }
+}
+
 /// 深度迭代清理客户端发送的 [undefined] 脏字符串，防止 Gemini 接口校验失败
 pub fn deep_clean_undefined(value: &mut Value) {
     match value {
@@ -336,4 +372,23 @@ mod tests {
          assert_eq!(config_4k_wide["imageSize"], "4K");
          assert_eq!(config_4k_wide["aspectRatio"], "21:9");
     }
+
+    #[test]
+    fn test_build_function_calling_config() {
+        assert_eq!(build_function_calling_config(&json!("auto")), Some(json!({"mode": "AUTO"})));
+        assert_eq!(build_function_calling_config(&json!("none")), Some(json!({"mode": "NONE"})));
+        assert_eq!(build_function_calling_config(&json!("required")), Some(json!({"mode": "ANY"})));
+        assert_eq!(
+            build_function_calling_config(&json!({"type": "function", "function": {"name": "get_weather"}})),
+            Some(json!({"mode": "ANY", "allowedFunctionNames": ["get_weather"]}))
+        );
+
+        // Claude 格式
+        assert_eq!(build_function_calling_config(&json!({"type": "any"})), Some(json!({"mode": "ANY"})));
+        assert_eq!(
+            build_function_calling_config(&json!({"type": "tool", "name": "search"})),
+            Some(json!({"mode": "ANY", "allowedFunctionNames": ["search"]}))
+        );
+        assert_eq!(build_function_calling_config(&json!({"type": "tool"})), None);
+    }
 }
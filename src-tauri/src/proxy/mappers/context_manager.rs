@@ -2,6 +2,8 @@
 //! 
 //! Responsible for estimating token usage and purifying context (stripping thinking blocks)
 //! to prevent "Prompt is too long" errors and avoid invalid signatures.
+//! Also provides the pure parts of conversation compaction (split planning, transcript
+//! rendering, summary splicing); the upstream summarization call lives in `proxy::compaction`.
 
 use super::claude::models::{ClaudeRequest, Message, MessageContent, ContentBlock, SystemPrompt};
 use tracing::{info, debug};
@@ -30,6 +32,44 @@ fn estimate_tokens_from_str(s: &str) -> u32 {
     (s.len() as f32 / 3.5).ceil() as u32
 }
 
+/// Max characters of a single tool input/result kept in the compaction transcript
+const TRANSCRIPT_BLOCK_CHARS: usize = 2_000;
+
+/// Marker prefix of the synthetic summary message inserted by compaction
+pub const COMPACTION_SUMMARY_PREFIX: &str = "[Summary of earlier conversation]";
+
+fn truncate_chars(s: &str, max_chars: usize) -> String {
+    match s.char_indices().nth(max_chars) {
+        Some((idx, _)) => format!("{}… [truncated]", &s[..idx]),
+        None => s.to_string(),
+    }
+}
+
+fn has_tool_result(msg: &Message) -> bool {
+    matches!(&msg.content, MessageContent::Array(blocks)
+        if blocks.iter().any(|b| matches!(b, ContentBlock::ToolResult { .. })))
+}
+
+/// Whether every tool_result in `messages` answers a tool_use that is also in `messages`
+fn tool_pairs_closed(messages: &[Message]) -> bool {
+    let blocks = || {
+        messages.iter().flat_map(|m| match &m.content {
+            MessageContent::Array(blocks) => blocks.as_slice(),
+            MessageContent::String(_) => &[],
+        })
+    };
+    let uses: std::collections::HashSet<&str> = blocks()
+        .filter_map(|b| match b {
+            ContentBlock::ToolUse { id, .. } => Some(id.as_str()),
+            _ => None,
+        })
+        .collect();
+    blocks().all(|b| match b {
+        ContentBlock::ToolResult { tool_use_id, .. } => uses.contains(tool_use_id.as_str()),
+        _ => true,
+    })
+}
+
 /// Context Manager implementation
 pub struct ContextManager;
 
@@ -193,6 +233,102 @@ impl ContextManager {
 
         modified
     }
+
+    /// Input window of the upstream model (Claude models: 200k, Gemini: see `get_context_limit_for_model`)
+    pub fn model_context_window(model: &str) -> u32 {
+        if model.starts_with("claude") {
+            200_000
+        } else {
+            super::claude::utils::get_context_limit_for_model(model)
+        }
+    }
+
+    /// Choose where to split history for compaction
+    ///
+    /// Returns `split` such that `messages[..split]` can be summarized and `messages[split..]`
+    /// (at least the last `keep_recent` messages) is kept verbatim. The kept part starts either
+    /// with a fresh user turn (no tool_result), or with an assistant turn whose tool_use/tool_result
+    /// pairs all land in the kept part (agent loops, where every later user turn is a tool_result).
+    pub fn plan_compaction(messages: &[Message], keep_recent: usize) -> Option<usize> {
+        let upper = messages.len().saturating_sub(keep_recent.max(1));
+        // Need at least one full user/assistant exchange to summarize
+        (2..=upper).rev().find(|&i| match messages[i].role.as_str() {
+            "user" => !has_tool_result(&messages[i]) && messages[i - 1].role == "assistant",
+            "assistant" => messages[i - 1].role == "user" && tool_pairs_closed(&messages[i..]),
+            _ => false,
+        })
+    }
+
+    /// Render messages as a plain-text transcript for the summarization model
+    pub fn render_transcript(messages: &[Message]) -> String {
+        let mut out = String::new();
+        for msg in messages {
+            let speaker = if msg.role == "assistant" { "Assistant" } else { "User" };
+            let mut lines = Vec::new();
+            match &msg.content {
+                MessageContent::String(s) => lines.push(s.clone()),
+                MessageContent::Array(blocks) => {
+                    for block in blocks {
+                        match block {
+                            ContentBlock::Text { text } => lines.push(text.clone()),
+                            ContentBlock::ToolUse { id, name, input, .. } => lines.push(format!(
+                                "[tool_use {} id={}] {}",
+                                name,
+                                id,
+                                truncate_chars(&input.to_string(), TRANSCRIPT_BLOCK_CHARS)
+                            )),
+                            ContentBlock::ToolResult { tool_use_id, content, is_error } => {
+                                let text = match content {
+                                    serde_json::Value::String(s) => s.clone(),
+                                    serde_json::Value::Array(items) => items
+                                        .iter()
+                                        .filter_map(|i| i.get("text").and_then(|t| t.as_str()))
+                                        .collect::<Vec<_>>()
+                                        .join("\n"),
+                                    other => other.to_string(),
+                                };
+                                let status = if is_error.unwrap_or(false) { " (error)" } else { "" };
+                                lines.push(format!(
+                                    "[tool_result id={}{}] {}",
+                                    tool_use_id,
+                                    status,
+                                    truncate_chars(&text, TRANSCRIPT_BLOCK_CHARS)
+                                ));
+                            }
+                            ContentBlock::Image { .. } => lines.push("[image]".to_string()),
+                            ContentBlock::Document { .. } => lines.push("[document]".to_string()),
+                            // Thinking is not needed to reconstruct the conversation state
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            if !lines.is_empty() {
+                out.push_str(&format!("{}: {}\n\n", speaker, lines.join("\n")));
+            }
+        }
+        out
+    }
+
+    /// Replace `messages[..split]` with a summary exchange (user summary + assistant ack)
+    ///
+    /// When the kept part starts with an assistant turn, only the user summary is inserted.
+    pub fn apply_compaction(messages: &mut Vec<Message>, split: usize, summary: &str) {
+        let split = split.min(messages.len());
+        let mut replacement = vec![Message {
+            role: "user".to_string(),
+            content: MessageContent::String(format!("{}\n\n{}", COMPACTION_SUMMARY_PREFIX, summary.trim())),
+        }];
+        if messages.get(split).is_none_or(|m| m.role != "assistant") {
+            replacement.push(Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Array(vec![ContentBlock::Text {
+                    text: "Understood. I will continue from this summary.".to_string(),
+                }]),
+            });
+        }
+        messages.splice(..split, replacement);
+    }
 }
 
 #[cfg(test)]
@@ -206,6 +342,7 @@ mod tests {
             messages: vec![],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
             temperature: None,
@@ -292,4 +429,68 @@ mod tests {
             assert!(matches!(blocks[0], ContentBlock::Text { .. }));
         }
     }
+
+    fn text_message(role: &str, text: &str) -> Message {
+        Message { role: role.into(), content: MessageContent::String(text.into()) }
+    }
+
+    #[test]
+    fn test_plan_compaction_keeps_tool_pairs_together() {
+        let messages = vec![
+            text_message("user", "Q1"),
+            text_message("assistant", "A1"),
+            text_message("user", "Q2"),
+            Message { role: "assistant".into(), content: MessageContent::Array(vec![
+                ContentBlock::ToolUse { id: "t1".into(), name: "read".into(), input: serde_json::json!({"path": "a"}), signature: None, cache_control: None },
+            ])},
+            Message { role: "user".into(), content: MessageContent::Array(vec![
+                ContentBlock::ToolResult { tool_use_id: "t1".into(), content: serde_json::json!("file a"), is_error: None },
+            ])},
+            text_message("assistant", "A2"),
+            text_message("user", "Q3"),
+        ];
+
+        // keep_recent=3 would split at index 4 (a tool_result), so it moves back to the tool_use at index 3
+        assert_eq!(ContextManager::plan_compaction(&messages, 3), Some(3));
+        assert_eq!(ContextManager::plan_compaction(&messages, 1), Some(6));
+        assert_eq!(ContextManager::plan_compaction(&messages, 6), None);
+
+        let transcript = ContextManager::render_transcript(&messages[..6]);
+        assert!(transcript.contains("[tool_use read id=t1]"));
+        assert!(transcript.contains("[tool_result id=t1] file a"));
+
+        let mut compacted = messages.clone();
+        ContextManager::apply_compaction(&mut compacted, 6, "did stuff");
+        assert_eq!(compacted.len(), 3);
+        assert_eq!(compacted[0].role, "user");
+        assert_eq!(compacted[1].role, "assistant");
+        assert!(matches!(&compacted[2].content, MessageContent::String(s) if s == "Q3"));
+    }
+
+    #[test]
+    fn test_plan_compaction_in_agent_loop() {
+        // One prompt followed by tool_use/tool_result round-trips only
+        let mut messages = vec![text_message("user", "fix the build")];
+        for n in 0..6 {
+            let id = format!("t{}", n);
+            messages.push(Message { role: "assistant".into(), content: MessageContent::Array(vec![
+                ContentBlock::ToolUse { id: id.clone(), name: "bash".into(), input: serde_json::json!({"cmd": "make"}), signature: None, cache_control: None },
+            ])});
+            messages.push(Message { role: "user".into(), content: MessageContent::Array(vec![
+                ContentBlock::ToolResult { tool_use_id: id, content: serde_json::json!("error"), is_error: None },
+            ])});
+        }
+
+        // Split just before an assistant turn so its tool_result stays with it
+        let split = ContextManager::plan_compaction(&messages, 4).unwrap();
+        assert_eq!(split, 9);
+        assert_eq!(messages[split].role, "assistant");
+
+        let mut compacted = messages.clone();
+        ContextManager::apply_compaction(&mut compacted, split, "ran make several times");
+        assert_eq!(compacted.len(), 1 + messages.len() - split);
+        assert_eq!(compacted[0].role, "user");
+        assert_eq!(compacted[1].role, "assistant");
+        assert!(tool_pairs_closed(&compacted));
+    }
 }
@@ -8,4 +8,5 @@ pub mod gemini;
 pub mod openai;
 pub mod signature_store;
 pub mod tool_result_compressor;
+pub mod tool_call_repair;
 pub mod context_manager;
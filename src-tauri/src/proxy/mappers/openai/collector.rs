@@ -2,6 +2,7 @@
 // 用于非 Stream 请求的自动转换
 
 use super::models::*;
+use crate::proxy::common::sse::SseDecoder;
 use bytes::Bytes;
 use futures::StreamExt;
 use serde_json::Value;
@@ -13,17 +14,6 @@ struct SseEvent {
     data: Value,
 }
 
-/// 解析 SSE 行
-fn parse_sse_line(line: &str) -> Option<(String, String)> {
-    if let Some(colon_pos) = line.find(':') {
-        let key = &line[..colon_pos];
-        let value = line[colon_pos + 1..].trim_start();
-        Some((key.to_string(), value.to_string()))
-    } else {
-        None
-    }
-}
-
 /// 将 OpenAI SSE Stream 收集为完整的 OpenAIResponse
 pub async fn collect_openai_stream_to_json<S>(
     mut stream: S,
@@ -31,33 +21,25 @@ pub async fn collect_openai_stream_to_json<S>(
 where
     S: futures::Stream<Item = Result<Bytes, io::Error>> + Unpin,
 {
-    let mut chunks = Vec::new();
-    let mut current_data = String::new();
+    let mut decoder = SseDecoder::new();
+    let mut raw_events = Vec::new();
 
-    // 1. 收集所有 SSE 事件
+    // 1. 收集所有 SSE 事件 (事件可能跨 chunk 边界)
     while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
-        let text = String::from_utf8_lossy(&chunk);
-
-        for line in text.lines() {
-            if line.is_empty() {
-                // 空行表示事件结束
-                if !current_data.is_empty() {
-                    if let Ok(data) = serde_json::from_str::<Value>(&current_data) {
-                        chunks.push(SseEvent { data });
-                    }
-                    current_data.clear();
-                }
-            } else if let Some((key, value)) = parse_sse_line(line) {
-                if key == "data" {
-                    if value == "[DONE]" {
-                        break;
-                    }
-                    current_data = value;
-                }
-            }
-        }
+        raw_events.extend(decoder.feed(&chunk));
     }
+    raw_events.extend(decoder.finish());
+
+    let chunks: Vec<SseEvent> = raw_events
+        .into_iter()
+        .filter(|raw| raw.data.trim() != "[DONE]")
+        .filter_map(|raw| {
+            serde_json::from_str::<Value>(&raw.data)
+                .ok()
+                .map(|data| SseEvent { data })
+        })
+        .collect();
 
     // 2. 重建 OpenAIResponse
     let mut response = OpenAIResponse {
@@ -208,4 +190,31 @@ mod tests {
             panic!("Expected String content");
         }
     }
+
+    #[tokio::test]
+    async fn test_collect_events_split_across_chunks() {
+        let sse_text = concat!(
+            "data: {\"id\":\"chatcmpl-456\",\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"你好，\"},\"finish_reason\":null}]}\r\n\r\n",
+            ": keep-alive\r\n\r\n",
+            "data: {\"id\":\"chatcmpl-456\",\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"世界 🌍\"},\"finish_reason\":\"stop\"}]}\r\n\r\n",
+            "data: [DONE]\r\n\r\n",
+        );
+
+        // 按各种块大小切分，覆盖跨事件边界与多字节 UTF-8 被截断的情况
+        for size in 1..=17 {
+            let chunks: Vec<Result<Bytes, io::Error>> = sse_text
+                .as_bytes()
+                .chunks(size)
+                .map(|c| Ok(Bytes::copy_from_slice(c)))
+                .collect();
+
+            let response = collect_openai_stream_to_json(stream::iter(chunks)).await.unwrap();
+            assert_eq!(response.id, "chatcmpl-456", "chunk size {}", size);
+            match &response.choices[0].message.content {
+                Some(OpenAIContent::String(text)) => assert_eq!(text, "你好，世界 🌍", "chunk size {}", size),
+                _ => panic!("Expected String content"),
+            }
+            assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
+        }
+    }
 }
@@ -2,8 +2,10 @@
 use super::models::*;
 use serde_json::{json, Value};
 use super::streaming::get_thought_signature;
+use crate::proxy::SignatureCache;
 
-pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mapped_model: &str) -> Value {
+/// `session_id` 为处理器解析出的会话 ID (X-Session-Id 头或会话指纹)，用于查找思维签名
+pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mapped_model: &str, session_id: &str) -> Value {
     // 将 OpenAI 工具转为 Value 数组以便探测
     let tools_val = request.tools.as_ref().map(|list| {
         list.iter().map(|v| v.clone()).collect::<Vec<_>>()
@@ -24,14 +26,14 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     let has_incompatible_assistant_history = request.messages.iter()
         .any(|msg| msg.role == "assistant" && msg.reasoning_content.as_ref().map(|s| s.is_empty()).unwrap_or(true));
     
-    // 获取全局存储的思维签名
-    let global_thought_sig = get_thought_signature();
+    // 按会话获取思维签名，避免并发对话互相覆盖
+    let session_thought_sig = get_thought_signature(session_id);
     
     // [NEW] 决定是否开启 Thinking 功能:
     // 如果是 Claude 思考模型且历史不兼容且没有可用签名来占位, 则禁用 Thinking 以防 400
     let mut actual_include_thinking = is_thinking_model;
-    if is_claude_thinking && has_incompatible_assistant_history && global_thought_sig.is_none() {
-        tracing::warn!("[OpenAI-Thinking] Incompatible assistant history detected for Claude thinking model without global signature. Disabling thinking for this request to avoid 400 error.");
+    if is_claude_thinking && has_incompatible_assistant_history && session_thought_sig.is_none() {
+        tracing::warn!("[OpenAI-Thinking] Incompatible assistant history detected for Claude thinking model without session signature. Disabling thinking for this request to avoid 400 error.");
         actual_include_thinking = false;
     }
 
@@ -79,10 +81,8 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
         }
     }
 
-    // 从全局存储获取 thoughtSignature 支持
-    let global_thought_sig = get_thought_signature();
-    if global_thought_sig.is_some() {
-        tracing::debug!("从全局存储获取到 thoughtSignature (长度: {})", global_thought_sig.as_ref().unwrap().len());
+    if let Some(sig) = &session_thought_sig {
+        tracing::debug!("[OpenAI-Signature] 会话 {} 获取到 thoughtSignature (长度: {})", session_id, sig.len());
     }
 
     // 2. 构建 Gemini contents (过滤掉 system/developer 指令)
@@ -106,7 +106,7 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                         "text": reasoning,
                         "thought": true,
                     });
-                    if let Some(ref sig) = global_thought_sig {
+                    if let Some(ref sig) = session_thought_sig {
                         thought_part["thoughtSignature"] = json!(sig);
                     }
                     parts.push(thought_part);
@@ -121,8 +121,8 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                     "thought": true,
                 });
                 
-                // [NEW] 优先使用全局存储的思维签名 (如果可用)
-                if let Some(ref sig) = global_thought_sig {
+                // [NEW] 优先使用会话存储的思维签名 (如果可用)
+                if let Some(ref sig) = session_thought_sig {
                     thought_part["thoughtSignature"] = json!(sig);
                 } else if !mapped_model.starts_with("projects/") && mapped_model.contains("gemini") {
                     // [FIX] 仅针对 Gemini 思维模型注入跳过标签, Claude 不识别此标签
@@ -249,8 +249,12 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                     // [New] 递归清理参数中可能存在的非法校验字段
                     crate::proxy::common::json_schema::clean_json_schema(&mut func_call_part);
 
-                    // [修复] 为该消息内的所有工具调用注入 thoughtSignature
-                    if let Some(ref sig) = global_thought_sig {
+                    // [修复] 为该消息内的所有工具调用注入 thoughtSignature:
+                    // 优先按 tool_call id 精确恢复，其次使用会话最近的签名
+                    let tool_sig = SignatureCache::global()
+                        .get_tool_signature(&tc.id)
+                        .or_else(|| session_thought_sig.clone());
+                    if let Some(sig) = tool_sig {
                         func_call_part["thoughtSignature"] = json!(sig);
                     } else if is_thinking_model && !mapped_model.starts_with("projects/") {
                         // [NEW] Handle missing signature for Gemini thinking models
@@ -334,8 +338,19 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     }
 
     if let Some(fmt) = &request.response_format {
-        if fmt.r#type == "json_object" {
-            gen_config["responseMimeType"] = json!("application/json");
+        match fmt.r#type.as_str() {
+            "json_object" => {
+                gen_config["responseMimeType"] = json!("application/json");
+            }
+            "json_schema" => {
+                gen_config["responseMimeType"] = json!("application/json");
+                if let Some(schema) = fmt.schema() {
+                    let mut response_schema = schema.clone();
+                    crate::proxy::common::json_schema::clean_json_schema(&mut response_schema);
+                    gen_config["responseSchema"] = response_schema;
+                }
+            }
+            _ => {}
         }
     }
 
@@ -428,6 +443,22 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
         
         if !function_declarations.is_empty() {
             inner_request["tools"] = json!([{ "functionDeclarations": function_declarations }]);
+
+            // tool_choice -> toolConfig.functionCallingConfig
+            if let Some(mut calling_config) = request
+                .tool_choice
+                .as_ref()
+                .and_then(crate::proxy::mappers::common_utils::build_function_calling_config)
+            {
+                if let Some(names) = calling_config.get_mut("allowedFunctionNames").and_then(|v| v.as_array_mut()) {
+                    for name in names.iter_mut() {
+                        if name == "local_shell_call" {
+                            *name = json!("shell");
+                        }
+                    }
+                }
+                inner_request["toolConfig"] = json!({ "functionCallingConfig": calling_config });
+            }
         }
     }
     
@@ -465,11 +496,13 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     if let Some(image_config) = config.image_config {
          if let Some(obj) = inner_request.as_object_mut() {
              obj.remove("tools");
+             obj.remove("toolConfig");
              obj.remove("systemInstruction");
              let gen_config = obj.entry("generationConfig").or_insert_with(|| json!({}));
              if let Some(gen_obj) = gen_config.as_object_mut() {
                  gen_obj.remove("thinkingConfig");
                  gen_obj.remove("responseMimeType"); 
+                 gen_obj.remove("responseSchema");
                  gen_obj.remove("responseModalities");
                  gen_obj.insert("imageConfig".to_string(), image_config);
              }
@@ -513,6 +546,7 @@ fn enforce_uppercase_types(value: &mut Value) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proxy::session_manager::SessionManager;
 
     #[test]
     fn test_transform_openai_request_multimodal() {
@@ -547,10 +581,159 @@ mod tests {
             prompt: None,
         };
 
-        let result = transform_openai_request(&req, "test-v", "gemini-1.5-flash");
+        let result = transform_openai_request(&req, "test-v", "gemini-1.5-flash", "test-session");
         let parts = &result["request"]["contents"][0]["parts"];
         assert_eq!(parts.as_array().unwrap().len(), 2);
         assert_eq!(parts[0]["text"].as_str().unwrap(), "What is in this image?");
         assert_eq!(parts[1]["inlineData"]["mimeType"].as_str().unwrap(), "image/png");
     }
+
+    fn tool_loop_request(question: &str, call_id: &str) -> OpenAIRequest {
+        let message = |role: &str| OpenAIMessage {
+            role: role.to_string(),
+            content: None,
+            reasoning_content: None,
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        };
+        OpenAIRequest {
+            model: "gpt-4".to_string(),
+            messages: vec![
+                OpenAIMessage {
+                    content: Some(OpenAIContent::String(question.to_string())),
+                    ..message("user")
+                },
+                OpenAIMessage {
+                    tool_calls: Some(vec![ToolCall {
+                        id: call_id.to_string(),
+                        r#type: "function".to_string(),
+                        function: ToolFunction {
+                            name: "get_weather".to_string(),
+                            arguments: "{\"city\":\"Paris\"}".to_string(),
+                        },
+                    }]),
+                    ..message("assistant")
+                },
+                OpenAIMessage {
+                    content: Some(OpenAIContent::String("sunny".to_string())),
+                    tool_call_id: Some(call_id.to_string()),
+                    ..message("tool")
+                },
+            ],
+            stream: false,
+            n: None,
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            stop: None,
+            response_format: None,
+            tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+            instructions: None,
+            input: None,
+            prompt: None,
+        }
+    }
+
+    fn function_call_signature(body: &Value) -> Option<String> {
+        body["request"]["contents"][1]["parts"]
+            .as_array()?
+            .iter()
+            .find(|p| p.get("functionCall").is_some())?
+            .get("thoughtSignature")
+            .and_then(|s| s.as_str())
+            .map(|s| s.to_string())
+    }
+
+    #[test]
+    fn test_thought_signature_is_scoped_to_session() {
+        let sig = "session-scoped-signature-".repeat(4);
+        let req_a = tool_loop_request("What is the weather like in Paris today?", "call_session_a");
+        let req_b = tool_loop_request("Summarize the quarterly sales report please", "call_session_b");
+
+        // 上游响应中的签名写入会话 A 与其 tool_call id
+        let gemini_resp = json!({
+            "candidates": [{
+                "content": {"parts": [{
+                    "functionCall": {"name": "get_weather", "args": {"city": "Paris"}, "id": "call_session_a"},
+                    "thoughtSignature": sig
+                }]},
+                "finishReason": "STOP"
+            }]
+        });
+        let session_a = SessionManager::extract_openai_session_id(&req_a);
+        super::super::response::transform_openai_response(&gemini_resp, &session_a);
+
+        let body_a = transform_openai_request(&req_a, "test-v", "gemini-1.5-flash", &session_a);
+        assert_eq!(function_call_signature(&body_a).as_deref(), Some(sig.as_str()));
+
+        let session_b = SessionManager::extract_openai_session_id(&req_b);
+        let body_b = transform_openai_request(&req_b, "test-v", "gemini-1.5-flash", &session_b);
+        assert_eq!(function_call_signature(&body_b), None);
+    }
+
+    #[test]
+    fn test_thought_signature_uses_client_session_id() {
+        let sig = "client-session-signature-".repeat(4);
+        let req = tool_loop_request("Plan a three day trip to Kyoto in spring", "call_client_session");
+        let gemini_resp = json!({
+            "candidates": [{
+                "content": {"parts": [{
+                    "functionCall": {"name": "get_weather", "args": {"city": "Kyoto"}, "id": "call_client_session"},
+                    "thoughtSignature": sig
+                }]},
+                "finishReason": "STOP"
+            }]
+        });
+        // 签名按 X-Session-Id 提供的会话 ID 缓存，而非请求指纹
+        super::super::response::transform_openai_response(&gemini_resp, "client-session-kyoto");
+
+        let body = transform_openai_request(&req, "test-v", "gemini-1.5-flash", "client-session-kyoto");
+        assert_eq!(function_call_signature(&body).as_deref(), Some(sig.as_str()));
+    }
+
+    #[test]
+    fn test_tool_call_signature_recovered_by_id() {
+        let sig = "tool-call-level-signature-".repeat(4);
+        SignatureCache::global().cache_tool_signature("call_tool_level", sig.clone());
+
+        let req = tool_loop_request("Book a table for two at eight tonight", "call_tool_level");
+        let body = transform_openai_request(&req, "test-v", "gemini-1.5-flash", "test-session");
+        assert_eq!(function_call_signature(&body).as_deref(), Some(sig.as_str()));
+    }
+
+    #[test]
+    fn test_structured_output_and_tool_choice_mapping() {
+        let mut req = tool_loop_request("Extract the city from this sentence", "call_structured");
+        req.response_format = Some(ResponseFormat {
+            r#type: "json_schema".to_string(),
+            json_schema: Some(JsonSchemaFormat {
+                name: Some("city".to_string()),
+                description: None,
+                schema: Some(json!({
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"],
+                    "additionalProperties": false
+                })),
+                strict: Some(true),
+            }),
+        });
+        req.tools = Some(vec![json!({
+            "type": "function",
+            "function": {"name": "get_weather", "parameters": {"type": "object", "properties": {}}}
+        })]);
+        req.tool_choice = Some(json!({"type": "function", "function": {"name": "get_weather"}}));
+
+        let body = transform_openai_request(&req, "test-v", "gemini-1.5-flash", "test-session");
+        let gen_config = &body["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+        assert_eq!(gen_config["responseSchema"]["properties"]["city"]["type"], "string");
+        assert_eq!(
+            body["request"]["toolConfig"]["functionCallingConfig"],
+            json!({"mode": "ANY", "allowedFunctionNames": ["get_weather"]})
+        );
+    }
 }
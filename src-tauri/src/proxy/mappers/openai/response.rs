@@ -2,7 +2,7 @@
 use super::models::*;
 use serde_json::Value;
 
-pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
+pub fn transform_openai_response(gemini_response: &Value, session_id: &str) -> OpenAIResponse {
     // 解包 response 字段
     let raw = gemini_response.get("response").unwrap_or(gemini_response);
 
@@ -14,6 +14,7 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
             let mut content_out = String::new();
             let mut thought_out = String::new();
             let mut tool_calls = Vec::new();
+            let mut last_signature: Option<String> = None;
 
             // 提取 content 和 tool_calls
             if let Some(parts) = candidate
@@ -28,7 +29,8 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                         .or(part.get("thought_signature"))
                         .and_then(|s| s.as_str())
                     {
-                        super::streaming::store_thought_signature(sig);
+                        super::streaming::store_thought_signature(session_id, sig);
+                        last_signature = Some(sig.to_string());
                     }
 
                     // 检查该 part 是否是思考内容 (thought: true)
@@ -60,6 +62,10 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                             .map(|s| s.to_string())
                             .unwrap_or_else(|| format!("{}-{}", name, uuid::Uuid::new_v4()));
 
+                        if let Some(sig) = last_signature.as_deref() {
+                            super::streaming::store_tool_call_signature(session_id, &id, sig);
+                        }
+
                         tool_calls.push(ToolCall {
                             id,
                             r#type: "function".to_string(),
@@ -228,7 +234,7 @@ mod tests {
             "responseId": "resp_123"
         });
 
-        let result = transform_openai_response(&gemini_resp);
+        let result = transform_openai_response(&gemini_resp, "test-session");
         assert_eq!(result.object, "chat.completion");
         let content = match result.choices[0].message.content.as_ref().unwrap() {
             OpenAIContent::String(s) => s,
@@ -255,7 +261,7 @@ mod tests {
             "responseId": "resp_123"
         });
 
-        let result = transform_openai_response(&gemini_resp);
+        let result = transform_openai_response(&gemini_resp, "test-session");
         
         assert!(result.usage.is_some());
         let usage = result.usage.unwrap();
@@ -277,7 +283,7 @@ mod tests {
             "responseId": "resp_123"
         });
 
-        let result = transform_openai_response(&gemini_resp);
+        let result = transform_openai_response(&gemini_resp, "test-session");
         assert!(result.usage.is_none());
     }
 }
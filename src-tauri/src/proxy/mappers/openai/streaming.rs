@@ -1,54 +1,44 @@
 // OpenAI 流式转换
-use bytes::{Bytes, BytesMut};
+use bytes::Bytes;
 use futures::{Stream, StreamExt};
 use serde_json::{json, Value};
 use std::pin::Pin;
-use std::sync::{Mutex, OnceLock};
 use chrono::Utc;
 use uuid::Uuid;
-use tracing::debug;
 use rand::Rng;
 
-// === 全局 ThoughtSignature 存储 ===
-// 用于在流式响应和后续请求之间传递签名，避免嵌入到用户可见的文本中
-static GLOBAL_THOUGHT_SIG: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+use crate::proxy::common::sse::decode_stream;
 
-fn get_thought_sig_storage() -> &'static Mutex<Option<String>> {
-    GLOBAL_THOUGHT_SIG.get_or_init(|| Mutex::new(None))
+use crate::proxy::mappers::tool_call_repair::ToolSchemas;
+use crate::proxy::SignatureCache;
+use std::sync::Arc;
+
+// === 会话级 ThoughtSignature 存储 ===
+// 签名按处理器解析出的会话 ID (X-Session-Id 头，缺省为 SessionManager::extract_openai_session_id 的会话指纹) 存入 SignatureCache，
+// 工具调用的签名同时按 tool_call id 缓存，避免并发对话互相覆盖
+
+/// 读取 Gemini part 上的 thoughtSignature
+fn part_thought_signature(part: &Value) -> Option<&str> {
+    part.get("thoughtSignature")
+        .or(part.get("thought_signature"))
+        .and_then(|s| s.as_str())
 }
 
-/// 保存 thoughtSignature 到全局存储
-/// 注意：只在新签名比现有签名更长时才存储，避免短签名覆盖有效签名
-pub fn store_thought_signature(sig: &str) {
-    if let Ok(mut guard) = get_thought_sig_storage().lock() {
-        let should_store = match &*guard {
-            None => true, // 没有签名，直接存储
-            Some(existing) => sig.len() > existing.len(), // 只有新签名更长才存储
-        };
-        
-        if should_store {
-            tracing::debug!("[ThoughtSig] 存储新签名 (长度: {}，替换旧长度: {:?})", 
-                sig.len(), 
-                guard.as_ref().map(|s| s.len())
-            );
-            *guard = Some(sig.to_string());
-        } else {
-            tracing::debug!("[ThoughtSig] 跳过短签名 (新长度: {}，现有长度: {})", 
-                sig.len(), 
-                guard.as_ref().map(|s| s.len()).unwrap_or(0)
-            );
-        }
-    }
+/// 保存会话的 thoughtSignature (仅在新签名更长时替换，见 SignatureCache)
+pub fn store_thought_signature(session_id: &str, sig: &str) {
+    tracing::debug!("[ThoughtSig] 会话 {} 捕获签名 (长度: {})", session_id, sig.len());
+    SignatureCache::global().cache_session_signature(session_id, sig.to_string());
 }
 
-/// 获取全局存储的 thoughtSignature（不清除）
-#[allow(dead_code)]
-pub fn get_thought_signature() -> Option<String> {
-    if let Ok(guard) = get_thought_sig_storage().lock() {
-        guard.clone()
-    } else {
-        None
-    }
+/// 保存工具调用对应的 thoughtSignature，供下一轮按 tool_call id 精确回填
+pub fn store_tool_call_signature(session_id: &str, call_id: &str, sig: &str) {
+    SignatureCache::global().cache_tool_signature(call_id, sig.to_string());
+    store_thought_signature(session_id, sig);
+}
+
+/// 获取会话最近的 thoughtSignature（不清除）
+pub fn get_thought_signature(session_id: &str) -> Option<String> {
+    SignatureCache::global().get_session_signature(session_id)
 }
 
 /// Extract and convert Gemini usageMetadata to OpenAI usage format
@@ -84,10 +74,12 @@ fn extract_usage_metadata(u: &Value) -> Option<super::models::OpenAIUsage> {
 }
 
 pub fn create_openai_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     model: String,
+    session_id: String,
+    tool_schemas: Option<Arc<ToolSchemas>>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
-    let mut buffer = BytesMut::new();
+    let mut events = decode_stream(gemini_stream);
     
     // 在流开始时生成固定的 ID 和 timestamp，所有 chunk 共用
     let stream_id = format!("chatcmpl-{}", Uuid::new_v4());
@@ -95,6 +87,7 @@ pub fn create_openai_sse_stream(
     
     let stream = async_stream::stream! {
         let mut emitted_tool_calls = std::collections::HashSet::new();
+        let mut last_signature: Option<String> = None;
         let mut final_usage: Option<super::models::OpenAIUsage> = None;
         let mut error_occurred = false;  // [FIX] 标志位,避免双重 [DONE]
         
@@ -105,238 +98,228 @@ pub fn create_openai_sse_stream(
         loop {
             tokio::select! {
                 // 处理上游数据
-                item = gemini_stream.next() => {
+                item = events.next() => {
                     match item {
-                        Some(Ok(bytes)) => {
-                    // Verbose logging for debugging image fragmentation
-                    debug!("[OpenAI-SSE] Received chunk: {} bytes", bytes.len());
-                    buffer.extend_from_slice(&bytes);
-                    
-                    // Process complete lines from buffer
-                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                        let line_raw = buffer.split_to(pos + 1);
-                        if let Ok(line_str) = std::str::from_utf8(&line_raw) {
-                            let line = line_str.trim();
-                            if line.is_empty() { continue; }
-
-                            if line.starts_with("data: ") {
-                                let json_part = line.trim_start_matches("data: ").trim();
-                                if json_part == "[DONE]" {
-                                    continue;
+                        Some(Ok(event)) => {
+                            let json_part = event.data.trim();
+                            if json_part.is_empty() || json_part == "[DONE]" { continue; }
+
+                            if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
+                                // Log raw chunk for debugging gemini-3 thoughts
+                                tracing::debug!("Gemini SSE Chunk: {}", json_part);
+
+                                // Handle v1internal wrapper if present
+                                let mut actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) {
+                                    inner
+                                } else {
+                                    json
+                                };
+                                if let Some(schemas) = &tool_schemas {
+                                    schemas.repair_gemini_response(&mut actual_data);
                                 }
 
-                                if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
-                                    // Log raw chunk for debugging gemini-3 thoughts
-                                    tracing::debug!("Gemini SSE Chunk: {}", json_part);
-
-                                    // Handle v1internal wrapper if present
-                                    let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) {
-                                        inner
-                                    } else {
-                                        json
-                                    };
-
-                                    // Capture usageMetadata if present
-                                    if let Some(u) = actual_data.get("usageMetadata") {
-                                        final_usage = extract_usage_metadata(u);
-                                    }
+                                // Capture usageMetadata if present
+                                if let Some(u) = actual_data.get("usageMetadata") {
+                                    final_usage = extract_usage_metadata(u);
+                                }
 
-                                    // Extract candidates
-                                    if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
-                                        for (idx, candidate) in candidates.iter().enumerate() {
-                                            let parts = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array());
-
-                                            let mut content_out = String::new();
-                                            let mut thought_out = String::new();
-                                            
-                                            if let Some(parts_list) = parts {
-                                                for part in parts_list {
-                                                    let is_thought_part = part.get("thought")
-                                                        .and_then(|v| v.as_bool())
-                                                        .unwrap_or(false);
-                                                    
-                                                    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                                                        if is_thought_part {
-                                                            thought_out.push_str(text);
-                                                        } else {
-                                                            content_out.push_str(text);
-                                                        }
-                                                    }
-                                                    // 捕获 thoughtSignature (Gemini 3 工具调用必需)
-                                                    if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
-                                                        store_thought_signature(sig);
+                                // Extract candidates
+                                if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
+                                    for (idx, candidate) in candidates.iter().enumerate() {
+                                        let parts = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array());
+
+                                        let mut content_out = String::new();
+                                        let mut thought_out = String::new();
+                                
+                                        if let Some(parts_list) = parts {
+                                            for part in parts_list {
+                                                let is_thought_part = part.get("thought")
+                                                    .and_then(|v| v.as_bool())
+                                                    .unwrap_or(false);
+                                        
+                                                if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                                                    if is_thought_part {
+                                                        thought_out.push_str(text);
+                                                    } else {
+                                                        content_out.push_str(text);
                                                     }
+                                                }
+                                                // 捕获 thoughtSignature (Gemini 3 工具调用必需)
+                                                if let Some(sig) = part_thought_signature(part) {
+                                                    store_thought_signature(&session_id, sig);
+                                                    last_signature = Some(sig.to_string());
+                                                }
 
-                                                    if let Some(img) = part.get("inlineData") {
-                                                        let mime_type = img.get("mimeType").and_then(|v| v.as_str()).unwrap_or("image/png");
-                                                        let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
-                                                        if !data.is_empty() {
-                                                            content_out.push_str(&format!("![image](data:{};base64,{})", mime_type, data));
-                                                        }
+                                                if let Some(img) = part.get("inlineData") {
+                                                    let mime_type = img.get("mimeType").and_then(|v| v.as_str()).unwrap_or("image/png");
+                                                    let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
+                                                    if !data.is_empty() {
+                                                        content_out.push_str(&format!("![image](data:{};base64,{})", mime_type, data));
                                                     }
+                                                }
 
-                                                    // Handle function call
-                                                    if let Some(func_call) = part.get("functionCall") {
-                                                        let call_key = serde_json::to_string(func_call).unwrap_or_default();
-                                                        if !emitted_tool_calls.contains(&call_key) {
-                                                            emitted_tool_calls.insert(call_key);
-                                                            
-                                                            let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
-                                                            let args = func_call.get("args").unwrap_or(&json!({})).to_string();
-                                                            
-                                                            // Generate stable ID
-                                                            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                                                            use std::hash::{Hash, Hasher};
-                                                            serde_json::to_string(func_call).unwrap_or_default().hash(&mut hasher);
-                                                            let call_id = format!("call_{:x}", hasher.finish());
-                                                            
-                                                            // Emit tool_calls delta
-                                                            let tool_call_chunk = json!({
-                                                                "id": &stream_id,
-                                                                "object": "chat.completion.chunk",
-                                                                "created": created_ts,
-                                                                "model": &model,
-                                                                "choices": [{
-                                                                    "index": idx as u32,
-                                                                    "delta": {
-                                                                        "role": "assistant",
-                                                                        "tool_calls": [{
-                                                                            "index": 0,
-                                                                            "id": call_id,
-                                                                            "type": "function",
-                                                                            "function": {
-                                                                                "name": name,
-                                                                                "arguments": args
-                                                                            }
-                                                                        }]
-                                                                    },
-                                                                    "finish_reason": serde_json::Value::Null
-                                                                }]
-                                                            });
-                                                            
-                                                            let sse_out = format!("data: {}\n\n", serde_json::to_string(&tool_call_chunk).unwrap_or_default());
-                                                            yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                                // Handle function call
+                                                if let Some(func_call) = part.get("functionCall") {
+                                                    let call_key = serde_json::to_string(func_call).unwrap_or_default();
+                                                    if !emitted_tool_calls.contains(&call_key) {
+                                                        emitted_tool_calls.insert(call_key);
+                                                
+                                                        let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
+                                                        let args = func_call.get("args").unwrap_or(&json!({})).to_string();
+                                                
+                                                        // Generate stable ID
+                                                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                                                        use std::hash::{Hash, Hasher};
+                                                        serde_json::to_string(func_call).unwrap_or_default().hash(&mut hasher);
+                                                        let call_id = format!("call_{:x}", hasher.finish());
+                                                        if let Some(sig) = last_signature.as_deref() {
+                                                            store_tool_call_signature(&session_id, &call_id, sig);
                                                         }
+                                                
+                                                        // Emit tool_calls delta
+                                                        let tool_call_chunk = json!({
+                                                            "id": &stream_id,
+                                                            "object": "chat.completion.chunk",
+                                                            "created": created_ts,
+                                                            "model": &model,
+                                                            "choices": [{
+                                                                "index": idx as u32,
+                                                                "delta": {
+                                                                    "role": "assistant",
+                                                                    "tool_calls": [{
+                                                                        "index": 0,
+                                                                        "id": call_id,
+                                                                        "type": "function",
+                                                                        "function": {
+                                                                            "name": name,
+                                                                            "arguments": args
+                                                                        }
+                                                                    }]
+                                                                },
+                                                                "finish_reason": serde_json::Value::Null
+                                                            }]
+                                                        });
+                                                
+                                                        let sse_out = format!("data: {}\n\n", serde_json::to_string(&tool_call_chunk).unwrap_or_default());
+                                                        yield Ok::<Bytes, String>(Bytes::from(sse_out));
                                                     }
                                                 }
                                             }
+                                        }
 
 
-                                            // 处理联网搜索引文 (Grounding Metadata) - 流式
-                                            if let Some(grounding) = candidate.get("groundingMetadata") {
-                                                let mut grounding_text = String::new();
-                                                
-                                                // 1. 处理搜索词
-                                                if let Some(queries) = grounding.get("webSearchQueries").and_then(|q| q.as_array()) {
-                                                    let query_list: Vec<&str> = queries.iter().filter_map(|v| v.as_str()).collect();
-                                                    if !query_list.is_empty() {
-                                                        grounding_text.push_str("\n\n---\n**🔍 已为您搜索：** ");
-                                                        grounding_text.push_str(&query_list.join(", "));
-                                                    }
+                                        // 处理联网搜索引文 (Grounding Metadata) - 流式
+                                        if let Some(grounding) = candidate.get("groundingMetadata") {
+                                            let mut grounding_text = String::new();
+                                    
+                                            // 1. 处理搜索词
+                                            if let Some(queries) = grounding.get("webSearchQueries").and_then(|q| q.as_array()) {
+                                                let query_list: Vec<&str> = queries.iter().filter_map(|v| v.as_str()).collect();
+                                                if !query_list.is_empty() {
+                                                    grounding_text.push_str("\n\n---\n**🔍 已为您搜索：** ");
+                                                    grounding_text.push_str(&query_list.join(", "));
                                                 }
+                                            }
 
-                                                // 2. 处理来源链接 (Chunks)
-                                                if let Some(chunks) = grounding.get("groundingChunks").and_then(|c| c.as_array()) {
-                                                    let mut links = Vec::new();
-                                                    for (i, chunk) in chunks.iter().enumerate() {
-                                                        if let Some(web) = chunk.get("web") {
-                                                            let title = web.get("title").and_then(|v| v.as_str()).unwrap_or("网页来源");
-                                                            let uri = web.get("uri").and_then(|v| v.as_str()).unwrap_or("#");
-                                                            links.push(format!("[{}] [{}]({})", i + 1, title, uri));
-                                                        }
-                                                    }
-                                                    if !links.is_empty() {
-                                                        grounding_text.push_str("\n\n**🌐 来源引文：**\n");
-                                                        grounding_text.push_str(&links.join("\n"));
+                                            // 2. 处理来源链接 (Chunks)
+                                            if let Some(chunks) = grounding.get("groundingChunks").and_then(|c| c.as_array()) {
+                                                let mut links = Vec::new();
+                                                for (i, chunk) in chunks.iter().enumerate() {
+                                                    if let Some(web) = chunk.get("web") {
+                                                        let title = web.get("title").and_then(|v| v.as_str()).unwrap_or("网页来源");
+                                                        let uri = web.get("uri").and_then(|v| v.as_str()).unwrap_or("#");
+                                                        links.push(format!("[{}] [{}]({})", i + 1, title, uri));
                                                     }
                                                 }
-                                                
-                                                if !grounding_text.is_empty() {
-                                                    content_out.push_str(&grounding_text);
+                                                if !links.is_empty() {
+                                                    grounding_text.push_str("\n\n**🌐 来源引文：**\n");
+                                                    grounding_text.push_str(&links.join("\n"));
                                                 }
                                             }
-
-                                            // 只有当 content 和 thought 都为空时才跳过
-                                            if content_out.is_empty() && thought_out.is_empty() {
-                                                // Skip empty chunks if no text/grounding/thought was found
-                                                if candidate.get("finishReason").is_none() {
-                                                    continue;
-                                                }
+                                    
+                                            if !grounding_text.is_empty() {
+                                                content_out.push_str(&grounding_text);
                                             }
-                                                
-                                            // Extract finish reason
-                                            let finish_reason = candidate.get("finishReason")
-                                                .and_then(|f| f.as_str())
-                                                .map(|f| match f {
-                                                    "STOP" => "stop",
-                                                    "MAX_TOKENS" => "length",
-                                                    "SAFETY" => "content_filter",
-                                                    "RECITATION" => "content_filter",
-                                                    _ => f,
-                                                });
-
-                                            // Construct OpenAI SSE chunk
-                                            // 如果有思考内容，先发送 reasoning_content chunk
-                                            if !thought_out.is_empty() {
-                                                let reasoning_chunk = json!({
-                                                    "id": &stream_id,
-                                                    "object": "chat.completion.chunk",
-                                                    "created": created_ts,
-                                                    "model": model,
-                                                    "choices": [
-                                                        {
-                                                            "index": idx as u32,
-                                                            "delta": {
-                                                                "role": "assistant",
-                                                                "content": serde_json::Value::Null,
-                                                                "reasoning_content": thought_out
-                                                            },
-                                                            "finish_reason": serde_json::Value::Null
-                                                        }
-                                                    ]
-                                                });
-                                                let sse_out = format!("data: {}\n\n", serde_json::to_string(&reasoning_chunk).unwrap_or_default());
-                                                yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                        }
+
+                                        // 只有当 content 和 thought 都为空时才跳过
+                                        if content_out.is_empty() && thought_out.is_empty() {
+                                            // Skip empty chunks if no text/grounding/thought was found
+                                            if candidate.get("finishReason").is_none() {
+                                                continue;
                                             }
+                                        }
+                                    
+                                        // Extract finish reason
+                                        let finish_reason = candidate.get("finishReason")
+                                            .and_then(|f| f.as_str())
+                                            .map(|f| match f {
+                                                "STOP" => "stop",
+                                                "MAX_TOKENS" => "length",
+                                                "SAFETY" => "content_filter",
+                                                "RECITATION" => "content_filter",
+                                                _ => f,
+                                            });
 
-                                            // 发送正常 content chunk
-                                            if !content_out.is_empty() || finish_reason.is_some() {
-                                                let mut openai_chunk = json!({
-                                                    "id": &stream_id,
-                                                    "object": "chat.completion.chunk",
-                                                    "created": created_ts,
-                                                    "model": model,
-                                                    "choices": [
-                                                        {
-                                                            "index": idx as u32,
-                                                            "delta": {
-                                                                "content": content_out
-                                                            },
-                                                            "finish_reason": finish_reason
-                                                        }
-                                                    ]
-                                                });
-                                                
-                                                // [FIX] 将 usage 嵌入到 chunk 中
-                                                if let Some(ref usage) = final_usage {
-                                                    openai_chunk["usage"] = serde_json::to_value(usage).unwrap();
-                                                }
-                                                
-                                                // [FIX] 如果是最后一个 chunk,标记 usage 已发送
-                                                if finish_reason.is_some() {
-                                                    final_usage = None;
-                                                }
+                                        // Construct OpenAI SSE chunk
+                                        // 如果有思考内容，先发送 reasoning_content chunk
+                                        if !thought_out.is_empty() {
+                                            let reasoning_chunk = json!({
+                                                "id": &stream_id,
+                                                "object": "chat.completion.chunk",
+                                                "created": created_ts,
+                                                "model": model,
+                                                "choices": [
+                                                    {
+                                                        "index": idx as u32,
+                                                        "delta": {
+                                                            "role": "assistant",
+                                                            "content": serde_json::Value::Null,
+                                                            "reasoning_content": thought_out
+                                                        },
+                                                        "finish_reason": serde_json::Value::Null
+                                                    }
+                                                ]
+                                            });
+                                            let sse_out = format!("data: {}\n\n", serde_json::to_string(&reasoning_chunk).unwrap_or_default());
+                                            yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                        }
 
-                                                let sse_out = format!("data: {}\n\n", serde_json::to_string(&openai_chunk).unwrap_or_default());
-                                                yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                        // 发送正常 content chunk
+                                        if !content_out.is_empty() || finish_reason.is_some() {
+                                            let mut openai_chunk = json!({
+                                                "id": &stream_id,
+                                                "object": "chat.completion.chunk",
+                                                "created": created_ts,
+                                                "model": model,
+                                                "choices": [
+                                                    {
+                                                        "index": idx as u32,
+                                                        "delta": {
+                                                            "content": content_out
+                                                        },
+                                                        "finish_reason": finish_reason
+                                                    }
+                                                ]
+                                            });
+                                    
+                                            // [FIX] 将 usage 嵌入到 chunk 中
+                                            if let Some(ref usage) = final_usage {
+                                                openai_chunk["usage"] = serde_json::to_value(usage).unwrap();
                                             }
+                                    
+                                            // [FIX] 如果是最后一个 chunk,标记 usage 已发送
+                                            if finish_reason.is_some() {
+                                                final_usage = None;
+                                            }
+
+                                            let sse_out = format!("data: {}\n\n", serde_json::to_string(&openai_chunk).unwrap_or_default());
+                                            yield Ok::<Bytes, String>(Bytes::from(sse_out));
                                         }
                                     }
                                 }
                             }
                         }
-                    }
-                }
                         Some(Err(e)) => {
                             use crate::proxy::mappers::error_classifier::classify_stream_error;
                             let (error_type, user_message, i18n_key) = classify_stream_error(&e);
@@ -396,10 +379,11 @@ pub fn create_openai_sse_stream(
 }
 
 pub fn create_legacy_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     model: String,
+    session_id: String,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
-    let mut buffer = BytesMut::new();
+    let mut events = decode_stream(gemini_stream);
     
     // Generate constant alphanumeric ID (mimics OpenAI base62 format)
     let charset = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
@@ -424,96 +408,84 @@ pub fn create_legacy_sse_stream(
         loop {
             tokio::select! {
                 // 处理上游数据
-                item = gemini_stream.next() => {
+                item = events.next() => {
                     match item {
-                        Some(Ok(bytes)) => {
-                    buffer.extend_from_slice(&bytes);
-                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                        let line_raw = buffer.split_to(pos + 1);
-                        if let Ok(line_str) = std::str::from_utf8(&line_raw) {
-                            let line = line_str.trim();
-                            if line.is_empty() { continue; }
-
-                            if line.starts_with("data: ") {
-                                let json_part = line.trim_start_matches("data: ").trim();
-                                if json_part == "[DONE]" { continue; }
-
-                                if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
-                                    let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) { inner } else { json };
-                                    
-                                    // Capture usageMetadata if present
-                                    if let Some(u) = actual_data.get("usageMetadata") {
-                                        final_usage = extract_usage_metadata(u);
-                                    }
-                                    
-                                    let mut content_out = String::new();
-                                    if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
-                                        if let Some(parts) = candidates.get(0).and_then(|c| c.get("content")).and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
-                                            for part in parts {
-                                                if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                                                    content_out.push_str(text);
-                                                }
-                                                /* 禁用思维链输出到正文
-                                                if let Some(thought_text) = part.get("thought").and_then(|t| t.as_str()) {
-                                                    // // content_out.push_str(thought_text);
-                                                }
-                                                */
-                                                // 捕获 thoughtSignature
-                                                // 捕获 thoughtSignature 到全局存储
-                                                if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
-                                                    store_thought_signature(sig);
-                                                }
+                        Some(Ok(event)) => {
+                            let json_part = event.data.trim();
+                            if json_part.is_empty() || json_part == "[DONE]" { continue; }
+
+                            if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
+                                let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) { inner } else { json };
+                        
+                                // Capture usageMetadata if present
+                                if let Some(u) = actual_data.get("usageMetadata") {
+                                    final_usage = extract_usage_metadata(u);
+                                }
+                        
+                                let mut content_out = String::new();
+                                if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
+                                    if let Some(parts) = candidates.get(0).and_then(|c| c.get("content")).and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
+                                        for part in parts {
+                                            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                                                content_out.push_str(text);
+                                            }
+                                            /* 禁用思维链输出到正文
+                                            if let Some(thought_text) = part.get("thought").and_then(|t| t.as_str()) {
+                                                // // content_out.push_str(thought_text);
+                                            }
+                                            */
+                                            // 捕获 thoughtSignature 到会话存储
+                                            if let Some(sig) = part_thought_signature(part) {
+                                                store_thought_signature(&session_id, sig);
                                             }
                                         }
                                     }
+                                }
 
-                                    let finish_reason = actual_data.get("candidates")
-                                        .and_then(|c| c.as_array())
-                                        .and_then(|c| c.get(0))
-                                        .and_then(|c| c.get("finishReason"))
-                                        .and_then(|f| f.as_str())
-                                        .map(|f| match f {
-                                            "STOP" => "stop",
-                                            "MAX_TOKENS" => "length",
-                                            "SAFETY" => "content_filter",
-                                            _ => f,
-                                        });
-
-                                    // Construct LEGACY completion chunk - STRICT VERSION
-                                    let mut legacy_chunk = json!({
-                                        "id": &stream_id,
-                                        "object": "text_completion",
-                                        "created": created_ts,
-                                        "model": &model,
-                                        "choices": [
-                                            {
-                                                "text": content_out,
-                                                "index": 0,
-                                                "logprobs": null,
-                                                "finish_reason": finish_reason // Will be null if None
-                                            }
-                                        ]
+                                let finish_reason = actual_data.get("candidates")
+                                    .and_then(|c| c.as_array())
+                                    .and_then(|c| c.get(0))
+                                    .and_then(|c| c.get("finishReason"))
+                                    .and_then(|f| f.as_str())
+                                    .map(|f| match f {
+                                        "STOP" => "stop",
+                                        "MAX_TOKENS" => "length",
+                                        "SAFETY" => "content_filter",
+                                        _ => f,
                                     });
-                                    
-                                    // [FIX] 将 usage 嵌入到 chunk 中
-                                    if let Some(ref usage) = final_usage {
-                                        legacy_chunk["usage"] = serde_json::to_value(usage).unwrap();
-                                    }
-                                    
-                                    // [FIX] 如果是最后一个 chunk,标记 usage 已发送
-                                    if finish_reason.is_some() {
-                                        final_usage = None;
-                                    }
 
-                                    let json_str = serde_json::to_string(&legacy_chunk).unwrap_or_default();
-                                    tracing::debug!("Legacy Stream Chunk: {}", json_str); 
-                                    let sse_out = format!("data: {}\n\n", json_str);
-                                    yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                // Construct LEGACY completion chunk - STRICT VERSION
+                                let mut legacy_chunk = json!({
+                                    "id": &stream_id,
+                                    "object": "text_completion",
+                                    "created": created_ts,
+                                    "model": &model,
+                                    "choices": [
+                                        {
+                                            "text": content_out,
+                                            "index": 0,
+                                            "logprobs": null,
+                                            "finish_reason": finish_reason // Will be null if None
+                                        }
+                                    ]
+                                });
+                        
+                                // [FIX] 将 usage 嵌入到 chunk 中
+                                if let Some(ref usage) = final_usage {
+                                    legacy_chunk["usage"] = serde_json::to_value(usage).unwrap();
                                 }
+                        
+                                // [FIX] 如果是最后一个 chunk,标记 usage 已发送
+                                if finish_reason.is_some() {
+                                    final_usage = None;
+                                }
+
+                                let json_str = serde_json::to_string(&legacy_chunk).unwrap_or_default();
+                                tracing::debug!("Legacy Stream Chunk: {}", json_str); 
+                                let sse_out = format!("data: {}\n\n", json_str);
+                                yield Ok::<Bytes, String>(Bytes::from(sse_out));
                             }
                         }
-                    }
-                }
                         Some(Err(e)) => {
                             use crate::proxy::mappers::error_classifier::classify_stream_error;
                             let (error_type, user_message, i18n_key) = classify_stream_error(&e);
@@ -576,10 +548,12 @@ pub fn create_legacy_sse_stream(
 }
 
 pub fn create_codex_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     _model: String,
+    session_id: String,
+    tool_schemas: Option<Arc<ToolSchemas>>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
-    let mut buffer = BytesMut::new();
+    let mut events = decode_stream(gemini_stream);
     
     // Generate alphanumeric ID
     let charset = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
@@ -605,6 +579,7 @@ pub fn create_codex_sse_stream(
 
         let mut full_content = String::new();
         let mut emitted_tool_calls = std::collections::HashSet::new();
+        let mut last_signature: Option<String> = None;
         let mut last_finish_reason = "stop".to_string();
         let mut accumulated_usage: Option<super::models::OpenAIUsage> = None;
 
@@ -620,162 +595,160 @@ pub fn create_codex_sse_stream(
                 }
 
                 // Upstream data
-                item = gemini_stream.next() => {
+                item = events.next() => {
                     match item {
-                        Some(Ok(bytes)) => {
-                            buffer.extend_from_slice(&bytes);
-                            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                                let line_raw = buffer.split_to(pos + 1);
-                                if let Ok(line_str) = std::str::from_utf8(&line_raw) {
-                                    let line = line_str.trim();
-                                    if line.is_empty() || !line.starts_with("data: ") { continue; }
-                                    
-                                    let json_part = line.trim_start_matches("data: ").trim();
-                                    if json_part == "[DONE]" { continue; }
-
-                                    if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
-                                        let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) { inner } else { json };
-                                        
-                                        // Capture usageMetadata if present
-                                        if let Some(u) = actual_data.get("usageMetadata") {
-                                            accumulated_usage = extract_usage_metadata(u);
-                                        }
-                                        
-                                        // Capture finish reason
-                                        if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
-                                            if let Some(candidate) = candidates.get(0) {
-                                                if let Some(reason) = candidate.get("finishReason").and_then(|r| r.as_str()) {
-                                                    last_finish_reason = match reason {
-                                                        "STOP" => "stop".to_string(),
-                                                        "MAX_TOKENS" => "length".to_string(),
-                                                        _ => "stop".to_string(),
-                                                    };
-                                                }
-                                            }
+                        Some(Ok(event)) => {
+                            let json_part = event.data.trim();
+                            if json_part.is_empty() || json_part == "[DONE]" { continue; }
+
+                            if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
+                                let mut actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) { inner } else { json };
+                                if let Some(schemas) = &tool_schemas {
+                                    schemas.repair_gemini_response(&mut actual_data);
+                                }
+                                
+                                // Capture usageMetadata if present
+                                if let Some(u) = actual_data.get("usageMetadata") {
+                                    accumulated_usage = extract_usage_metadata(u);
+                                }
+                                
+                                // Capture finish reason
+                                if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
+                                    if let Some(candidate) = candidates.get(0) {
+                                        if let Some(reason) = candidate.get("finishReason").and_then(|r| r.as_str()) {
+                                            last_finish_reason = match reason {
+                                                "STOP" => "stop".to_string(),
+                                                "MAX_TOKENS" => "length".to_string(),
+                                                _ => "stop".to_string(),
+                                            };
                                         }
+                                    }
+                                }
 
-                                        // text delta
-                                        let mut delta_text = String::new();
-                                        if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
-                                            if let Some(candidate) = candidates.get(0) {
-                                                if let Some(parts) = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
-                                                    for part in parts {
-                                                        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                                                            let clean_text = text.replace('“', "\"").replace('”', "\"");
-                                                            delta_text.push_str(&clean_text);
-                                                        }
+                                // text delta
+                                let mut delta_text = String::new();
+                                if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
+                                    if let Some(candidate) = candidates.get(0) {
+                                        if let Some(parts) = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
+                                            for part in parts {
+                                                if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                                                    let clean_text = text.replace('“', "\"").replace('”', "\"");
+                                                    delta_text.push_str(&clean_text);
+                                                }
+                                                
+                                                // 捕获 thoughtSignature
+                                                if let Some(sig) = part_thought_signature(part) {
+                                                    tracing::debug!("[Codex-SSE] 捕获 thoughtSignature (长度: {})", sig.len());
+                                                    store_thought_signature(&session_id, sig);
+                                                    last_signature = Some(sig.to_string());
+                                                }
+                                                
+                                                // Handle function call in chunk with deduplication
+                                                if let Some(func_call) = part.get("functionCall") {
+                                                    let call_key = serde_json::to_string(func_call).unwrap_or_default();
+                                                    if !emitted_tool_calls.contains(&call_key) {
+                                                        emitted_tool_calls.insert(call_key);
+
+                                                        let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
+                                                        let name_str = name.to_string();
                                                         
-                                                        // 捕获 thoughtSignature
-                                                        if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
-                                                            tracing::debug!("[Codex-SSE] 捕获 thoughtSignature (长度: {})", sig.len());
-                                                            store_thought_signature(sig);
+                                                        let fallback_args = json!({});
+                                                        let args_obj = func_call.get("args").unwrap_or(&fallback_args);
+                                                        let args_str = args_obj.to_string();
+
+                                                        // Use content-based hash for call_id
+                                                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                                                        use std::hash::{Hash, Hasher};
+                                                        name_str.hash(&mut hasher);
+                                                        args_str.hash(&mut hasher);
+                                                        let call_id = format!("call_{:x}", hasher.finish());
+                                                        if let Some(sig) = last_signature.as_deref() {
+                                                            store_tool_call_signature(&session_id, &call_id, sig);
                                                         }
                                                         
-                                                        // Handle function call in chunk with deduplication
-                                                        if let Some(func_call) = part.get("functionCall") {
-                                                            let call_key = serde_json::to_string(func_call).unwrap_or_default();
-                                                            if !emitted_tool_calls.contains(&call_key) {
-                                                                emitted_tool_calls.insert(call_key);
-
-                                                                let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
-                                                                let name_str = name.to_string();
-                                                                
-                                                                let fallback_args = json!({});
-                                                                let args_obj = func_call.get("args").unwrap_or(&fallback_args);
-                                                                let args_str = args_obj.to_string();
-
-                                                                // Use content-based hash for call_id
-                                                                let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                                                                use std::hash::{Hash, Hasher};
-                                                                name_str.hash(&mut hasher);
-                                                                args_str.hash(&mut hasher);
-                                                                let call_id = format!("call_{:x}", hasher.finish());
-                                                                
-                                                                // Determine event type based on tool name
-                                                                let maybe_item_added_ev: Option<Value> = if name_str == "shell" || name_str == "local_shell" {
-                                                                    // Map to local_shell_call
-                                                                    let cmd_vec: Vec<String> = if args_obj.as_object().map(|o| o.is_empty()).unwrap_or(true) {
-                                                                        vec!["powershell.exe".to_string(), "-Command".to_string(), "exit 0".to_string()]
-                                                                    } else if let Some(arr) = args_obj.get("command").and_then(|v| v.as_array()) {
-                                                                        arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect()
-                                                                    } else if let Some(cmd_str) = args_obj.get("command").and_then(|v| v.as_str()) {
-                                                                        if cmd_str.contains(' ') {
-                                                                            vec!["powershell.exe".to_string(), "-Command".to_string(), cmd_str.to_string()]
-                                                                        } else {
-                                                                            vec![cmd_str.to_string()]
-                                                                        }
-                                                                    } else {
-                                                                        vec!["powershell.exe".to_string(), "-Command".to_string(), "exit 0".to_string()]
-                                                                    };
-                                                                    
-                                                                    Some(json!({
-                                                                        "type": "response.output_item.added",
-                                                                        "item": {
-                                                                            "type": "local_shell_call",
-                                                                            "status": "in_progress",
-                                                                            "call_id": &call_id,
-                                                                            "action": {
-                                                                                "type": "exec",
-                                                                                "command": cmd_vec
-                                                                            }
-                                                                        }
-                                                                    }))
-                                                                } else if name_str == "googleSearch" || name_str == "web_search" || name_str == "google_search" {
-                                                                    // Map to web_search_call
-                                                                    let query_val = args_obj.get("query").and_then(|v| v.as_str()).unwrap_or("");
-                                                                    Some(json!({
-                                                                        "type": "response.output_item.added",
-                                                                        "item": {
-                                                                            "type": "web_search_call",
-                                                                            "status": "in_progress",
-                                                                            "call_id": &call_id,
-                                                                            "action": {
-                                                                                "type": "search",
-                                                                                "query": query_val
-                                                                            }
-                                                                        }
-                                                                    }))
+                                                        // Determine event type based on tool name
+                                                        let maybe_item_added_ev: Option<Value> = if name_str == "shell" || name_str == "local_shell" {
+                                                            // Map to local_shell_call
+                                                            let cmd_vec: Vec<String> = if args_obj.as_object().map(|o| o.is_empty()).unwrap_or(true) {
+                                                                vec!["powershell.exe".to_string(), "-Command".to_string(), "exit 0".to_string()]
+                                                            } else if let Some(arr) = args_obj.get("command").and_then(|v| v.as_array()) {
+                                                                arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect()
+                                                            } else if let Some(cmd_str) = args_obj.get("command").and_then(|v| v.as_str()) {
+                                                                if cmd_str.contains(' ') {
+                                                                    vec!["powershell.exe".to_string(), "-Command".to_string(), cmd_str.to_string()]
                                                                 } else {
-                                                                    // Default function_call
-                                                                    Some(json!({
-                                                                        "type": "response.output_item.added",
-                                                                        "item": {
-                                                                            "type": "function_call",
-                                                                            "name": name,
-                                                                            "arguments": args_str,
-                                                                            "call_id": &call_id
-                                                                        }
-                                                                    }))
-                                                                };
-
-                                                                if let Some(item_added_ev) = maybe_item_added_ev {
-                                                                    yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&item_added_ev).unwrap())));
-                                                                    
-                                                                    // Emit response.output_item.done
-                                                                    let mut item_done_ev = item_added_ev.clone();
-                                                                    if let Some(obj) = item_done_ev.as_object_mut() {
-                                                                        obj.insert("type".to_string(), json!("response.output_item.done"));
+                                                                    vec![cmd_str.to_string()]
+                                                                }
+                                                            } else {
+                                                                vec!["powershell.exe".to_string(), "-Command".to_string(), "exit 0".to_string()]
+                                                            };
+                                                            
+                                                            Some(json!({
+                                                                "type": "response.output_item.added",
+                                                                "item": {
+                                                                    "type": "local_shell_call",
+                                                                    "status": "in_progress",
+                                                                    "call_id": &call_id,
+                                                                    "action": {
+                                                                        "type": "exec",
+                                                                        "command": cmd_vec
+                                                                    }
+                                                                }
+                                                            }))
+                                                        } else if name_str == "googleSearch" || name_str == "web_search" || name_str == "google_search" {
+                                                            // Map to web_search_call
+                                                            let query_val = args_obj.get("query").and_then(|v| v.as_str()).unwrap_or("");
+                                                            Some(json!({
+                                                                "type": "response.output_item.added",
+                                                                "item": {
+                                                                    "type": "web_search_call",
+                                                                    "status": "in_progress",
+                                                                    "call_id": &call_id,
+                                                                    "action": {
+                                                                        "type": "search",
+                                                                        "query": query_val
                                                                     }
-                                                                    yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&item_done_ev).unwrap())));
                                                                 }
+                                                            }))
+                                                        } else {
+                                                            // Default function_call
+                                                            Some(json!({
+                                                                "type": "response.output_item.added",
+                                                                "item": {
+                                                                    "type": "function_call",
+                                                                    "name": name,
+                                                                    "arguments": args_str,
+                                                                    "call_id": &call_id
+                                                                }
+                                                            }))
+                                                        };
+
+                                                        if let Some(item_added_ev) = maybe_item_added_ev {
+                                                            yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&item_added_ev).unwrap())));
+                                                            
+                                                            // Emit response.output_item.done
+                                                            let mut item_done_ev = item_added_ev.clone();
+                                                            if let Some(obj) = item_done_ev.as_object_mut() {
+                                                                obj.insert("type".to_string(), json!("response.output_item.done"));
                                                             }
+                                                            yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&item_done_ev).unwrap())));
                                                         }
                                                     }
                                                 }
                                             }
                                         }
-
-                                        if !delta_text.is_empty() {
-                                            full_content.push_str(&delta_text);
-                                            // 2. Emit response.output_text.delta
-                                            let delta_ev = json!({
-                                                "type": "response.output_text.delta",
-                                                "delta": delta_text
-                                            });
-                                            yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&delta_ev).unwrap())));
-                                        }
                                     }
                                 }
+
+                                if !delta_text.is_empty() {
+                                    full_content.push_str(&delta_text);
+                                    // 2. Emit response.output_text.delta
+                                    let delta_ev = json!({
+                                        "type": "response.output_text.delta",
+                                        "delta": delta_text
+                                    });
+                                    yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&delta_ev).unwrap())));
+                                }
                             }
                         }
                         Some(Err(e)) => {
@@ -0,0 +1,532 @@
+//! 工具调用参数的 Schema 校验与修复
+//!
+//! `remap_function_call_args` 只能按工具名修补已知的个别问题。这里根据请求中声明的
+//! `input_schema` 对每个完成的 functionCall 做通用修复:
+//! - 整体或单个字段被序列化成字符串的 JSON (stringified JSON)
+//! - 可安全转换的标量类型 (字符串 ↔ 数字 / 布尔，单值 → 数组); 对象 / 数组不会被转成字符串
+//! - 枚举值大小写不一致、字段名大小写 / 下划线风格不一致
+//! - 缺失但 schema 提供了 default 的必填字段，值为 null 的可选字段
+//!
+//! Claude 与 OpenAI 协议共用: OpenAI 路径通过 `repair_gemini_response` 在映射前修复上游响应。
+//! 修复后会再次校验，仍不符合 schema 的调用由调用方决定是否要求模型重新生成。
+
+use super::claude::models::Tool;
+use crate::proxy::common::json_schema::validate_json_schema;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+const MAX_REPAIR_DEPTH: usize = 32;
+
+/// [FIX #547] Gemini sometimes sends boolean parameters as strings (e.g., "true", "-n", "false")
+pub fn coerce_to_bool(value: &Value) -> Option<Value> {
+    match value {
+        Value::Bool(_) => Some(value.clone()),
+        Value::String(s) => {
+            let lower = s.trim().to_lowercase();
+            if lower == "true" || lower == "yes" || lower == "1" || lower == "-n" {
+                Some(json!(true))
+            } else if lower == "false" || lower == "no" || lower == "0" {
+                Some(json!(false))
+            } else {
+                None
+            }
+        }
+        Value::Number(n) => Some(json!(n.as_i64().map(|i| i != 0).unwrap_or(false))),
+        _ => None,
+    }
+}
+
+/// 单次修复的结果
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    /// 已应用的修复，格式为 `路径: 说明`
+    pub fixes: Vec<String>,
+    /// 修复后仍然存在的 schema 违规
+    pub errors: Vec<String>,
+}
+
+impl RepairReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// 请求中声明的工具 schema (工具名 → input_schema)
+#[derive(Debug, Default, Clone)]
+pub struct ToolSchemas {
+    schemas: HashMap<String, Value>,
+}
+
+impl ToolSchemas {
+    /// 从 Claude 请求的 tools 构建 (跳过 web_search 等没有 input_schema 的服务端工具)
+    pub fn from_claude_tools(tools: &[Tool]) -> Self {
+        let schemas = tools
+            .iter()
+            .filter_map(|t| Some((t.name.clone()?, t.input_schema.clone()?)))
+            .collect();
+        Self { schemas }
+    }
+
+    /// 从 OpenAI 请求的 tools 构建 (Chat Completions 的 `function.parameters` 与 Responses 的顶层 `parameters`)
+    pub fn from_openai_tools(tools: &[Value]) -> Self {
+        let schemas = tools
+            .iter()
+            .filter_map(|t| {
+                let func = t.get("function").unwrap_or(t);
+                let name = func.get("name")?.as_str()?;
+                let schema = func.get("parameters").filter(|p| p.is_object())?;
+                Some((name.to_string(), schema.clone()))
+            })
+            .collect();
+        Self { schemas }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    /// 按工具名查找 schema，找不到时忽略大小写 (上游会把 Grep 改写为 grep)
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.schemas.get(name).or_else(|| {
+            self.schemas
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v)
+        })
+    }
+
+    /// 修复并校验一次调用的参数；未声明的工具原样返回
+    pub fn repair(&self, name: &str, args: &mut Value) -> RepairReport {
+        match self.get(name) {
+            Some(schema) => repair_tool_args(args, schema),
+            None => RepairReport::default(),
+        }
+    }
+
+    /// 修复 Gemini 响应 (可带 v1internal 的 `response` 包装) 中每个 functionCall 的参数
+    pub fn repair_gemini_response(&self, response: &mut Value) {
+        let raw = match response.get("response") {
+            Some(_) => &mut response["response"],
+            None => response,
+        };
+        let Some(candidates) = raw.get_mut("candidates").and_then(|c| c.as_array_mut()) else {
+            return;
+        };
+        let parts = candidates
+            .iter_mut()
+            .filter_map(|c| c.pointer_mut("/content/parts").and_then(|p| p.as_array_mut()))
+            .flatten();
+        for part in parts {
+            let Some(Value::Object(call)) = part.get_mut("functionCall") else {
+                continue;
+            };
+            let name = call.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string();
+            let args = call.entry("args").or_insert(Value::Null);
+            let report = self.repair(&name, args);
+            if !report.fixes.is_empty() {
+                tracing::debug!("[ToolCallRepair] Repaired args for '{}': {:?}", name, report.fixes);
+            }
+            if !report.is_valid() {
+                tracing::warn!("[ToolCallRepair] Tool call '{}' still violates its schema: {:?}", name, report.errors);
+            }
+        }
+    }
+
+    /// 仅校验，不修改参数
+    pub fn validate(&self, name: &str, args: &Value) -> Result<(), Vec<String>> {
+        match self.get(name) {
+            Some(schema) => validate_json_schema(args, schema),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 按 schema 修复工具参数，然后返回修复记录与剩余的校验错误
+pub fn repair_tool_args(args: &mut Value, schema: &Value) -> RepairReport {
+    let mut fixes = Vec::new();
+
+    // 参数整体被序列化为字符串 / 缺失
+    match args {
+        Value::String(s) => {
+            if let Ok(parsed @ Value::Object(_)) = serde_json::from_str::<Value>(s.trim()) {
+                *args = parsed;
+                fixes.push("$: parsed stringified arguments".to_string());
+            }
+        }
+        Value::Null => {
+            *args = json!({});
+            fixes.push("$: replaced null arguments with {}".to_string());
+        }
+        _ => {}
+    }
+
+    repair_node(args, schema, schema, "$", &mut fixes, 0);
+
+    let errors = validate_json_schema(args, schema).err().unwrap_or_default();
+    RepairReport { fixes, errors }
+}
+
+fn resolve<'a>(schema: &'a Value, root: &'a Value) -> &'a Value {
+    let mut current = schema;
+    // 只跟随有限层级的 $ref，防止自引用
+    for _ in 0..8 {
+        match current
+            .get("$ref")
+            .and_then(|r| r.as_str())
+            .and_then(|r| r.strip_prefix("#/"))
+        {
+            Some(path) => {
+                match path.split('/').try_fold(root, |node, seg| node.get(seg)) {
+                    Some(target) => current = target,
+                    None => break,
+                }
+            }
+            None => break,
+        }
+    }
+    current
+}
+
+fn schema_types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(ts)) => ts.iter().filter_map(|t| t.as_str()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn matches_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn allows_null(schema: &Value) -> bool {
+    schema.get("nullable").and_then(|n| n.as_bool()).unwrap_or(false)
+        || schema_types(schema).contains(&"null")
+}
+
+fn number_value(f: f64) -> Option<Value> {
+    if f.fract() == 0.0 && f.abs() < i64::MAX as f64 {
+        Some(json!(f as i64))
+    } else {
+        serde_json::Number::from_f64(f).map(Value::Number)
+    }
+}
+
+/// 尝试把 value 无损地转换为目标类型
+fn coerce(value: &Value, ty: &str) -> Option<Value> {
+    match (ty, value) {
+        ("object", Value::String(s)) => match serde_json::from_str::<Value>(s.trim()) {
+            Ok(parsed @ Value::Object(_)) => Some(parsed),
+            _ => None,
+        },
+        ("array", Value::String(s)) => match serde_json::from_str::<Value>(s.trim()) {
+            Ok(parsed @ Value::Array(_)) => Some(parsed),
+            _ => Some(json!([value])),
+        },
+        ("array", Value::Null) => None,
+        ("array", other) => Some(json!([other])),
+        ("boolean", _) => coerce_to_bool(value),
+        ("integer", Value::String(s)) => s.trim().parse::<i64>().ok().map(|i| json!(i)),
+        ("integer", Value::Number(n)) => n
+            .as_f64()
+            .filter(|f| f.fract() == 0.0)
+            .and_then(number_value),
+        ("number", Value::String(s)) => s.trim().parse::<f64>().ok().and_then(number_value),
+        ("string", Value::Number(n)) => Some(json!(n.to_string())),
+        ("string", Value::Bool(b)) => Some(json!(b.to_string())),
+        // 对象 / 数组传给字符串参数通常是模型理解错了参数，交给校验报错而不是序列化
+        _ => None,
+    }
+}
+
+/// 字段名归一化: 忽略大小写、下划线与连字符 (filePath / file_path / FILE-PATH)
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+fn repair_node(
+    value: &mut Value,
+    schema: &Value,
+    root: &Value,
+    path: &str,
+    fixes: &mut Vec<String>,
+    depth: usize,
+) {
+    if depth > MAX_REPAIR_DEPTH {
+        return;
+    }
+    let schema = resolve(schema, root);
+
+    if let Some(all_of) = schema.get("allOf").and_then(|a| a.as_array()) {
+        for sub in all_of {
+            repair_node(value, sub, root, path, fixes, depth + 1);
+        }
+    }
+
+    // anyOf / oneOf: 已经匹配某个分支就不动；否则采用第一个修复后能通过校验的分支
+    for key in ["anyOf", "oneOf"] {
+        let Some(branches) = schema.get(key).and_then(|b| b.as_array()) else {
+            continue;
+        };
+        let branch_ok = |v: &Value, b: &Value| validate_json_schema(v, resolve(b, root)).is_ok();
+        if branches.iter().any(|b| branch_ok(value, b)) {
+            continue;
+        }
+        for branch in branches {
+            let mut candidate = value.clone();
+            let mut branch_fixes = Vec::new();
+            repair_node(&mut candidate, branch, root, path, &mut branch_fixes, depth + 1);
+            if branch_ok(&candidate, branch) {
+                *value = candidate;
+                fixes.extend(branch_fixes);
+                break;
+            }
+        }
+    }
+
+    let types = schema_types(schema);
+    if !types.is_empty() && !types.iter().any(|t| matches_type(value, t)) {
+        if let Some((ty, coerced)) = types.iter().find_map(|t| coerce(value, t).map(|c| (*t, c))) {
+            fixes.push(format!("{}: coerced {} to {}", path, json_type_name(value), ty));
+            *value = coerced;
+        }
+    }
+
+    if let (Some(options), Some(s)) = (schema.get("enum").and_then(|e| e.as_array()), value.as_str()) {
+        if !options.iter().any(|o| o.as_str() == Some(s)) {
+            if let Some(canonical) = options
+                .iter()
+                .filter_map(|o| o.as_str())
+                .find(|o| o.eq_ignore_ascii_case(s.trim()))
+            {
+                fixes.push(format!("{}: normalized enum value \"{}\" to \"{}\"", path, s, canonical));
+                *value = json!(canonical);
+            }
+        }
+    }
+
+    match value {
+        Value::Object(obj) => repair_object(obj, schema, root, path, fixes, depth),
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items").filter(|s| s.is_object()) {
+                for (i, item) in items.iter_mut().enumerate() {
+                    repair_node(item, item_schema, root, &format!("{}[{}]", path, i), fixes, depth + 1);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn repair_object(
+    obj: &mut Map<String, Value>,
+    schema: &Value,
+    root: &Value,
+    path: &str,
+    fixes: &mut Vec<String>,
+    depth: usize,
+) {
+    let empty = Map::new();
+    let properties = schema.get("properties").and_then(|p| p.as_object()).unwrap_or(&empty);
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(|r| r.as_array())
+        .map(|r| r.iter().filter_map(|k| k.as_str()).collect())
+        .unwrap_or_default();
+
+    // 1. 字段名风格不一致: 只在目标字段缺失时重命名
+    let unknown: Vec<String> = obj.keys().filter(|k| !properties.contains_key(*k)).cloned().collect();
+    for key in unknown {
+        let normalized = normalize_key(&key);
+        let target = properties
+            .keys()
+            .find(|p| !obj.contains_key(*p) && normalize_key(p) == normalized)
+            .cloned();
+        if let Some(target) = target {
+            if let Some(v) = obj.remove(&key) {
+                fixes.push(format!("{}: renamed field \"{}\" to \"{}\"", path, key, target));
+                obj.insert(target, v);
+            }
+        }
+    }
+
+    // 2. 可选字段为 null 时移除 (很多客户端的校验器不接受显式 null)
+    let null_optionals: Vec<String> = obj
+        .iter()
+        .filter(|(k, v)| {
+            v.is_null()
+                && !required.contains(&k.as_str())
+                && properties
+                    .get(*k)
+                    .is_some_and(|p| !allows_null(resolve(p, root)))
+        })
+        .map(|(k, _)| k.clone())
+        .collect();
+    for key in null_optionals {
+        obj.remove(&key);
+        fixes.push(format!("{}.{}: removed null optional field", path, key));
+    }
+
+    // 3. 缺失的必填字段使用 schema 中的 default 补齐
+    for key in &required {
+        if obj.contains_key(*key) {
+            continue;
+        }
+        if let Some(default) = properties.get(*key).and_then(|p| resolve(p, root).get("default")) {
+            obj.insert(key.to_string(), default.clone());
+            fixes.push(format!("{}.{}: filled missing required field from default", path, key));
+        }
+    }
+
+    // 4. 递归修复已声明的字段
+    for (key, prop_schema) in properties {
+        if let Some(child) = obj.get_mut(key) {
+            repair_node(child, prop_schema, root, &format!("{}.{}", path, key), fixes, depth + 1);
+        }
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "file_path": {"type": "string"},
+                "limit": {"type": "integer"},
+                "ratio": {"type": "number"},
+                "recursive": {"type": "boolean"},
+                "paths": {"type": "array", "items": {"type": "string"}},
+                "options": {
+                    "type": "object",
+                    "properties": {"mode": {"type": "string", "enum": ["fast", "thorough"]}}
+                },
+                "encoding": {"type": "string", "default": "utf-8"},
+                "label": {"type": "string"}
+            },
+            "required": ["file_path", "encoding"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_repairs_common_violations() {
+        let mut args = json!({
+            "filePath": "/tmp/a.txt",
+            "limit": "20",
+            "ratio": "0.5",
+            "recursive": "yes",
+            "paths": "src",
+            "options": "{\"mode\": \"FAST\"}",
+            "label": null
+        });
+        let report = repair_tool_args(&mut args, &schema());
+        assert!(report.is_valid(), "remaining errors: {:?}", report.errors);
+        assert_eq!(
+            args,
+            json!({
+                "file_path": "/tmp/a.txt",
+                "limit": 20,
+                "ratio": 0.5,
+                "recursive": true,
+                "paths": ["src"],
+                "options": {"mode": "fast"},
+                "encoding": "utf-8"
+            })
+        );
+        assert!(report.fixes.iter().any(|f| f.starts_with("$.limit: coerced string to integer")));
+    }
+
+    #[test]
+    fn test_stringified_arguments_and_unrepairable_call() {
+        let mut args = json!("{\"file_path\": \"/tmp/b.txt\", \"encoding\": \"ascii\"}");
+        let report = repair_tool_args(&mut args, &schema());
+        assert!(report.is_valid());
+        assert_eq!(args["file_path"], "/tmp/b.txt");
+
+        // 缺少没有 default 的必填字段，无法安全修复
+        let mut args = json!({"limit": "many"});
+        let report = repair_tool_args(&mut args, &schema());
+        assert!(!report.is_valid());
+        assert!(report.errors.iter().any(|e| e.contains("file_path")));
+        assert_eq!(args["limit"], "many");
+    }
+
+    #[test]
+    fn test_any_of_and_tool_lookup() {
+        let tools = vec![Tool {
+            type_: None,
+            name: Some("Grep".to_string()),
+            description: None,
+            input_schema: Some(json!({
+                "type": "object",
+                "properties": {
+                    "head_limit": {"anyOf": [{"type": "integer"}, {"type": "null"}]}
+                }
+            })),
+        }];
+        let schemas = ToolSchemas::from_claude_tools(&tools);
+        let mut args = json!({"head_limit": "5"});
+        let report = schemas.repair("grep", &mut args);
+        assert!(report.is_valid());
+        assert_eq!(args, json!({"head_limit": 5}));
+
+        let mut untouched = json!({"anything": "goes"});
+        assert_eq!(schemas.repair("unknown_tool", &mut untouched), RepairReport::default());
+    }
+
+    #[test]
+    fn test_object_not_coerced_to_string() {
+        let mut args = json!({"file_path": {"path": "/tmp/a.txt"}, "encoding": ["utf-8"]});
+        let report = repair_tool_args(&mut args, &schema());
+        assert!(!report.is_valid());
+        assert_eq!(args["file_path"], json!({"path": "/tmp/a.txt"}));
+        assert_eq!(args["encoding"], json!(["utf-8"]));
+    }
+
+    #[test]
+    fn test_openai_tools_and_gemini_response() {
+        let tools = vec![
+            json!({"type": "function", "function": {"name": "read_file", "parameters": schema()}}),
+            json!({"type": "function", "name": "list_dir", "parameters": {"type": "object", "properties": {"depth": {"type": "integer"}}}}),
+            json!({"type": "web_search"}),
+        ];
+        let schemas = ToolSchemas::from_openai_tools(&tools);
+        assert!(schemas.get("read_file").is_some() && schemas.get("list_dir").is_some());
+
+        let mut response = json!({"response": {"candidates": [{"content": {"parts": [
+            {"text": "Reading"},
+            {"functionCall": {"name": "read_file", "args": "{\"filePath\": \"/tmp/c.txt\"}"}},
+            {"functionCall": {"name": "list_dir", "args": {"depth": "2"}}}
+        ]}}]}});
+        schemas.repair_gemini_response(&mut response);
+        let parts = &response["response"]["candidates"][0]["content"]["parts"];
+        assert_eq!(parts[1]["functionCall"]["args"], json!({"file_path": "/tmp/c.txt", "encoding": "utf-8"}));
+        assert_eq!(parts[2]["functionCall"]["args"], json!({"depth": 2}));
+    }
+}
@@ -4,9 +4,19 @@
 //! - 浏览器快照压缩 (头+尾保留)
 //! - 大文件提示压缩 (提取关键信息)
 //! - 通用截断 (200,000 字符限制)
+//! - 按工具名配置的策略 (保留 / 头尾截断 / 智能压缩 / 清理 HTML / 卸载到本地)
+//!
+//! 卸载 (offload) 的结果保存在数据目录的 `tool_results/` 下，正文替换为引用，
+//! 模型可以通过注入的检索工具 [`RETRIEVAL_TOOL_NAME`] 分段读取。
 
+use crate::proxy::config::{ToolResultAction, ToolResultConfig, ToolResultPolicy};
+use once_cell::sync::Lazy;
 use regex::Regex;
-use serde_json::Value;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
 use tracing::{debug, info};
 
 /// 最大工具结果字符数 (约 20 万,防止 prompt 超长)
@@ -179,15 +189,16 @@ fn compact_browser_snapshot(text: &str, max_chars: usize) -> Option<String> {
 
 /// 安全的文本截断 (尽量不在标签中间截断)
 fn truncate_text_safe(text: &str, max_chars: usize) -> String {
-    if text.len() <= max_chars {
+    // 按字符计数，limit 为第 max_chars 个字符的字节位置
+    let Some(limit) = char_byte_index(text, max_chars) else {
         return text.to_string();
-    }
+    };
     
     // 尝试寻找一个安全的截断点 (不在 < 和 > 之间)
-    let mut split_pos = max_chars;
+    let mut split_pos = limit;
     
     // 向前查找是否有未闭合的标签开始符
-    let sub = &text[..max_chars];
+    let sub = &text[..limit];
     if let Some(last_open) = sub.rfind('<') {
         if let Some(last_close) = sub.rfind('>') {
             if last_open > last_close {
@@ -205,7 +216,7 @@ fn truncate_text_safe(text: &str, max_chars: usize) -> String {
         if let Some(last_close_brace) = sub.rfind('}') {
             if last_open_brace > last_close_brace {
                 // 可能在 JSON 中间，如果距离截断点较近，尝试回退
-                if max_chars - last_open_brace < 100 {
+                if limit - last_open_brace < 100 {
                     split_pos = split_pos.min(last_open_brace);
                 }
             }
@@ -213,7 +224,7 @@ fn truncate_text_safe(text: &str, max_chars: usize) -> String {
     }
 
     let truncated = &text[..split_pos];
-    let omitted = text.len() - split_pos;
+    let omitted = text[split_pos..].chars().count();
     format!("{}\n...[truncated {} chars]", truncated, omitted)
 }
 
@@ -329,6 +340,414 @@ fn is_base64_image(block: &Value) -> bool {
             == Some("base64")
 }
 
+/// 注入给模型的检索工具名，用于读取被卸载的工具结果
+pub const RETRIEVAL_TOOL_NAME: &str = "antigravity_fetch_tool_result";
+
+/// 引用文本的前缀，用于识别请求中是否存在卸载的结果
+const OFFLOAD_MARKER: &str = "[tool_result offloaded: ref=";
+
+/// 卸载引用中附带的预览长度
+const OFFLOAD_PREVIEW_CHARS: usize = 2_000;
+
+/// 检索工具单次返回的默认 / 最大字符数
+const RETRIEVAL_DEFAULT_LENGTH: usize = 20_000;
+const RETRIEVAL_MAX_LENGTH: usize = 50_000;
+
+/// 两次过期清理之间的最小间隔 (秒)
+const OFFLOAD_CLEANUP_INTERVAL_SECS: i64 = 3600;
+
+struct PolicyState {
+    config: ToolResultConfig,
+    offload_dir: Option<PathBuf>,
+}
+
+static POLICY_STATE: Lazy<RwLock<PolicyState>> = Lazy::new(|| {
+    RwLock::new(PolicyState {
+        config: ToolResultConfig::default(),
+        offload_dir: None,
+    })
+});
+
+static LAST_OFFLOAD_CLEANUP: AtomicI64 = AtomicI64::new(0);
+
+/// 应用工具结果配置 (启动及配置热更新时调用)
+pub fn configure(config: ToolResultConfig) {
+    let offload_dir = crate::modules::account::get_data_dir()
+        .map(|d| d.join("tool_results"))
+        .map_err(|e| tracing::warn!("[ToolCompressor] Offload disabled, data dir unavailable: {}", e))
+        .ok();
+    if let Some(dir) = &offload_dir {
+        cleanup_offloaded(dir, config.offload_ttl_hours);
+    }
+    if let Ok(mut state) = POLICY_STATE.write() {
+        state.config = config;
+        state.offload_dir = offload_dir;
+    }
+}
+
+/// 策略处理结果
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PolicyOutcome {
+    /// 命中 keep 策略，调用方不应再做长度截断
+    pub keep_intact: bool,
+    /// 至少有一段内容被卸载到本地
+    pub offloaded: bool,
+}
+
+fn tool_pattern_matches(pattern: &str, tool_name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = tool_name.to_lowercase();
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+/// 按全局配置处理一个工具结果的 content
+pub fn apply_tool_result_policy(tool_name: &str, content: &mut Value) -> PolicyOutcome {
+    let state = match POLICY_STATE.read() {
+        Ok(state) => state,
+        Err(_) => return PolicyOutcome::default(),
+    };
+    apply_policy_with(&state.config, state.offload_dir.as_deref(), tool_name, content)
+}
+
+/// 按给定配置处理工具结果; 未命中任何策略时沿用内置的智能压缩
+pub fn apply_policy_with(
+    config: &ToolResultConfig,
+    offload_dir: Option<&Path>,
+    tool_name: &str,
+    content: &mut Value,
+) -> PolicyOutcome {
+    let Some(policy) = config.policies.iter().find(|p| tool_pattern_matches(&p.tool, tool_name)) else {
+        if let Some(blocks) = content.as_array_mut() {
+            sanitize_tool_result_blocks(blocks);
+        }
+        return PolicyOutcome::default();
+    };
+
+    let max_chars = policy.max_chars.unwrap_or(config.default_max_chars);
+    let mut outcome = PolicyOutcome {
+        keep_intact: policy.action == ToolResultAction::Keep,
+        offloaded: false,
+    };
+
+    match content {
+        Value::String(text) => {
+            *text = apply_action(policy, max_chars, offload_dir, text, &mut outcome, config.offload_ttl_hours);
+        }
+        Value::Array(blocks) => {
+            let mut removed_image = false;
+            if policy.drop_images {
+                blocks.retain(|b| {
+                    let image = is_base64_image(b);
+                    removed_image |= image;
+                    !image
+                });
+            }
+            for block in blocks.iter_mut() {
+                if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
+                    let processed = apply_action(policy, max_chars, offload_dir, text, &mut outcome, config.offload_ttl_hours);
+                    block["text"] = Value::String(processed);
+                }
+            }
+            if removed_image {
+                blocks.push(json!({
+                    "type": "text",
+                    "text": "[image omitted by tool result policy]"
+                }));
+            }
+        }
+        _ => {}
+    }
+
+    debug!(
+        "[ToolCompressor] Applied policy {:?} ({:?}) to tool '{}'",
+        policy.tool, policy.action, tool_name
+    );
+    outcome
+}
+
+fn apply_action(
+    policy: &ToolResultPolicy,
+    max_chars: usize,
+    offload_dir: Option<&Path>,
+    text: &str,
+    outcome: &mut PolicyOutcome,
+    ttl_hours: u64,
+) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    match policy.action {
+        ToolResultAction::Keep => text.to_string(),
+        ToolResultAction::Truncate => truncate_head_tail(text, max_chars, policy.head_ratio),
+        ToolResultAction::Summarize => compact_tool_result_text(text, max_chars),
+        ToolResultAction::StripHtml => truncate_text_safe(&deep_clean_html(text), max_chars),
+        ToolResultAction::Offload => match offload_dir.map(|dir| offload_text(dir, text, ttl_hours)) {
+            Some(Ok(reference)) => {
+                outcome.offloaded = true;
+                reference
+            }
+            Some(Err(e)) => {
+                tracing::warn!("[ToolCompressor] Offload failed, truncating instead: {}", e);
+                truncate_head_tail(text, max_chars, policy.head_ratio)
+            }
+            None => truncate_head_tail(text, max_chars, policy.head_ratio),
+        },
+    }
+}
+
+/// 第 n 个字符的字节位置 (字符数不超过 n 时返回 None)
+fn char_byte_index(text: &str, n: usize) -> Option<usize> {
+    text.char_indices().nth(n).map(|(i, _)| i)
+}
+
+/// 保留头部 head_ratio 和尾部剩余部分，中间替换为省略提示 (均按字符计数)
+fn truncate_head_tail(text: &str, max_chars: usize, head_ratio: f64) -> String {
+    let total = text.chars().count();
+    if total <= max_chars {
+        return text.to_string();
+    }
+    let head_chars = (max_chars as f64 * head_ratio.clamp(0.0, 1.0)) as usize;
+    let tail_chars = max_chars.saturating_sub(head_chars);
+    let head_end = char_byte_index(text, head_chars).unwrap_or(text.len());
+    let tail_start = char_byte_index(text, total - tail_chars).unwrap_or(text.len());
+    format!(
+        "{}\n...[omitted {} chars]...\n{}",
+        &text[..head_end],
+        total - head_chars - tail_chars,
+        &text[tail_start..]
+    )
+}
+
+fn offload_reference_id(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    format!("tr_{}", &format!("{:x}", digest)[..16])
+}
+
+fn is_valid_reference(reference: &str) -> bool {
+    reference.len() == 19
+        && reference.starts_with("tr_")
+        && reference[3..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// 以 0600 权限写入 (工具结果可能包含敏感内容)
+fn write_private(path: &Path, content: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut f| f.write_all(content))
+        .map_err(|e| format!("write {}: {}", path.display(), e))
+}
+
+/// 保存完整结果并返回替换用的引用文本 (按内容去重，重复请求不会重复写入)
+fn offload_text(dir: &Path, text: &str, ttl_hours: u64) -> Result<String, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {}", dir.display(), e))?;
+    let reference = offload_reference_id(text);
+    let path = dir.join(format!("{}.txt", reference));
+    if path.exists() {
+        // 复用已有文件时刷新 mtime，避免仍在使用的结果被 TTL 清理
+        if let Err(e) = std::fs::File::options()
+            .append(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(std::time::SystemTime::now()))
+        {
+            tracing::warn!("[ToolCompressor] Failed to refresh {}: {}", path.display(), e);
+        }
+    } else {
+        write_private(&path, text.as_bytes())?;
+        debug!("[ToolCompressor] Offloaded {} chars to {}", text.chars().count(), path.display());
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let last = LAST_OFFLOAD_CLEANUP.load(Ordering::Relaxed);
+    if now - last > OFFLOAD_CLEANUP_INTERVAL_SECS
+        && LAST_OFFLOAD_CLEANUP
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        cleanup_offloaded(dir, ttl_hours);
+    }
+
+    let total_chars = text.chars().count();
+    let preview_end = text.char_indices().nth(OFFLOAD_PREVIEW_CHARS).map(|(i, _)| i).unwrap_or(text.len());
+    Ok(format!(
+        "{}{}, {} chars]\nPreview:\n{}\n...\n[Full output stored outside the prompt. Call {} with ref=\"{}\" and an offset/length (in chars) to read more.]",
+        OFFLOAD_MARKER,
+        reference,
+        total_chars,
+        &text[..preview_end],
+        RETRIEVAL_TOOL_NAME,
+        reference
+    ))
+}
+
+/// 删除超过 TTL 的卸载文件
+fn cleanup_offloaded(dir: &Path, ttl_hours: u64) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let ttl = std::time::Duration::from_secs(ttl_hours.saturating_mul(3600));
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > ttl);
+        if expired && std::fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        info!("[ToolCompressor] Removed {} expired offloaded tool results", removed);
+    }
+}
+
+/// 检索工具的 functionDeclaration
+pub fn retrieval_tool_declaration() -> Value {
+    json!({
+        "name": RETRIEVAL_TOOL_NAME,
+        "description": "Read part of a tool result that was stored outside the prompt because it was too large. \
+Use the ref from the '[tool_result offloaded: ref=...]' notice.",
+        "parameters": {
+            "type": "object",
+            "properties": {
+                "ref": { "type": "string", "description": "Reference id, e.g. tr_0123456789abcdef" },
+                "offset": { "type": "integer", "description": "Start position in characters (default 0)" },
+                "length": { "type": "integer", "description": "Number of characters to read (default 20000, max 50000)" }
+            },
+            "required": ["ref"]
+        }
+    })
+}
+
+/// 请求 contents 中是否存在卸载引用 (决定是否注入检索工具)
+pub fn contains_offload_reference(contents: &Value) -> bool {
+    match contents {
+        Value::String(s) => s.contains(OFFLOAD_MARKER),
+        Value::Array(items) => items.iter().any(contains_offload_reference),
+        Value::Object(map) => map.values().any(contains_offload_reference),
+        _ => false,
+    }
+}
+
+/// 在 Gemini 请求中注入检索工具 (仅当已有 functionDeclarations 时，不能与 googleSearch 混用)
+pub fn inject_retrieval_tool(inner_request: &mut Value) -> bool {
+    let Some(declarations) = inner_request
+        .get_mut("tools")
+        .and_then(|t| t.as_array_mut())
+        .and_then(|tools| tools.iter_mut().find_map(|t| t.get_mut("functionDeclarations")))
+        .and_then(|d| d.as_array_mut())
+    else {
+        return false;
+    };
+    if declarations.iter().any(|d| d["name"] == RETRIEVAL_TOOL_NAME) {
+        return true;
+    }
+    declarations.push(retrieval_tool_declaration());
+    true
+}
+
+fn fetch_offloaded_in(dir: &Path, reference: &str, offset: usize, length: usize) -> Result<String, String> {
+    if !is_valid_reference(reference) {
+        return Err(format!("invalid ref {:?}", reference));
+    }
+    let path = dir.join(format!("{}.txt", reference));
+    let text = std::fs::read_to_string(&path).map_err(|_| format!("ref {} not found or expired", reference))?;
+    let length = if length == 0 { RETRIEVAL_DEFAULT_LENGTH } else { length.min(RETRIEVAL_MAX_LENGTH) };
+    let total = text.chars().count();
+    let chunk: String = text.chars().skip(offset).take(length).collect();
+    let end = (offset + chunk.chars().count()).min(total);
+    Ok(format!(
+        "[ref={} chars {}-{} of {}{}]\n{}",
+        reference,
+        offset.min(total),
+        end,
+        total,
+        if end < total { "; call again with a larger offset for more" } else { "" },
+        chunk
+    ))
+}
+
+/// 执行一次检索工具调用 (参数为模型给出的 args)
+pub fn execute_retrieval(args: &Value) -> String {
+    let dir = match POLICY_STATE.read() {
+        Ok(state) => state.offload_dir.clone(),
+        Err(_) => None,
+    };
+    let Some(dir) = dir else {
+        return "Error: tool result offloading is not available".to_string();
+    };
+    let reference = args.get("ref").and_then(|v| v.as_str()).unwrap_or("");
+    let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    let length = args.get("length").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    fetch_offloaded_in(&dir, reference, offset, length).unwrap_or_else(|e| format!("Error: {}", e))
+}
+
+/// 为模型发出的检索调用构建后续请求的两轮 contents (完整的 model 回合 + user functionResponse)
+///
+/// `model_parts` 是本轮收到的全部原始 part (文本 / 思考 / 签名)，流式分片的相邻文本会合并；
+/// 已下发给客户端的其它工具调用不回放。为空时只回放检索调用本身。
+pub fn build_retrieval_turns(model_parts: &[Value], call_parts: &[Value]) -> Vec<Value> {
+    let mut model_turn: Vec<Value> = Vec::new();
+    for part in model_parts {
+        if let Some(call) = part.get("functionCall") {
+            if call["name"] == RETRIEVAL_TOOL_NAME {
+                model_turn.push(part.clone());
+            }
+            continue;
+        }
+        if let (Some(last), Some(text)) = (model_turn.last_mut(), part["text"].as_str()) {
+            let same_block = last["text"].is_string()
+                && last.get("thought") == part.get("thought")
+                && last.get("thoughtSignature").is_none();
+            if same_block {
+                let merged = format!("{}{}", last["text"].as_str().unwrap_or_default(), text);
+                last["text"] = Value::String(merged);
+                if let Some(signature) = part.get("thoughtSignature") {
+                    last["thoughtSignature"] = signature.clone();
+                }
+                continue;
+            }
+        }
+        model_turn.push(part.clone());
+    }
+    if model_turn.is_empty() {
+        model_turn = call_parts.to_vec();
+    }
+
+    let responses: Vec<Value> = call_parts
+        .iter()
+        .filter_map(|part| part.get("functionCall"))
+        .map(|call| {
+            let mut response = json!({
+                "functionResponse": {
+                    "name": RETRIEVAL_TOOL_NAME,
+                    "response": { "result": execute_retrieval(call.get("args").unwrap_or(&Value::Null)) }
+                }
+            });
+            if let Some(id) = call.get("id") {
+                response["functionResponse"]["id"] = id.clone();
+            }
+            response
+        })
+        .collect();
+    vec![
+        json!({ "role": "model", "parts": model_turn }),
+        json!({ "role": "user", "parts": responses }),
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -437,4 +856,142 @@ Please read the file locally."#;
         });
         assert!(!is_base64_image(&text_block));
     }
+
+    fn policy(tool: &str, action: ToolResultAction, max_chars: usize) -> ToolResultPolicy {
+        ToolResultPolicy {
+            tool: tool.to_string(),
+            action,
+            max_chars: Some(max_chars),
+            head_ratio: 0.5,
+            drop_images: true,
+        }
+    }
+
+    #[test]
+    fn test_policies_by_tool_pattern() {
+        let config = ToolResultConfig {
+            policies: vec![
+                policy("read_file", ToolResultAction::Keep, 10),
+                policy("mcp__browser_*", ToolResultAction::Truncate, 100),
+                policy("fetch*", ToolResultAction::StripHtml, 60),
+            ],
+            ..ToolResultConfig::default()
+        };
+        let long = format!("{}{}", "h".repeat(500), "t".repeat(500));
+
+        let mut content = json!(long.clone());
+        let outcome = apply_policy_with(&config, None, "Read_File", &mut content);
+        assert!(outcome.keep_intact);
+        assert_eq!(content.as_str().unwrap().len(), 1000);
+
+        let mut content = json!([{"type": "text", "text": long.clone()}]);
+        apply_policy_with(&config, None, "mcp__browser_snapshot", &mut content);
+        let text = content[0]["text"].as_str().unwrap();
+        assert!(text.starts_with(&"h".repeat(50)));
+        assert!(text.ends_with(&"t".repeat(50)));
+        assert!(text.contains("[omitted 900 chars]"));
+
+        let html = format!("<html><script>{}</script><p>hello</p></html>", "x".repeat(200));
+        let mut content = json!(html);
+        apply_policy_with(&config, None, "fetch_url", &mut content);
+        assert_eq!(content, json!("<html>[script omitted]<p>hello</p></html>"));
+
+        // 未命中策略时沿用原有的智能压缩
+        let mut content = json!([{"type": "text", "text": "short"}]);
+        assert_eq!(apply_policy_with(&config, None, "other", &mut content), PolicyOutcome::default());
+        assert_eq!(content[0]["text"], "short");
+    }
+
+    #[test]
+    fn test_offload_and_retrieve() {
+        let dir = std::env::temp_dir().join(format!("tool_results_test_{}", uuid::Uuid::new_v4().simple()));
+        let config = ToolResultConfig {
+            policies: vec![policy("bash", ToolResultAction::Offload, 3_000)],
+            ..ToolResultConfig::default()
+        };
+        let output: String = (0..1_000).map(|i| format!("line {:04}\n", i)).collect();
+
+        let mut content = json!(output.clone());
+        let outcome = apply_policy_with(&config, Some(&dir), "bash", &mut content);
+        assert!(outcome.offloaded);
+        let reference_text = content.as_str().unwrap();
+        assert!(contains_offload_reference(&json!([{"parts": [{"text": reference_text}]}])));
+        assert!(reference_text.len() < output.len());
+
+        let reference = offload_reference_id(&output);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join(format!("{}.txt", reference))).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let chunk = fetch_offloaded_in(&dir, &reference, 10, 10).unwrap();
+        assert!(chunk.starts_with(&format!("[ref={} chars 10-20 of {}", reference, output.len())));
+        assert!(chunk.ends_with("line 0001\n"));
+        assert!(fetch_offloaded_in(&dir, "../../etc/passwd", 0, 10).is_err());
+
+        let mut request = json!({"tools": [{"functionDeclarations": [{"name": "bash"}]}]});
+        assert!(inject_retrieval_tool(&mut request));
+        assert!(inject_retrieval_tool(&mut request));
+        assert_eq!(request["tools"][0]["functionDeclarations"].as_array().unwrap().len(), 2);
+
+        // 复用已卸载的结果时刷新 mtime
+        let path = dir.join(format!("{}.txt", reference));
+        let stale = std::time::SystemTime::now() - std::time::Duration::from_secs(7200);
+        std::fs::File::options().append(true).open(&path).unwrap().set_modified(stale).unwrap();
+        let mut content = json!(output.clone());
+        apply_policy_with(&config, Some(&dir), "bash", &mut content);
+        let age = std::fs::metadata(&path).unwrap().modified().unwrap().elapsed().unwrap();
+        assert!(age < std::time::Duration::from_secs(60));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_truncation_counts_chars() {
+        let text = "中".repeat(1_000);
+        let result = truncate_head_tail(&text, 100, 0.5);
+        assert!(result.starts_with(&"中".repeat(50)));
+        assert!(result.ends_with(&"中".repeat(50)));
+        assert!(result.contains("[omitted 900 chars]"));
+
+        // 字符数在上限内时不截断 (即使字节数超过)
+        let config = ToolResultConfig {
+            policies: vec![policy("fetch*", ToolResultAction::StripHtml, 500)],
+            ..ToolResultConfig::default()
+        };
+        let mut content = json!("中".repeat(400));
+        apply_policy_with(&config, None, "fetch_url", &mut content);
+        assert_eq!(content.as_str().unwrap().chars().count(), 400);
+
+        let result = truncate_text_safe(&"中".repeat(300), 200);
+        assert!(result.starts_with(&"中".repeat(200)));
+        assert!(result.ends_with("[truncated 100 chars]"));
+    }
+
+    #[test]
+    fn test_retrieval_turns_keep_model_turn() {
+        let call = json!({"functionCall": {"name": RETRIEVAL_TOOL_NAME, "args": {"ref": "tr_0"}, "id": "c1"}, "thoughtSignature": "sig"});
+        let parts = vec![
+            json!({"text": "Let me ", "thought": true}),
+            json!({"text": "check.", "thought": true, "thoughtSignature": "t1"}),
+            json!({"text": "Reading the "}),
+            json!({"text": "full output."}),
+            json!({"functionCall": {"name": "bash", "args": {}}}),
+            call.clone(),
+        ];
+        let turns = build_retrieval_turns(&parts, std::slice::from_ref(&call));
+        assert_eq!(
+            turns[0]["parts"],
+            json!([
+                {"text": "Let me check.", "thought": true, "thoughtSignature": "t1"},
+                {"text": "Reading the full output."},
+                call
+            ])
+        );
+        assert_eq!(turns[1]["parts"][0]["functionResponse"]["id"], "c1");
+
+        let turns = build_retrieval_turns(&[], std::slice::from_ref(&call));
+        assert_eq!(turns[0]["parts"], json!([call]));
+    }
 }
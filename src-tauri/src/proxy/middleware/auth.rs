@@ -42,6 +42,19 @@ pub async fn auth_middleware(
     if matches!(effective_mode, ProxyAuthMode::AllExceptHealth) && path == "/healthz" {
         return Ok(next.run(request).await);
     }
+
+    // 图片文件由签名 URL 授权 (聊天前端渲染图片时不会携带 API Key)
+    if path.starts_with(crate::proxy::image_store::FILE_ROUTE_PREFIX) {
+        return Ok(next.run(request).await);
+    }
+    
+    // 已通过 mTLS 校验的客户端证书可替代 API Key
+    if security.client_cert_auth {
+        if let Some(cert) = request.extensions().get::<crate::proxy::tls::ClientCertificate>() {
+            tracing::debug!("Authorized by client certificate {}", cert.fingerprint);
+            return Ok(next.run(request).await);
+        }
+    }
     
     // 从 header 中提取 API key
     let api_key = request
@@ -7,6 +7,8 @@ use axum::{
 use std::time::Instant;
 use crate::proxy::server::AppState;
 use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::config::CaptureMode;
+use crate::proxy::common::sse;
 use serde_json::Value;
 use futures::StreamExt;
 
@@ -28,6 +30,7 @@ pub async fn monitor_middleware(
     }
     
     let start = Instant::now();
+    let client_key = extract_client_key(request.headers());
     
     let mut model = if uri.contains("/v1beta/models/") {
         uri.split("/v1beta/models/")
@@ -38,6 +41,9 @@ pub async fn monitor_middleware(
         None
     };
 
+    let capture_policy = state.monitor.capture_policy();
+    let mut capture_mode = capture_policy.mode_for(&uri, model.as_deref(), client_key.as_deref());
+
     let request_body_str;
     let request = if method == "POST" {
         let (parts, body) = request.into_parts();
@@ -47,8 +53,11 @@ pub async fn monitor_middleware(
                     model = serde_json::from_slice::<Value>(&bytes).ok().and_then(|v|
                         v.get("model").and_then(|m| m.as_str()).map(|s| s.to_string())
                     );
+                    capture_mode = capture_policy.mode_for(&uri, model.as_deref(), client_key.as_deref());
                 }
-                request_body_str = if let Ok(s) = std::str::from_utf8(&bytes) {
+                request_body_str = if capture_mode == CaptureMode::Metadata {
+                    None
+                } else if let Ok(s) = std::str::from_utf8(&bytes) {
                     Some(s.to_string())
                 } else {
                     Some("[Binary Request Data]".to_string())
@@ -89,6 +98,13 @@ pub async fn monitor_middleware(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
+    // 会话 ID (handler 通过 X-Session-Id 回传)，用于累计会话用量
+    let session_usage = response
+        .headers()
+        .get(crate::proxy::session_manager::SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|sid| (state.token_manager.clone(), sid.to_string()));
+
     // Determine protocol from URL path
     let protocol = if uri.contains("/v1/messages") {
         Some("anthropic".to_string())
@@ -117,20 +133,29 @@ pub async fn monitor_middleware(
         input_tokens: None,
         output_tokens: None,
         protocol,
+        cached_tokens: None,
+        thinking_tokens: None,
+        cache_creation_tokens: None,
+        client_key,
     };
 
     if content_type.contains("text/event-stream") {
         let (parts, body) = response.into_parts();
         let mut stream = body.into_data_stream();
         let (tx, rx) = tokio::sync::mpsc::channel(64);
+        let buffer_limit = capture_policy.stream_buffer_limit(capture_mode, MAX_RESPONSE_LOG_SIZE);
         
         tokio::spawn(async move {
             let mut all_stream_data = Vec::new();
             let mut last_few_bytes = Vec::new();
+            let mut buffer_capped = false;
             
             while let Some(chunk_res) = stream.next().await {
                 if let Ok(chunk) = chunk_res {
-                    all_stream_data.extend_from_slice(&chunk);
+                    // 按采集策略限制缓冲量, token 统计由尾部数据兜底
+                    let take = chunk.len().min(buffer_limit - all_stream_data.len());
+                    all_stream_data.extend_from_slice(&chunk[..take]);
+                    buffer_capped |= take < chunk.len();
                     
                     if chunk.len() > 8192 {
                         last_few_bytes = chunk.slice(chunk.len()-8192..).to_vec();
@@ -146,17 +171,23 @@ pub async fn monitor_middleware(
                 }
             }
             
+            let stream_text = match std::str::from_utf8(&all_stream_data) {
+                Ok(s) => Some(s),
+                // 截断位置可能落在多字节字符中间
+                Err(e) if buffer_capped && e.error_len().is_none() => {
+                    std::str::from_utf8(&all_stream_data[..e.valid_up_to()]).ok()
+                }
+                Err(_) => None,
+            };
+
             // Parse and consolidate stream data into readable format
-            if let Ok(full_response) = std::str::from_utf8(&all_stream_data) {
+            if let Some(full_response) = stream_text {
                 let mut thinking_content = String::new();
                 let mut response_content = String::new();
                 let mut thinking_signature = String::new();
                 
-                for line in full_response.lines() {
-                    if !line.starts_with("data: ") {
-                        continue;
-                    }
-                    let json_str = line.trim_start_matches("data: ").trim();
+                for event in sse::decode_all(full_response.as_bytes()) {
+                    let json_str = event.data.trim();
                     if json_str == "[DONE]" {
                         continue;
                     }
@@ -216,6 +247,7 @@ pub async fn monitor_middleware(
                                     .and_then(|v| v.as_u64())
                                     .map(|v| v as u32);
                             }
+                            apply_usage_details(usage, &mut log);
                         }
                     }
                 }
@@ -250,28 +282,31 @@ pub async fn monitor_middleware(
             }
             
             // Fallback token extraction from tail if not already extracted
-            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                if let Ok(full_tail) = std::str::from_utf8(&last_few_bytes) {
-                    for line in full_tail.lines().rev() {
-                        if line.starts_with("data: ") && (line.contains("\"usage\"") || line.contains("\"usageMetadata\"")) {
-                            let json_str = line.trim_start_matches("data: ").trim();
-                            if let Ok(json) = serde_json::from_str::<Value>(json_str) {
-                                if let Some(usage) = json.get("usage")
-                                    .or(json.get("usageMetadata"))
-                                    .or(json.get("response").and_then(|r| r.get("usage")))
-                                {
-                                    log.input_tokens = usage.get("prompt_tokens")
-                                        .or(usage.get("input_tokens"))
-                                        .or(usage.get("promptTokenCount"))
-                                        .and_then(|v| v.as_u64())
-                                        .map(|v| v as u32);
-                                    log.output_tokens = usage.get("completion_tokens")
-                                        .or(usage.get("output_tokens"))
-                                        .or(usage.get("candidatesTokenCount"))
-                                        .and_then(|v| v.as_u64())
-                                        .map(|v| v as u32);
-                                    break;
-                                }
+            // (缓冲被截断时, 最终 usage 只存在于尾部数据中)
+            if (log.input_tokens.is_none() && log.output_tokens.is_none()) || buffer_capped {
+                // 尾部从任意位置截取, 首个不完整的事件会因解析失败而被忽略
+                for event in sse::decode_all(&last_few_bytes).iter().rev() {
+                    if event.data.contains("\"usage\"") || event.data.contains("\"usageMetadata\"") {
+                        let json_str = event.data.trim();
+                        if let Ok(json) = serde_json::from_str::<Value>(json_str) {
+                            if let Some(usage) = json.get("usage")
+                                .or(json.get("usageMetadata"))
+                                .or(json.get("response").and_then(|r| r.get("usage")))
+                            {
+                                log.input_tokens = usage.get("prompt_tokens")
+                                    .or(usage.get("input_tokens"))
+                                    .or(usage.get("promptTokenCount"))
+                                    .and_then(|v| v.as_u64())
+                                    .map(|v| v as u32)
+                                    .or(log.input_tokens);
+                                log.output_tokens = usage.get("completion_tokens")
+                                    .or(usage.get("output_tokens"))
+                                    .or(usage.get("candidatesTokenCount"))
+                                    .and_then(|v| v.as_u64())
+                                    .map(|v| v as u32)
+                                    .or(log.output_tokens);
+                                apply_usage_details(usage, &mut log);
+                                break;
                             }
                         }
                     }
@@ -281,6 +316,7 @@ pub async fn monitor_middleware(
             if log.status >= 400 {
                 log.error = Some("Stream Error or Failed".to_string());
             }
+            record_session_usage(&session_usage, &log);
             monitor.log_request(log).await;
         });
 
@@ -310,6 +346,7 @@ pub async fn monitor_middleware(
                                     .and_then(|v| v.as_u64())
                                     .map(|v| v as u32);
                             }
+                            apply_usage_details(usage, &mut log);
                         }
                     }
                     log.response_body = Some(s.to_string());
@@ -320,18 +357,141 @@ pub async fn monitor_middleware(
                 if log.status >= 400 {
                     log.error = log.response_body.clone();
                 }
+                record_session_usage(&session_usage, &log);
                 monitor.log_request(log).await;
                 Response::from_parts(parts, Body::from(bytes))
             }
             Err(_) => {
                 log.response_body = Some("[Response too large (>100MB)]".to_string());
+                record_session_usage(&session_usage, &log);
                 monitor.log_request(log).await;
                 Response::from_parts(parts, Body::empty())
             }
         }
     } else {
         log.response_body = Some(format!("[{}]", content_type));
+        record_session_usage(&session_usage, &log);
         monitor.log_request(log).await;
         response
     }
 }
+
+/// 将本次请求的 token 用量累计到会话记录
+fn record_session_usage(
+    session_usage: &Option<(std::sync::Arc<crate::proxy::TokenManager>, String)>,
+    log: &ProxyRequestLog,
+) {
+    if let Some((token_manager, session_id)) = session_usage {
+        token_manager.record_session_usage(
+            session_id,
+            log.input_tokens.unwrap_or(0),
+            log.output_tokens.unwrap_or(0),
+        );
+    }
+}
+
+/// 提取缓存/思考 token 明细, 并统一口径:
+/// - input_tokens 包含缓存命中与缓存写入部分 (Anthropic 格式的 cache_read_input_tokens / cache_creation_input_tokens 单独计数, 需要累加)
+/// - output_tokens 不包含思考部分 (OpenAI 格式的 reasoning_tokens 包含在 completion_tokens 内, 需要扣除)
+fn apply_usage_details(usage: &Value, log: &mut ProxyRequestLog) {
+    let as_u32 = |v: Option<&Value>| v.and_then(|v| v.as_u64()).map(|v| v as u32);
+
+    if let Some(cache_read) = as_u32(usage.get("cache_read_input_tokens")) {
+        log.cached_tokens = Some(cache_read);
+        log.input_tokens = Some(log.input_tokens.unwrap_or(0).saturating_add(cache_read));
+    } else {
+        log.cached_tokens = as_u32(
+            usage
+                .get("cachedContentTokenCount")
+                .or(usage.pointer("/prompt_tokens_details/cached_tokens"))
+                .or(usage.pointer("/input_tokens_details/cached_tokens")),
+        );
+    }
+
+    if let Some(cache_write) = as_u32(usage.get("cache_creation_input_tokens")).filter(|v| *v > 0) {
+        log.cache_creation_tokens = Some(cache_write);
+        log.input_tokens = Some(log.input_tokens.unwrap_or(0).saturating_add(cache_write));
+    }
+
+    if let Some(thoughts) = as_u32(usage.get("thoughtsTokenCount")) {
+        log.thinking_tokens = Some(thoughts);
+    } else if let Some(reasoning) = as_u32(
+        usage
+            .pointer("/completion_tokens_details/reasoning_tokens")
+            .or(usage.pointer("/output_tokens_details/reasoning_tokens")),
+    ) {
+        log.thinking_tokens = Some(reasoning);
+        log.output_tokens = log.output_tokens.map(|v| v.saturating_sub(reasoning));
+    }
+}
+
+/// 提取客户端 API Key 并脱敏 (最多保留末 4 位), 用于按客户端统计
+fn extract_client_key(headers: &axum::http::HeaderMap) -> Option<String> {
+    let key = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .map(strip_bearer)
+        .or_else(|| headers.get("x-api-key").and_then(|h| h.to_str().ok()))
+        .or_else(|| headers.get("x-goog-api-key").and_then(|h| h.to_str().ok()))?
+        .trim();
+
+    if key.is_empty() {
+        return None;
+    }
+    Some(mask_client_key(key))
+}
+
+/// 去掉 Authorization 头的 Bearer 前缀 (不区分大小写)
+fn strip_bearer(value: &str) -> &str {
+    match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("bearer ") => value[7..].trim_start(),
+        _ => value,
+    }
+}
+
+/// 短于 16 个字符的 Key 完全隐藏，否则只保留末 4 位
+fn mask_client_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() < 16 {
+        return "***".to_string();
+    }
+    let suffix: String = chars[chars.len() - 4..].iter().collect();
+    format!("***{}", suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_client_key() {
+        assert_eq!(mask_client_key("sk-1234567890abcdef"), "***cdef");
+        assert_eq!(mask_client_key("sk-1234567890abc"), "***0abc");
+        assert_eq!(mask_client_key("sk-1234567890ab"), "***");
+        assert_eq!(mask_client_key("short"), "***");
+    }
+
+    #[test]
+    fn test_usage_details_include_cache_writes() {
+        let mut log: ProxyRequestLog = serde_json::from_value(serde_json::json!({
+            "id": "1", "timestamp": 0, "method": "POST", "url": "/v1/messages", "status": 200, "duration": 1,
+            "input_tokens": 100
+        }))
+        .unwrap();
+        let usage = serde_json::json!({ "cache_read_input_tokens": 300, "cache_creation_input_tokens": 50 });
+        apply_usage_details(&usage, &mut log);
+        assert_eq!(log.input_tokens, Some(450));
+        assert_eq!(log.cached_tokens, Some(300));
+        assert_eq!(log.cache_creation_tokens, Some(50));
+    }
+
+    #[test]
+    fn test_extract_client_key_strips_bearer() {
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert(axum::http::header::AUTHORIZATION, "bearer sk-1234567890abcdef".parse().unwrap());
+        assert_eq!(extract_client_key(&headers).as_deref(), Some("***cdef"));
+        headers.insert(axum::http::header::AUTHORIZATION, "BEARER sk-1234567890abcdef".parse().unwrap());
+        assert_eq!(extract_client_key(&headers).as_deref(), Some("***cdef"));
+        assert_eq!(strip_bearer("Bearer"), "Bearer");
+    }
+}
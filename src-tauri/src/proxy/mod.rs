@@ -17,11 +17,16 @@ pub mod providers;         // Extra upstream providers (z.ai, etc.)
 pub mod zai_vision_mcp;    // Built-in Vision MCP server state
 pub mod zai_vision_tools;  // Built-in Vision MCP tools (z.ai vision API)
 pub mod monitor;           // 监控
+pub mod log_capture;       // 日志采集与脱敏策略
 pub mod rate_limit;        // 限流跟踪
 pub mod sticky_config;     // 粘性调度配置
 pub mod session_manager;   // 会话指纹管理
 pub mod audio;             // 音频处理模块
 pub mod signature_cache;   // Signature Cache (v3.3.16)
+pub mod compaction;        // 服务端会话压缩
+pub mod model_catalog;     // 实时模型目录
+pub mod image_store;       // 生成图片的本地存储与签名 URL
+pub mod tls;               // 监听端口 TLS / mTLS
 pub mod cli_sync;          // CLI 配置同步 (v3.3.35)
 
 
@@ -0,0 +1,524 @@
+// 实时模型目录 (Live Model Catalog)
+//
+// 定期对号池中每个账号调用 fetchAvailableModels，合并出真实可用的模型、上下文/输出上限、
+// 模态与思考能力以及全池剩余配额，供 /v1/models、/v1/models/claude 与 /v1beta/models 统一输出。
+// 内置别名与自定义映射仍会列出，能力信息取自其映射到的上游模型。
+
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+use crate::proxy::upstream::client::UpstreamClient;
+use crate::proxy::TokenManager;
+
+/// 目录刷新间隔 (同时也是过期时间)
+const CATALOG_TTL: Duration = Duration::from_secs(10 * 60);
+/// 同时查询的账号数
+const FETCH_CONCURRENCY: usize = 8;
+/// 刷新失败后的重试间隔
+const RETRY_BACKOFF: Duration = Duration::from_secs(60);
+/// 列表请求等待首次刷新的最长时间
+const FIRST_REFRESH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 兼容旧版本的固定 created 时间戳
+const MODEL_CREATED: i64 = 1706745600;
+const MODEL_CREATED_AT: &str = "2024-02-01T00:00:00Z";
+
+/// 合并后的单个上游模型信息
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct CatalogModel {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub context_window: Option<u64>,
+    pub max_output_tokens: Option<u64>,
+    pub supports_images: bool,
+    pub supports_video: bool,
+    pub supports_thinking: bool,
+    pub thinking_budget: Option<i64>,
+    /// 号池中列出该模型的账号数
+    pub accounts_total: usize,
+    /// 其中仍有剩余配额的账号数
+    pub accounts_available: usize,
+    /// 全池平均剩余配额 (0.0 - 1.0)，无配额信息时为 None
+    pub remaining_fraction: Option<f64>,
+    /// 最早的配额重置时间 (RFC 3339)
+    pub reset_time: Option<String>,
+}
+
+impl CatalogModel {
+    pub fn available(&self) -> bool {
+        self.accounts_available > 0
+    }
+
+    pub fn input_modalities(&self) -> Vec<&'static str> {
+        let mut modalities = vec!["text"];
+        if self.supports_images {
+            modalities.push("image");
+        }
+        if self.supports_video {
+            modalities.push("video");
+        }
+        modalities
+    }
+
+    pub fn output_modalities(&self) -> Vec<&'static str> {
+        if self.id.contains("image") {
+            vec!["text", "image"]
+        } else {
+            vec!["text"]
+        }
+    }
+}
+
+/// 列表中的一项: 对外暴露的模型 ID 与其上游模型信息
+#[derive(Debug, Clone)]
+pub struct ListedModel {
+    pub id: String,
+    pub info: Option<CatalogModel>,
+}
+
+#[derive(Default)]
+struct CatalogSnapshot {
+    models: BTreeMap<String, CatalogModel>,
+    refreshed_at: Option<Instant>,
+    last_attempt: Option<Instant>,
+}
+
+pub struct ModelCatalog {
+    snapshot: RwLock<CatalogSnapshot>,
+    /// 最近一次成功刷新时上游返回的全部模型 ID (含未公开列出的)，供同步的配置校验使用
+    known_ids: std::sync::RwLock<Option<Arc<HashSet<String>>>>,
+    refresh_lock: Mutex<()>,
+    auto_refresh_started: AtomicBool,
+}
+
+/// 只保留对外有意义的模型 (与配额页面一致)
+fn is_public_model(id: &str) -> bool {
+    id.contains("gemini") || id.contains("claude")
+}
+
+/// 合并多个账号的 fetchAvailableModels 响应
+pub fn merge_account_models(responses: &[Value]) -> BTreeMap<String, CatalogModel> {
+    let mut merged: BTreeMap<String, CatalogModel> = BTreeMap::new();
+    let mut fractions: HashMap<String, (f64, usize)> = HashMap::new();
+
+    for response in responses {
+        let Some(models) = response.get("models").and_then(|m| m.as_object()) else {
+            continue;
+        };
+        for (id, info) in models {
+            if !is_public_model(id) {
+                continue;
+            }
+            let entry = merged.entry(id.clone()).or_insert_with(|| CatalogModel {
+                id: id.clone(),
+                ..Default::default()
+            });
+            let as_u64 = |key: &str| {
+                info.get(key)
+                    .and_then(|v| v.as_u64().or_else(|| v.as_str().and_then(|s| s.parse().ok())))
+            };
+            let as_bool = |key: &str| info.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+
+            if entry.display_name.is_none() {
+                entry.display_name = info.get("displayName").and_then(|v| v.as_str()).map(String::from);
+            }
+            entry.context_window = entry.context_window.max(as_u64("maxTokens"));
+            entry.max_output_tokens = entry.max_output_tokens.max(as_u64("maxOutputTokens"));
+            entry.supports_images |= as_bool("supportsImages");
+            entry.supports_video |= as_bool("supportsVideo");
+            entry.supports_thinking |= as_bool("supportsThinking");
+            if entry.thinking_budget.is_none() {
+                entry.thinking_budget = info.get("thinkingBudget").and_then(|v| v.as_i64());
+            }
+
+            entry.accounts_total += 1;
+            let quota = info.get("quotaInfo");
+            let fraction = quota
+                .and_then(|q| q.get("remainingFraction"))
+                .and_then(|v| v.as_f64());
+            // 没有 remainingFraction 的配额信息表示已耗尽
+            let has_quota = match (quota, fraction) {
+                (None, _) => true,
+                (Some(_), Some(f)) => f > 0.0,
+                (Some(_), None) => false,
+            };
+            if has_quota {
+                entry.accounts_available += 1;
+            }
+            if quota.is_some() {
+                let sum = fractions.entry(id.clone()).or_insert((0.0, 0));
+                sum.0 += fraction.unwrap_or(0.0);
+                sum.1 += 1;
+            }
+            if let Some(reset) = quota.and_then(|q| q.get("resetTime")).and_then(|v| v.as_str()) {
+                if entry.reset_time.as_deref().is_none_or(|current| reset < current) {
+                    entry.reset_time = Some(reset.to_string());
+                }
+            }
+        }
+    }
+
+    for (id, (sum, count)) in fractions {
+        if let Some(entry) = merged.get_mut(&id) {
+            entry.remaining_fraction = Some(sum / count as f64);
+        }
+    }
+    merged
+}
+
+/// 上游模型 ID 集合是否包含 `id` (精确匹配，或如 gemini-3-pro-image-4k-16x9 这样的变体前缀)
+pub fn catalog_lists(known: &HashSet<String>, id: &str) -> bool {
+    known.contains(id) || known.iter().any(|k| id.starts_with(&format!("{}-", k)))
+}
+
+/// 找到对外 ID 对应的上游模型: 精确匹配，否则取最长的前缀匹配 (如 gemini-3-pro-image-4k-16x9)
+fn lookup_target<'a>(models: &'a BTreeMap<String, CatalogModel>, target: &str) -> Option<&'a CatalogModel> {
+    models.get(target).or_else(|| {
+        models
+            .values()
+            .filter(|m| target.starts_with(&format!("{}-", m.id)))
+            .max_by_key(|m| m.id.len())
+    })
+}
+
+impl ModelCatalog {
+    fn new() -> Self {
+        Self {
+            snapshot: RwLock::new(CatalogSnapshot::default()),
+            known_ids: std::sync::RwLock::new(None),
+            refresh_lock: Mutex::new(()),
+            auto_refresh_started: AtomicBool::new(false),
+        }
+    }
+
+    pub fn global() -> &'static ModelCatalog {
+        static INSTANCE: OnceLock<ModelCatalog> = OnceLock::new();
+        INSTANCE.get_or_init(ModelCatalog::new)
+    }
+
+    /// 启动后台定期刷新 (重复调用只会启动一次)
+    pub fn start_auto_refresh(&'static self, token_manager: Arc<TokenManager>, upstream: Arc<UpstreamClient>) {
+        if self.auto_refresh_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CATALOG_TTL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.refresh(&token_manager, &upstream).await {
+                    tracing::warn!("[ModelCatalog] Refresh failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// 立即从号池刷新目录
+    pub async fn refresh(&self, token_manager: &TokenManager, upstream: &UpstreamClient) -> Result<usize, String> {
+        let _guard = self.refresh_lock.lock().await;
+        self.snapshot.write().await.last_attempt = Some(Instant::now());
+        let emails = token_manager.account_emails();
+        if emails.is_empty() {
+            return Err("No accounts available".to_string());
+        }
+
+        let responses: Vec<Value> = futures::stream::iter(emails)
+            .map(|email| async move {
+                let (access_token, project_id, _) = token_manager.get_token_by_email(&email).await.ok()?;
+                match upstream.fetch_available_models(&access_token, Some(&project_id)).await {
+                    Ok(response) => Some(response),
+                    Err(e) => {
+                        tracing::debug!("[ModelCatalog] fetchAvailableModels failed for {}: {}", email, e);
+                        None
+                    }
+                }
+            })
+            .buffer_unordered(FETCH_CONCURRENCY)
+            .filter_map(|r| async move { r })
+            .collect()
+            .await;
+
+        if responses.is_empty() {
+            return Err("fetchAvailableModels failed for every account".to_string());
+        }
+
+        let ids: HashSet<String> = responses
+            .iter()
+            .filter_map(|r| r.get("models").and_then(|m| m.as_object()))
+            .flat_map(|m| m.keys().cloned())
+            .collect();
+        if let Ok(mut known) = self.known_ids.write() {
+            *known = Some(Arc::new(ids));
+        }
+        let models = merge_account_models(&responses);
+        let count = models.len();
+        let mut snapshot = self.snapshot.write().await;
+        snapshot.models = models;
+        snapshot.refreshed_at = Some(Instant::now());
+        tracing::info!("[ModelCatalog] Refreshed {} models from {} account(s)", count, responses.len());
+        Ok(count)
+    }
+
+    /// 目录过期时刷新 (最多等待 FIRST_REFRESH_TIMEOUT，失败时沿用旧数据并退避重试)
+    async fn ensure_fresh(&self, token_manager: &TokenManager, upstream: &UpstreamClient) {
+        let (stale, backing_off) = {
+            let snapshot = self.snapshot.read().await;
+            (
+                snapshot.refreshed_at.is_none_or(|t| t.elapsed() > CATALOG_TTL),
+                snapshot.last_attempt.is_some_and(|t| t.elapsed() < RETRY_BACKOFF),
+            )
+        };
+        if !stale || backing_off {
+            return;
+        }
+        match tokio::time::timeout(FIRST_REFRESH_TIMEOUT, self.refresh(token_manager, upstream)).await {
+            Ok(Err(e)) => tracing::debug!("[ModelCatalog] Refresh skipped: {}", e),
+            Err(_) => tracing::warn!("[ModelCatalog] Refresh timed out, serving cached catalog"),
+            Ok(Ok(_)) => {}
+        }
+    }
+
+    /// 上游模型 ID 集合; 目录尚未成功加载时为 None
+    pub fn known_model_ids(&self) -> Option<Arc<HashSet<String>>> {
+        self.known_ids.read().ok().and_then(|k| k.clone())
+    }
+
+    /// 当前目录中的上游模型
+    pub async fn models(&self) -> Vec<CatalogModel> {
+        self.snapshot.read().await.models.values().cloned().collect()
+    }
+
+    /// 对外模型列表: 上游真实模型 + 内置别名与自定义映射 (附带其目标模型的信息)
+    pub async fn list(
+        &self,
+        token_manager: &TokenManager,
+        upstream: &UpstreamClient,
+        custom_mapping: &tokio::sync::RwLock<HashMap<String, String>>,
+    ) -> Vec<ListedModel> {
+        use crate::proxy::common::model_mapping::{get_all_dynamic_models, map_claude_model_to_gemini};
+
+        self.ensure_fresh(token_manager, upstream).await;
+        let aliases = get_all_dynamic_models(custom_mapping).await;
+        let mapping = custom_mapping.read().await;
+        let snapshot = self.snapshot.read().await;
+
+        let mut ids: Vec<String> = snapshot.models.keys().cloned().chain(aliases).collect();
+        ids.sort();
+        ids.dedup();
+        ids.into_iter()
+            .map(|id| {
+                let target = mapping
+                    .get(&id)
+                    .cloned()
+                    .unwrap_or_else(|| map_claude_model_to_gemini(&id));
+                let info = snapshot
+                    .models
+                    .get(&id)
+                    .or_else(|| lookup_target(&snapshot.models, &target))
+                    .cloned();
+                ListedModel { id, info }
+            })
+            .collect()
+    }
+}
+
+/// OpenAI / Anthropic 列表共用的扩展字段
+fn capability_fields(info: &CatalogModel) -> Map<String, Value> {
+    let mut fields = Map::new();
+    fields.insert("available".to_string(), json!(info.available()));
+    if let Some(limit) = info.context_window {
+        fields.insert("context_window".to_string(), json!(limit));
+    }
+    if let Some(limit) = info.max_output_tokens {
+        fields.insert("max_output_tokens".to_string(), json!(limit));
+    }
+    fields.insert(
+        "capabilities".to_string(),
+        json!({
+            "thinking": info.supports_thinking,
+            "vision": info.supports_images,
+            "input_modalities": info.input_modalities(),
+            "output_modalities": info.output_modalities(),
+        }),
+    );
+    fields.insert(
+        "quota".to_string(),
+        json!({
+            "remaining_fraction": info.remaining_fraction,
+            "accounts_available": info.accounts_available,
+            "accounts_total": info.accounts_total,
+            "reset_time": info.reset_time,
+        }),
+    );
+    fields
+}
+
+/// OpenAI 格式 (GET /v1/models)
+pub fn openai_list(models: &[ListedModel]) -> Value {
+    let data: Vec<Value> = models
+        .iter()
+        .map(|m| {
+            let mut entry = json!({
+                "id": m.id,
+                "object": "model",
+                "created": MODEL_CREATED,
+                "owned_by": "antigravity"
+            });
+            if let (Some(info), Some(obj)) = (&m.info, entry.as_object_mut()) {
+                obj.extend(capability_fields(info));
+            }
+            entry
+        })
+        .collect();
+    json!({ "object": "list", "data": data })
+}
+
+/// Anthropic 格式 (GET /v1/models 带 anthropic-version 头，或 /v1/models/claude)
+pub fn anthropic_list(models: &[ListedModel]) -> Value {
+    let data: Vec<Value> = models
+        .iter()
+        .map(|m| {
+            let display_name = m
+                .info
+                .as_ref()
+                .filter(|info| info.id == m.id)
+                .and_then(|info| info.display_name.clone())
+                .unwrap_or_else(|| m.id.clone());
+            let mut entry = json!({
+                "type": "model",
+                "id": m.id,
+                "display_name": display_name,
+                "created_at": MODEL_CREATED_AT
+            });
+            if let (Some(info), Some(obj)) = (&m.info, entry.as_object_mut()) {
+                obj.extend(capability_fields(info));
+            }
+            entry
+        })
+        .collect();
+    json!({
+        "data": data,
+        "has_more": false,
+        "first_id": models.first().map(|m| m.id.clone()),
+        "last_id": models.last().map(|m| m.id.clone()),
+    })
+}
+
+/// Gemini 格式的单个模型 (无目录信息时沿用旧的默认上限)
+pub fn gemini_model(id: &str, info: Option<&CatalogModel>) -> Value {
+    let display_name = info
+        .filter(|i| i.id == id)
+        .and_then(|i| i.display_name.clone())
+        .unwrap_or_else(|| id.to_string());
+    let mut model = json!({
+        "name": format!("models/{}", id),
+        "baseModelId": info.map(|i| i.id.as_str()).unwrap_or(id),
+        "version": "001",
+        "displayName": display_name,
+        "description": "",
+        "inputTokenLimit": info.and_then(|i| i.context_window).unwrap_or(128000),
+        "outputTokenLimit": info.and_then(|i| i.max_output_tokens).unwrap_or(8192),
+        "supportedGenerationMethods": ["generateContent", "streamGenerateContent", "countTokens"],
+        "temperature": 1.0,
+        "topP": 0.95,
+        "topK": 64
+    });
+    if let Some(info) = info {
+        model["thinking"] = json!(info.supports_thinking);
+        model["available"] = json!(info.available());
+        model["remainingQuotaFraction"] = json!(info.remaining_fraction);
+    }
+    model
+}
+
+/// Gemini 格式 (GET /v1beta/models)
+pub fn gemini_list(models: &[ListedModel]) -> Value {
+    let models: Vec<Value> = models.iter().map(|m| gemini_model(&m.id, m.info.as_ref())).collect();
+    json!({ "models": models })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(fraction: Option<f64>, reset: &str) -> Value {
+        let mut quota = json!({ "resetTime": reset });
+        if let Some(f) = fraction {
+            quota["remainingFraction"] = json!(f);
+        }
+        json!({
+            "models": {
+                "gemini-3-pro-image": {
+                    "displayName": "Gemini 3 Pro Image",
+                    "maxTokens": 65536,
+                    "maxOutputTokens": "32768",
+                    "supportsImages": true,
+                    "quotaInfo": quota
+                },
+                "claude-sonnet-4-5-thinking": {
+                    "maxTokens": 200000,
+                    "supportsThinking": true,
+                    "thinkingBudget": 24576
+                },
+                "chat_20706": {}
+            }
+        })
+    }
+
+    #[test]
+    fn test_merge_account_models() {
+        let merged = merge_account_models(&[
+            account(Some(0.5), "2026-01-02T00:00:00Z"),
+            account(None, "2026-01-01T00:00:00Z"),
+        ]);
+        assert_eq!(merged.len(), 2, "internal models are hidden");
+
+        let image = &merged["gemini-3-pro-image"];
+        assert_eq!(image.context_window, Some(65536));
+        assert_eq!(image.max_output_tokens, Some(32768));
+        assert_eq!(image.accounts_total, 2);
+        assert_eq!(image.accounts_available, 1);
+        assert_eq!(image.remaining_fraction, Some(0.25));
+        assert_eq!(image.reset_time.as_deref(), Some("2026-01-01T00:00:00Z"));
+        assert_eq!(image.input_modalities(), vec!["text", "image"]);
+        assert_eq!(image.output_modalities(), vec!["text", "image"]);
+
+        let claude = &merged["claude-sonnet-4-5-thinking"];
+        assert!(claude.supports_thinking && claude.available());
+        assert_eq!(claude.remaining_fraction, None);
+
+        // 图片模型的分辨率/比例后缀按前缀匹配
+        let target = lookup_target(&merged, "gemini-3-pro-image-4k-16x9").unwrap();
+        assert_eq!(target.id, "gemini-3-pro-image");
+        assert!(lookup_target(&merged, "gemini-3-pro-imagex").is_none());
+    }
+
+    #[test]
+    fn test_list_formats() {
+        let merged = merge_account_models(&[account(Some(1.0), "2026-01-01T00:00:00Z")]);
+        let listed = vec![
+            ListedModel { id: "claude-sonnet-4-5-20250929".to_string(), info: merged.get("claude-sonnet-4-5-thinking").cloned() },
+            ListedModel { id: "custom-model".to_string(), info: None },
+        ];
+
+        let openai = openai_list(&listed);
+        assert_eq!(openai["data"][0]["context_window"], 200000);
+        assert_eq!(openai["data"][0]["capabilities"]["thinking"], true);
+        assert!(openai["data"][1].get("available").is_none());
+
+        let anthropic = anthropic_list(&listed);
+        assert_eq!(anthropic["data"][0]["type"], "model");
+        assert_eq!(anthropic["data"][0]["display_name"], "claude-sonnet-4-5-20250929");
+        assert_eq!(anthropic["last_id"], "custom-model");
+
+        let gemini = gemini_list(&listed);
+        assert_eq!(gemini["models"][0]["inputTokenLimit"], 200000);
+        assert_eq!(gemini["models"][0]["baseModelId"], "claude-sonnet-4-5-thinking");
+        assert_eq!(gemini["models"][1]["outputTokenLimit"], 8192);
+    }
+}
@@ -2,6 +2,8 @@ use serde::{Serialize, Deserialize};
 use std::collections::VecDeque;
 use tokio::sync::RwLock;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crate::proxy::log_capture::CapturePolicy;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyRequestLog {
@@ -20,6 +22,14 @@ pub struct ProxyRequestLog {
     pub input_tokens: Option<u32>,
     pub output_tokens: Option<u32>,
     pub protocol: Option<String>,     // 协议类型: "openai", "anthropic", "gemini"
+    #[serde(default)]
+    pub cached_tokens: Option<u32>,   // 命中缓存的输入 token (包含在 input_tokens 内)
+    #[serde(default)]
+    pub thinking_tokens: Option<u32>, // 思考 token (不包含在 output_tokens 内)
+    #[serde(default, skip_serializing)]
+    pub cache_creation_tokens: Option<u32>, // 写入缓存的输入 token (包含在 input_tokens 内, 仅用于计费, 不落库)
+    #[serde(default)]
+    pub client_key: Option<String>,   // 客户端 API Key (脱敏后)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -34,6 +44,8 @@ pub struct ProxyMonitor {
     pub stats: RwLock<ProxyStats>,
     pub max_logs: usize,
     pub enabled: AtomicBool,
+    capture_policy: std::sync::RwLock<Arc<CapturePolicy>>,
+    writer: crate::modules::log_writer::LogWriter,
 }
 
 impl ProxyMonitor {
@@ -62,6 +74,8 @@ impl ProxyMonitor {
             stats: RwLock::new(ProxyStats::default()),
             max_logs,
             enabled: AtomicBool::new(false), // Default to disabled
+            capture_policy: std::sync::RwLock::new(Arc::new(CapturePolicy::default())),
+            writer: crate::modules::log_writer::LogWriter::start(),
         }
     }
 
@@ -73,24 +87,51 @@ impl ProxyMonitor {
         self.enabled.load(Ordering::Relaxed)
     }
 
-    pub async fn log_request(&self, log: ProxyRequestLog) {
+    /// 更新正文采集/脱敏策略
+    pub fn set_capture_config(&self, config: crate::proxy::config::LogCaptureConfig) {
+        let policy = Arc::new(CapturePolicy::new(config));
+        if let Ok(mut current) = self.capture_policy.write() {
+            *current = policy;
+        }
+    }
+
+    pub fn capture_policy(&self) -> Arc<CapturePolicy> {
+        self.capture_policy
+            .read()
+            .map(|p| p.clone())
+            .unwrap_or_default()
+    }
+
+    pub async fn log_request(&self, mut log: ProxyRequestLog) {
         if let (Some(account), Some(input), Some(output)) = (
             &log.account_email,
             log.input_tokens,
             log.output_tokens,
         ) {
-            let model = log.model.clone().unwrap_or_else(|| "unknown".to_string());
-            let account = account.clone();
-            tokio::spawn(async move {
-                if let Err(e) = crate::modules::token_stats::record_usage(&account, &model, input, output) {
-                    tracing::debug!("Failed to record token stats: {}", e);
-                }
-            });
+            let record = crate::modules::token_stats::UsageRecord {
+                timestamp: chrono::Utc::now(),
+                account_email: account.clone(),
+                model: log.model.clone().unwrap_or_else(|| "unknown".to_string()),
+                protocol: log.protocol.clone().unwrap_or_default(),
+                client_key: log.client_key.clone().unwrap_or_default(),
+                usage: crate::modules::pricing::TokenUsage {
+                    input_tokens: input,
+                    output_tokens: output,
+                    cached_tokens: log.cached_tokens.unwrap_or(0),
+                    thinking_tokens: log.thinking_tokens.unwrap_or(0),
+                    cache_creation_tokens: log.cache_creation_tokens.unwrap_or(0),
+                },
+            };
+            self.writer.enqueue_usage(record).await;
         }
 
         if !self.is_enabled() {
             return;
         }
+        let policy = self.capture_policy();
+        policy.apply(&mut log);
+        let compress = policy.config().compress_bodies;
+
         tracing::info!("[Monitor] Logging request: {} {}", log.method, log.url);
         // Update stats
         {
@@ -112,29 +153,21 @@ impl ProxyMonitor {
             logs.push_front(log.clone());
         }
 
-        // Save to DB
-        let log_to_save = log.clone();
-        tokio::spawn(async move {
-            if let Err(e) = crate::modules::proxy_db::save_log(&log_to_save) {
-                tracing::error!("Failed to save proxy log to DB: {}", e);
-            }
-            
-            // Record token stats if available
-            if let (Some(account), Some(input), Some(output)) = (
-                &log_to_save.account_email,
-                log_to_save.input_tokens,
-                log_to_save.output_tokens,
-            ) {
-                let model = log_to_save.model.clone().unwrap_or_else(|| "unknown".to_string());
-                if let Err(e) = crate::modules::token_stats::record_usage(account, &model, input, output) {
-                    tracing::debug!("Failed to record token stats: {}", e);
-                }
-            }
-        });
+        // Save to DB (batched by the background writer)
+        self.writer.enqueue_log(log, compress);
 
         // No UI event emission in web-only mode.
     }
 
+    /// Wait for queued logs and usage records to be written
+    pub async fn flush(&self) {
+        self.writer.flush().await;
+    }
+
+    pub fn writer_stats(&self) -> crate::modules::log_writer::LogWriterStats {
+        self.writer.stats()
+    }
+
     pub async fn get_logs(&self, limit: usize) -> Vec<ProxyRequestLog> {
         // Try to get from DB first for true history
         match crate::modules::proxy_db::get_logs(limit) {
@@ -5,6 +5,8 @@ pub struct ProxySecurityConfig {
     pub auth_mode: ProxyAuthMode,
     pub api_key: String,
     pub allow_lan_access: bool,
+    /// 持有已校验客户端证书 (mTLS) 的请求免 API Key
+    pub client_cert_auth: bool,
 }
 
 impl ProxySecurityConfig {
@@ -13,6 +15,9 @@ impl ProxySecurityConfig {
             auth_mode: config.auth_mode.clone(),
             api_key: config.api_key.clone(),
             allow_lan_access: config.allow_lan_access,
+            client_cert_auth: config.tls.enabled
+                && config.tls.client_cert_auth
+                && config.tls.client_auth != crate::proxy::config::ClientAuthMode::Off,
         }
     }
 
@@ -40,6 +45,7 @@ mod tests {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
             allow_lan_access: false,
+            client_cert_auth: false,
         };
         assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Off));
     }
@@ -50,6 +56,7 @@ mod tests {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
             allow_lan_access: true,
+            client_cert_auth: false,
         };
         assert!(matches!(
             s.effective_auth_mode(),
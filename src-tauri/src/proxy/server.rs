@@ -13,6 +13,9 @@ use tracing::{debug, error};
 use tokio::sync::RwLock;
 use std::sync::atomic::AtomicUsize;
 
+/// 音频转录/翻译的上传上限 (长音频在服务端分段; 上传与解码后的 PCM 都驻留内存，不宜过大)
+const AUDIO_UPLOAD_LIMIT: usize = 100 * 1024 * 1024;
+
 /// Axum 应用状态
 #[derive(Clone)]
 pub struct AppState {
@@ -57,6 +60,10 @@ pub fn build_router(
     let zai_state = Arc::new(RwLock::new(zai_config));
     let provider_rr = Arc::new(AtomicUsize::new(0));
     let zai_vision_mcp_state = Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
+    crate::proxy::SignatureCache::global().configure_persistence(
+        experimental_config.persist_signature_cache,
+        experimental_config.signature_cache_max_entries,
+    );
     let experimental_state = Arc::new(RwLock::new(experimental_config));
 
     let state = AppState {
@@ -77,6 +84,9 @@ pub fn build_router(
         experimental: experimental_state.clone(),
     };
 
+    crate::proxy::model_catalog::ModelCatalog::global()
+        .start_auto_refresh(token_manager.clone(), state.upstream.clone());
+
     // 构建路由 - 使用新架构的 handlers！
     use crate::proxy::handlers;
     let app = Router::new()
@@ -99,10 +109,24 @@ pub fn build_router(
             "/v1/images/edits",
             post(handlers::openai::handle_images_edits),
         ) // 图像编辑 API
+        .route(
+            "/v1/images/files/:file",
+            get(handlers::openai::handle_image_file),
+        ) // 已保存图片 (签名 URL)
         .route(
             "/v1/audio/transcriptions",
-            post(handlers::audio::handle_audio_transcription),
+            post(handlers::audio::handle_audio_transcription)
+                .layer(DefaultBodyLimit::max(AUDIO_UPLOAD_LIMIT)),
         ) // 音频转录 API
+        .route(
+            "/v1/audio/translations",
+            post(handlers::audio::handle_audio_translation)
+                .layer(DefaultBodyLimit::max(AUDIO_UPLOAD_LIMIT)),
+        ) // 音频翻译 API
+        .route(
+            "/v1/audio/speech",
+            post(handlers::audio::handle_audio_speech),
+        ) // 语音合成 API
         // Claude Protocol
         .route("/v1/messages", post(handlers::claude::handle_messages))
         .route(
@@ -204,6 +228,10 @@ impl AxumServer {
     pub async fn update_experimental(&self, config: &crate::proxy::config::ProxyConfig) {
         let mut exp = self.experimental.write().await;
         *exp = config.experimental.clone();
+        crate::proxy::SignatureCache::global().configure_persistence(
+            exp.persist_signature_cache,
+            exp.signature_cache_max_entries,
+        );
         tracing::info!("实验性配置已热更新");
     }
     /// 启动 Axum 服务器
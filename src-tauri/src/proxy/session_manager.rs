@@ -6,7 +6,25 @@ use serde_json::Value;
 /// 会话管理器工具
 pub struct SessionManager;
 
+/// 客户端显式指定会话的请求头 (优先于指纹)，响应中同名头回传实际使用的会话 ID
+pub const SESSION_HEADER: &str = "X-Session-Id";
+
+/// 客户端会话 ID 的最大长度
+const MAX_CLIENT_SESSION_ID_LEN: usize = 128;
+
 impl SessionManager {
+    /// 读取客户端通过 X-Session-Id 显式指定的会话 ID (仅接受可见 ASCII 字符)
+    pub fn client_session_id(headers: &axum::http::HeaderMap) -> Option<String> {
+        let value = headers.get(SESSION_HEADER)?.to_str().ok()?.trim();
+        if value.is_empty()
+            || value.len() > MAX_CLIENT_SESSION_ID_LEN
+            || !value.chars().all(|c| c.is_ascii_graphic())
+        {
+            return None;
+        }
+        Some(value.to_string())
+    }
+
     /// 根据 Claude 请求生成稳定的会话指纹 (Session Fingerprint)
     /// 
     /// 设计理念:
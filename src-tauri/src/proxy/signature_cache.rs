@@ -1,6 +1,10 @@
+use serde::Serialize;
 use std::collections::HashMap;
-use std::sync::{Mutex, OnceLock};
-use std::time::{Duration, SystemTime};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::modules::signature_db::SignatureStore;
 
 // Node.js proxy uses 2 hours TTL
 const SIGNATURE_TTL: Duration = Duration::from_secs(2 * 60 * 60);
@@ -11,6 +15,11 @@ const TOOL_CACHE_LIMIT: usize = 500;      // Layer 1: Tool-specific signatures
 const FAMILY_CACHE_LIMIT: usize = 200;    // Layer 2: Model family mappings
 const SESSION_CACHE_LIMIT: usize = 1000;  // Layer 3: Session-based signatures (largest)
 
+// Layer names used as keys in the persistent store
+const LAYER_TOOL: &str = "tool";
+const LAYER_FAMILY: &str = "family";
+const LAYER_SESSION: &str = "session";
+
 /// Cache entry with timestamp for TTL
 #[derive(Clone, Debug)]
 struct CacheEntry<T> {
@@ -26,11 +35,65 @@ impl<T> CacheEntry<T> {
         }
     }
 
+    /// Rebuild an entry loaded from the persistent store, keeping its original age
+    fn restored(data: T, stored_at: i64) -> Self {
+        Self {
+            data,
+            timestamp: UNIX_EPOCH + Duration::from_secs(stored_at.max(0) as u64),
+        }
+    }
+
     fn is_expired(&self) -> bool {
         self.timestamp.elapsed().unwrap_or(Duration::ZERO) > SIGNATURE_TTL
     }
 }
 
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[derive(Default)]
+struct LayerCounters {
+    hits: AtomicU64,
+    persisted_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LayerCounters {
+    fn snapshot(&self, entries: usize) -> LayerStats {
+        LayerStats {
+            entries,
+            hits: self.hits.load(Ordering::Relaxed),
+            persisted_hits: self.persisted_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Hit/miss counters of one cache layer
+#[derive(Debug, Clone, Serialize)]
+pub struct LayerStats {
+    /// Entries currently held in memory
+    pub entries: usize,
+    /// Lookups served from memory
+    pub hits: u64,
+    /// Lookups that missed memory but were restored from the persistent store
+    pub persisted_hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SignatureCacheStats {
+    pub persistence_enabled: bool,
+    pub persisted_entries: usize,
+    pub tool: LayerStats,
+    pub family: LayerStats,
+    pub session: LayerStats,
+}
+
 /// Triple-layer signature cache to handle:
 /// 1. Signature recovery for tool calls (when clients strip them)
 /// 2. Cross-model compatibility checks (preventing Claude signatures on Gemini models)
@@ -51,6 +114,13 @@ pub struct SignatureCache {
     /// Value: The most recent valid thought signature for this session
     /// This prevents signature pollution between different conversations
     session_signatures: Mutex<HashMap<String, CacheEntry<String>>>,
+
+    /// Optional SQLite backing store; entries are loaded lazily on memory misses
+    store: RwLock<Option<Arc<SignatureStore>>>,
+
+    tool_stats: LayerCounters,
+    family_stats: LayerCounters,
+    session_stats: LayerCounters,
 }
 
 impl SignatureCache {
@@ -59,6 +129,10 @@ impl SignatureCache {
             tool_signatures: Mutex::new(HashMap::new()),
             thinking_families: Mutex::new(HashMap::new()),
             session_signatures: Mutex::new(HashMap::new()),
+            store: RwLock::new(None),
+            tool_stats: LayerCounters::default(),
+            family_stats: LayerCounters::default(),
+            session_stats: LayerCounters::default(),
         }
     }
 
@@ -68,6 +142,88 @@ impl SignatureCache {
         INSTANCE.get_or_init(SignatureCache::new)
     }
 
+    /// Enable, resize or disable the persistent store (`signature_cache.db`)
+    pub fn configure_persistence(&self, enabled: bool, max_entries: usize) {
+        let Ok(mut store) = self.store.write() else {
+            return;
+        };
+        match (enabled, store.as_ref()) {
+            (true, Some(existing)) => existing.set_max_entries(max_entries),
+            (true, None) => match SignatureStore::open(SIGNATURE_TTL, max_entries) {
+                Ok(opened) => {
+                    tracing::info!("[SignatureCache] Persistence enabled (max {} entries)", max_entries);
+                    *store = Some(Arc::new(opened));
+                }
+                Err(e) => tracing::error!("[SignatureCache] Failed to open signature store: {}", e),
+            },
+            (false, Some(_)) => {
+                tracing::info!("[SignatureCache] Persistence disabled");
+                *store = None;
+            }
+            (false, None) => {}
+        }
+    }
+
+    /// Wait for queued writes to reach `signature_cache.db` (used on shutdown)
+    pub fn flush_persistence(&self) {
+        if let Some(store) = self.persistent_store() {
+            store.flush();
+        }
+    }
+
+    fn persistent_store(&self) -> Option<Arc<SignatureStore>> {
+        self.store.read().ok().and_then(|store| store.clone())
+    }
+
+    fn persist(&self, layer: &'static str, key: &str, value: &str) {
+        if let Some(store) = self.persistent_store() {
+            store.put(layer, key, value, unix_now());
+        }
+    }
+
+    /// Memory first, then the persistent store (restoring the entry into memory)
+    fn lookup(
+        &self,
+        layer: &'static str,
+        map: &Mutex<HashMap<String, CacheEntry<String>>>,
+        counters: &LayerCounters,
+        key: &str,
+    ) -> Option<String> {
+        if let Ok(cache) = map.lock() {
+            if let Some(entry) = cache.get(key) {
+                if !entry.is_expired() {
+                    counters.hits.fetch_add(1, Ordering::Relaxed);
+                    return Some(entry.data.clone());
+                }
+            }
+        }
+
+        // 锁外查询 SQLite (读连接池，多线程运行时下移出异步工作线程)，避免阻塞其他请求的内存命中
+        if let Some((data, stored_at)) = self.persistent_store().and_then(|store| store.get(layer, key)) {
+            tracing::debug!("[SignatureCache] Restored {} entry from persistent store", layer);
+            if let Ok(mut cache) = map.lock() {
+                cache.insert(key.to_string(), CacheEntry::restored(data.clone(), stored_at));
+            }
+            counters.persisted_hits.fetch_add(1, Ordering::Relaxed);
+            return Some(data);
+        }
+
+        counters.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    pub fn stats(&self) -> SignatureCacheStats {
+        let len = |map: &Mutex<HashMap<String, CacheEntry<String>>>| map.lock().map(|m| m.len()).unwrap_or(0);
+        let store = self.persistent_store();
+        SignatureCacheStats {
+            persistence_enabled: store.is_some(),
+            persisted_entries: store.map(|s| s.count()).unwrap_or(0),
+            tool: self.tool_stats.snapshot(len(&self.tool_signatures)),
+            family: self.family_stats.snapshot(len(&self.thinking_families)),
+            session: self.session_stats.snapshot(len(&self.session_signatures)),
+        }
+    }
+
     /// Store a tool call signature
     pub fn cache_tool_signature(&self, tool_use_id: &str, signature: String) {
         if signature.len() < MIN_SIGNATURE_LENGTH {
@@ -76,6 +232,7 @@ impl SignatureCache {
         
         if let Ok(mut cache) = self.tool_signatures.lock() {
             tracing::debug!("[SignatureCache] Caching tool signature for id: {}", tool_use_id);
+            self.persist(LAYER_TOOL, tool_use_id, &signature);
             cache.insert(tool_use_id.to_string(), CacheEntry::new(signature));
             
             // Clean up expired entries when limit is reached
@@ -92,15 +249,11 @@ impl SignatureCache {
 
     /// Retrieve a signature for a tool_use_id
     pub fn get_tool_signature(&self, tool_use_id: &str) -> Option<String> {
-        if let Ok(cache) = self.tool_signatures.lock() {
-            if let Some(entry) = cache.get(tool_use_id) {
-                if !entry.is_expired() {
-                    tracing::debug!("[SignatureCache] Hit tool signature for id: {}", tool_use_id);
-                    return Some(entry.data.clone());
-                }
-            }
+        let hit = self.lookup(LAYER_TOOL, &self.tool_signatures, &self.tool_stats, tool_use_id);
+        if hit.is_some() {
+            tracing::debug!("[SignatureCache] Hit tool signature for id: {}", tool_use_id);
         }
-        None
+        hit
     }
 
     /// Store model family for a signature
@@ -111,6 +264,7 @@ impl SignatureCache {
 
         if let Ok(mut cache) = self.thinking_families.lock() {
             tracing::debug!("[SignatureCache] Caching thinking family for sig (len={}): {}", signature.len(), family);
+            self.persist(LAYER_FAMILY, &signature, &family);
             cache.insert(signature, CacheEntry::new(family));
             
             if cache.len() > FAMILY_CACHE_LIMIT {
@@ -126,16 +280,7 @@ impl SignatureCache {
 
     /// Get model family for a signature
     pub fn get_signature_family(&self, signature: &str) -> Option<String> {
-        if let Ok(cache) = self.thinking_families.lock() {
-            if let Some(entry) = cache.get(signature) {
-                if !entry.is_expired() {
-                    return Some(entry.data.clone());
-                } else {
-                    tracing::debug!("[SignatureCache] Signature family entry expired");
-                }
-            }
-        }
-        None
+        self.lookup(LAYER_FAMILY, &self.thinking_families, &self.family_stats, signature)
     }
 
     // ===== Layer 3: Session-based Signature Storage =====
@@ -167,6 +312,7 @@ impl SignatureCache {
                     session_id,
                     signature.len()
                 );
+                self.persist(LAYER_SESSION, session_id, &signature);
                 cache.insert(session_id.to_string(), CacheEntry::new(signature));
             }
 
@@ -190,26 +336,20 @@ impl SignatureCache {
     /// Retrieve the latest thinking signature for a session.
     /// Returns None if not found or expired.
     pub fn get_session_signature(&self, session_id: &str) -> Option<String> {
-        if let Ok(cache) = self.session_signatures.lock() {
-            if let Some(entry) = cache.get(session_id) {
-                if !entry.is_expired() {
-                    tracing::debug!(
-                        "[SignatureCache] Session {} -> HIT (len={})",
-                        session_id,
-                        entry.data.len()
-                    );
-                    return Some(entry.data.clone());
-                } else {
-                    tracing::debug!("[SignatureCache] Session {} -> EXPIRED", session_id);
-                }
-            }
+        let hit = self.lookup(LAYER_SESSION, &self.session_signatures, &self.session_stats, session_id);
+        match &hit {
+            Some(sig) => tracing::debug!("[SignatureCache] Session {} -> HIT (len={})", session_id, sig.len()),
+            None => tracing::debug!("[SignatureCache] Session {} -> MISS", session_id),
         }
-        None
+        hit
     }
 
-    /// Clear all caches (for testing or manual reset)
+    /// Clear all caches, including the persistent store (for testing or manual reset)
     #[allow(dead_code)] // Used in tests
     pub fn clear(&self) {
+        if let Some(store) = self.persistent_store() {
+            store.clear();
+        }
         if let Ok(mut cache) = self.tool_signatures.lock() {
             cache.clear();
         }
@@ -302,4 +442,47 @@ mod tests {
         assert!(cache.get_signature_family(&sig).is_none());
         assert!(cache.get_session_signature("sid-1").is_none());
     }
+
+    #[test]
+    fn test_entries_survive_restart_via_store() {
+        let (store, _keep) = crate::modules::signature_db::open_in_memory(
+            "signature_cache_restart",
+            SIGNATURE_TTL,
+            100,
+        );
+        let store = Arc::new(store);
+        let tool_sig = "t".repeat(60);
+        let session_sig = "s".repeat(70);
+
+        let before = SignatureCache::new();
+        *before.store.write().unwrap() = Some(store.clone());
+        before.cache_tool_signature("toolu_persist", tool_sig.clone());
+        before.cache_thinking_family(tool_sig.clone(), "gemini".to_string());
+        before.cache_session_signature("sid-persist", session_sig.clone());
+        store.flush();
+
+        // 模拟重启：内存为空，命中后从持久层懒加载
+        let after = SignatureCache::new();
+        *after.store.write().unwrap() = Some(store);
+        assert_eq!(after.get_tool_signature("toolu_persist"), Some(tool_sig.clone()));
+        assert_eq!(after.get_signature_family(&tool_sig), Some("gemini".to_string()));
+        assert_eq!(after.get_session_signature("sid-persist"), Some(session_sig));
+        assert_eq!(after.get_tool_signature("toolu_persist"), Some(tool_sig));
+        assert!(after.get_session_signature("sid-unknown").is_none());
+
+        let stats = after.stats();
+        assert!(stats.persistence_enabled);
+        assert_eq!(stats.persisted_entries, 3);
+        assert_eq!(stats.tool.persisted_hits, 1);
+        assert_eq!(stats.tool.hits, 1);
+        assert_eq!(stats.session.misses, 1);
+        assert_eq!(stats.tool.entries, 1);
+
+        // 清空同时删除持久层，重启后也不会恢复
+        after.clear();
+        assert_eq!(after.stats().persisted_entries, 0);
+        let restarted = SignatureCache::new();
+        *restarted.store.write().unwrap() = after.persistent_store();
+        assert!(restarted.get_tool_signature("toolu_persist").is_none());
+    }
 }
@@ -25,6 +25,8 @@ pub struct StickySessionConfig {
     pub mode: SchedulingMode,
     /// 缓存优先模式下的最大等待时间 (秒)
     pub max_wait_seconds: u64,
+    /// 会话记录闲置多久后过期 (秒)，0 表示永不过期
+    pub session_ttl_seconds: u64,
 }
 
 impl Default for StickySessionConfig {
@@ -32,6 +34,7 @@ impl Default for StickySessionConfig {
         Self {
             mode: SchedulingMode::Balance,
             max_wait_seconds: 60,
+            session_ttl_seconds: 3600,
         }
     }
 }
@@ -0,0 +1,339 @@
+// 监听端口 TLS / mTLS
+//
+// 开启局域网访问后 API Key 与请求内容会以明文经过网络。这里基于 rustls 为反代与管理后台提供 HTTPS:
+// - 使用配置的证书/私钥，或在首次运行时生成自签名证书 (<data_dir>/tls/)
+// - 定时检查证书、私钥与客户端 CA 文件，变化后热加载 (失败时保留旧证书)
+// - 可选校验客户端证书 (mTLS)，并可用客户端证书替代 API Key
+
+use axum::extract::ConnectInfo;
+use axum::Router;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{crypto, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tower::Service;
+
+use crate::proxy::config::{ClientAuthMode, TlsConfig};
+
+const TLS_DIR: &str = "tls";
+const SELF_SIGNED_CERT: &str = "cert.pem";
+const SELF_SIGNED_KEY: &str = "key.pem";
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const CERT_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// 通过 mTLS 校验的客户端证书，作为请求扩展传给中间件与处理器
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    /// 证书 DER 的 SHA-256 指纹 (十六进制)
+    pub fingerprint: String,
+}
+
+impl ClientCertificate {
+    fn from_der(der: &CertificateDer<'_>) -> Self {
+        Self {
+            fingerprint: format!("{:x}", Sha256::digest(der.as_ref())),
+        }
+    }
+}
+
+/// 证书相关文件
+#[derive(Debug, Clone)]
+struct TlsFiles {
+    cert: PathBuf,
+    key: PathBuf,
+    client_ca: Option<PathBuf>,
+}
+
+impl TlsFiles {
+    fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        for path in [Some(&self.cert), Some(&self.key), self.client_ca.as_ref()].into_iter().flatten() {
+            hasher.update(std::fs::read(path).unwrap_or_default());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// 当前生效的 TLS 配置，证书热加载时整体替换
+pub struct TlsState {
+    files: TlsFiles,
+    client_auth: ClientAuthMode,
+    server_config: RwLock<Arc<ServerConfig>>,
+}
+
+impl TlsState {
+    /// 按配置加载证书 (必要时生成自签名证书)
+    pub fn load(settings: &TlsConfig) -> Result<Arc<Self>, String> {
+        let files = resolve_files(settings)?;
+        let server_config = build_server_config(&files, settings.client_auth)?;
+        Ok(Arc::new(Self {
+            files,
+            client_auth: settings.client_auth,
+            server_config: RwLock::new(Arc::new(server_config)),
+        }))
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        let config = self
+            .server_config
+            .read()
+            .map(|c| c.clone())
+            .unwrap_or_else(|e| e.into_inner().clone());
+        TlsAcceptor::from(config)
+    }
+
+    /// 定时检查证书文件，变化后重新加载
+    pub fn spawn_reload(self: &Arc<Self>) {
+        let state = self.clone();
+        tokio::spawn(async move {
+            let mut seen = state.files.fingerprint();
+            let mut interval = tokio::time::interval(CERT_RELOAD_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let current = state.files.fingerprint();
+                if current == seen {
+                    continue;
+                }
+                seen = current;
+                match build_server_config(&state.files, state.client_auth) {
+                    Ok(config) => {
+                        if let Ok(mut current) = state.server_config.write() {
+                            *current = Arc::new(config);
+                        }
+                        tracing::info!("[TLS] Reloaded certificate {}", state.files.cert.display());
+                    }
+                    Err(e) => tracing::error!("[TLS] Keeping previous certificate, reload failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+fn resolve_files(settings: &TlsConfig) -> Result<TlsFiles, String> {
+    let non_empty = |p: &Option<String>| p.as_deref().map(str::trim).filter(|p| !p.is_empty()).map(PathBuf::from);
+    let client_ca = non_empty(&settings.client_ca_path);
+    if settings.client_auth != ClientAuthMode::Off && client_ca.is_none() {
+        return Err("tls.client_ca_path is required when client certificate verification is enabled".to_string());
+    }
+
+    let (cert, key) = match (non_empty(&settings.cert_path), non_empty(&settings.key_path)) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) if settings.self_signed => {
+            let dir = crate::modules::account::get_data_dir()?.join(TLS_DIR);
+            ensure_self_signed(&dir, &settings.self_signed_hosts)?
+        }
+        (None, None) => return Err("TLS is enabled but tls.cert_path / tls.key_path are not set".to_string()),
+        _ => return Err("tls.cert_path and tls.key_path must be set together".to_string()),
+    };
+    Ok(TlsFiles { cert, key, client_ca })
+}
+
+/// 生成自签名证书 (已存在时直接复用)
+pub fn ensure_self_signed(dir: &Path, hosts: &[String]) -> Result<(PathBuf, PathBuf), String> {
+    let cert_path = dir.join(SELF_SIGNED_CERT);
+    let key_path = dir.join(SELF_SIGNED_KEY);
+    if cert_path.exists() && key_path.exists() {
+        return Ok((cert_path, key_path));
+    }
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let hosts = if hosts.is_empty() { vec!["localhost".to_string()] } else { hosts.to_vec() };
+    let generated = rcgen::generate_simple_self_signed(hosts.clone())
+        .map_err(|e| format!("Failed to generate self-signed certificate: {}", e))?;
+    std::fs::write(&cert_path, generated.cert.pem())
+        .map_err(|e| format!("Failed to write {}: {}", cert_path.display(), e))?;
+    write_private(&key_path, generated.key_pair.serialize_pem().as_bytes())?;
+
+    tracing::warn!(
+        "[TLS] Generated self-signed certificate for {} at {}; clients must trust it explicitly",
+        hosts.join(", "),
+        cert_path.display()
+    );
+    Ok((cert_path, key_path))
+}
+
+fn write_private(path: &Path, content: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut f| f.write_all(content))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read certificates from {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn build_server_config(files: &TlsFiles, client_auth: ClientAuthMode) -> Result<ServerConfig, String> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let certs = load_certs(&files.cert)?;
+    let key = PrivateKeyDer::from_pem_file(&files.key)
+        .map_err(|e| format!("Failed to read private key from {}: {}", files.key.display(), e))?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+    let builder = match (client_auth, &files.client_ca) {
+        (ClientAuthMode::Off, _) => builder.with_no_client_auth(),
+        (_, None) => return Err("tls.client_ca_path is required for client certificate verification".to_string()),
+        (mode, Some(ca_path)) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("Invalid CA certificate in {}: {}", ca_path.display(), e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if mode == ClientAuthMode::Optional {
+                verifier.allow_unauthenticated()
+            } else {
+                verifier
+            };
+            builder.with_client_cert_verifier(verifier.build().map_err(|e| e.to_string())?)
+        }
+    };
+
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Certificate and private key do not match: {}", e))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// 以 HTTPS 提供服务，`shutdown` 完成后停止接受新连接并等待进行中的请求结束
+pub async fn serve<F>(listener: TcpListener, app: Router, tls: Arc<TlsState>, shutdown: F) -> Result<(), String>
+where
+    F: Future<Output = ()> + Send,
+{
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let active = Arc::new(());
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::warn!("[TLS] Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let acceptor = tls.acceptor();
+        let app = app.clone();
+        let mut shutdown_rx = shutdown_rx.clone();
+        let guard = active.clone();
+        tokio::spawn(async move {
+            let _guard = guard;
+            let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    tracing::debug!("[TLS] Handshake with {} failed: {}", addr, e);
+                    return;
+                }
+                Err(_) => {
+                    tracing::debug!("[TLS] Handshake with {} timed out", addr);
+                    return;
+                }
+            };
+            let client_cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(ClientCertificate::from_der);
+
+            let service = hyper::service::service_fn(move |mut request: hyper::Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(addr));
+                if let Some(cert) = &client_cert {
+                    request.extensions_mut().insert(cert.clone());
+                }
+                // Router 始终就绪，无需 poll_ready
+                app.clone().call(request)
+            });
+
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            tokio::pin!(conn);
+            tokio::select! {
+                result = conn.as_mut() => {
+                    if let Err(e) = result {
+                        tracing::debug!("[TLS] Connection from {} closed with error: {}", addr, e);
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    conn.as_mut().graceful_shutdown();
+                    let _ = conn.as_mut().await;
+                }
+            }
+        });
+    }
+
+    let _ = shutdown_tx.send(true);
+    let deadline = tokio::time::Instant::now() + SHUTDOWN_GRACE;
+    while Arc::strong_count(&active) > 1 && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_signed_and_server_config() {
+        let dir = std::env::temp_dir().join(format!("ag_tls_{}", uuid::Uuid::new_v4().simple()));
+        let (cert, key) = ensure_self_signed(&dir, &["localhost".to_string(), "127.0.0.1".to_string()]).unwrap();
+        let first = std::fs::read(&cert).unwrap();
+        // 已存在时复用
+        ensure_self_signed(&dir, &[]).unwrap();
+        assert_eq!(std::fs::read(&cert).unwrap(), first);
+
+        let files = TlsFiles {
+            cert: cert.clone(),
+            key,
+            client_ca: None,
+        };
+        let config = build_server_config(&files, ClientAuthMode::Off).unwrap();
+        assert_eq!(config.alpn_protocols[0], b"h2".to_vec());
+
+        // 用自签名证书充当客户端 CA
+        let files = TlsFiles {
+            client_ca: Some(cert.clone()),
+            ..files
+        };
+        assert!(build_server_config(&files, ClientAuthMode::Required).is_ok());
+        assert!(build_server_config(&TlsFiles { client_ca: None, ..files.clone() }, ClientAuthMode::Optional).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
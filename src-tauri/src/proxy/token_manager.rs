@@ -4,6 +4,7 @@ use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use serde::Serialize;
 
 use crate::proxy::rate_limit::RateLimitTracker;
 use crate::proxy::sticky_config::StickySessionConfig;
@@ -23,6 +24,45 @@ pub struct ProxyToken {
     pub protected_models: HashSet<String>, // [NEW #621]
 }
 
+/// 会话记录: 粘性绑定的账号与使用统计 (时间戳为 Unix 秒)
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionRecord {
+    pub session_id: String,
+    /// 绑定的账号 ID，None 表示当前未绑定 (如绑定账号被限流后解绑)
+    pub account_id: Option<String>,
+    pub created_at: i64,
+    pub last_used_at: i64,
+    pub request_count: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl SessionRecord {
+    fn new(session_id: &str, now: i64) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            account_id: None,
+            created_at: now,
+            last_used_at: now,
+            request_count: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+
+    fn is_expired(&self, ttl_seconds: u64, now: i64) -> bool {
+        ttl_seconds > 0 && now - self.last_used_at > ttl_seconds as i64
+    }
+}
+
+/// 管理接口返回的会话信息 (附带绑定账号的邮箱)
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    pub record: SessionRecord,
+    pub account_email: Option<String>,
+}
+
 
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>,  // account_id -> ProxyToken
@@ -31,7 +71,7 @@ pub struct TokenManager {
     data_dir: PathBuf,
     rate_limit_tracker: Arc<RateLimitTracker>,  // 新增: 限流跟踪器
     sticky_config: Arc<tokio::sync::RwLock<StickySessionConfig>>, // 新增：调度配置
-    session_accounts: Arc<DashMap<String, String>>, // 新增：会话与账号映射 (SessionID -> AccountID)
+    sessions: Arc<DashMap<String, SessionRecord>>, // 会话记录 (SessionID -> 绑定账号与统计)
     preferred_account_id: Arc<tokio::sync::RwLock<Option<String>>>, // [FIX #820] 优先使用的账号ID（固定账号模式）
 }
 
@@ -45,7 +85,7 @@ impl TokenManager {
             data_dir,
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
             sticky_config: Arc::new(tokio::sync::RwLock::new(StickySessionConfig::default())),
-            session_accounts: Arc::new(DashMap::new()),
+            sessions: Arc::new(DashMap::new()),
             preferred_account_id: Arc::new(tokio::sync::RwLock::new(None)), // [FIX #820]
         }
     }
@@ -53,6 +93,8 @@ impl TokenManager {
     /// 启动限流记录自动清理后台任务（每60秒检查并清除过期记录）
     pub fn start_auto_cleanup(&self) {
         let tracker = self.rate_limit_tracker.clone();
+        let sessions = self.sessions.clone();
+        let sticky_config = self.sticky_config.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
             loop {
@@ -61,6 +103,11 @@ impl TokenManager {
                 if cleaned > 0 {
                     tracing::info!("🧹 Auto-cleanup: Removed {} expired rate limit record(s)", cleaned);
                 }
+                let ttl = sticky_config.read().await.session_ttl_seconds;
+                let evicted = Self::evict_expired_sessions(&sessions, ttl, chrono::Utc::now().timestamp());
+                if evicted > 0 {
+                    tracing::info!("🧹 Auto-cleanup: Evicted {} idle session(s)", evicted);
+                }
             }
         });
         tracing::info!("✅ Rate limit auto-cleanup task started (interval: 60s)");
@@ -649,7 +696,7 @@ impl TokenManager {
                 let sid = session_id.unwrap();
                 
                 // 1. 检查会话是否已绑定账号
-                if let Some(bound_id) = self.session_accounts.get(sid).map(|v| v.clone()) {
+                if let Some(bound_id) = self.bound_account(sid, scheduling.session_ttl_seconds) {
                     // 【修复】先通过 account_id 找到对应的账号，获取其 email
                     // 2. 转换 email -> account_id 检查绑定的账号是否限流
                     if let Some(bound_token) = tokens_snapshot.iter().find(|t| t.account_id == bound_id) {
@@ -662,19 +709,19 @@ impl TokenManager {
                                 "Sticky Session: Bound account {} is rate-limited ({}s), unbinding and switching.",
                                 bound_token.email, reset_sec
                             );
-                            self.session_accounts.remove(sid);
+                            self.unbind_session(sid);
                         } else if !attempted.contains(&bound_id) && !(quota_protection_enabled && bound_token.protected_models.contains(&normalized_target)) {
                             // 3. 账号可用且未被标记为尝试失败，优先复用
                             tracing::debug!("Sticky Session: Successfully reusing bound account {} for session {}", bound_token.email, sid);
                             target_token = Some(bound_token.clone());
                         } else if quota_protection_enabled && bound_token.protected_models.contains(&normalized_target) {
                             tracing::debug!("Sticky Session: Bound account {} is quota-protected for model {} [{}], unbinding and switching.", bound_token.email, normalized_target, target_model);
-                            self.session_accounts.remove(sid);
+                            self.unbind_session(sid);
                         }
                     } else {
                         // 绑定的账号已不存在（可能被删除），解绑
                         tracing::debug!("Sticky Session: Bound account not found for session {}, unbinding", sid);
-                        self.session_accounts.remove(sid);
+                        self.unbind_session(sid);
                     }
                 }
             }
@@ -730,7 +777,7 @@ impl TokenManager {
                         // 如果是会话首次分配且需要粘性，在此建立绑定
                         if let Some(sid) = session_id {
                             if scheduling.mode != SchedulingMode::PerformanceFirst {
-                                self.session_accounts.insert(sid.to_string(), candidate.account_id.clone());
+                                self.bind_session(sid, &candidate.account_id);
                                 tracing::debug!("Sticky Session: Bound new account {} to session {}", candidate.email, sid);
                             }
                         }
@@ -1016,6 +1063,11 @@ impl TokenManager {
         self.tokens.len()
     }
 
+    /// 当前号池中所有账号的邮箱 (用于合并各账号的模型列表)
+    pub fn account_emails(&self) -> Vec<String> {
+        self.tokens.iter().map(|e| e.value().email.clone()).collect()
+    }
+
     /// 通过 email 获取指定账号的 Token（用于预热等需要指定账号的场景）
     /// 此方法会自动刷新过期的 token
     pub async fn get_token_by_email(&self, email: &str) -> Result<(String, String, String), String> {
@@ -1430,15 +1482,95 @@ impl TokenManager {
         tracing::debug!("Scheduling configuration updated: {:?}", *config);
     }
 
-    /// 清除特定会话的粘性映射
-    #[allow(dead_code)]
-    pub fn clear_session_binding(&self, session_id: &str) {
-        self.session_accounts.remove(session_id);
+    /// 清除特定会话 (绑定与统计)，返回会话是否存在
+    pub fn clear_session_binding(&self, session_id: &str) -> bool {
+        self.sessions.remove(session_id).is_some()
     }
 
     /// 清除所有会话的粘性映射
     pub fn clear_all_sessions(&self) {
-        self.session_accounts.clear();
+        self.sessions.clear();
+    }
+
+    /// 查询会话当前绑定的账号 (已过期的会话视为未绑定)
+    fn bound_account(&self, session_id: &str, ttl_seconds: u64) -> Option<String> {
+        let now = chrono::Utc::now().timestamp();
+        let mut record = self.sessions.get_mut(session_id)?;
+        if record.is_expired(ttl_seconds, now) {
+            drop(record);
+            self.sessions.remove(session_id);
+            return None;
+        }
+        record.last_used_at = now;
+        record.account_id.clone()
+    }
+
+    /// 将会话绑定到账号 (保留已有的创建时间与统计)
+    fn bind_session(&self, session_id: &str, account_id: &str) {
+        let now = chrono::Utc::now().timestamp();
+        let mut record = self
+            .sessions
+            .entry(session_id.to_string())
+            .or_insert_with(|| SessionRecord::new(session_id, now));
+        record.account_id = Some(account_id.to_string());
+        record.last_used_at = now;
+    }
+
+    /// 解除会话的账号绑定 (保留统计，下次请求重新分配账号)
+    fn unbind_session(&self, session_id: &str) {
+        if let Some(mut record) = self.sessions.get_mut(session_id) {
+            record.account_id = None;
+        }
+    }
+
+    fn evict_expired_sessions(sessions: &DashMap<String, SessionRecord>, ttl_seconds: u64, now: i64) -> usize {
+        let before = sessions.len();
+        sessions.retain(|_, record| !record.is_expired(ttl_seconds, now));
+        before - sessions.len()
+    }
+
+    /// 记录一次会话请求的用量 (由监控中间件在请求完成后调用)
+    pub fn record_session_usage(&self, session_id: &str, input_tokens: u32, output_tokens: u32) {
+        let now = chrono::Utc::now().timestamp();
+        let mut record = self
+            .sessions
+            .entry(session_id.to_string())
+            .or_insert_with(|| SessionRecord::new(session_id, now));
+        record.last_used_at = now;
+        record.request_count += 1;
+        record.input_tokens += input_tokens as u64;
+        record.output_tokens += output_tokens as u64;
+    }
+
+    fn session_info(&self, record: SessionRecord) -> SessionInfo {
+        let account_email = record
+            .account_id
+            .as_ref()
+            .and_then(|id| self.tokens.get(id).map(|t| t.email.clone()));
+        SessionInfo { record, account_email }
+    }
+
+    /// 列出所有会话 (最近使用的在前)
+    pub fn list_sessions(&self) -> Vec<SessionInfo> {
+        let mut records: Vec<SessionRecord> = self.sessions.iter().map(|e| e.value().clone()).collect();
+        records.sort_by_key(|r| std::cmp::Reverse(r.last_used_at));
+        records.into_iter().map(|r| self.session_info(r)).collect()
+    }
+
+    /// 查询单个会话
+    pub fn get_session(&self, session_id: &str) -> Option<SessionInfo> {
+        let record = self.sessions.get(session_id)?.clone();
+        Some(self.session_info(record))
+    }
+
+    /// 手动将会话绑定到指定账号 (账号必须已加载)
+    pub fn rebind_session(&self, session_id: &str, account_id: &str) -> Result<SessionInfo, String> {
+        if !self.tokens.contains_key(account_id) {
+            return Err(format!("Account not found or disabled: {}", account_id));
+        }
+        self.bind_session(session_id, account_id);
+        self.get_session(session_id)
+            .ok_or_else(|| format!("Session not found: {}", session_id))
     }
 
     // ===== [FIX #820] 固定账号模式相关方法 =====
@@ -1469,3 +1601,39 @@ fn truncate_reason(reason: &str, max_len: usize) -> String {
     s.push('…');
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_records_rebind_and_ttl() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        manager.bind_session("s1", "acc-1");
+        manager.record_session_usage("s1", 100, 20);
+        manager.record_session_usage("s1", 50, 5);
+
+        let info = manager.get_session("s1").unwrap();
+        assert_eq!(info.record.account_id.as_deref(), Some("acc-1"));
+        assert_eq!(info.record.request_count, 2);
+        assert_eq!(info.record.input_tokens, 150);
+        assert_eq!(info.record.output_tokens, 25);
+
+        // 解绑后保留统计
+        manager.unbind_session("s1");
+        assert_eq!(manager.bound_account("s1", 3600), None);
+        assert_eq!(manager.get_session("s1").unwrap().record.request_count, 2);
+
+        // 只能绑定到已加载的账号
+        assert!(manager.rebind_session("s1", "missing").is_err());
+
+        let now = chrono::Utc::now().timestamp();
+        manager.record_session_usage("s2", 0, 0);
+        manager.sessions.get_mut("s1").unwrap().last_used_at = now - 7200;
+        assert_eq!(TokenManager::evict_expired_sessions(&manager.sessions, 0, now), 0);
+        assert_eq!(TokenManager::evict_expired_sessions(&manager.sessions, 3600, now), 1);
+        assert!(manager.get_session("s1").is_none());
+        assert!(manager.clear_session_binding("s2"));
+        assert!(manager.list_sessions().is_empty());
+    }
+}
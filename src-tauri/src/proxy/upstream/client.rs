@@ -201,9 +201,8 @@ impl UpstreamClient {
 
     /// 获取可用模型列表
     /// 
-    /// 获取远端模型列表，支持多端点自动 Fallback
-    #[allow(dead_code)] // API ready for future model discovery feature
-    pub async fn fetch_available_models(&self, access_token: &str) -> Result<Value, String> {
+    /// 获取远端模型列表 (含上下文上限、能力与配额)，支持多端点自动 Fallback
+    pub async fn fetch_available_models(&self, access_token: &str, project_id: Option<&str>) -> Result<Value, String> {
         let mut headers = header::HeaderMap::new();
         headers.insert(
             header::CONTENT_TYPE,
@@ -229,7 +228,7 @@ impl UpstreamClient {
                 .http_client
                 .post(&url)
                 .headers(headers.clone())
-                .json(&serde_json::json!({}))
+                .json(&project_id.map_or_else(|| serde_json::json!({}), |p| serde_json::json!({ "project": p })))
                 .send()
                 .await;
 
@@ -7,16 +7,79 @@ use crate::modules;
 use crate::proxy;
 
 async fn shutdown_signal() {
-    let _ = tokio::signal::ctrl_c().await;
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    // systemd / docker stop 发送的是 SIGTERM
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to install SIGTERM handler: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
     info!("Shutdown signal received");
 }
 
+/// 监听地址是否仅限本机访问
+fn is_loopback_host(host: &str) -> bool {
+    let host = host.trim().trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// 确定监听地址; 通过 --bind 监听非回环地址时按局域网访问处理 (auth=auto 启用鉴权、提示未启用 TLS)
+fn resolve_bind_host(proxy_config: &mut proxy::ProxyConfig, bind: Option<&str>) -> String {
+    let host = bind
+        .map(str::to_string)
+        .unwrap_or_else(|| proxy_config.get_bind_address().to_string());
+    if !is_loopback_host(&host) {
+        proxy_config.allow_lan_access = true;
+    }
+    host
+}
+
+/// `serve` 的命令行参数，覆盖配置文件中的同名项
+#[derive(Debug, Clone, Default)]
+pub struct ServeOptions {
+    pub port: Option<u16>,
+    pub bind: Option<String>,
+    pub log_level: Option<String>,
+}
+
 pub async fn run() -> Result<(), String> {
-    modules::logger::init_logger();
+    run_with(ServeOptions::default()).await
+}
+
+pub async fn run_with(options: ServeOptions) -> Result<(), String> {
+    modules::logger::init_logger_with_level(options.log_level.as_deref());
+
+    let app_data_dir = modules::account::get_data_dir()?;
+    // 同一数据目录只允许一个实例运行 (在任何数据库初始化之前占用)，多个账号池请使用不同的 --profile / --data-dir
+    let mut instance_lock = modules::profile::InstanceLock::acquire(&app_data_dir)?;
 
     if let Err(e) = modules::token_stats::init_db() {
         error!("Failed to initialize token stats database: {}", e);
     }
+    modules::token_stats::start_rollup_maintenance_task();
+    if let Err(e) = modules::audit_log::init_db() {
+        error!("Failed to initialize audit log database: {}", e);
+    }
+    modules::audit_log::start_retention_task();
 
     let app_config = match modules::config::load_app_config() {
         Ok(config) => config,
@@ -26,9 +89,18 @@ pub async fn run() -> Result<(), String> {
         }
     };
 
-    let proxy_config = app_config.proxy;
-    let app_data_dir = modules::account::get_data_dir()?;
+    let mut proxy_config = app_config.proxy.clone();
+    if let Some(port) = options.port {
+        proxy_config.port = port;
+    }
+    let host = resolve_bind_host(&mut proxy_config, options.bind.as_deref());
     let _ = modules::account::get_accounts_dir()?;
+    instance_lock.set_port(proxy_config.port)?;
+    info!(
+        "Using data directory {} (profile: {})",
+        app_data_dir.display(),
+        modules::profile::active_profile().unwrap_or("default")
+    );
 
     let token_manager = Arc::new(proxy::TokenManager::new(app_data_dir));
     token_manager.start_auto_cleanup();
@@ -49,6 +121,11 @@ pub async fn run() -> Result<(), String> {
 
     let monitor = Arc::new(proxy::monitor::ProxyMonitor::new(1000));
     monitor.set_enabled(proxy_config.enable_logging);
+    monitor.set_capture_config(proxy_config.log_capture.clone());
+    proxy::mappers::tool_result_compressor::configure(proxy_config.tool_results.clone());
+    proxy::image_store::configure(proxy_config.image_storage.clone());
+    proxy::audio::speech::configure(proxy_config.speech.clone());
+    proxy::audio::transcription::configure(proxy_config.transcription.clone());
 
     let (proxy_router, runtime) = proxy::server::build_router(
         token_manager.clone(),
@@ -61,28 +138,105 @@ pub async fn run() -> Result<(), String> {
         proxy_config.experimental.clone(),
     );
 
-    let web_api_router = modules::web_api::router(modules::web_api::WebApiState {
+    let web_auth = Arc::new(modules::web_auth::WebAuth::load(
+        app_config.management.session_ttl_hours,
+    )?);
+
+    let web_api_state = modules::web_api::WebApiState {
         token_manager: token_manager.clone(),
         proxy_runtime: runtime,
         monitor: monitor.clone(),
-    });
+        auth: web_auth,
+    };
+    modules::config_reload::spawn_watcher(
+        web_api_state.clone(),
+        &app_config,
+        modules::config_reload::ListenBinding {
+            port: proxy_config.port,
+            port_override: options.port.is_some_and(|port| port != app_config.proxy.port),
+            allow_lan_access: proxy_config.allow_lan_access,
+        },
+    );
+    let web_api_router = modules::web_api::router(web_api_state, &app_config.management);
     let app = Router::new()
         .merge(proxy_router)
         .nest("/api", web_api_router);
 
-    let host = proxy_config.get_bind_address().to_string();
     let port = proxy_config.port;
     let addr = format!("{}:{}", host, port);
-    info!("Web server listening on http://{}", addr);
+
+    proxy::image_store::set_listener_tls(proxy_config.tls.enabled);
+    // 证书问题在绑定端口前报错
+    let tls = if proxy_config.tls.enabled {
+        let state = proxy::tls::TlsState::load(&proxy_config.tls)?;
+        state.spawn_reload();
+        Some(state)
+    } else {
+        if proxy_config.allow_lan_access {
+            warn!("LAN access is enabled without TLS; API keys and prompts are sent in plain text (see proxy.tls)");
+        }
+        None
+    };
 
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .map_err(|e| format!("failed_to_bind_port: {}", e))?;
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .map_err(|e| format!("failed_to_run_server: {}", e))?;
+    match tls {
+        Some(tls) => {
+            info!("Web server listening on https://{}", addr);
+            proxy::tls::serve(listener, app, tls, shutdown_signal()).await?;
+        }
+        None => {
+            info!("Web server listening on http://{}", addr);
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+            )
+                .with_graceful_shutdown(shutdown_signal())
+                .await
+                .map_err(|e| format!("failed_to_run_server: {}", e))?;
+        }
+    }
+
+    // 等待后台写入线程落库剩余的日志与 token 统计
+    monitor.flush().await;
+    info!("Flushed pending request logs");
+    let _ = tokio::task::spawn_blocking(|| proxy::SignatureCache::global().flush_persistence()).await;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_loopback_host() {
+        assert!(is_loopback_host("127.0.0.1"));
+        assert!(is_loopback_host("127.0.0.2"));
+        assert!(is_loopback_host("localhost"));
+        assert!(is_loopback_host("[::1]"));
+        assert!(!is_loopback_host("0.0.0.0"));
+        assert!(!is_loopback_host("::"));
+        assert!(!is_loopback_host("192.168.1.10"));
+    }
+
+    #[test]
+    fn test_non_loopback_bind_enables_lan_access() {
+        let mut config = proxy::ProxyConfig::default();
+        assert_eq!(resolve_bind_host(&mut config, None), "127.0.0.1");
+        assert!(!config.allow_lan_access);
+
+        assert_eq!(resolve_bind_host(&mut config, Some("localhost")), "localhost");
+        assert!(!config.allow_lan_access);
+
+        assert_eq!(resolve_bind_host(&mut config, Some("0.0.0.0")), "0.0.0.0");
+        assert!(config.allow_lan_access);
+        config.auth_mode = proxy::ProxyAuthMode::Auto;
+        assert!(matches!(
+            proxy::ProxySecurityConfig::from_proxy_config(&config).effective_auth_mode(),
+            proxy::ProxyAuthMode::AllExceptHealth
+        ));
+    }
+}
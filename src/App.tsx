@@ -8,6 +8,7 @@ import ApiProxy from './pages/ApiProxy';
 import Monitor from './pages/Monitor';
 import TokenStats from './pages/TokenStats';
 import OAuthCallback from './pages/OAuthCallback';
+import Login from './pages/Login';
 import ThemeManager from './components/common/ThemeManager';
 import { UpdateNotification } from './components/UpdateNotification';
 import { useEffect, useState } from 'react';
@@ -51,6 +52,10 @@ const router = createBrowserRouter([
     path: '/oauth/callback',
     element: <OAuthCallback />,
   },
+  {
+    path: '/login',
+    element: <Login />,
+  },
 ]);
 
 function App() {
@@ -0,0 +1,54 @@
+import { type FormEvent, useState } from 'react';
+import { login } from '../utils/request';
+
+export default function Login() {
+  const [username, setUsername] = useState('admin');
+  const [password, setPassword] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const [submitting, setSubmitting] = useState(false);
+
+  const handleSubmit = async (e: FormEvent) => {
+    e.preventDefault();
+    setSubmitting(true);
+    setError(null);
+    try {
+      await login(username, password);
+      const next = new URLSearchParams(window.location.search).get('next') || '/';
+      // Full reload so stores refetch with the new session
+      window.location.assign(next.startsWith('/') ? next : '/');
+    } catch (err) {
+      setError(String(err instanceof Error ? err.message : err));
+      setSubmitting(false);
+    }
+  };
+
+  return (
+    <div className="h-screen flex items-center justify-center bg-[#FAFBFC] dark:bg-base-300 text-gray-700 dark:text-gray-200">
+      <form
+        onSubmit={handleSubmit}
+        className="w-full max-w-sm bg-white dark:bg-base-100 rounded-xl shadow-sm border border-gray-100 dark:border-base-200 p-6 space-y-4"
+      >
+        <h1 className="text-lg font-semibold">Antigravity Manager</h1>
+        <input
+          className="input input-bordered w-full"
+          placeholder="Username"
+          autoComplete="username"
+          value={username}
+          onChange={(e) => setUsername(e.target.value)}
+        />
+        <input
+          className="input input-bordered w-full"
+          type="password"
+          placeholder="Password"
+          autoComplete="current-password"
+          value={password}
+          onChange={(e) => setPassword(e.target.value)}
+        />
+        {error && <div className="text-sm text-red-500">{error}</div>}
+        <button type="submit" className="btn btn-primary w-full" disabled={submitting || !password}>
+          {submitting ? 'Signing in...' : 'Sign in'}
+        </button>
+      </form>
+    </div>
+  );
+}
@@ -10,8 +10,8 @@ export default function OAuthCallback() {
   useEffect(() => {
     const params = new URLSearchParams(window.location.search);
     const code = params.get('code');
+    const state = params.get('state');
     const error = params.get('error');
-    const redirectUri = `${window.location.origin}/oauth/callback`;
 
     if (error) {
       setMessage(`OAuth failed: ${error}`);
@@ -21,8 +21,12 @@ export default function OAuthCallback() {
       setMessage('Missing OAuth code.');
       return;
     }
+    if (!state) {
+      setMessage('Missing OAuth state.');
+      return;
+    }
 
-    completeOAuthLogin(code, redirectUri)
+    completeOAuthLogin(code, state)
       .then(() => {
         setMessage('OAuth completed. Redirecting...');
         setTimeout(() => navigate('/accounts'), 1000);
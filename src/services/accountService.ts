@@ -54,9 +54,9 @@ export async function startOAuthLogin(redirectUri: string): Promise<string> {
     }
 }
 
-export async function completeOAuthLogin(code: string, redirectUri: string): Promise<Account> {
+export async function completeOAuthLogin(code: string, state: string): Promise<Account> {
     try {
-        return await invoke('complete_oauth_login', { code, redirectUri });
+        return await invoke('complete_oauth_login', { code, state });
     } catch (error) {
         if (typeof error === 'string') {
             if (error.includes('Refresh Token') || error.includes('refresh_token')) {
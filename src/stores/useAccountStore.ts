@@ -21,7 +21,7 @@ interface AccountState {
 
     // 新增 actions
     startOAuthLogin: (redirectUri: string) => Promise<string>;
-    completeOAuthLogin: (code: string, redirectUri: string) => Promise<void>;
+    completeOAuthLogin: (code: string, state: string) => Promise<void>;
     cancelOAuthLogin: () => Promise<void>;
     importV1Accounts: () => Promise<void>;
     importFromDb: () => Promise<void>;
@@ -184,10 +184,10 @@ export const useAccountStore = create<AccountState>((set, get) => ({
         }
     },
 
-    completeOAuthLogin: async (code: string, redirectUri: string) => {
+    completeOAuthLogin: async (code: string, state: string) => {
         set({ loading: true, error: null });
         try {
-            await accountService.completeOAuthLogin(code, redirectUri);
+            await accountService.completeOAuthLogin(code, state);
             await get().fetchAccounts();
             set({ loading: false });
         } catch (error) {
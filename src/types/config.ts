@@ -17,6 +17,85 @@ export interface ProxyConfig {
     zai?: ZaiConfig;
     scheduling?: StickySessionConfig;
     experimental?: ExperimentalConfig;
+    log_capture?: LogCaptureConfig;
+    tool_results?: ToolResultConfig;
+    image_storage?: ImageStorageConfig;
+    speech?: SpeechConfig;
+    transcription?: TranscriptionConfig;
+    tls?: TlsConfig;
+}
+
+export type CaptureMode = 'metadata' | 'truncated' | 'full';
+
+export interface CaptureRule {
+    route?: string;
+    model?: string;
+    client_key?: string;
+    mode: CaptureMode;
+}
+
+export interface LogCaptureConfig {
+    default_mode: CaptureMode;
+    rules: CaptureRule[];
+    max_body_bytes: number;
+    redact_secrets: boolean;
+    redact_patterns: string[];
+    strip_base64_images: boolean;
+    compress_bodies: boolean;
+}
+
+export type ToolResultAction = 'keep' | 'truncate' | 'summarize' | 'strip_html' | 'offload';
+
+export interface ToolResultPolicy {
+    tool: string;
+    action: ToolResultAction;
+    max_chars?: number;
+    head_ratio?: number;
+    drop_images?: boolean;
+}
+
+export interface ToolResultConfig {
+    policies: ToolResultPolicy[];
+    default_max_chars: number;
+    offload_ttl_hours: number;
+}
+
+export interface ImageStorageConfig {
+    enabled: boolean;
+    url_ttl_seconds: number;
+    retention_days: number; // 0 = keep forever
+    max_images: number; // 0 = unlimited
+    max_total_mb: number; // 0 = unlimited
+    public_base_url?: string | null;
+}
+
+export interface SpeechConfig {
+    default_model: string;
+    default_voice: string;
+    voice_map: Record<string, string>; // OpenAI voice -> Gemini prebuilt voice
+    default_format: 'wav' | 'pcm' | 'mp3' | 'opus' | 'aac' | 'flac';
+    max_chunk_chars: number;
+    ffmpeg_path?: string | null;
+}
+
+export interface TranscriptionConfig {
+    default_model: string;
+    segment_seconds: number;
+    overlap_seconds: number;
+    max_concurrency: number;
+}
+
+export type ClientAuthMode = 'off' | 'optional' | 'required';
+
+export interface TlsConfig {
+    enabled: boolean;
+    cert_path?: string | null;
+    key_path?: string | null;
+    self_signed: boolean;
+    self_signed_hosts: string[];
+    client_auth: ClientAuthMode;
+    client_ca_path?: string | null;
+    client_cert_auth: boolean;
 }
 
 export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
@@ -24,6 +103,7 @@ export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
 export interface StickySessionConfig {
     mode: SchedulingMode;
     max_wait_seconds: number;
+    session_ttl_seconds?: number; // 0 = never expire
 }
 
 export type ZaiDispatchMode = 'off' | 'exclusive' | 'pooled' | 'fallback';
@@ -68,6 +148,15 @@ export interface PinnedQuotaModelsConfig {
 
 export interface ExperimentalConfig {
     enable_usage_scaling: boolean;
+    persist_signature_cache?: boolean;
+    signature_cache_max_entries?: number;
+    repair_tool_call_args?: boolean;
+    reemit_invalid_tool_calls?: boolean;
+    enable_context_compaction?: boolean;
+    context_compaction_threshold?: number;
+    context_compaction_keep_recent?: number;
+    context_compaction_model?: string;
+    validate_structured_output?: boolean;
 }
 
 export interface AppConfig {
@@ -1,4 +1,5 @@
 const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://127.0.0.1:8045';
+const SESSION_KEY = 'antigravity_session';
 
 interface InvokeResponse<T> {
   ok: boolean;
@@ -6,15 +7,74 @@ interface InvokeResponse<T> {
   error?: string;
 }
 
+export interface LoginSession {
+  token: string;
+  username: string;
+  role: 'viewer' | 'operator' | 'admin';
+  expires_at: number;
+}
+
+export function getSession(): LoginSession | null {
+  try {
+    const raw = localStorage.getItem(SESSION_KEY);
+    return raw ? (JSON.parse(raw) as LoginSession) : null;
+  } catch {
+    return null;
+  }
+}
+
+function authHeaders(): Record<string, string> {
+  const session = getSession();
+  return session ? { Authorization: `Bearer ${session.token}` } : {};
+}
+
+function handleUnauthorized() {
+  localStorage.removeItem(SESSION_KEY);
+  if (window.location.pathname !== '/login') {
+    const next = encodeURIComponent(window.location.pathname + window.location.search);
+    window.location.assign(`/login?next=${next}`);
+  }
+}
+
+export async function login(username: string, password: string): Promise<LoginSession> {
+  const resp = await fetch(`${API_BASE_URL}/api/auth/login`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ username, password }),
+  });
+  const payload = (await resp.json()) as InvokeResponse<LoginSession>;
+  if (!resp.ok || !payload.ok || !payload.data) {
+    throw new Error(payload.error || `Login failed: ${resp.status}`);
+  }
+  localStorage.setItem(SESSION_KEY, JSON.stringify(payload.data));
+  return payload.data;
+}
+
+export async function logout(): Promise<void> {
+  try {
+    await fetch(`${API_BASE_URL}/api/auth/logout`, {
+      method: 'POST',
+      headers: authHeaders(),
+    });
+  } finally {
+    localStorage.removeItem(SESSION_KEY);
+    window.location.assign('/login');
+  }
+}
+
 export async function request<T>(cmd: string, args?: any): Promise<T> {
   try {
     const resp = await fetch(`${API_BASE_URL}/api/invoke`, {
       method: 'POST',
       headers: {
         'Content-Type': 'application/json',
+        ...authHeaders(),
       },
       body: JSON.stringify({ cmd, args: args ?? {} }),
     });
+    if (resp.status === 401) {
+      handleUnauthorized();
+    }
     const payload = (await resp.json()) as InvokeResponse<T>;
     if (!resp.ok || !payload.ok) {
       const message = payload.error || `Request failed: ${resp.status}`;